@@ -0,0 +1,138 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::sync::Arc;
+use tcb::broadcast::hooks::NoHooks;
+use tcb::channel::unbounded;
+use tcb::channel::Receiver;
+use tcb::configuration::middleware_configuration::Configuration;
+use tcb::graph::middleware::dot::Dot;
+use tcb::graph::middleware::graph::GRAPH;
+use tcb::graph::middleware::message_types::ClientMessage;
+use tcb::graph::middleware::node::Node;
+use tcb::graph::structs::message::Message;
+
+/**
+ * Number of messages sent by each remote peer in a run.
+ */
+const ROUNDS: usize = 200;
+
+/**
+ * Builds the messages received by peer 0 when every other peer sends a message
+ * per round that depends on the messages of the previous round, with the
+ * fingerprints calculated by the Reader threads.
+ *
+ * # Arguments
+ *
+ * `peer_number` - Number of peers in the group.
+ */
+fn build_messages(peer_number: usize) -> Vec<(Message, u64)> {
+    let mut messages = Vec::with_capacity(ROUNDS * (peer_number - 1));
+
+    for counter in 1..=ROUNDS {
+        let context: Vec<Dot> = if counter == 1 {
+            Vec::new()
+        } else {
+            (1..peer_number)
+                .map(|id| Dot::new(id, counter - 1))
+                .collect()
+        };
+
+        for id in 1..peer_number {
+            let payload = vec![0; 64];
+            let fingerprint = Node::calculate_fingerprint(&payload, &context);
+            let message = Message::new(payload, Dot::new(id, counter), context.clone(), None);
+
+            messages.push((message, fingerprint));
+        }
+    }
+
+    messages
+}
+
+/**
+ * Creates the causal graph of peer 0, with the receivers of its deliveries
+ * and stable messages, which must outlive the graph.
+ */
+fn build_graph(peer_number: usize) -> (GRAPH, Receiver<ClientMessage>) {
+    let (client, client_receiver) = unbounded();
+
+    let graph = GRAPH::new(
+        0,
+        peer_number,
+        client.clone(),
+        client,
+        Arc::new(Configuration::default()),
+        Arc::new(NoHooks),
+    );
+
+    (graph, client_receiver)
+}
+
+/**
+ * Receives every message in the order they were sent, so each one is
+ * delivered right away and stabilizes the messages of the previous round.
+ */
+fn in_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("graph_in_order");
+
+    for peer_number in [4, 16, 64] {
+        let messages = build_messages(peer_number);
+        group.throughput(Throughput::Elements(messages.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(peer_number),
+            &peer_number,
+            |b, &peer_number| {
+                b.iter_batched(
+                    || (build_graph(peer_number), messages.clone()),
+                    |((mut graph, client_receiver), messages)| {
+                        for (message, fingerprint) in messages {
+                            graph.receive(message, fingerprint);
+                        }
+
+                        (graph, client_receiver)
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/**
+ * Receives the rounds in reverse order, so every message waits in the graph
+ * for its dependencies and the whole run is delivered by the first round.
+ */
+fn out_of_order(c: &mut Criterion) {
+    let mut group = c.benchmark_group("graph_out_of_order");
+
+    for peer_number in [4, 16, 64] {
+        let mut messages = build_messages(peer_number);
+        messages.reverse();
+        group.throughput(Throughput::Elements(messages.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(peer_number),
+            &peer_number,
+            |b, &peer_number| {
+                b.iter_batched(
+                    || (build_graph(peer_number), messages.clone()),
+                    |((mut graph, client_receiver), messages)| {
+                        for (message, fingerprint) in messages {
+                            graph.receive(message, fingerprint);
+                        }
+
+                        (graph, client_receiver)
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, in_order, out_of_order);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::Arc;
+use tcb::channel::unbounded;
+use tcb::configuration::middleware_configuration::Configuration;
+use tcb::graph::communication::deserializer::{self, RawMessage};
+use tcb::graph::middleware::dot::Dot;
+use tcb::graph::structs::message::Message;
+use tcb::graph::structs::message_type::ClientPeerMiddleware;
+use tcb::transport::encoding;
+
+/**
+ * Number of senders whose messages are read from the same peer, e.g. a
+ * donor relaying the retained messages of the group.
+ */
+const SENDERS: usize = 16;
+
+/**
+ * Number of messages of each sender in a run.
+ */
+const ROUNDS: usize = 100;
+
+/**
+ * Size in bytes of the payload of each message, which is hashed for
+ * the fingerprint of the message.
+ */
+const PAYLOAD_SIZE: usize = 4096;
+
+/**
+ * Builds the serialized messages read by peer 0 from peer 1, interleaving
+ * the messages of every sender. Each message depends on the previous
+ * message of every sender.
+ */
+fn build_messages(configuration: &Configuration) -> Vec<Vec<u8>> {
+    let mut messages = Vec::with_capacity(ROUNDS * SENDERS);
+
+    for counter in 1..=ROUNDS {
+        let context: Vec<Dot> = (1..=SENDERS)
+            .filter(|_| counter > 1)
+            .map(|id| Dot::new(id, counter - 1))
+            .collect();
+
+        for id in 1..=SENDERS {
+            let message = Message::new(
+                vec![id as u8; PAYLOAD_SIZE],
+                Dot::new(id, counter),
+                context.clone(),
+                None,
+            );
+
+            messages.push(
+                encoding::serialize(configuration.wire_encoding, &message)
+                    .expect("ERROR: Failed to serialize the message"),
+            );
+        }
+    }
+
+    messages
+}
+
+/**
+ * Hands every message read from a peer to the Deserializer threads, sharded
+ * by sender, and waits until the Middleware channel received them all. A
+ * single thread is the previous sharding by peer, where every message read
+ * from a peer is handled by the same thread.
+ */
+fn deserialization_workers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sharded_deserialization");
+    group.throughput(Throughput::Elements((ROUNDS * SENDERS) as u64));
+
+    for workers in [1, 2, 4, 8] {
+        let configuration = Arc::new(Configuration {
+            deserialization_workers: workers,
+            ..Configuration::default()
+        });
+
+        let messages = build_messages(&configuration);
+
+        let (middleware_channel, middleware_receiver) = unbounded::<ClientPeerMiddleware>();
+        let deserializers = deserializer::start_workers(0, &middleware_channel, &configuration);
+
+        group.bench_with_input(BenchmarkId::from_parameter(workers), &workers, |b, _| {
+            b.iter(|| {
+                for msg in messages.iter().cloned() {
+                    let deserializer = deserializer::get_worker(
+                        &deserializers,
+                        &msg,
+                        configuration.wire_encoding,
+                        1,
+                    );
+
+                    deserializer
+                        .send(RawMessage {
+                            peer_id: 1,
+                            msg,
+                            signature: None,
+                            encoding: configuration.wire_encoding,
+                            closed: None,
+                        })
+                        .expect("ERROR: Failed to send the message to the deserializer");
+                }
+
+                for _ in 0..messages.len() {
+                    middleware_receiver
+                        .recv()
+                        .expect("ERROR: Failed to receive the deserialized message");
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, deserialization_workers);
+criterion_main!(benches);
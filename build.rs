@@ -0,0 +1,6 @@
+fn main() {
+    //Generating the gRPC service of the sidecar from its protocol definition
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/tcb.proto")
+        .expect("ERROR: Failed to compile the gRPC protocol definition");
+}
@@ -1,7 +1,7 @@
 use tcb::causality_checker::petgraph::plot_graph;
 use tcb::causality_checker::{
     causality_checker::check_causal_delivery,
-    causality_checker_structs::{CausalCheck, CausalityChecker},
+    causality_checker_structs::{CausalCheck, CausalityChecker, CheckerOutputConfig},
 };
 use tcb::graph::middleware::dot::Dot;
 
@@ -81,12 +81,22 @@ fn main() {
             //for the rendering to happen. Only use if the number of nodes in the graph
             //is relatively small. The filename for writing the graph into is passed from the
             //configuration file.
-            plot_graph(graph, &format!("graph_filename"));
+            match plot_graph(graph, "graph_filename") {
+                Ok(_) => {}
+                Err(e) => println!("ERROR: Failed to write the graph\n\t- {}", e),
+            }
         }
         CausalityChecker::Error(error) => {
             //An error happened while traversing the dot sequences.
             //The state of the causality checker should be logged as to debug the problem.
-            error.log_causal_check_error(format!("tcb_output/causal_error_output.txt"));
+            //The output directory and file names can be changed with a CheckerOutputConfig
+            match error.write_causal_check_error(&CheckerOutputConfig::default()) {
+                Ok(_) => {}
+                Err(e) => println!(
+                    "ERROR: Failed to log the causality checker error\n\t- {}",
+                    e
+                ),
+            }
         }
     }
 }
@@ -0,0 +1,241 @@
+mod room;
+
+use room::{ChatMessage, Room};
+use std::env;
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::thread;
+use std::time::{Duration, Instant};
+use tcb::broadcast::broadcast_trait::{GenericReturn, TcbError, TCB};
+use tcb::channel::Receiver;
+use tcb::channel::{unbounded, TryRecvError};
+use tcb::configuration::middleware_configuration::{Configuration, Peer, Topology};
+use tcb::graph::graph::GRAPH;
+
+/**
+ * Maximum time waiting for a delivery before handling the typed lines.
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/**
+ * Time without answering the probes after which a member is evicted from the room.
+ */
+const DROP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/**
+ * Number of messages kept in the history before the stable ones are pruned.
+ */
+const MAX_HISTORY: usize = 20;
+
+/**
+ * Chat room where every member runs a GRAPH middleware instance. Messages are
+ * typed, serialized with bincode, and delivered in a causal order, so a reply is
+ * never shown before the message it answers. Stable messages, which every member
+ * already delivered, are acked and pruned from the history once it's full. The
+ * middleware doesn't reconnect to a member that dropped, so a member that stops
+ * answering the probes is evicted and the others keep chatting without it.
+ *
+ * Usage: chat <local id> <name> <address of peer 0> <address of peer 1> ...
+ *
+ * e.g. in three terminals, with ids 0, 1 and 2:
+ * cargo run --example chat -- 0 alice localhost:7000 localhost:7001 localhost:7002
+ *
+ * Commands: `/reply <number> <text>`, `/history` and `/quit`.
+ */
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let arguments: Vec<String> = env::args().collect();
+
+    if arguments.len() < 5 {
+        return Err(
+            "Usage: chat <local id> <name> <address of peer 0> <address of peer 1> ...".into(),
+        );
+    }
+
+    let local_id: usize = arguments[1].parse()?;
+    let name = arguments[2].clone();
+
+    let peers = arguments[3..]
+        .iter()
+        .enumerate()
+        .map(|(id, address)| Peer::new(id, address.clone()))
+        .collect();
+
+    //Probing the members often enough to notice when one of them drops
+    let configuration = Configuration {
+        topology: Topology::new(local_id, peers),
+        probe_interval: 500000,
+        ..Configuration::default()
+    };
+
+    println!("Waiting for every member to join...");
+    let mut tcb = GRAPH::new(configuration);
+    println!("Joined the room as {}", name);
+
+    let lines = read_lines();
+    let mut room = Room::new(MAX_HISTORY);
+    let mut sent = 0;
+    let mut evicted: Vec<usize> = Vec::new();
+    let joined = Instant::now();
+
+    loop {
+        //Handling every typed line before waiting for the next delivery
+        loop {
+            match lines.try_recv() {
+                Ok(line) => {
+                    if !handle_line(&mut tcb, &mut room, local_id, &name, &mut sent, line.trim()) {
+                        tcb.end();
+                        return Ok(());
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    tcb.end();
+                    return Ok(());
+                }
+            }
+        }
+
+        match tcb.recv_timeout(POLL_INTERVAL) {
+            Ok(GenericReturn::Delivery(payload, id, counter)) => {
+                match bincode::deserialize::<ChatMessage>(&payload) {
+                    Ok(message) => {
+                        let entry = room.push(id, counter, message).clone();
+                        println!("{}", room.format(&entry));
+                    }
+                    Err(e) => {
+                        println!("ERROR: Failed to deserialize a chat message\n\t- {}", e);
+                    }
+                }
+            }
+            Ok(GenericReturn::Stable(id, counter)) => {
+                //Acking the stable message so it's deleted from the causal graph
+                tcb.tcbstable(id, counter)?;
+                room.mark_stable(id, counter);
+            }
+            Ok(GenericReturn::Evicted(id)) => {
+                println!("Member {} left the room", id);
+            }
+            Ok(GenericReturn::Fault(fault)) => {
+                println!("Fault -> {:?}", fault);
+            }
+            Ok(_) => {}
+            Err(TcbError::Timeout) => {}
+            Err(_) => {
+                println!("ERROR: The middleware terminated");
+                return Ok(());
+            }
+        }
+
+        //Evicting the members that stopped answering the probes, so the
+        //messages keep becoming stable and the history keeps being pruned
+        if joined.elapsed() > DROP_TIMEOUT {
+            for peer in tcb.metrics().peers {
+                if !evicted.contains(&peer.peer_id) && !peer.is_healthy(DROP_TIMEOUT) {
+                    println!("Member {} stopped answering, evicting it", peer.peer_id);
+                    tcb.evict_peer(peer.peer_id)?;
+                    evicted.push(peer.peer_id);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Spawns the thread that reads the lines typed by the user.
+ */
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = unbounded();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    receiver
+}
+
+/**
+ * Handles a typed line, which is either a command or a message to be posted.
+ * Returns false if the user quit.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance
+ *
+ * `room` - History of the room
+ *
+ * `local_id` - Id of the local member
+ *
+ * `name` - Name of the local member
+ *
+ * `sent` - Number of messages sent by the local member
+ *
+ * `line` - Typed line
+ */
+fn handle_line(
+    tcb: &mut GRAPH,
+    room: &mut Room,
+    local_id: usize,
+    name: &str,
+    sent: &mut usize,
+    line: &str,
+) -> bool {
+    let (text, reply_to) = if line == "/quit" {
+        return false;
+    } else if line == "/history" {
+        for entry in room.iter() {
+            println!("{}", room.format(entry));
+        }
+        return true;
+    } else if let Some(reply) = line.strip_prefix("/reply ") {
+        let mut parts = reply.splitn(2, ' ');
+
+        let replied = parts
+            .next()
+            .and_then(|number| number.trim_start_matches('#').parse().ok())
+            .and_then(|number| room.get(number));
+
+        match (replied, parts.next()) {
+            (Some(replied), Some(text)) => (text, Some((replied.id, replied.counter))),
+            _ => {
+                println!("Usage: /reply <number of a message in the history> <text>");
+                return true;
+            }
+        }
+    } else if line.is_empty() {
+        return true;
+    } else {
+        (line, None)
+    };
+
+    let message = ChatMessage {
+        author: name.to_string(),
+        text: text.to_string(),
+        reply_to,
+    };
+
+    let serialized_message =
+        bincode::serialize(&message).expect("ERROR: Failed to serialize a chat message");
+
+    match tcb.send(serialized_message) {
+        Ok(_) => {
+            //The local member's messages aren't delivered back, so they're added to the history here
+            *sent += 1;
+            let entry = room.push(local_id, *sent, message).clone();
+            println!("{}", room.format(&entry));
+        }
+        Err(_) => {
+            println!("ERROR: Failed to send the message");
+        }
+    }
+
+    true
+}
@@ -0,0 +1,184 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/**
+ * Typed message broadcast to the chat room.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatMessage {
+    ///Name of the member who posted the message
+    pub author: String,
+    ///Posted text
+    pub text: String,
+    ///Sender id and counter of the message being replied to
+    pub reply_to: Option<(usize, usize)>,
+}
+
+/**
+ * Message kept in the history of the room.
+ */
+#[derive(Debug, Clone)]
+pub struct Entry {
+    ///Number shown to the user, which doesn't change when the history is pruned
+    pub number: usize,
+    ///Sender id
+    pub id: usize,
+    ///Message counter
+    pub counter: usize,
+    ///Delivered or sent message
+    pub message: ChatMessage,
+    ///Flag set once every member delivered the message
+    pub stable: bool,
+}
+
+/**
+ * History of the chat room, in the order the messages were delivered. Since the
+ * middleware delivers the messages in a causal order, a reply is always after
+ * the message it answers, at every member.
+ */
+pub struct Room {
+    ///Messages in the order they were delivered
+    entries: VecDeque<Entry>,
+    ///Number of the next entry
+    next_number: usize,
+    ///Number of entries kept before the stable ones are pruned
+    max_history: usize,
+}
+
+impl Room {
+    /**
+     * Creates an empty room.
+     *
+     * # Arguments
+     *
+     * `max_history` - Number of entries kept before the stable ones are pruned
+     */
+    pub fn new(max_history: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            next_number: 1,
+            max_history,
+        }
+    }
+
+    /**
+     * Appends a delivered or sent message to the history and returns its entry.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     *
+     * `message` - Delivered or sent message
+     */
+    pub fn push(&mut self, id: usize, counter: usize, message: ChatMessage) -> &Entry {
+        self.entries.push_back(Entry {
+            number: self.next_number,
+            id,
+            counter,
+            message,
+            stable: false,
+        });
+        self.next_number += 1;
+
+        self.entries.back().unwrap()
+    }
+
+    /**
+     * Marks a message as stable and prunes the history.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     */
+    pub fn mark_stable(&mut self, id: usize, counter: usize) {
+        if let Some(entry) = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.id == id && entry.counter == counter)
+        {
+            entry.stable = true;
+        }
+
+        self.prune();
+    }
+
+    /**
+     * Removes the oldest entries while the history is over its size, as long as
+     * they are stable. An entry that isn't stable may still be answered by a
+     * member that didn't deliver it yet, so it's kept until every member has it.
+     */
+    fn prune(&mut self) {
+        while self.entries.len() > self.max_history {
+            match self.entries.front() {
+                Some(entry) if entry.stable => {
+                    self.entries.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /**
+     * Returns the entry with a number, if it wasn't pruned.
+     *
+     * # Arguments
+     *
+     * `number` - Number shown to the user
+     */
+    pub fn get(&self, number: usize) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.number == number)
+    }
+
+    /**
+     * Returns the entry of a message, if it wasn't pruned.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and counter of the message
+     */
+    pub fn find(&self, dot: (usize, usize)) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| (entry.id, entry.counter) == dot)
+    }
+
+    /**
+     * Returns the entries in the order they were delivered.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /**
+     * Formats an entry to be shown to the user.
+     *
+     * # Arguments
+     *
+     * `entry` - Entry of the history
+     */
+    pub fn format(&self, entry: &Entry) -> String {
+        let stable = if entry.stable { "" } else { " *" };
+
+        match entry.message.reply_to {
+            Some(dot) => {
+                let replied = match self.find(dot) {
+                    Some(replied) => format!("#{} {}", replied.number, replied.message.author),
+                    None => String::from("an older message"),
+                };
+
+                format!(
+                    "#{} {} (re {}): {}{}",
+                    entry.number, entry.message.author, replied, entry.message.text, stable
+                )
+            }
+            None => format!(
+                "#{} {}: {}{}",
+                entry.number, entry.message.author, entry.message.text, stable
+            ),
+        }
+    }
+}
@@ -1,23 +1,28 @@
-use crossbeam::crossbeam_channel::RecvTimeoutError;
 use std::error::Error;
 use std::time::{Duration, SystemTime};
-use tcb::broadcast::broadcast_trait::{GenericReturn, TCB};
-use tcb::configuration::middleware_configuration::read_configuration_file;
+use tcb::broadcast::broadcast_trait::{GenericReturn, TcbError, TCB};
+use tcb::configuration::middleware_configuration::{read_configuration_file, Peer, Topology};
 use tcb::graph::graph::GRAPH;
 use tcb::vv::version_vector::VV;
 
 pub fn main() -> Result<(), Box<dyn Error>> {
-    //Vec with the addresses and ports where the other peers are waiting for connections
-    let group_addresses = vec![format!("localhost:61888")];
+    //Ids and addresses of every peer in the group, including the local peer
+    let peers = vec![
+        Peer::new(0, "localhost:12345".to_string()),
+        Peer::new(1, "localhost:61888".to_string()),
+    ];
 
     //Simulating a client that uses the graph based middleware approach
-    generic_client::<GRAPH>(0, 12345, group_addresses)?;
+    generic_client::<GRAPH>(Topology::new(0, peers))?;
 
-    //Vec with the addresses and ports where the other peers are waiting for connections
-    let group_addresses = vec![format!("localhost:61889")];
+    //Ids and addresses of every peer in the group, including the local peer
+    let peers = vec![
+        Peer::new(0, "localhost:54321".to_string()),
+        Peer::new(1, "localhost:61889".to_string()),
+    ];
 
     //Simulating a client that uses the version vector middleware approach
-    generic_client::<VV>(0, 54321, group_addresses)?;
+    generic_client::<VV>(Topology::new(0, peers))?;
 
     Ok(())
 }
@@ -29,26 +34,20 @@ pub fn main() -> Result<(), Box<dyn Error>> {
  *
  * # Arguments
  *
- * `local_id` - Client globally unique id
- *
- * `local_port` - Port where the middleware instance will listen for connections  
- *
- * `peer_addresses` - Addresses of the other peers in the group
+ * `topology` - Client globally unique id and the addresses of the peers in the group
 */
-pub fn generic_client<T: TCB>(
-    local_id: usize,
-    local_port: usize,
-    peer_addresses: Vec<String>,
-) -> Result<(), Box<dyn Error>> {
+pub fn generic_client<T: TCB>(topology: Topology) -> Result<(), Box<dyn Error>> {
+    let local_id = topology.local_id;
     let mut sent_messages = 0;
 
     //String with the path to the configuration file
     let configuration_file = format!("path-to-config-file.toml");
 
     //Reading the configuration file
-    let configuration = read_configuration_file(configuration_file)?;
+    let mut configuration = read_configuration_file(configuration_file)?;
+    configuration.topology = topology;
 
-    let mut middleware = T::new(local_id, local_port, peer_addresses, configuration);
+    let mut middleware = T::new(configuration);
 
     while sent_messages < 100 {
         deliver_messages(&mut middleware)?;
@@ -66,6 +65,18 @@ pub fn generic_client<T: TCB>(
 
     //Client sent 100 messages while delivering messages for 10s before each send
 
+    //Ending the middleware and printing the summary of its activity
+    let report = middleware.shutdown();
+    println!(
+        "Sent {} | Delivered {} | Stable {} | Pending {} | Bytes sent {} | Bytes received {}",
+        report.sent,
+        report.delivered,
+        report.stable,
+        report.pending,
+        report.bytes_sent,
+        report.bytes_received
+    );
+
     Ok(())
 }
 
@@ -91,16 +102,45 @@ fn deliver_messages<T: TCB>(tcb: &mut T) -> Result<(), Box<dyn Error>> {
                     id, cntr, delivered_message
                 );
             }
+            Ok(GenericReturn::TracedDelivery(serialized_message, id, cntr, trace_id)) => {
+                let delivered_message = String::from_utf8(serialized_message)?;
+                println!(
+                    "Delivered message -> ({}, {}) {} [{}]",
+                    id, cntr, delivered_message, trace_id
+                );
+            }
+            Ok(GenericReturn::HeaderedDelivery(serialized_message, id, cntr, headers)) => {
+                let delivered_message = String::from_utf8(serialized_message)?;
+                println!(
+                    "Delivered message -> ({}, {}) {} {:?}",
+                    id,
+                    cntr,
+                    delivered_message,
+                    headers.keys().collect::<Vec<_>>()
+                );
+            }
             Ok(GenericReturn::Stable(id, cntr)) => {
                 println!("Stable message -> ({}, {})", id, cntr);
             }
+            Ok(GenericReturn::StableFrontier(frontier)) => {
+                println!("Stable frontier -> {:?}", frontier);
+            }
+            Ok(GenericReturn::Evicted(id)) => {
+                println!("Evicted peer -> {}", id);
+            }
+            Ok(GenericReturn::Fault(fault)) => {
+                println!("Fault -> {:?}", fault);
+            }
+            Ok(GenericReturn::SendFailed(failure)) => {
+                println!("Send failed -> {:?}", failure);
+            }
             Err(e) => match e {
-                RecvTimeoutError::Timeout => {
+                TcbError::Timeout => {
                     //Timeout finished and no more message delivery
                     //Exit function call
                     break;
                 }
-                RecvTimeoutError::Disconnected => {
+                _ => {
                     //An error occurred before the fimeout finished
                     //Handle it
                     panic!("Error was thrown before reading timeout ended");
@@ -21,18 +21,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     //Reading the configuration file
     let configuration = read_configuration_file(configuration_file)?;
 
-    //The peer's unique id in the group that must be a natural number
-    //This id starts at 0 and sequentially grows with each peer
-    let id: usize = 0;
-
-    //The local port where the middleware will wait for connections
-    let port: usize = 61887;
-
-    //Vec with the addresses and ports where the other peers are waiting for connections
-    let group_addresses = vec![format!("localhost:61888")];
+    //The peer's unique id, the port where the middleware will wait for connections
+    //and the addresses of the other peers are set in the topology of the configuration file
 
     //Creates a new graph based middleware instance
-    let mut graph = GRAPH::new(id, port, group_addresses, configuration);
+    let mut graph = GRAPH::new(configuration);
 
     //Creating and serializing the message to be sent
     let message = format!("Hello world");
@@ -56,12 +49,38 @@ fn main() -> Result<(), Box<dyn Error>> {
             let delivered_message = String::from_utf8(serialized_delivery)?;
             println!("Delivered message -> {}", delivered_message);
         }
+        GenericReturn::TracedDelivery(serialized_delivery, _id, _counter, trace_id) => {
+            //Same as a delivery, but the message was sent with a trace id
+            let delivered_message = String::from_utf8(serialized_delivery)?;
+            println!("Delivered message -> {} [{}]", delivered_message, trace_id);
+        }
+        GenericReturn::HeaderedDelivery(serialized_delivery, _id, _counter, headers) => {
+            //Same as a delivery, but the message was sent with metadata headers
+            let delivered_message = String::from_utf8(serialized_delivery)?;
+            println!("Delivered message -> {} {:?}", delivered_message, headers);
+        }
         GenericReturn::Stable(id, counter) => {
             //Acking the stable message so it can be softly deleted from the causal graph
             //and its position reused by another message.
             //Otherwise the causal graph will exponentially grow and
             //constantly allocate more positions.
-            graph.tcbstable(id, counter);
+            graph.tcbstable(id, counter)?;
+        }
+        GenericReturn::StableFrontier(frontier) => {
+            //Only notified by the VV middleware when the stable frontier advances are coalesced
+            println!("Stable frontier -> {:?}", frontier);
+        }
+        GenericReturn::Evicted(id) => {
+            //A peer evicted with evict_peer is no longer part of the group
+            println!("Evicted peer -> {}", id);
+        }
+        GenericReturn::Fault(fault) => {
+            //A peer broke the protocol, e.g. by equivocating in the Byzantine-tolerant mode
+            println!("Fault -> {:?}", fault);
+        }
+        GenericReturn::SendFailed(failure) => {
+            //A local message that never left the peer, or wasn't written to a peer's connection
+            println!("Send failed -> {:?}", failure);
         }
     }
 
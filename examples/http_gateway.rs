@@ -0,0 +1,34 @@
+use std::env;
+use std::error::Error;
+use std::net::SocketAddr;
+use tcb::broadcast::broadcast_trait::TCB;
+use tcb::configuration::middleware_configuration::read_configuration_file_with_env;
+use tcb::graph::graph::GRAPH;
+use tcb::sidecar::http;
+use tcb::vv::version_vector::VV;
+
+/**
+ * Runs a middleware instance behind the HTTP/JSON gateway, which can be
+ * used with curl to send messages and follow the deliveries.
+ *
+ * Usage: http_gateway <configuration file> <listening address> <graph|vv>
+ */
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let arguments: Vec<String> = env::args().collect();
+
+    if arguments.len() != 4 {
+        return Err(
+            "Usage: http_gateway <configuration file> <listening address> <graph|vv>".into(),
+        );
+    }
+
+    //Reading the configuration file, overridden by the TCB_ environment variables
+    let configuration = read_configuration_file_with_env(arguments[1].clone())?;
+    let address: SocketAddr = arguments[2].parse()?;
+
+    match arguments[3].as_str() {
+        "graph" => http::serve(GRAPH::new(configuration), address),
+        "vv" => http::serve(VV::new(configuration), address),
+        approach => Err(format!("Unknown middleware approach {}", approach).into()),
+    }
+}
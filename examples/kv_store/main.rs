@@ -0,0 +1,224 @@
+mod store;
+
+use std::env;
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::thread;
+use std::time::Duration;
+use store::{Operation, Store};
+use tcb::broadcast::broadcast_trait::{GenericReturn, TcbError, TCB};
+use tcb::channel::Receiver;
+use tcb::channel::{unbounded, TryRecvError};
+use tcb::configuration::middleware_configuration::{Configuration, Peer, Topology};
+use tcb::graph::graph::GRAPH;
+
+/**
+ * Maximum time waiting for a delivery before handling the typed commands.
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/**
+ * Replicated key-value store where every replica runs a GRAPH middleware instance.
+ * Each key is a multi-value register: a write carries the dots of the values its
+ * writer observed, which causal delivery guarantees were applied before it at
+ * every replica, so concurrent writes are kept side by side until a later write
+ * resolves them. Stable writes are acked and their dots dropped from the metadata.
+ *
+ * Usage: kv_store <local id> <address of peer 0> <address of peer 1> ...
+ *
+ * e.g. in two terminals, with ids 0 and 1:
+ * cargo run --example kv_store -- 0 localhost:7000 localhost:7001
+ *
+ * Commands: `put <key> <value>`, `del <key>`, `get <key>`, `dump` and `quit`.
+ */
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let arguments: Vec<String> = env::args().collect();
+
+    if arguments.len() < 4 {
+        return Err(
+            "Usage: kv_store <local id> <address of peer 0> <address of peer 1> ...".into(),
+        );
+    }
+
+    let local_id: usize = arguments[1].parse()?;
+
+    let peers = arguments[2..]
+        .iter()
+        .enumerate()
+        .map(|(id, address)| Peer::new(id, address.clone()))
+        .collect();
+
+    let configuration = Configuration {
+        topology: Topology::new(local_id, peers),
+        ..Configuration::default()
+    };
+
+    println!("Waiting for every replica to connect...");
+    let mut tcb = GRAPH::new(configuration);
+    println!("Replica {} is ready", local_id);
+
+    let commands = read_lines();
+    let mut store = Store::new();
+    let mut sent = 0;
+
+    loop {
+        //Handling every typed command before waiting for the next delivery
+        loop {
+            match commands.try_recv() {
+                Ok(command) => {
+                    if !handle_command(&mut tcb, &mut store, local_id, &mut sent, command.trim()) {
+                        tcb.end();
+                        return Ok(());
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    tcb.end();
+                    return Ok(());
+                }
+            }
+        }
+
+        match tcb.recv_timeout(POLL_INTERVAL) {
+            Ok(GenericReturn::Delivery(payload, id, counter)) => {
+                match bincode::deserialize::<Operation>(&payload) {
+                    Ok(operation) => {
+                        store.apply((id, counter), operation);
+                    }
+                    Err(e) => {
+                        println!("ERROR: Failed to deserialize a write\n\t- {}", e);
+                    }
+                }
+            }
+            Ok(GenericReturn::Stable(id, counter)) => {
+                //Acking the stable write so it's deleted from the causal graph,
+                //and dropping its dot from the store's metadata
+                tcb.tcbstable(id, counter)?;
+                store.mark_stable((id, counter));
+            }
+            Ok(GenericReturn::Evicted(id)) => {
+                println!("Replica {} was evicted", id);
+            }
+            Ok(GenericReturn::Fault(fault)) => {
+                println!("Fault -> {:?}", fault);
+            }
+            Ok(_) => {}
+            Err(TcbError::Timeout) => {}
+            Err(_) => {
+                println!("ERROR: The middleware terminated");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/**
+ * Spawns the thread that reads the commands typed by the user.
+ */
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = unbounded();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    receiver
+}
+
+/**
+ * Handles a typed command. Returns false if the user quit.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance
+ *
+ * `store` - Local replica of the store
+ *
+ * `local_id` - Id of the local replica
+ *
+ * `sent` - Number of writes sent by the local replica
+ *
+ * `command` - Typed command
+ */
+fn handle_command(
+    tcb: &mut GRAPH,
+    store: &mut Store,
+    local_id: usize,
+    sent: &mut usize,
+    command: &str,
+) -> bool {
+    let mut parts = command.splitn(3, ' ');
+
+    let operation = match (parts.next(), parts.next(), parts.next()) {
+        (Some("put"), Some(key), Some(value)) => store.write(key, Some(value.to_string())),
+        (Some("del"), Some(key), None) => store.write(key, None),
+        (Some("get"), Some(key), None) => {
+            print_key(store, key);
+            return true;
+        }
+        (Some("dump"), None, None) => {
+            for key in store.keys() {
+                print_key(store, key);
+            }
+            println!("{} writes not stable yet", store.unstable_writes());
+            return true;
+        }
+        (Some("quit"), None, None) => return false,
+        (Some(""), None, None) => return true,
+        _ => {
+            println!("Commands: put <key> <value>, del <key>, get <key>, dump, quit");
+            return true;
+        }
+    };
+
+    let serialized_operation =
+        bincode::serialize(&operation).expect("ERROR: Failed to serialize a write");
+
+    match tcb.send(serialized_operation) {
+        Ok(_) => {
+            //The local writes aren't delivered back, so they're applied here
+            *sent += 1;
+            store.apply((local_id, *sent), operation);
+        }
+        Err(_) => {
+            println!("ERROR: Failed to send the write");
+        }
+    }
+
+    true
+}
+
+/**
+ * Prints the values of a key, marking the concurrent ones.
+ *
+ * # Arguments
+ *
+ * `store` - Local replica of the store
+ *
+ * `key` - Printed key
+ */
+fn print_key(store: &Store, key: &str) {
+    let versions = store.get(key);
+
+    match versions.len() {
+        0 => println!("{} is not set", key),
+        1 => println!("{} = {}", key, versions[0].value),
+        _ => {
+            let values: Vec<String> = versions
+                .iter()
+                .map(|version| format!("{} (from {})", version.value, version.dot.0))
+                .collect();
+
+            println!("{} has concurrent values: {}", key, values.join(", "));
+        }
+    }
+}
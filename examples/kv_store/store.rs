@@ -0,0 +1,179 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/**
+ * Sender id and counter of the message that wrote a value.
+ */
+pub type Dot = (usize, usize);
+
+/**
+ * Write broadcast to every replica of the store.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Operation {
+    ///Written key
+    pub key: String,
+    ///Written value, or `None` if the key was deleted
+    pub value: Option<String>,
+    ///Dots of the values the writer observed for the key that weren't stable yet
+    pub overwrites: Vec<Dot>,
+}
+
+/**
+ * Value of a key and the dot of the write that set it.
+ */
+#[derive(Debug, Clone)]
+pub struct Version {
+    ///Dot of the write
+    pub dot: Dot,
+    ///Written value
+    pub value: String,
+    ///Flag set once every replica delivered the write
+    pub stable: bool,
+}
+
+/**
+ * Replicated map where each key is a multi-value register. A write replaces the
+ * values its writer observed, so concurrent writes to a key are all kept until a
+ * later write resolves them, instead of one of them being lost.
+ *
+ * The middleware delivers the writes in a causal order, so the values a write
+ * replaces were always applied before it. Once a write is stable, every replica
+ * delivered it and every write delivered afterwards was sent after it, so it's
+ * replaced by the next write to its key without being listed in the overwritten
+ * dots. The dots of the stable values are dropped from the metadata, which keeps
+ * the writes small for keys that aren't concurrently written.
+ */
+#[derive(Default)]
+pub struct Store {
+    ///Values of each key, more than one if the key was written concurrently
+    entries: HashMap<String, Vec<Version>>,
+    ///Key of the values whose writes aren't stable yet, indexed by their dot
+    unstable: HashMap<Dot, String>,
+}
+
+impl Store {
+    /**
+     * Creates an empty store.
+     */
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+            unstable: HashMap::new(),
+        }
+    }
+
+    /**
+     * Builds the write of a key, which overwrites every value of the key
+     * observed by the local replica.
+     *
+     * # Arguments
+     *
+     * `key` - Written key
+     *
+     * `value` - Written value, or `None` to delete the key
+     */
+    pub fn write(&self, key: &str, value: Option<String>) -> Operation {
+        let overwrites = match self.entries.get(key) {
+            Some(versions) => versions
+                .iter()
+                .filter(|version| !version.stable)
+                .map(|version| version.dot)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Operation {
+            key: key.to_string(),
+            value,
+            overwrites,
+        }
+    }
+
+    /**
+     * Applies a delivered or local write, replacing the stable values of the key
+     * and the values the writer observed, and keeping the concurrent ones.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and counter of the write
+     *
+     * `operation` - Delivered or local write
+     */
+    pub fn apply(&mut self, dot: Dot, operation: Operation) {
+        let versions = self.entries.entry(operation.key.clone()).or_default();
+
+        versions.retain(|version| !version.stable && !operation.overwrites.contains(&version.dot));
+
+        for overwritten in &operation.overwrites {
+            self.unstable.remove(overwritten);
+        }
+
+        match operation.value {
+            Some(value) => {
+                versions.push(Version {
+                    dot,
+                    value,
+                    stable: false,
+                });
+                self.unstable.insert(dot, operation.key);
+            }
+            //A deleted key without concurrent values is removed from the map
+            None => {
+                if versions.is_empty() {
+                    self.entries.remove(&operation.key);
+                }
+            }
+        }
+    }
+
+    /**
+     * Marks a write as stable, so its dot is no longer sent as metadata.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and counter of the stable write
+     */
+    pub fn mark_stable(&mut self, dot: Dot) {
+        let key = match self.unstable.remove(&dot) {
+            Some(key) => key,
+            None => return,
+        };
+
+        if let Some(versions) = self.entries.get_mut(&key) {
+            for version in versions.iter_mut().filter(|version| version.dot == dot) {
+                version.stable = true;
+            }
+        }
+    }
+
+    /**
+     * Returns the values of a key, more than one if it was written concurrently.
+     *
+     * # Arguments
+     *
+     * `key` - Read key
+     */
+    pub fn get(&self, key: &str) -> Vec<&Version> {
+        match self.entries.get(key) {
+            Some(versions) => versions.iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /**
+     * Returns the keys of the store, sorted.
+     */
+    pub fn keys(&self) -> Vec<&String> {
+        let mut keys: Vec<&String> = self.entries.keys().collect();
+        keys.sort();
+        keys
+    }
+
+    /**
+     * Returns the number of writes whose dots are still kept as metadata.
+     */
+    pub fn unstable_writes(&self) -> usize {
+        self.unstable.len()
+    }
+}
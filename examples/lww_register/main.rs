@@ -0,0 +1,227 @@
+mod register;
+
+use register::{Operation, Registers};
+use std::env;
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::thread;
+use std::time::Duration;
+use tcb::broadcast::broadcast_trait::{GenericReturn, TcbError, TCB};
+use tcb::channel::Receiver;
+use tcb::channel::{unbounded, TryRecvError};
+use tcb::configuration::middleware_configuration::{Configuration, Peer, Topology};
+use tcb::graph::graph::GRAPH;
+
+/**
+ * Maximum time waiting for a delivery before handling the typed commands.
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/**
+ * Replicated last-writer-wins registers where every replica runs a GRAPH
+ * middleware instance. Causal delivery lets the writes be ordered by a Lamport
+ * clock instead of physical clocks, and the stability of the winning write tells
+ * when a value is committed, i.e. no concurrent write can still replace it.
+ * Stable writes are acked so they're deleted from the causal graph.
+ *
+ * Usage: lww_register <local id> <address of peer 0> <address of peer 1> ...
+ *
+ * e.g. in two terminals, with ids 0 and 1:
+ * cargo run --example lww_register -- 0 localhost:7000 localhost:7001
+ *
+ * Commands: `set <name> <value>`, `get <name>`, `dump` and `quit`.
+ */
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let arguments: Vec<String> = env::args().collect();
+
+    if arguments.len() < 4 {
+        return Err(
+            "Usage: lww_register <local id> <address of peer 0> <address of peer 1> ...".into(),
+        );
+    }
+
+    let local_id: usize = arguments[1].parse()?;
+
+    let peers = arguments[2..]
+        .iter()
+        .enumerate()
+        .map(|(id, address)| Peer::new(id, address.clone()))
+        .collect();
+
+    let configuration = Configuration {
+        topology: Topology::new(local_id, peers),
+        ..Configuration::default()
+    };
+
+    println!("Waiting for every replica to connect...");
+    let mut tcb = GRAPH::new(configuration);
+    println!("Replica {} is ready", local_id);
+
+    let commands = read_lines();
+    let mut registers = Registers::new();
+    let mut sent = 0;
+
+    loop {
+        //Handling every typed command before waiting for the next delivery
+        loop {
+            match commands.try_recv() {
+                Ok(command) => {
+                    if !handle_command(
+                        &mut tcb,
+                        &mut registers,
+                        local_id,
+                        &mut sent,
+                        command.trim(),
+                    ) {
+                        tcb.end();
+                        return Ok(());
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    tcb.end();
+                    return Ok(());
+                }
+            }
+        }
+
+        match tcb.recv_timeout(POLL_INTERVAL) {
+            Ok(GenericReturn::Delivery(payload, id, counter)) => {
+                match bincode::deserialize::<Operation>(&payload) {
+                    Ok(operation) => {
+                        registers.apply((id, counter), operation);
+                    }
+                    Err(e) => {
+                        println!("ERROR: Failed to deserialize a write\n\t- {}", e);
+                    }
+                }
+            }
+            Ok(GenericReturn::Stable(id, counter)) => {
+                //Acking the stable write so it's deleted from the causal graph,
+                //and committing its value if it won
+                tcb.tcbstable(id, counter)?;
+                registers.mark_stable((id, counter));
+            }
+            Ok(GenericReturn::Evicted(id)) => {
+                println!("Replica {} was evicted", id);
+            }
+            Ok(GenericReturn::Fault(fault)) => {
+                println!("Fault -> {:?}", fault);
+            }
+            Ok(_) => {}
+            Err(TcbError::Timeout) => {}
+            Err(_) => {
+                println!("ERROR: The middleware terminated");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/**
+ * Spawns the thread that reads the commands typed by the user.
+ */
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = unbounded();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    receiver
+}
+
+/**
+ * Handles a typed command. Returns false if the user quit.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance
+ *
+ * `registers` - Local replica of the registers
+ *
+ * `local_id` - Id of the local replica
+ *
+ * `sent` - Number of writes sent by the local replica
+ *
+ * `command` - Typed command
+ */
+fn handle_command(
+    tcb: &mut GRAPH,
+    registers: &mut Registers,
+    local_id: usize,
+    sent: &mut usize,
+    command: &str,
+) -> bool {
+    let mut parts = command.splitn(3, ' ');
+
+    let operation = match (parts.next(), parts.next(), parts.next()) {
+        (Some("set"), Some(name), Some(value)) => registers.write(name, value.to_string()),
+        (Some("get"), Some(name), None) => {
+            print_register(registers, name);
+            return true;
+        }
+        (Some("dump"), None, None) => {
+            for name in registers.names() {
+                print_register(registers, name);
+            }
+            println!("{} values not committed yet", registers.uncommitted());
+            return true;
+        }
+        (Some("quit"), None, None) => return false,
+        (Some(""), None, None) => return true,
+        _ => {
+            println!("Commands: set <name> <value>, get <name>, dump, quit");
+            return true;
+        }
+    };
+
+    let serialized_operation =
+        bincode::serialize(&operation).expect("ERROR: Failed to serialize a write");
+
+    match tcb.send(serialized_operation) {
+        Ok(_) => {
+            //The local writes aren't delivered back, so they're applied here
+            *sent += 1;
+            registers.apply((local_id, *sent), operation);
+        }
+        Err(_) => {
+            println!("ERROR: Failed to send the write");
+        }
+    }
+
+    true
+}
+
+/**
+ * Prints the value of a register and whether it's committed.
+ *
+ * # Arguments
+ *
+ * `registers` - Local replica of the registers
+ *
+ * `name` - Printed register
+ */
+fn print_register(registers: &Registers, name: &str) {
+    match registers.get(name) {
+        Some(value) if value.stable => {
+            println!("{} = {} (committed)", name, value.value);
+        }
+        Some(value) => {
+            println!(
+                "{} = {} (from {} at {}, may still be replaced by a concurrent write)",
+                name, value.value, value.dot.0, value.timestamp
+            );
+        }
+        None => println!("{} is not set", name),
+    }
+}
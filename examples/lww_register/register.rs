@@ -0,0 +1,174 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/**
+ * Sender id and counter of the message that wrote a value.
+ */
+pub type Dot = (usize, usize);
+
+/**
+ * Write broadcast to every replica of the registers.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Operation {
+    ///Written register
+    pub name: String,
+    ///Written value
+    pub value: String,
+    ///Lamport timestamp of the write
+    pub timestamp: u64,
+}
+
+/**
+ * Value of a register and the write that set it.
+ */
+#[derive(Debug, Clone)]
+pub struct Value {
+    ///Written value
+    pub value: String,
+    ///Lamport timestamp of the write
+    pub timestamp: u64,
+    ///Dot of the write
+    pub dot: Dot,
+    ///Flag set once every replica delivered the write
+    pub stable: bool,
+}
+
+/**
+ * Last-writer-wins registers, where the write with the highest timestamp wins
+ * and concurrent writes with the same timestamp are ordered by their senders' ids.
+ *
+ * The middleware delivers the writes in a causal order, so a Lamport clock is
+ * enough: a write is only sent after the writes its sender delivered, which it
+ * always outranks, so a write never loses to a value it overwrote and no
+ * physical clock, with its skew, is needed. Once the winning write is stable,
+ * every replica delivered it and every write delivered afterwards was sent after
+ * it, so no concurrent write can still replace it and its value is committed.
+ */
+#[derive(Default)]
+pub struct Registers {
+    ///Value of each register, sorted by name
+    values: BTreeMap<String, Value>,
+    ///Register of the values whose writes aren't stable yet, indexed by their dot
+    unstable: HashMap<Dot, String>,
+    ///Lamport clock of the local replica
+    clock: u64,
+}
+
+impl Registers {
+    /**
+     * Creates the registers without values.
+     */
+    pub fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            unstable: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /**
+     * Builds the write of a register, timestamped after every delivered write.
+     *
+     * # Arguments
+     *
+     * `name` - Written register
+     *
+     * `value` - Written value
+     */
+    pub fn write(&mut self, name: &str, value: String) -> Operation {
+        self.clock += 1;
+
+        Operation {
+            name: name.to_string(),
+            value,
+            timestamp: self.clock,
+        }
+    }
+
+    /**
+     * Applies a delivered or local write, which replaces the register's value
+     * if it has a higher timestamp or the same timestamp and a higher sender id.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and counter of the write
+     *
+     * `operation` - Delivered or local write
+     */
+    pub fn apply(&mut self, dot: Dot, operation: Operation) {
+        self.clock = self.clock.max(operation.timestamp);
+
+        let wins = match self.values.get(&operation.name) {
+            Some(current) => (operation.timestamp, dot.0) > (current.timestamp, current.dot.0),
+            None => true,
+        };
+
+        //The losing write is discarded right away, since it never wins later
+        if !wins {
+            return;
+        }
+
+        let replaced = self.values.insert(
+            operation.name.clone(),
+            Value {
+                value: operation.value,
+                timestamp: operation.timestamp,
+                dot,
+                stable: false,
+            },
+        );
+
+        if let Some(replaced) = replaced {
+            self.unstable.remove(&replaced.dot);
+        }
+
+        self.unstable.insert(dot, operation.name);
+    }
+
+    /**
+     * Marks a write as stable, which commits its value if it's still the
+     * register's value.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and counter of the stable write
+     */
+    pub fn mark_stable(&mut self, dot: Dot) {
+        let name = match self.unstable.remove(&dot) {
+            Some(name) => name,
+            None => return,
+        };
+
+        if let Some(value) = self.values.get_mut(&name) {
+            if value.dot == dot {
+                value.stable = true;
+            }
+        }
+    }
+
+    /**
+     * Returns the value of a register, if it was written.
+     *
+     * # Arguments
+     *
+     * `name` - Read register
+     */
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    /**
+     * Returns the names of the written registers, sorted.
+     */
+    pub fn names(&self) -> Vec<&String> {
+        self.values.keys().collect()
+    }
+
+    /**
+     * Returns the number of values that may still be replaced by a concurrent write.
+     */
+    pub fn uncommitted(&self) -> usize {
+        self.unstable.len()
+    }
+}
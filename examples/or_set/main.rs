@@ -0,0 +1,202 @@
+mod set;
+
+use set::{Operation, Set};
+use std::env;
+use std::error::Error;
+use std::io::{self, BufRead};
+use std::thread;
+use std::time::Duration;
+use tcb::broadcast::broadcast_trait::{GenericReturn, TcbError, TCB};
+use tcb::channel::Receiver;
+use tcb::channel::{unbounded, TryRecvError};
+use tcb::configuration::middleware_configuration::{Configuration, Peer, Topology};
+use tcb::graph::graph::GRAPH;
+
+/**
+ * Maximum time waiting for a delivery before handling the typed commands.
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/**
+ * Replicated observed-remove set where every replica runs a GRAPH middleware
+ * instance. An add tags its element with the dot of its message and a remove
+ * only deletes the tags its sender observed, so an add concurrent with a remove
+ * wins. Stable operations are acked and the dots of the stable adds are dropped
+ * from the metadata, which `list` shows shrinking once the group is quiet.
+ *
+ * Usage: or_set <local id> <address of peer 0> <address of peer 1> ...
+ *
+ * e.g. in two terminals, with ids 0 and 1:
+ * cargo run --example or_set -- 0 localhost:7000 localhost:7001
+ *
+ * Commands: `add <element>`, `rm <element>`, `has <element>`, `list` and `quit`.
+ */
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let arguments: Vec<String> = env::args().collect();
+
+    if arguments.len() < 4 {
+        return Err("Usage: or_set <local id> <address of peer 0> <address of peer 1> ...".into());
+    }
+
+    let local_id: usize = arguments[1].parse()?;
+
+    let peers = arguments[2..]
+        .iter()
+        .enumerate()
+        .map(|(id, address)| Peer::new(id, address.clone()))
+        .collect();
+
+    let configuration = Configuration {
+        topology: Topology::new(local_id, peers),
+        ..Configuration::default()
+    };
+
+    println!("Waiting for every replica to connect...");
+    let mut tcb = GRAPH::new(configuration);
+    println!("Replica {} is ready", local_id);
+
+    let commands = read_lines();
+    let mut set = Set::new();
+    let mut sent = 0;
+
+    loop {
+        //Handling every typed command before waiting for the next delivery
+        loop {
+            match commands.try_recv() {
+                Ok(command) => {
+                    if !handle_command(&mut tcb, &mut set, local_id, &mut sent, command.trim()) {
+                        tcb.end();
+                        return Ok(());
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    tcb.end();
+                    return Ok(());
+                }
+            }
+        }
+
+        match tcb.recv_timeout(POLL_INTERVAL) {
+            Ok(GenericReturn::Delivery(payload, id, counter)) => {
+                match bincode::deserialize::<Operation>(&payload) {
+                    Ok(operation) => {
+                        set.apply((id, counter), operation);
+                    }
+                    Err(e) => {
+                        println!("ERROR: Failed to deserialize an operation\n\t- {}", e);
+                    }
+                }
+            }
+            Ok(GenericReturn::Stable(id, counter)) => {
+                //Acking the stable operation so it's deleted from the causal graph,
+                //and dropping its dot from the set's metadata
+                tcb.tcbstable(id, counter)?;
+                set.mark_stable((id, counter));
+            }
+            Ok(GenericReturn::Evicted(id)) => {
+                println!("Replica {} was evicted", id);
+            }
+            Ok(GenericReturn::Fault(fault)) => {
+                println!("Fault -> {:?}", fault);
+            }
+            Ok(_) => {}
+            Err(TcbError::Timeout) => {}
+            Err(_) => {
+                println!("ERROR: The middleware terminated");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/**
+ * Spawns the thread that reads the commands typed by the user.
+ */
+fn read_lines() -> Receiver<String> {
+    let (sender, receiver) = unbounded();
+
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(line) => {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+
+    receiver
+}
+
+/**
+ * Handles a typed command. Returns false if the user quit.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance
+ *
+ * `set` - Local replica of the set
+ *
+ * `local_id` - Id of the local replica
+ *
+ * `sent` - Number of operations sent by the local replica
+ *
+ * `command` - Typed command
+ */
+fn handle_command(
+    tcb: &mut GRAPH,
+    set: &mut Set,
+    local_id: usize,
+    sent: &mut usize,
+    command: &str,
+) -> bool {
+    let mut parts = command.splitn(2, ' ');
+
+    let operation = match (parts.next(), parts.next()) {
+        (Some("add"), Some(element)) => set.add(element),
+        (Some("rm"), Some(element)) => match set.remove(element) {
+            Some(operation) => operation,
+            None => {
+                println!("{} is not in the set", element);
+                return true;
+            }
+        },
+        (Some("has"), Some(element)) => {
+            println!("{} -> {}", element, set.contains(element));
+            return true;
+        }
+        (Some("list"), None) => {
+            for element in set.elements() {
+                println!("{} ({} tags)", element, set.tags(element));
+            }
+            println!("{} adds not stable yet", set.unstable_adds());
+            return true;
+        }
+        (Some("quit"), None) => return false,
+        (Some(""), None) => return true,
+        _ => {
+            println!("Commands: add <element>, rm <element>, has <element>, list, quit");
+            return true;
+        }
+    };
+
+    let serialized_operation =
+        bincode::serialize(&operation).expect("ERROR: Failed to serialize an operation");
+
+    match tcb.send(serialized_operation) {
+        Ok(_) => {
+            //The local operations aren't delivered back, so they're applied here
+            *sent += 1;
+            set.apply((local_id, *sent), operation);
+        }
+        Err(_) => {
+            println!("ERROR: Failed to send the operation");
+        }
+    }
+
+    true
+}
@@ -0,0 +1,209 @@
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/**
+ * Sender id and counter of the message that added an element.
+ */
+pub type Dot = (usize, usize);
+
+/**
+ * Operation broadcast to every replica of the set.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum Operation {
+    ///Adds an element, tagged with the dot of its message
+    Add { element: String },
+    ///Removes the tags of an element its sender observed that weren't stable yet
+    Remove { element: String, observed: Vec<Dot> },
+}
+
+/**
+ * Tag of an element, with the dot of the add that created it.
+ */
+#[derive(Debug, Clone)]
+pub struct Tag {
+    ///Dot of the add
+    pub dot: Dot,
+    ///Flag set once every replica delivered the add
+    pub stable: bool,
+}
+
+/**
+ * Observed-remove set, where an add wins over a concurrent remove. Each add
+ * tags its element with a unique dot, and a remove only deletes the tags its
+ * sender observed, so an element added concurrently with its removal is kept.
+ *
+ * The middleware delivers the operations in a causal order, so a remove always
+ * arrives after the adds it observed and the set needs no tombstones. Once an
+ * add is stable, every replica delivered it and every operation delivered
+ * afterwards was sent after it, so the next remove of its element deletes the
+ * tag without listing it. The dots of the stable tags are dropped from the
+ * metadata, which keeps the removes small for the elements that aren't
+ * concurrently added and removed.
+ */
+#[derive(Default)]
+pub struct Set {
+    ///Tags of each element in the set, sorted by element
+    elements: BTreeMap<String, Vec<Tag>>,
+    ///Element of the tags whose adds aren't stable yet, indexed by their dot
+    unstable: HashMap<Dot, String>,
+}
+
+impl Set {
+    /**
+     * Creates an empty set.
+     */
+    pub fn new() -> Self {
+        Self {
+            elements: BTreeMap::new(),
+            unstable: HashMap::new(),
+        }
+    }
+
+    /**
+     * Builds the add of an element.
+     *
+     * # Arguments
+     *
+     * `element` - Added element
+     */
+    pub fn add(&self, element: &str) -> Operation {
+        Operation::Add {
+            element: element.to_string(),
+        }
+    }
+
+    /**
+     * Builds the remove of an element, which deletes every tag of the element
+     * observed by the local replica. Returns `None` if the element isn't in the set.
+     *
+     * # Arguments
+     *
+     * `element` - Removed element
+     */
+    pub fn remove(&self, element: &str) -> Option<Operation> {
+        let tags = self.elements.get(element)?;
+
+        let observed = tags
+            .iter()
+            .filter(|tag| !tag.stable)
+            .map(|tag| tag.dot)
+            .collect();
+
+        Some(Operation::Remove {
+            element: element.to_string(),
+            observed,
+        })
+    }
+
+    /**
+     * Applies a delivered or local operation.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and counter of the operation
+     *
+     * `operation` - Delivered or local operation
+     */
+    pub fn apply(&mut self, dot: Dot, operation: Operation) {
+        match operation {
+            Operation::Add { element } => {
+                self.elements
+                    .entry(element.clone())
+                    .or_default()
+                    .push(Tag { dot, stable: false });
+                self.unstable.insert(dot, element);
+            }
+            Operation::Remove { element, observed } => {
+                let tags = match self.elements.get_mut(&element) {
+                    Some(tags) => tags,
+                    None => return,
+                };
+
+                //The stable tags were observed by the sender, since it delivered them first
+                tags.retain(|tag| !tag.stable && !observed.contains(&tag.dot));
+
+                for removed in &observed {
+                    self.unstable.remove(removed);
+                }
+
+                //The element stays in the set if it was added concurrently
+                if tags.is_empty() {
+                    self.elements.remove(&element);
+                }
+            }
+        }
+    }
+
+    /**
+     * Marks an add as stable, so its dot is no longer sent as metadata.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and counter of the stable operation
+     */
+    pub fn mark_stable(&mut self, dot: Dot) {
+        let element = match self.unstable.remove(&dot) {
+            Some(element) => element,
+            None => return,
+        };
+
+        if let Some(tags) = self.elements.get_mut(&element) {
+            for tag in tags.iter_mut().filter(|tag| tag.dot == dot) {
+                tag.stable = true;
+            }
+
+            //A single stable tag is enough to keep the element in the set
+            let mut kept_stable = false;
+
+            tags.retain(|tag| {
+                if !tag.stable {
+                    return true;
+                }
+
+                let keep = !kept_stable;
+                kept_stable = true;
+                keep
+            });
+        }
+    }
+
+    /**
+     * Checks if an element is in the set.
+     *
+     * # Arguments
+     *
+     * `element` - Checked element
+     */
+    pub fn contains(&self, element: &str) -> bool {
+        self.elements.contains_key(element)
+    }
+
+    /**
+     * Returns the elements of the set, sorted.
+     */
+    pub fn elements(&self) -> Vec<&String> {
+        self.elements.keys().collect()
+    }
+
+    /**
+     * Returns the number of tags kept for an element.
+     *
+     * # Arguments
+     *
+     * `element` - Checked element
+     */
+    pub fn tags(&self, element: &str) -> usize {
+        match self.elements.get(element) {
+            Some(tags) => tags.len(),
+            None => 0,
+        }
+    }
+
+    /**
+     * Returns the number of adds whose dots are still kept as metadata.
+     */
+    pub fn unstable_adds(&self) -> usize {
+        self.unstable.len()
+    }
+}
@@ -20,18 +20,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     //Reading the configuration file
     let configuration = read_configuration_file(configuration_file)?;
 
-    //The peer's unique id in the group that must be a natural number
-    //This id starts at 0 and sequentially grows with each peer
-    let id: usize = 0;
-
-    //The local port where the middleware will wait for connections
-    let port: usize = 61887;
-
-    //Vec with the addresses and ports where the other peers are waiting for connections
-    let group_addresses = vec![format!("localhost:61888")];
+    //The peer's unique id, the port where the middleware will wait for connections
+    //and the addresses of the other peers are set in the topology of the configuration file
 
     //Creates a new version vector based middleware instance
-    let mut vv = VV::new(id, port, group_addresses, configuration);
+    let mut vv = VV::new(configuration);
 
     //Creating and serializing the message to be sent
     let message = format!("Hello world");
@@ -0,0 +1,94 @@
+use crate::configuration::middleware_configuration::Configuration;
+use std::collections::{BTreeMap, HashMap};
+
+/**
+ * Deliveries sent to the Client that it didn't acknowledge yet, when delivery
+ * acks are enabled. A delivery is only consumed once the Client acks its dot,
+ * so the unacked deliveries can be sent again if the Client lost them, e.g.
+ * when the thread processing them crashed and was restarted.
+ */
+#[derive(Debug)]
+pub struct PendingAcks<T> {
+    ///Unacked deliveries in the order they were delivered
+    deliveries: BTreeMap<u64, T>,
+    ///Position of each unacked delivery, by sender id and message counter
+    positions: HashMap<(usize, usize), u64>,
+    ///Number of deliveries added
+    pushed: u64,
+}
+
+impl<T> PendingAcks<T> {
+    /**
+     * Creates the pending acks of a middleware, or `None` if delivery acks aren't enabled.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn from_configuration(configuration: &Configuration) -> Option<Self> {
+        if configuration.delivery_acks {
+            Some(Self {
+                deliveries: BTreeMap::new(),
+                positions: HashMap::new(),
+                pushed: 0,
+            })
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Adds a delivery sent to the Client.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     *
+     * `delivery` - Delivery sent to the Client
+     */
+    pub fn push(&mut self, id: usize, counter: usize, delivery: T) {
+        self.positions.insert((id, counter), self.pushed);
+        self.deliveries.insert(self.pushed, delivery);
+        self.pushed += 1;
+    }
+
+    /**
+     * Removes an acked delivery. Returns `false` if the delivery wasn't
+     * pending, because it was already acked or wasn't delivered.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     */
+    pub fn ack(&mut self, id: usize, counter: usize) -> bool {
+        match self.positions.remove(&(id, counter)) {
+            Some(position) => self.deliveries.remove(&position).is_some(),
+            None => false,
+        }
+    }
+
+    /**
+     * Returns the unacked deliveries in the order they were delivered.
+     */
+    pub fn unacked(&self) -> impl Iterator<Item = &T> {
+        self.deliveries.values()
+    }
+
+    /**
+     * Returns the number of unacked deliveries.
+     */
+    pub fn len(&self) -> usize {
+        self.deliveries.len()
+    }
+
+    /**
+     * Checks if every delivery was acked.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.deliveries.is_empty()
+    }
+}
@@ -1,6 +1,30 @@
+use crate::channel::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use crate::clock::dot::Dot;
 use crate::configuration::middleware_configuration::Configuration;
-use crossbeam::{RecvError, RecvTimeoutError, TryRecvError};
-use std::time::Duration;
+use crate::metrics::middleware_metrics::{Metrics, ShutdownReport};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/**
+ * Predicate that decides if a delivered message is pushed to the Client,
+ * called by the Middleware thread with the message's sender id, message id
+ * and payload. Messages for which it returns false still advance the causal
+ * state, but their payload never reaches the Client.
+ */
+pub type DeliveryFilter = Arc<dyn Fn(usize, usize, &[u8]) -> bool + Send + Sync>;
+
+/**
+ * Metadata headers attached to a message by the sender, e.g. routing hints,
+ * content types or auth claims. The middleware carries them opaquely with the
+ * message and returns them on its delivery, without encrypting them.
+ */
+pub type Headers = BTreeMap<String, Vec<u8>>;
 
 /**
  * Required API for the Tagged Causal Broadcast middleware.
@@ -14,30 +38,53 @@ pub trait TCB {
      */
     type SendCallReturn;
 
+    /**
+     * Type of the receiver of the stability notifications.
+     */
+    type StableReceiver;
+
+    /**
+     * Type of the cloneable handle that broadcasts messages from other threads.
+     */
+    type Sender: Clone + Send + Sync;
+
     /**
      * Creates a new middleware instance. This function only returns after the middleware
-     * has a connection to every other peer in both directions.
+     * has a connection to every other peer in both directions, unless the configuration
+     * sets a lazy setup. The local id, the port where the middleware will be listening
+     * for connections and the addresses of the other peers are set by the configuration's
+     * topology, which must be valid.
      *
-     * # Arguments
+     * With retention enabled, a GRAPH peer that restarts receives the messages it missed
+     * from every peer it reconnects to. This isn't supported by the VV middleware, nor in
+     * signed or Byzantine-tolerant groups, where a restarted peer starts from scratch.
      *
-     * `local_id` - Peer's globally unique id in the group.
+     * # Arguments
      *
-     * `local_port` - Port where the middleware will be listening for connections.
+     * `configuration` - Middleware's configuration file.
+     */
+    fn new(configuration: Configuration) -> Self;
+
+    /**
+     * Creates a new middleware instance like `new`, but returns
+     * `TcbError::StartupTimeout` with the connections that weren't made instead of
+     * panicking if the configured startup timeout ends first. The threads started
+     * by the middleware are stopped and its listener is closed before returning.
      *
-     * `peer_addresses` - Addresses the middleware will connect to.
+     * # Arguments
      *
      * `configuration` - Middleware's configuration file.
      */
-    fn new(
-        local_id: usize,
-        local_port: usize,
-        peer_addresses: Vec<String>,
-        configuration: Configuration,
-    ) -> Self;
+    fn try_new(configuration: Configuration) -> Result<Self, TcbError>
+    where
+        Self: Sized;
 
     /**
      * Broadcasts a message to every peer in the group.
-     * Returns the sent message context if successfull.
+     * Returns the receipt of the sent message's dot and context in `GRAPH` if
+     * successfull, `TcbError::Terminated` if the middleware terminated or
+     * `TcbError::CounterExhausted` if the message counter is exhausted, which
+     * `GRAPH` returns through the receipt.
      *
      * # Arguments
      *
@@ -46,36 +93,112 @@ pub trait TCB {
     fn send(&mut self, msg: Vec<u8>) -> Self::SendCallReturn;
 
     /**
-     * Signals and waits for the middleware to terminate.
+     * Broadcasts a message tagged with a trace id to every peer in the group.
+     * The trace id is carried with the message and returned on its delivery
+     * at the other peers, so distributed traces can follow the broadcast.
+     * Returns the same as `send`.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `trace_id` - Trace or correlation id of the message
+     */
+    fn send_traced(&mut self, msg: Vec<u8>, trace_id: u128) -> Self::SendCallReturn;
+
+    /**
+     * Broadcasts a message with metadata headers to every peer in the group.
+     * The headers are carried with the message and returned on its delivery
+     * at the other peers, so applications don't need to wrap the payload to
+     * attach their own metadata. Returns the same as `send`.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `headers` - Metadata headers of the message
+     */
+    fn send_with_headers(&mut self, msg: Vec<u8>, headers: Headers) -> Self::SendCallReturn;
+
+    /**
+     * Broadcasts a message that is ordered after the explicit dependencies, besides
+     * the messages already delivered by the Client, e.g. a reply that must follow
+     * a request whose dot was learned out of band. The middleware only broadcasts
+     * the message once every dependency was delivered locally, and the messages
+     * sent after it wait for it to keep their order. Dependencies on messages of
+     * evicted peers that were never delivered are ignored. Returns the same as `send`.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `deps` - Dots of the messages this message must be delivered after
+     */
+    fn send_after(&mut self, msg: Vec<u8>, deps: &[Dot]) -> Self::SendCallReturn;
+
+    /**
+     * Signals and waits for the middleware to terminate. Once it returns, every
+     * call that needs the middleware returns `TcbError::Terminated`, including
+     * the calls of the handles returned by `sender`, and ending it again does nothing.
      */
     fn end(&self);
 
+    /**
+     * Ends the middleware like `end`, then returns the summary of its activity:
+     * the messages sent, delivered, stable and still pending, the bytes moved
+     * and the final metrics of the connection to each peer. Shutting down an
+     * ended middleware only returns the summary again.
+     *
+     * ```
+     * use tcb::broadcast::broadcast_trait::{GenericReturn, TCB};
+     *
+     * let (mut first, mut second) = tcb::quickstart::local_pair();
+     *
+     * first.send(b"hello".to_vec()).unwrap();
+     *
+     * match second.recv() {
+     *     Ok(GenericReturn::Delivery(..)) => {}
+     *     _ => panic!("The message wasn't delivered"),
+     * }
+     *
+     * let report = second.shutdown();
+     * assert_eq!(report.sent, 0);
+     * assert_eq!(report.delivered, 1);
+     * assert_eq!(report.pending, 0);
+     * assert!(report.bytes_received > 0);
+     * assert_eq!(report.peers.len(), 1);
+     *
+     * let report = first.shutdown();
+     * assert_eq!(report.sent, 1);
+     * ```
+     */
+    fn shutdown(&self) -> ShutdownReport;
+
     /**
      * Delivers a message from the middleware. Blocks the calling thread
-     * until a message is delivered or the channel to the middleware is
-     * empty or disconnected.
+     * until a message is delivered, or returns `TcbError::Terminated` once
+     * the middleware terminated.
      */
-    fn recv(&mut self) -> Result<GenericReturn, RecvError>;
+    fn recv(&mut self) -> Result<GenericReturn, TcbError>;
 
     /**
      * Attempts to deliver a message from the middleware without blocking
-     * the caller thread. Either a message is immeadiately delivered
-     * from the channel or an error is returned if the channel is empty.
+     * the caller thread. Either a message is immeadiately delivered from
+     * the channel or `TcbError::Empty` is returned if the channel is empty.
      */
-    fn try_recv(&mut self) -> Result<GenericReturn, TryRecvError>;
+    fn try_recv(&mut self) -> Result<GenericReturn, TcbError>;
 
     /**
      * Waits for a message to be delivered from the middleware for a
-     * limited time. If the channel is empty and not disconnected, the
-     * caller thread is blocked until a message is received in the channel
-     * or the timeout ends. If there are no messages until the timeout ends or
-     * the channel becomes disconnected, an error is returned.
+     * limited time. If the channel is empty, the caller thread is blocked
+     * until a message is received in the channel or the timeout ends, in
+     * which case `TcbError::Timeout` is returned.
      *
      * # Arguments
      *
      * `duration` - Timeout duration
      */
-    fn recv_timeout(&mut self, duration: Duration) -> Result<GenericReturn, RecvTimeoutError>;
+    fn recv_timeout(&mut self, duration: Duration) -> Result<GenericReturn, TcbError>;
 
     /**
      * ACKS a stable message. This is needed for the GRAPH approach so the node with
@@ -93,18 +216,467 @@ pub trait TCB {
      *
      * `counter` - Stable dot counter field
      */
-    fn tcbstable(&mut self, id: usize, counter: usize);
+    fn tcbstable(&mut self, id: usize, counter: usize) -> Result<(), TcbError>;
+
+    /**
+     * Evicts a peer that is known to be permanently gone from the group. The
+     * middleware closes the connections to the peer, ignores any further messages
+     * from it and stops waiting for it when calculating causal stability, so the
+     * remaining peers can resume making stability progress. The client is notified
+     * with a `GenericReturn::Evicted` once the eviction was handled by the middleware.
+     *
+     * # Arguments
+     *
+     * `id` - Evicted peer's globally unique id
+     */
+    fn evict_peer(&mut self, id: usize) -> Result<(), TcbError>;
+
+    /**
+     * Forces every Sender thread to immediately write its buffered messages
+     * into the stream, instead of waiting for the batching thresholds or
+     * timeouts. Useful at latency-critical moments, such as the end of a
+     * client transaction.
+     */
+    fn flush(&mut self) -> Result<(), TcbError>;
+
+    /**
+     * Returns the receiver of the stability notifications if the configuration
+     * routes them to their own channel. The receiver can be moved to another
+     * thread, e.g. a garbage collection thread that acks the stable messages,
+     * so the deliveries aren't interleaved with the stability bookkeeping.
+     * Returns `None` if the notifications are returned by `recv`, if they are
+     * acked automatically or if the receiver was already taken.
+     */
+    fn stable_receiver(&mut self) -> Option<Self::StableReceiver>;
+
+    /**
+     * Returns a handle that broadcasts messages from other threads. The handle
+     * can be cloned and shared between producer threads, which don't need a
+     * lock around the whole middleware instance. The deliveries are still
+     * received through the middleware instance.
+     */
+    fn sender(&self) -> Self::Sender;
+
+    /**
+     * Takes a consistent cut of the messages delivered by the middleware, for
+     * applications implementing consistent backups. The Sender threads are flushed
+     * and the middleware returns its delivered version vector, including the local
+     * messages. The messages delivered up to the cut that the Client didn't receive
+     * yet are returned with it, so once the Client applies them its state is the
+     * state at the cut, and every later delivery is above the cut.
+     */
+    fn snapshot(&mut self) -> Result<Snapshot, TcbError>;
+
+    /**
+     * Registers a predicate that selects which deliveries are returned to the
+     * Client, or removes the current one if `None` is passed. The messages that
+     * don't match are still delivered inside the middleware, so the causal order
+     * of the matching messages and of the Client's own messages is kept, and they
+     * are still notified when stable.
+     *
+     * # Arguments
+     *
+     * `filter` - Predicate over the sender id, message id and payload
+     */
+    fn set_delivery_filter(&mut self, filter: Option<DeliveryFilter>) -> Result<(), TcbError>;
+
+    /**
+     * Stops the middleware from delivering messages to the Client, e.g. while
+     * it does a slow operation. The messages received from the peers keep being
+     * handled and wait in the middleware until the deliveries are resumed, and the
+     * deliveries already sent to the Client can still be received.
+     */
+    fn pause_delivery(&mut self) -> Result<(), TcbError>;
+
+    /**
+     * Resumes the deliveries to the Client after `pause_delivery`, starting with
+     * the messages that were held back while paused.
+     */
+    fn resume_delivery(&mut self) -> Result<(), TcbError>;
+
+    /**
+     * Acknowledges that the Client processed a delivered message, when delivery
+     * acks are enabled in the configuration. The middleware keeps every delivery
+     * until it's acked, so the Client can receive the unacked ones again with
+     * `replay_unacked`. Does nothing if delivery acks aren't enabled.
+     *
+     * # Arguments
+     *
+     * `id` - Delivered message's sender id
+     *
+     * `counter` - Delivered message's counter
+     */
+    fn ack(&mut self, id: usize, counter: usize) -> Result<(), TcbError>;
+
+    /**
+     * Sends the deliveries that weren't acked to the Client again, in the order
+     * they were delivered, e.g. after the thread processing them crashed and
+     * was restarted. They are returned by `recv` and its variants after the
+     * deliveries already in the channel, and stay pending until they are acked.
+     */
+    fn replay_unacked(&mut self) -> Result<(), TcbError>;
+
+    /**
+     * Returns a snapshot of the middleware metrics, such as the round
+     * trip time measured to each peer.
+     */
+    fn metrics(&self) -> Metrics;
+
+    /**
+     * Returns the timestamps of the internal events of the retained messages, in
+     * the order they were delivered, to break down their latency offline. Empty
+     * unless messages are retained in the configuration. Returns
+     * `TcbError::Terminated` if the middleware terminated.
+     */
+    fn debug_dump(&self) -> Result<Vec<MessageTimestamps>, TcbError>;
+
+    /**
+     * Explains why a message wasn't delivered yet, for debugging: whether it
+     * wasn't received, or which messages of which peers it waits for, from the
+     * middleware's current state. Returns `TcbError::Terminated` if the
+     * middleware terminated.
+     *
+     * # Arguments
+     *
+     * `id` - Message's sender id
+     *
+     * `counter` - Message's counter
+     */
+    fn explain(&self, id: usize, counter: usize) -> Result<Explanation, TcbError>;
+
+    /**
+     * Returns the causal graph of the middleware serialized in a format, with
+     * the stage and edges of each node, to inspect the causal state of a wedged
+     * peer. Returns `None` for the VV implementation, which has no causal graph,
+     * and `TcbError::Terminated` if the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `format` - Format of the serialized graph
+     */
+    fn render_graph(&self, format: GraphFormat) -> Result<Option<String>, TcbError>;
+
+    /**
+     * Waits for the middleware to be connected to every peer in both directions,
+     * for a limited time. Returns true once connected. Unless the configuration
+     * sets a lazy setup, the middleware is connected when it's created.
+     *
+     * # Arguments
+     *
+     * `timeout` - Maximum waiting time
+     */
+    fn wait_ready(&self, timeout: Duration) -> bool;
+
+    /**
+     * Returns the address the middleware listens on for connections, which has
+     * the port chosen by the OS if the configuration set the local port to 0.
+     * Returns `None` over the UDP or shared memory transports.
+     */
+    fn local_addr(&self) -> Option<SocketAddr>;
 }
 
 /**
  * Enum for a generic message delivery call return from the BroadcastAPI trait.
  * If its a delivery, the return will the serialized message, the sender's id
- * and the message's id.
+ * and the message's id, plus the trace id or the headers if the message was sent with them.
  * If its a stable message, the return will be the sender's id and the message's id.
 */
 pub enum GenericReturn {
     ///Tuple with the serialized message, sender id and message id
     Delivery(Vec<u8>, usize, usize),
+    ///Tuple with the serialized message, sender id, message id and trace id
+    TracedDelivery(Vec<u8>, usize, usize, u128),
+    ///Tuple with the serialized message, sender id, message id and headers
+    HeaderedDelivery(Vec<u8>, usize, usize, Headers),
     ///Tuple with the sender id and message id
     Stable(usize, usize),
+    ///Counter of the last stable message of each peer, indexed by the peer's id,
+    ///once the stable frontier advanced
+    StableFrontier(Vec<usize>),
+    ///Id of the peer that was evicted from the group
+    Evicted(usize),
+    ///Protocol fault detected in the messages of a peer
+    Fault(Fault),
+    ///Local message that wasn't sent to some or every peer after its send call returned
+    SendFailed(SendFailure),
+}
+
+/**
+ * Consistent cut of the delivered messages returned by `snapshot`.
+ */
+pub struct Snapshot {
+    ///Counter of the last delivered message of each peer, indexed by the peer's id
+    pub cut: Vec<usize>,
+    ///Deliveries and notifications up to the cut that weren't received by the Client yet
+    pub deliveries: Vec<GenericReturn>,
+}
+
+/**
+ * Local message that the middleware failed to send after its send call returned,
+ * so it never left the local peer or wasn't written to a peer's connection.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SendFailure {
+    ///Sender id of the message, which is the local peer's id
+    pub id: usize,
+    ///Message id of the message
+    pub counter: usize,
+    ///Peer the message wasn't sent to, or `None` if it wasn't sent to any peer
+    pub peer_id: Option<usize>,
+    ///Description of the error
+    pub error: String,
+}
+
+/**
+ * Monotonic timestamps of the internal events of a retained message. The time
+ * between a message's reception and its delivery is the causal wait, and the
+ * time between its delivery and its stability is how long the group took to
+ * deliver it.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageTimestamps {
+    ///Sender id
+    pub id: usize,
+    ///Message counter
+    pub counter: usize,
+    ///Size of the message's payload in bytes
+    pub size: usize,
+    ///Instant when the message was received, `None` if it was sent by the local peer
+    pub received_at: Option<Instant>,
+    ///Instant when the message was delivered
+    pub delivered_at: Instant,
+    ///Instant when the message became stable, `None` if it isn't stable yet
+    pub stable_at: Option<Instant>,
+}
+
+/**
+ * Formats of the causal graph returned by `render_graph`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    ///Graphviz DOT language
+    Dot,
+    ///JSON document
+    Json,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_lowercase().as_str() {
+            "dot" => Ok(GraphFormat::Dot),
+            "json" => Ok(GraphFormat::Json),
+            _ => Err(format!("unknown graph format {}", format)),
+        }
+    }
+}
+
+/**
+ * Reason a message wasn't delivered yet, returned by `explain`.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Explanation {
+    ///The message was delivered
+    Delivered,
+    ///The message wasn't received from its sender
+    NotReceived,
+    ///The message's sender was evicted, so its undelivered messages are ignored
+    Evicted,
+    ///The message's dependencies were delivered, and it waits for the previous
+    ///deliveries of a long chain or for the deliveries to be resumed
+    Ready,
+    ///The message waits for the delivery of its dependencies
+    Waiting {
+        dependencies: Vec<PendingDependency>,
+    },
+}
+
+/**
+ * Range of undelivered messages of a peer that a received message depends on.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingDependency {
+    ///Sender id
+    pub id: usize,
+    ///Counter of the first undelivered message
+    pub first: usize,
+    ///Counter of the last undelivered message
+    pub last: usize,
+    ///Whether every message in the range was received, in which case they
+    ///wait for their own dependencies
+    pub received: bool,
+}
+
+/**
+ * Enum of the protocol faults detected by the middleware.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    ///Two different messages were broadcast with the same sender id and message id
+    Equivocation { id: usize, counter: usize },
+    ///A message didn't carry the history digests of its dependencies
+    MissingHistory { id: usize, counter: usize },
+    ///A dependency wasn't received in time, with the dots of the received messages it blocks
+    MissingDependency {
+        id: usize,
+        counter: usize,
+        blocked: Vec<(usize, usize)>,
+    },
+    ///Messages of a peer with counters from `first` to `last` were skipped
+    MissingRange {
+        id: usize,
+        first: usize,
+        last: usize,
+    },
+    ///A message's payload didn't match its checksum, so it's kept pending until a copy is received
+    CorruptedPayload { id: usize, counter: usize },
+}
+
+/**
+ * Enum of the errors returned by the middleware API.
+ *
+ * ```
+ * use std::time::Duration;
+ * use tcb::broadcast::broadcast_trait::{TcbError, TCB};
+ *
+ * let (mut first, second) = tcb::quickstart::local_pair();
+ * let sender = first.sender();
+ *
+ * first.end();
+ *
+ * assert_eq!(first.send(b"late".to_vec()).err(), Some(TcbError::Terminated));
+ * assert_eq!(sender.send(b"late".to_vec()).err(), Some(TcbError::Terminated));
+ * assert_eq!(first.recv().err(), Some(TcbError::Terminated));
+ * assert_eq!(first.try_recv().err(), Some(TcbError::Terminated));
+ * assert_eq!(
+ *     first.recv_timeout(Duration::from_secs(1)).err(),
+ *     Some(TcbError::Terminated)
+ * );
+ * assert_eq!(first.tcbstable(1, 1), Err(TcbError::Terminated));
+ * assert_eq!(first.evict_peer(1), Err(TcbError::Terminated));
+ * assert_eq!(first.flush(), Err(TcbError::Terminated));
+ * assert_eq!(first.snapshot().err(), Some(TcbError::Terminated));
+ * assert_eq!(
+ *     first.catch_up(1, Duration::from_secs(1)).err(),
+ *     Some(TcbError::Terminated)
+ * );
+ *
+ * //Ending the middleware again does nothing
+ * first.end();
+ * second.end();
+ * ```
+ *
+ * The VV implementation returns the same errors once ended.
+ *
+ * ```
+ * use std::time::Duration;
+ * use tcb::broadcast::broadcast_trait::{TcbError, TCB};
+ *
+ * let (mut first, second) = tcb::quickstart::local_vv_pair();
+ * let sender = first.sender();
+ *
+ * first.end();
+ *
+ * assert_eq!(first.send(b"late".to_vec()), Err(TcbError::Terminated));
+ * assert_eq!(sender.send(b"late".to_vec()), Err(TcbError::Terminated));
+ * assert_eq!(first.recv().err(), Some(TcbError::Terminated));
+ * assert_eq!(first.try_recv().err(), Some(TcbError::Terminated));
+ * assert_eq!(
+ *     first.recv_timeout(Duration::from_secs(1)).err(),
+ *     Some(TcbError::Terminated)
+ * );
+ * assert_eq!(first.tcbstable(1, 1), Err(TcbError::Terminated));
+ * assert_eq!(first.evict_peer(1), Err(TcbError::Terminated));
+ * assert_eq!(first.flush(), Err(TcbError::Terminated));
+ * assert_eq!(first.snapshot().err(), Some(TcbError::Terminated));
+ *
+ * first.end();
+ * second.end();
+ * ```
+ *
+ * An IO error keeps its kind and message.
+ *
+ * ```
+ * use std::io;
+ * use tcb::broadcast::broadcast_trait::TcbError;
+ *
+ * let error = TcbError::from(io::Error::new(io::ErrorKind::Other, "disk quota exceeded"));
+ *
+ * assert_eq!(
+ *     error,
+ *     TcbError::Io(io::ErrorKind::Other, "disk quota exceeded".to_string())
+ * );
+ * assert!(error.to_string().contains("disk quota exceeded"));
+ * ```
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TcbError {
+    ///The middleware was ended, or its Middleware thread terminated
+    Terminated,
+    ///The local message counter is exhausted, so no more messages can be sent
+    CounterExhausted,
+    ///No message was ready to be delivered
+    Empty,
+    ///No message was delivered before the timeout
+    Timeout,
+    ///The donor peer couldn't relay every missing message when catching up
+    CatchUpFailed,
+    ///Writing a file failed with the error kind and message
+    Io(io::ErrorKind, String),
+    ///The middleware didn't connect to every peer before the startup timeout,
+    ///described with the connections that weren't made
+    StartupTimeout(String),
+}
+
+impl fmt::Display for TcbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TcbError::Terminated => f.pad("the middleware has terminated"),
+            TcbError::CounterExhausted => f.pad("the message counter is exhausted"),
+            TcbError::Empty => f.pad("no message is ready to be delivered"),
+            TcbError::Timeout => f.pad("timed out waiting for a delivery"),
+            TcbError::CatchUpFailed => f.pad("the donor couldn't relay the missing messages"),
+            TcbError::Io(_, message) => write!(f, "failed to write the file - {}", message),
+            TcbError::StartupTimeout(description) => f.pad(description),
+        }
+    }
+}
+
+impl Error for TcbError {}
+
+impl From<io::Error> for TcbError {
+    fn from(e: io::Error) -> Self {
+        TcbError::Io(e.kind(), e.to_string())
+    }
+}
+
+impl<T> From<SendError<T>> for TcbError {
+    fn from(_: SendError<T>) -> Self {
+        TcbError::Terminated
+    }
+}
+
+impl From<RecvError> for TcbError {
+    fn from(_: RecvError) -> Self {
+        TcbError::Terminated
+    }
+}
+
+impl From<TryRecvError> for TcbError {
+    fn from(e: TryRecvError) -> Self {
+        match e {
+            TryRecvError::Empty => TcbError::Empty,
+            TryRecvError::Disconnected => TcbError::Terminated,
+        }
+    }
+}
+
+impl From<RecvTimeoutError> for TcbError {
+    fn from(e: RecvTimeoutError) -> Self {
+        match e {
+            RecvTimeoutError::Timeout => TcbError::Timeout,
+            RecvTimeoutError::Disconnected => TcbError::Terminated,
+        }
+    }
 }
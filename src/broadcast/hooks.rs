@@ -0,0 +1,65 @@
+/**
+ * Callbacks fired by the Middleware thread at the key points of a message's
+ * life, with the message's dot and payload size. Every callback does nothing
+ * by default, so an implementation only overrides the ones it needs.
+ * The callbacks run in the Middleware thread and should return quickly,
+ * since they delay the delivery of every other message.
+ */
+pub trait Hooks: Send + Sync {
+    /**
+     * Called when a message broadcast by the local peer is handed to the Sender threads.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id of the message.
+     *
+     * `counter` - Counter of the message.
+     *
+     * `size` - Size of the payload in bytes.
+     */
+    fn on_send(&self, _id: usize, _counter: usize, _size: usize) {}
+
+    /**
+     * Called when a message from another peer reaches the Middleware thread.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id of the message.
+     *
+     * `counter` - Counter of the message.
+     *
+     * `size` - Size of the payload in bytes.
+     */
+    fn on_receive(&self, _id: usize, _counter: usize, _size: usize) {}
+
+    /**
+     * Called when a message is delivered to the Client.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id of the message.
+     *
+     * `counter` - Counter of the message.
+     *
+     * `size` - Size of the payload in bytes.
+     */
+    fn on_deliver(&self, _id: usize, _counter: usize, _size: usize) {}
+
+    /**
+     * Called when a message becomes causally stable.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id of the message.
+     *
+     * `counter` - Counter of the message.
+     */
+    fn on_stable(&self, _id: usize, _counter: usize) {}
+}
+
+/**
+ * Hooks that do nothing, used when the middleware is created without hooks.
+ */
+pub struct NoHooks;
+
+impl Hooks for NoHooks {}
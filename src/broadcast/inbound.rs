@@ -0,0 +1,145 @@
+use super::polling::BusyPoll;
+use crate::channel::{Receiver, RecvTimeoutError, TryRecvError};
+use std::collections::VecDeque;
+
+/**
+ * Queues of the messages received from each peer, drained in a round-robin order
+ * by the Middleware thread. Every Reader thread writes to the same channel, so a
+ * peer sending much faster than the others would otherwise delay the delivery of
+ * their messages behind its own. The messages of each peer keep their order.
+ */
+#[derive(Debug)]
+pub struct InboundQueues<T> {
+    ///Received messages of each peer, indexed by the peer's id
+    queues: Vec<VecDeque<T>>,
+    ///Id of the next peer whose queue is drained
+    next: usize,
+    ///Number of queued messages
+    len: usize,
+}
+
+impl<T> InboundQueues<T> {
+    /**
+     * Creates an empty queue for each peer.
+     *
+     * # Arguments
+     *
+     * `peer_number` - Number of peers in the group.
+     */
+    pub fn new(peer_number: usize) -> Self {
+        Self {
+            queues: (0..peer_number).map(|_| VecDeque::new()).collect(),
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /**
+     * Appends a message to the queue of the peer that sent it.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Id of the peer that sent the message
+     *
+     * `message` - Received message
+     */
+    pub fn push(&mut self, peer_id: usize, message: T) {
+        self.queues[peer_id].push_back(message);
+        self.len += 1;
+    }
+
+    /**
+     * Removes the oldest message of the next peer with queued messages,
+     * moving on to the following peer for the next call.
+     */
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let peer_number = self.queues.len();
+
+        for offset in 0..peer_number {
+            let peer_id = (self.next + offset) % peer_number;
+
+            if let Some(message) = self.queues[peer_id].pop_front() {
+                self.next = (peer_id + 1) % peer_number;
+                self.len -= 1;
+                return Some(message);
+            }
+        }
+
+        None
+    }
+
+    /**
+     * Returns the number of queued messages.
+     */
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /**
+     * Checks if no message is queued.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/**
+ * Returns the next message to be handled by the Middleware thread, blocking until
+ * one is received. Without inbound queues, the messages are returned in the order
+ * they were written to the channel. Otherwise, every message already in the channel
+ * is moved to the queue of the peer that sent it and the queues are drained in a
+ * round-robin order, while the other messages, e.g. from the Client, are returned
+ * right away. An error is returned once the channel is disconnected and empty, or a
+ * timeout once the deadline of the poller passes.
+ *
+ * # Arguments
+ *
+ * `receive_channel` - Channel where the middleware receives messages from the Client and Peers.
+ *
+ * `inbound` - Queues of the messages received from each peer, if fairness between peers is enabled.
+ *
+ * `poller` - Spins on the channel before blocking, if busy polling is enabled.
+ *
+ * `peer_of` - Function that returns the id of the peer that sent a message, or `None` if it wasn't sent by a peer.
+ */
+pub fn next_message<T, F>(
+    receive_channel: &Receiver<T>,
+    inbound: &mut Option<InboundQueues<T>>,
+    poller: &mut BusyPoll,
+    peer_of: F,
+) -> Result<T, RecvTimeoutError>
+where
+    F: Fn(&T) -> Option<usize>,
+{
+    let queues = match inbound {
+        Some(queues) => queues,
+        None => return poller.recv(receive_channel),
+    };
+
+    loop {
+        match receive_channel.try_recv() {
+            //Messages with ids outside the group are handled right away and discarded by the middleware
+            Ok(message) => match peer_of(&message) {
+                Some(peer_id) if peer_id < queues.queues.len() => queues.push(peer_id, message),
+                _ => return Ok(message),
+            },
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {
+                if queues.is_empty() {
+                    return Err(RecvTimeoutError::Disconnected);
+                }
+
+                break;
+            }
+        }
+    }
+
+    match queues.pop() {
+        Some(message) => Ok(message),
+        None => poller.recv(receive_channel),
+    }
+}
@@ -1,4 +1,36 @@
+/**
+ * Deliveries kept until they are acknowledged by the Client.
+ */
+pub mod acks;
 /**
  * Trait for implementing a tagged causal broadcast service.
  */
 pub mod broadcast_trait;
+/**
+ * Callbacks fired inside the middleware for instrumentation.
+ */
+pub mod hooks;
+/**
+ * Per-peer queues of the received messages, drained in a round-robin order.
+ */
+pub mod inbound;
+/**
+ * Busy polling of the Middleware thread's channel.
+ */
+pub mod polling;
+/**
+ * Receipts of the delivered messages sent back to their senders.
+ */
+pub mod receipts;
+/**
+ * Log of the delivered messages relayed to the peers catching up with the group.
+ */
+pub mod retention;
+/**
+ * Read-your-writes and monotonic-reads guarantees for threads sharing a middleware.
+ */
+pub mod session;
+/**
+ * End of the middleware's setup, waited for in the background in the lazy mode.
+ */
+pub mod setup;
@@ -0,0 +1,123 @@
+use crate::channel::{Receiver, RecvTimeoutError, TryRecvError};
+use crate::configuration::middleware_configuration::Configuration;
+use std::hint;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+ * Number of empty polls between yields of the spinning thread.
+ */
+const YIELD_INTERVAL: u32 = 64;
+
+/**
+ * Fraction of the configured spin time the spin time never drops below.
+ */
+const MIN_SPIN_FRACTION: u32 = 16;
+
+/**
+ * Receives the messages of the Middleware thread by spinning on the channel before
+ * parking the thread, which avoids the wakeup latency of a blocking receive. The
+ * spin time is halved each time the channel stays empty, so an idle middleware
+ * mostly parks instead of burning a core, and restored once a message arrives
+ * while spinning.
+ */
+#[derive(Debug)]
+pub struct BusyPoll {
+    ///Configured spin time, zero if the thread always parks
+    max_spin: Duration,
+    ///Current spin time
+    spin: Duration,
+    ///Instant after which the receives stop blocking, if set
+    deadline: Option<Instant>,
+}
+
+impl BusyPoll {
+    /**
+     * Creates the poller with the spin time set in the configuration.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn from_configuration(configuration: &Configuration) -> Self {
+        let max_spin = configuration.get_busy_poll();
+
+        Self {
+            max_spin,
+            spin: max_spin,
+            deadline: None,
+        }
+    }
+
+    /**
+     * Sets the instant after which the receives return a timeout instead of
+     * blocking, e.g. while the middleware is shutting down.
+     *
+     * # Arguments
+     *
+     * `deadline` - Instant after which the receives stop blocking, or `None` to block indefinitely
+     */
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /**
+     * Receives a message from a channel, spinning up to the current spin time
+     * before blocking until one is received. An error is returned if the
+     * channel is disconnected and empty, or a timeout once the deadline passes.
+     *
+     * # Arguments
+     *
+     * `receive_channel` - Channel where the messages are received.
+     */
+    pub fn recv<T>(&mut self, receive_channel: &Receiver<T>) -> Result<T, RecvTimeoutError> {
+        if self.max_spin == Duration::from_secs(0) {
+            return self.block(receive_channel);
+        }
+
+        let start = Instant::now();
+        let mut polls: u32 = 0;
+
+        while start.elapsed() < self.spin {
+            match receive_channel.try_recv() {
+                Ok(message) => {
+                    self.spin = self.max_spin;
+                    return Ok(message);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+            }
+
+            polls += 1;
+
+            if polls % YIELD_INTERVAL == 0 {
+                thread::yield_now();
+            } else {
+                hint::spin_loop();
+            }
+        }
+
+        //Spinning less while the channel stays empty
+        self.spin = (self.spin / 2).max(self.max_spin / MIN_SPIN_FRACTION);
+
+        self.block(receive_channel)
+    }
+
+    /**
+     * Blocks until a message is received from a channel or the deadline passes.
+     *
+     * # Arguments
+     *
+     * `receive_channel` - Channel where the messages are received.
+     */
+    fn block<T>(&self, receive_channel: &Receiver<T>) -> Result<T, RecvTimeoutError> {
+        match self.deadline {
+            Some(deadline) => {
+                receive_channel.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => receive_channel
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::channel::Sender;
+use crate::configuration::middleware_configuration::Configuration;
+use crate::transport::messages::MiddlewareSender;
+use std::time::{Duration, Instant};
+
+/**
+ * Maximum time the receipts of delivered messages are held while the
+ * Middleware thread is busy, so they are coalesced into fewer frames.
+ */
+const RECEIPT_INTERVAL: Duration = Duration::from_millis(1);
+
+/**
+ * Receipts of the messages delivered from each peer, sent back to their senders
+ * when delivery receipts are enabled. Each peer's messages are delivered in the
+ * order of their counters, so a receipt only carries the counter of the last
+ * delivered message and acknowledges every message before it.
+ */
+pub struct DeliveryReceipts {
+    ///Counter of the last message of each peer acknowledged to it
+    acknowledged: Vec<usize>,
+    ///Instant when the last receipts were sent
+    last_sent: Instant,
+}
+
+impl DeliveryReceipts {
+    /**
+     * Creates the receipts of a group, or `None` if delivery receipts aren't enabled.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     *
+     * `peer_number` - Number of peers in the group.
+     */
+    pub fn from_configuration(configuration: &Configuration, peer_number: usize) -> Option<Self> {
+        if configuration.delivery_receipts {
+            Some(Self {
+                acknowledged: vec![0; peer_number],
+                last_sent: Instant::now(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /**
+     * Checks if the receipts should be sent, which is when the Middleware thread
+     * is idle or the receipts were held for the maximum time.
+     *
+     * # Arguments
+     *
+     * `idle` - Whether the Middleware thread has no messages left to handle.
+     */
+    pub fn is_due(&self, idle: bool) -> bool {
+        idle || self.last_sent.elapsed() >= RECEIPT_INTERVAL
+    }
+
+    /**
+     * Sends a receipt to each peer whose messages were delivered since its last receipt.
+     *
+     * # Arguments
+     *
+     * `delivered` - Version vector of the delivered messages.
+     *
+     * `channels` - Channels to the Sender threads.
+     *
+     * `channel_peer_ids` - Ids of the peers connected to each channel.
+     */
+    pub fn send(
+        &mut self,
+        delivered: &[usize],
+        channels: &[Sender<MiddlewareSender>],
+        channel_peer_ids: &[usize],
+    ) {
+        for (channel, peer_id) in channels.iter().zip(channel_peer_ids.iter()) {
+            let counter = delivered[*peer_id];
+
+            if counter > self.acknowledged[*peer_id] {
+                self.acknowledged[*peer_id] = counter;
+
+                match channel.send(MiddlewareSender::Receipt { counter }) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!(
+                            "ERROR: Failed to send the delivery receipt to {}\n\t- {}",
+                            peer_id, e
+                        );
+                    }
+                }
+            }
+        }
+
+        self.last_sent = Instant::now();
+    }
+}
@@ -0,0 +1,269 @@
+use crate::broadcast::broadcast_trait::MessageTimestamps;
+use crate::configuration::middleware_configuration::Retention;
+use crate::metrics::middleware_metrics::RetentionMetrics;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/**
+ * Delivered message kept in the retention log.
+ */
+#[derive(Debug, Clone)]
+pub struct RetainedMessage<T> {
+    ///Sender id
+    pub id: usize,
+    ///Message counter
+    pub counter: usize,
+    ///Instant when the message was received, `None` if it was sent by the local peer
+    pub received_at: Option<Instant>,
+    ///Instant when the message was delivered
+    pub delivered_at: Instant,
+    ///Instant when the message became stable, `None` if it isn't stable yet
+    pub stable_at: Option<Instant>,
+    ///Size of the message's payload in bytes
+    pub size: usize,
+    ///Retained message
+    pub message: T,
+}
+
+/**
+ * Log of the delivered messages, in the order they were delivered, which is a
+ * causal order. Messages are kept until they are stable and older than the
+ * retention window, so a peer catching up with the group can receive every
+ * message it's missing from a single peer, unless the log exceeds its maximum
+ * number of messages or bytes, in which case the oldest messages are dropped.
+ */
+#[derive(Debug)]
+pub struct RetentionLog<T> {
+    ///Retained messages in the order they were delivered
+    entries: VecDeque<RetainedMessage<T>>,
+    ///Counter of the last message of each peer removed from the log
+    pruned: Vec<usize>,
+    ///Minimum time a delivered message is kept
+    window: Duration,
+    ///Maximum number of retained messages, unbounded if 0
+    max_messages: usize,
+    ///Maximum number of retained payload bytes, unbounded if 0
+    max_bytes: u64,
+    ///Number of retained payload bytes
+    bytes: u64,
+    ///Number of messages dropped because the log exceeded its bounds
+    dropped: u64,
+    ///Position of each retained message, counting the removed messages
+    positions: HashMap<(usize, usize), u64>,
+    ///Number of messages removed from the log
+    popped: u64,
+}
+
+impl<T> RetentionLog<T> {
+    /**
+     * Creates an empty retention log.
+     *
+     * # Arguments
+     *
+     * `peer_number` - Number of peers in the group.
+     *
+     * `retention` - Retention window and bounds set in the configuration.
+     */
+    pub fn new(peer_number: usize, retention: &Retention) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            pruned: vec![0; peer_number],
+            window: retention.get_window(),
+            max_messages: retention.max_messages,
+            max_bytes: retention.max_bytes,
+            bytes: 0,
+            dropped: 0,
+            positions: HashMap::new(),
+            popped: 0,
+        }
+    }
+
+    /**
+     * Appends a delivered message to the log and drops the oldest messages
+     * while the log exceeds its bounds.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     *
+     * `size` - Size of the message's payload in bytes
+     *
+     * `received_at` - Instant when the message was received, `None` if it was sent by the local peer
+     *
+     * `message` - Delivered message
+     */
+    pub fn push(
+        &mut self,
+        id: usize,
+        counter: usize,
+        size: usize,
+        received_at: Option<Instant>,
+        message: T,
+    ) {
+        self.bytes += size as u64;
+        self.positions
+            .insert((id, counter), self.popped + self.entries.len() as u64);
+        self.entries.push_back(RetainedMessage {
+            id,
+            counter,
+            received_at,
+            delivered_at: Instant::now(),
+            stable_at: None,
+            size,
+            message,
+        });
+
+        while self.exceeds_bounds() {
+            self.pop();
+            self.dropped += 1;
+        }
+    }
+
+    /**
+     * Checks if the log has more messages or bytes than its bounds.
+     */
+    fn exceeds_bounds(&self) -> bool {
+        (self.max_messages > 0 && self.entries.len() > self.max_messages)
+            || (self.max_bytes > 0 && self.bytes > self.max_bytes)
+    }
+
+    /**
+     * Removes the oldest message, recording its counter as removed.
+     */
+    fn pop(&mut self) {
+        if let Some(entry) = self.entries.pop_front() {
+            self.pruned[entry.id] = entry.counter;
+            self.bytes -= entry.size as u64;
+            self.positions.remove(&(entry.id, entry.counter));
+            self.popped += 1;
+        }
+    }
+
+    /**
+     * Records the instant when a retained message became stable. Messages that
+     * were already removed from the log are ignored.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     */
+    pub fn mark_stable(&mut self, id: usize, counter: usize) {
+        if let Some(position) = self.positions.get(&(id, counter)) {
+            let entry = &mut self.entries[(position - self.popped) as usize];
+
+            if entry.stable_at.is_none() {
+                entry.stable_at = Some(Instant::now());
+            }
+        }
+    }
+
+    /**
+     * Removes the oldest messages that are stable and were delivered before the
+     * retention window. Messages are only removed from the front of the log, so
+     * the remaining messages of each peer are contiguous.
+     *
+     * # Arguments
+     *
+     * `stable` - Function that checks if the message with a sender id and counter is stable
+     */
+    pub fn prune<F>(&mut self, stable: F)
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let now = Instant::now();
+
+        while let Some(entry) = self.entries.front() {
+            if now.duration_since(entry.delivered_at) < self.window
+                || !stable(entry.id, entry.counter)
+            {
+                break;
+            }
+
+            self.pop();
+        }
+    }
+
+    /**
+     * Checks if the log has every message above a version vector, which is
+     * false if a message above it was already removed.
+     *
+     * # Arguments
+     *
+     * `vector` - Delivered version vector of the peer catching up
+     */
+    pub fn covers(&self, vector: &[usize]) -> bool {
+        self.pruned
+            .iter()
+            .zip(vector.iter())
+            .all(|(pruned, counter)| pruned <= counter)
+    }
+
+    /**
+     * Returns the retained messages above a version vector, in the order they
+     * were delivered.
+     *
+     * # Arguments
+     *
+     * `vector` - Delivered version vector of the peer catching up
+     */
+    pub fn above<'a>(&'a self, vector: &'a [usize]) -> impl Iterator<Item = &'a T> + 'a {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.counter > vector[entry.id])
+            .map(|entry| &entry.message)
+    }
+
+    /**
+     * Returns the retained messages in the order they were delivered, e.g.
+     * for retransmissions or for dumping the recent history when debugging.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = &RetainedMessage<T>> {
+        self.entries.iter()
+    }
+
+    /**
+     * Returns the timestamps of the retained messages, in the order they were delivered.
+     */
+    pub fn timestamps(&self) -> Vec<MessageTimestamps> {
+        self.entries
+            .iter()
+            .map(|entry| MessageTimestamps {
+                id: entry.id,
+                counter: entry.counter,
+                size: entry.size,
+                received_at: entry.received_at,
+                delivered_at: entry.delivered_at,
+                stable_at: entry.stable_at,
+            })
+            .collect()
+    }
+
+    /**
+     * Returns the statistics of the log.
+     */
+    pub fn stats(&self) -> RetentionMetrics {
+        RetentionMetrics {
+            messages: self.entries.len(),
+            bytes: self.bytes,
+            dropped: self.dropped,
+        }
+    }
+
+    /**
+     * Returns the number of retained messages.
+     */
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /**
+     * Checks if no message is retained.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,192 @@
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/**
+ * Counters of the last message of each peer applied by the application,
+ * shared by the thread that applies the deliveries and the sessions.
+ * The middleware delivers the messages of each peer in the order of their
+ * counters, so a counter covers every previous message of the same peer.
+ */
+#[derive(Debug)]
+pub struct SessionFrontier {
+    ///Counter of the last applied message of each peer, indexed by the peer's id
+    applied: Mutex<Vec<usize>>,
+    ///Signalled when the applied counters advance
+    changed: Condvar,
+}
+
+impl SessionFrontier {
+    /**
+     * Creates the frontier of a group where no message was applied.
+     *
+     * # Arguments
+     *
+     * `peer_number` - Number of peers in the group.
+     */
+    pub fn new(peer_number: usize) -> Self {
+        Self {
+            applied: Mutex::new(vec![0; peer_number]),
+            changed: Condvar::new(),
+        }
+    }
+
+    /**
+     * Records that a message was applied to the application state and wakes the
+     * sessions waiting for it. Must be called for the delivered messages and for
+     * the messages sent by the local peer, which aren't delivered to the Client.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id of the message
+     *
+     * `counter` - Counter of the message
+     */
+    pub fn advance(&self, id: usize, counter: usize) {
+        let mut applied = self
+            .applied
+            .lock()
+            .expect("ERROR: Session frontier lock was poisoned");
+
+        if applied[id] < counter {
+            applied[id] = counter;
+            self.changed.notify_all();
+        }
+    }
+
+    /**
+     * Returns the counters of the last applied message of each peer.
+     */
+    pub fn snapshot(&self) -> Vec<usize> {
+        self.applied
+            .lock()
+            .expect("ERROR: Session frontier lock was poisoned")
+            .clone()
+    }
+
+    /**
+     * Blocks until every counter of a vector was applied, returning the applied
+     * counters, or returns `None` if the timeout ends first.
+     *
+     * # Arguments
+     *
+     * `vector` - Counters that must be applied
+     *
+     * `timeout` - Maximum waiting time
+     */
+    pub fn wait_for(&self, vector: &[usize], timeout: Duration) -> Option<Vec<usize>> {
+        let deadline = Instant::now() + timeout;
+        let mut applied = self
+            .applied
+            .lock()
+            .expect("ERROR: Session frontier lock was poisoned");
+
+        loop {
+            if vector
+                .iter()
+                .zip(applied.iter())
+                .all(|(counter, applied_counter)| counter <= applied_counter)
+            {
+                return Some(applied.clone());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining == Duration::from_secs(0) {
+                return None;
+            }
+
+            applied = self
+                .changed
+                .wait_timeout(applied, remaining)
+                .expect("ERROR: Session frontier lock was poisoned")
+                .0;
+        }
+    }
+}
+
+/**
+ * Session guarantees for an application thread sharing a middleware instance
+ * with other threads. The session vector has the messages the thread wrote and
+ * read, and a read only proceeds once they were applied, so the thread reads its
+ * own writes (read-your-writes) and never reads an older state than a previous
+ * read (monotonic reads).
+ */
+#[derive(Debug, Clone)]
+pub struct Session {
+    ///Counters of the messages written or read by the session, indexed by the peer's id
+    vector: Vec<usize>,
+}
+
+impl Session {
+    /**
+     * Creates a session that didn't write or read any message.
+     *
+     * # Arguments
+     *
+     * `peer_number` - Number of peers in the group.
+     */
+    pub fn new(peer_number: usize) -> Self {
+        Self {
+            vector: vec![0; peer_number],
+        }
+    }
+
+    /**
+     * Records a message broadcast by the session, which must be applied before
+     * the session's next read.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id of the message
+     *
+     * `counter` - Counter of the message
+     */
+    pub fn wrote(&mut self, id: usize, counter: usize) {
+        if self.vector[id] < counter {
+            self.vector[id] = counter;
+        }
+    }
+
+    /**
+     * Blocks until every message written or read by the session was applied and
+     * records the applied messages as read by the session. Returns false if the
+     * timeout ends first, in which case the read must not proceed.
+     *
+     * # Arguments
+     *
+     * `frontier` - Messages applied by the application
+     *
+     * `timeout` - Maximum waiting time
+     */
+    pub fn begin_read(&mut self, frontier: &SessionFrontier, timeout: Duration) -> bool {
+        match frontier.wait_for(&self.vector, timeout) {
+            Some(applied) => {
+                self.vector = applied;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /**
+     * Checks if a message was already written or read by the session, so the
+     * deliveries the session already observed can be filtered out.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id of the message
+     *
+     * `counter` - Counter of the message
+     */
+    pub fn covers(&self, id: usize, counter: usize) -> bool {
+        counter <= self.vector[id]
+    }
+
+    /**
+     * Returns the counters of the messages written or read by the session,
+     * which can be passed to another session or peer to continue it.
+     */
+    pub fn vector(&self) -> &Vec<usize> {
+        &self.vector
+    }
+}
@@ -0,0 +1,285 @@
+use crate::broadcast::broadcast_trait::TcbError;
+use crate::channel::Receiver;
+use crate::configuration::middleware_configuration::Configuration;
+use crate::metrics::middleware_metrics::MiddlewareMetrics;
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/**
+ * State of the setup shared by the threads waiting for its end.
+ */
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SetupState {
+    ///Number of threads that are waiting for the end of the setup
+    Waiting(usize),
+    ///Every thread arrived, so the middleware is connected to every peer
+    Ended,
+    ///The startup timed out before the middleware connected to every peer
+    Aborted,
+}
+
+/**
+ * Barrier signalling the end of the setup, once the middleware connected to every
+ * peer in both directions. Unlike a standard barrier, it can be aborted so the
+ * threads waiting for the setup stop when the startup times out.
+ */
+pub struct SetupBarrier {
+    ///Number of threads that must arrive for the setup to end
+    parties: usize,
+    ///State of the setup
+    state: Mutex<SetupState>,
+    ///Notified once the setup ended or was aborted
+    changed: Condvar,
+}
+
+impl SetupBarrier {
+    /**
+     * Creates the barrier of the setup.
+     *
+     * # Arguments
+     *
+     * `parties` - Number of threads that must arrive for the setup to end.
+     */
+    pub fn new(parties: usize) -> Self {
+        Self {
+            parties,
+            state: Mutex::new(SetupState::Waiting(0)),
+            changed: Condvar::new(),
+        }
+    }
+
+    /**
+     * Blocks the calling thread until every thread arrived. Returns false if the
+     * setup was aborted instead, in which case the caller must stop.
+     */
+    pub fn wait(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if let SetupState::Waiting(arrived) = *state {
+            if arrived + 1 == self.parties {
+                *state = SetupState::Ended;
+                self.changed.notify_all();
+            } else {
+                *state = SetupState::Waiting(arrived + 1);
+            }
+        }
+
+        let state = self
+            .changed
+            .wait_while(state, |state| matches!(state, SetupState::Waiting(_)))
+            .unwrap();
+
+        *state == SetupState::Ended
+    }
+
+    /**
+     * Waits for the end of the setup without arriving at the barrier. Returns true
+     * if it ended, or false if it was aborted or the timeout ended first.
+     *
+     * # Arguments
+     *
+     * `timeout` - Maximum waiting time, or `None` to wait until the setup ends.
+     */
+    pub fn wait_ended(&self, timeout: Option<Duration>) -> bool {
+        let state = self.state.lock().unwrap();
+        let waiting = |state: &mut SetupState| matches!(state, SetupState::Waiting(_));
+
+        let state = match timeout {
+            Some(timeout) => {
+                self.changed
+                    .wait_timeout_while(state, timeout, waiting)
+                    .unwrap()
+                    .0
+            }
+            None => self.changed.wait_while(state, waiting).unwrap(),
+        };
+
+        *state == SetupState::Ended
+    }
+
+    /**
+     * Aborts the setup, waking every waiting thread. Returns false if the setup
+     * had already ended, in which case it isn't aborted.
+     */
+    pub fn abort(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        if *state == SetupState::Ended {
+            return false;
+        }
+
+        *state = SetupState::Aborted;
+        self.changed.notify_all();
+
+        true
+    }
+
+    /**
+     * Returns true if the setup was aborted.
+     */
+    pub fn is_aborted(&self) -> bool {
+        *self.state.lock().unwrap() == SetupState::Aborted
+    }
+}
+
+/**
+ * Spawns the Setup thread that waits for the end of the setup, when the middleware
+ * is connected to every peer in both directions.
+ *
+ * # Arguments
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `connected` - Channel notified of each connection made to a peer, or `None`
+ * if the connections need no setup.
+ *
+ * `peer_number` - Number of other peers in the group.
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ *
+ * `metrics` - Metrics where the connections made to the peers are recorded.
+ */
+pub fn wait_for_setup(
+    local_id: usize,
+    connected: Option<Receiver<usize>>,
+    peer_number: usize,
+    setup_end_barrier: Arc<SetupBarrier>,
+    metrics: Arc<MiddlewareMetrics>,
+) {
+    thread::Builder::new()
+        .name(format!("setup_thread_{}", local_id))
+        .spawn(move || {
+            match connected {
+                Some(connected) => {
+                    for peer_id in connected.iter().take(peer_number) {
+                        metrics.update_connection(peer_id, true);
+                    }
+                }
+                None => {
+                    for peer_id in (0..=peer_number).filter(|peer_id| *peer_id != local_id) {
+                        metrics.update_connection(peer_id, true);
+                    }
+                }
+            }
+
+            setup_end_barrier.wait();
+        })
+        .unwrap();
+}
+
+/**
+ * Blocks the calling thread until the end of the setup. If the configured startup
+ * timeout ends first, the setup is aborted and the returned error describes the
+ * connections that weren't made.
+ *
+ * # Arguments
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `peer_addresses` - Addresses of the other peers.
+ *
+ * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics where the connections made to the peers are recorded.
+ */
+pub fn wait_for_startup(
+    setup_end_barrier: &SetupBarrier,
+    local_id: usize,
+    peer_addresses: &[String],
+    configuration: &Configuration,
+    metrics: &MiddlewareMetrics,
+) -> Result<(), TcbError> {
+    let timeout = match configuration.startup_timeout {
+        0 => None,
+        _ => Some(configuration.get_startup_timeout()),
+    };
+
+    //The setup may end between the timeout and the abort
+    if setup_end_barrier.wait_ended(timeout) || !setup_end_barrier.abort() {
+        return Ok(());
+    }
+
+    Err(TcbError::StartupTimeout(format!(
+        "{} timed out after {:?} connecting to the group{}",
+        local_id,
+        configuration.get_startup_timeout(),
+        missing_connections(local_id, peer_addresses, metrics)
+    )))
+}
+
+/**
+ * Wakes the Acceptor thread blocked waiting for a connection after the setup was
+ * aborted, so it stops and the listener is closed.
+ *
+ * # Arguments
+ *
+ * `local_addr` - Address of the TCP listener, or `None` if the middleware has none.
+ */
+pub fn wake_acceptor(local_addr: Option<SocketAddr>) {
+    if let Some(local_addr) = local_addr {
+        let address = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), local_addr.port());
+        let _ = TcpStream::connect(address);
+    }
+}
+
+/**
+ * Describes the connections to and from each peer that weren't made yet.
+ *
+ * # Arguments
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `peer_addresses` - Addresses of the other peers.
+ *
+ * `metrics` - Metrics where the connections made to the peers are recorded.
+ */
+pub fn missing_connections(
+    local_id: usize,
+    peer_addresses: &[String],
+    metrics: &MiddlewareMetrics,
+) -> String {
+    let mut description = String::new();
+
+    for peer in metrics.snapshot().peers {
+        if peer.outbound_connected && peer.inbound_connected {
+            continue;
+        }
+
+        let index = if peer.peer_id < local_id {
+            peer.peer_id
+        } else {
+            peer.peer_id - 1
+        };
+
+        let direction = |connected: bool| if connected { "connected" } else { "missing" };
+
+        description.push_str(&format!(
+            "\n\t- Peer {} at {}: outbound {}, inbound {}",
+            peer.peer_id,
+            peer_addresses[index],
+            direction(peer.outbound_connected),
+            direction(peer.inbound_connected)
+        ));
+    }
+
+    description
+}
+
+/**
+ * Waits for the end of the setup for a limited time. Returns true if the
+ * middleware is connected to every peer in both directions, or false if the
+ * timeout ended first or the setup was aborted.
+ *
+ * # Arguments
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ *
+ * `timeout` - Maximum waiting time.
+ */
+pub fn wait_ready(setup_end_barrier: &SetupBarrier, timeout: Duration) -> bool {
+    setup_end_barrier.wait_ended(Some(timeout))
+}
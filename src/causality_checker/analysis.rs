@@ -0,0 +1,215 @@
+use super::causality_checker_structs::CheckNode;
+use crate::clock::dot::Dot;
+use crate::graph::middleware::dag::ArrayMap;
+use bit_vec::BitVec;
+
+/**
+ * Builds the transitive reduction of a graph built by the causality checker, which
+ * keeps only the direct dependencies between messages, so the graph is easier to
+ * visualize. The nodes keep their indexes. The reachability of every node is kept
+ * in memory, so it's meant for graphs small enough to be visualized. Returns `None`
+ * if the graph has a cycle, such as one loaded from a corrupted file.
+ *
+ * # Arguments
+ *
+ * `dag` - Graph built by the causality checker.
+ */
+pub fn transitive_reduction(dag: &ArrayMap<CheckNode>) -> Option<ArrayMap<CheckNode>> {
+    let node_number = dag.node_number();
+    let order = topological_order(dag)?;
+
+    let mut position: Vec<usize> = vec![0; node_number];
+    for (i, index) in order.iter().enumerate() {
+        position[*index] = i;
+    }
+
+    //Nodes reachable from each node, computed from the sinks to the sources
+    let mut reachable: Vec<BitVec> = vec![BitVec::from_elem(node_number, false); node_number];
+    let mut reduced: ArrayMap<CheckNode> = ArrayMap::new(node_number);
+
+    for index in 0..node_number {
+        reduced.push(CheckNode::new(dag[index].dot));
+    }
+
+    for index in order.iter().rev() {
+        let mut successors: Vec<usize> = dag[*index].successors.to_vec();
+        successors.sort_by_key(|successor| position[*successor]);
+        successors.dedup();
+
+        let mut covered = BitVec::from_elem(node_number, false);
+
+        //A successor reachable through an earlier successor is an indirect dependency
+        for successor in successors {
+            if !covered.get(successor).unwrap() {
+                reduced[*index].successors.push(successor);
+                reduced[successor].predecessors.push(*index);
+            }
+
+            covered.set(successor, true);
+            covered.or(&reachable[successor]);
+        }
+
+        reachable[*index] = covered;
+    }
+
+    Some(reduced)
+}
+
+/**
+ * Returns the longest chain of causally dependent messages in a graph built by
+ * the causality checker, from its first to its last message. This is the critical
+ * path of the workload, since its messages can't be delivered in parallel. Returns
+ * `None` if the graph has a cycle.
+ *
+ * # Arguments
+ *
+ * `dag` - Graph built by the causality checker.
+ */
+pub fn critical_path(dag: &ArrayMap<CheckNode>) -> Option<Vec<Dot>> {
+    let node_number = dag.node_number();
+    let order = topological_order(dag)?;
+
+    //Length of the longest path ending at each node and its previous node
+    let mut lengths: Vec<usize> = vec![1; node_number];
+    let mut previous: Vec<Option<usize>> = vec![None; node_number];
+    let mut last: Option<usize> = None;
+
+    for index in order {
+        for predecessor in &dag[index].predecessors {
+            if lengths[*predecessor] + 1 > lengths[index] {
+                lengths[index] = lengths[*predecessor] + 1;
+                previous[index] = Some(*predecessor);
+            }
+        }
+
+        match last {
+            Some(last_index) if lengths[last_index] >= lengths[index] => {}
+            _ => last = Some(index),
+        }
+    }
+
+    let mut path: Vec<Dot> = Vec::with_capacity(last.map_or(0, |index| lengths[index]));
+
+    while let Some(index) = last {
+        path.push(dag[index].dot);
+        last = previous[index];
+    }
+
+    path.reverse();
+    Some(path)
+}
+
+/**
+ * Returns the indexes of the graph's nodes in a topological order, or `None` if
+ * the graph has a cycle.
+ *
+ * # Arguments
+ *
+ * `dag` - Graph built by the causality checker.
+ */
+fn topological_order(dag: &ArrayMap<CheckNode>) -> Option<Vec<usize>> {
+    let node_number = dag.node_number();
+    let mut pending: Vec<usize> = vec![0; node_number];
+
+    for node in dag.iter() {
+        for successor in &node.successors {
+            pending[*successor] += 1;
+        }
+    }
+
+    let mut ready: Vec<usize> = (0..node_number).filter(|i| pending[*i] == 0).collect();
+    let mut order: Vec<usize> = Vec::with_capacity(node_number);
+
+    while let Some(index) = ready.pop() {
+        order.push(index);
+
+        for successor in &dag[index].successors {
+            pending[*successor] -= 1;
+
+            if pending[*successor] == 0 {
+                ready.push(*successor);
+            }
+        }
+    }
+
+    //The nodes of a cycle never have all their predecessors ordered
+    if order.len() == node_number {
+        Some(order)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_graphs::build_graph;
+    use super::*;
+
+    /**
+     * Builds a graph where 0:1 precedes 1:1, 1:2 and 0:2 in a chain, and 2:1 precedes
+     * 0:2 on its own, with the indirect edge from 0:1 to 0:2.
+     */
+    fn build_chain_graph() -> ArrayMap<CheckNode> {
+        build_graph(
+            &[
+                Dot::new(0, 1),
+                Dot::new(1, 1),
+                Dot::new(1, 2),
+                Dot::new(0, 2),
+                Dot::new(2, 1),
+            ],
+            &[(0, 1), (1, 2), (2, 3), (0, 4), (4, 3), (0, 3)],
+        )
+    }
+
+    /**
+     * Returns the sorted successors of a node.
+     */
+    fn successors(dag: &ArrayMap<CheckNode>, index: usize) -> Vec<usize> {
+        let mut successors = dag[index].successors.to_vec();
+        successors.sort_unstable();
+        successors
+    }
+
+    #[test]
+    fn transitive_reduction_removes_the_indirect_edges() {
+        let reduced = transitive_reduction(&build_chain_graph()).unwrap();
+
+        assert_eq!(reduced.node_number(), 5);
+        assert_eq!(reduced[0].dot, Dot::new(0, 1));
+        assert_eq!(successors(&reduced, 0), vec![1, 4]);
+        assert_eq!(successors(&reduced, 1), vec![2]);
+        assert_eq!(successors(&reduced, 2), vec![3]);
+        assert_eq!(successors(&reduced, 3), Vec::<usize>::new());
+        assert_eq!(successors(&reduced, 4), vec![3]);
+
+        let mut predecessors = reduced[3].predecessors.to_vec();
+        predecessors.sort_unstable();
+        assert_eq!(predecessors, vec![2, 4]);
+    }
+
+    #[test]
+    fn critical_path_is_the_longest_chain() {
+        assert_eq!(
+            critical_path(&build_chain_graph()),
+            Some(vec![
+                Dot::new(0, 1),
+                Dot::new(1, 1),
+                Dot::new(1, 2),
+                Dot::new(0, 2)
+            ])
+        );
+        assert_eq!(critical_path(&ArrayMap::new(0)), Some(Vec::new()));
+    }
+
+    #[test]
+    fn graphs_with_a_cycle_are_refused() {
+        let dag = build_graph(
+            &[Dot::new(0, 1), Dot::new(1, 1), Dot::new(0, 2)],
+            &[(0, 1), (1, 2), (2, 1)],
+        );
+
+        assert!(transitive_reduction(&dag).is_none());
+        assert!(critical_path(&dag).is_none());
+    }
+}
@@ -1,9 +1,12 @@
 use super::causality_checker_structs::*;
+use crate::clock::dot::{Dot, DotMap};
+use crate::clock::version_vector::VersionVector;
 use crate::graph::middleware::dag::ArrayMap;
-use crate::graph::middleware::dot::Dot;
-use crate::vv::structs::version_vector::VersionVector;
 use bit_vec::BitVec;
-use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::atomic::Ordering;
 use std::usize;
 
 /**
@@ -22,225 +25,607 @@ pub fn check_causal_delivery(
     peer_dot_sequences: Vec<Vec<CausalCheck>>,
     graph_implementation: bool,
 ) -> CausalityChecker {
-    let mut global_causal_dag: ArrayMap<CheckNode> = ArrayMap::new(2 * peer_number);
-    let mut dot_to_index_map: HashMap<Dot, usize> = HashMap::new();
-    let mut peer_version_vectors: Vec<VersionVector> = Vec::with_capacity(peer_number);
-    let mut dot_version_vector_map: HashMap<Dot, VersionVector> = HashMap::new();
-    let mut peer_dot_sequence_indexes: Vec<usize> = Vec::with_capacity(peer_number);
-    let mut peer_dot_sequence_prev_indexes: Vec<usize> = Vec::with_capacity(peer_number);
-    let mut peer_version_matrices: Vec<VersionMatrix> = Vec::with_capacity(peer_number);
-
-    for _ in 0..peer_number {
-        peer_version_vectors.push(VersionVector::new(peer_number));
-        peer_dot_sequence_indexes.push(0);
-        peer_dot_sequence_prev_indexes.push(0);
-        peer_version_matrices.push(VersionMatrix::new(peer_number));
+    check_causal_delivery_with(
+        peer_number,
+        peer_dot_sequences,
+        graph_implementation,
+        &CheckerOptions::default(),
+    )
+}
+
+/**
+ * Starts the causality checker by using the group's dot sequences and
+ * the options of the verified properties.
+ *
+ * # Arguments
+ *
+ * `peer_number` - group size
+ *
+ * `peer_dot_sequences` - sequences with the messages' dots
+ *
+ * `graph_implementation` - flag that if True the middleware used a graph implementation
+ *
+ * `options` - properties verified by the checker
+ */
+pub fn check_causal_delivery_with(
+    peer_number: usize,
+    peer_dot_sequences: Vec<Vec<CausalCheck>>,
+    graph_implementation: bool,
+    options: &CheckerOptions,
+) -> CausalityChecker {
+    CausalityCheckerSession::new(
+        peer_number,
+        peer_dot_sequences,
+        graph_implementation,
+        options.clone(),
+    )
+    .run()
+}
+
+/**
+ * Cause of the error that ended a check, which is returned again by the later steps.
+ */
+#[derive(Clone, Serialize, Deserialize)]
+struct CheckerFailure {
+    ///Type of the error
+    error_type: CausalityCheckerErrorEnum,
+    ///Message detailing the cause of the error
+    message: String,
+    ///Dot where the error was thrown
+    current_dot: Dot,
+    ///Peer where the error was thrown
+    current_peer: usize,
+    ///Index in the peer's dot sequence where the error was thrown
+    current_peer_dot_sequence_index: usize,
+}
+
+/**
+ * State of a causality check over the group's dot sequences, which can be run until
+ * the end, stepped one entry at a time, or saved into a file to be resumed later.
+ */
+#[derive(Serialize, Deserialize)]
+pub struct CausalityCheckerSession {
+    ///Group size
+    peer_number: usize,
+    ///Flag that if True the middleware used a graph implementation
+    graph_implementation: bool,
+    ///Properties verified by the checker, which aren't saved with the session
+    #[serde(skip)]
+    options: CheckerOptions,
+    ///Dot sequences of the group's peers.
+    peer_dot_sequences: Vec<DotSequence>,
+    ///Graph built by the causality checker while traversing the dot sequences.
+    global_causal_dag: ArrayMap<CheckNode>,
+    ///Struct that maps a dot to its index in the causal dependency graph mapped as an array.
+    dot_to_index_map: DotMap<usize>,
+    ///Version vector of each peer.
+    peer_version_vectors: Vec<VersionVector>,
+    ///Structs with the version vectors of each dot.
+    dot_version_vector_map: DotMap<VersionVector>,
+    ///Vector with each peer's next dot sequence index.
+    peer_dot_sequence_indexes: Vec<usize>,
+    ///Vector with each peer's previous sent message dot sequence index.
+    peer_dot_sequence_prev_indexes: Vec<usize>,
+    ///Version matrix of each peer, used to verify the stable messages.
+    peer_version_matrices: Vec<VersionMatrix>,
+    ///Number of stable entries of each message.
+    stable_counts: DotMap<usize>,
+    ///Counter of the last pruned message of each peer.
+    pruned_counters: Vec<usize>,
+    ///Peer whose dot sequence is being traversed.
+    current_peer: usize,
+    ///Number of entries in the group's dot sequences.
+    total: usize,
+    ///Number of processed entries of the next progress report.
+    next_report: usize,
+    ///Number of processed entries of the next pruning.
+    next_prune: usize,
+    ///Error that ended the check, if any.
+    failure: Option<CheckerFailure>,
+}
+
+impl CausalityCheckerSession {
+    /**
+     * Builds a session that starts at the beginning of the group's dot sequences.
+     *
+     * # Arguments
+     *
+     * `peer_number` - group size
+     *
+     * `peer_dot_sequences` - sequences with the messages' dots
+     *
+     * `graph_implementation` - flag that if True the middleware used a graph implementation
+     *
+     * `options` - properties verified by the checker
+     */
+    pub fn new(
+        peer_number: usize,
+        peer_dot_sequences: Vec<Vec<CausalCheck>>,
+        graph_implementation: bool,
+        options: CheckerOptions,
+    ) -> Self {
+        let mut peer_version_vectors: Vec<VersionVector> = Vec::with_capacity(peer_number);
+        let mut peer_version_matrices: Vec<VersionMatrix> = Vec::with_capacity(peer_number);
+
+        for _ in 0..peer_number {
+            peer_version_vectors.push(VersionVector::new(peer_number));
+            peer_version_matrices.push(VersionMatrix::new(peer_number));
+        }
+
+        let total: usize = peer_dot_sequences
+            .iter()
+            .map(|sequence| sequence.len())
+            .sum();
+
+        Self {
+            peer_number,
+            graph_implementation,
+            next_report: options.progress_interval,
+            next_prune: Self::prune_interval(peer_number),
+            options,
+            peer_dot_sequences: peer_dot_sequences
+                .into_iter()
+                .map(DotSequence::new)
+                .collect(),
+            global_causal_dag: ArrayMap::new(2 * peer_number),
+            dot_to_index_map: DotMap::default(),
+            peer_version_vectors,
+            dot_version_vector_map: DotMap::default(),
+            peer_dot_sequence_indexes: vec![0; peer_number],
+            peer_dot_sequence_prev_indexes: vec![0; peer_number],
+            peer_version_matrices,
+            stable_counts: DotMap::default(),
+            pruned_counters: vec![0; peer_number],
+            current_peer: 0,
+            total,
+            failure: None,
+        }
     }
 
-    for i in 0..peer_number {
-        let initial_vec_dot_index = peer_dot_sequence_indexes[i];
-        let current_peer_dot_sequence = peer_dot_sequences
-            .get(i)
-            .expect("ERROR: When getting the current peer dot sequence");
-
-        for j in initial_vec_dot_index..current_peer_dot_sequence.len() {
-            match current_peer_dot_sequence
-                .get(j)
-                .expect("ERROR: When getting the dot of current peer dot sequence")
-            {
-                CausalCheck::Send { sent_dot, context } => {
-                    let current_peer_dot = sent_dot.clone();
-
-                    if current_peer_dot.id != i {
-                        return CausalityChecker::Error(CausalityCheckerError::new(
-                            CausalityCheckerErrorEnum::Send,
-                            "A Dot's id and a peer's id don't match!".to_string(),
-                            global_causal_dag,
-                            peer_dot_sequences,
-                            dot_to_index_map,
-                            peer_version_vectors,
-                            dot_version_vector_map,
-                            peer_dot_sequence_indexes,
-                            peer_dot_sequence_prev_indexes,
-                            current_peer_dot.clone(),
-                            i,
-                            j,
-                        ));
-                    }
+    /**
+     * Loads a session saved into a file, which continues from the entry where it was saved.
+     *
+     * # Arguments
+     *
+     * `path` - File where the session was saved
+     *
+     * `options` - properties verified by the checker, which should match the ones of the saved session
+     */
+    pub fn resume(path: &str, options: CheckerOptions) -> Result<Self, Box<dyn Error>> {
+        let file = BufReader::new(File::open(path)?);
+        let mut session: Self = bincode::deserialize_from(file)?;
+        session.options = options;
 
-                    if !handle_sender_delivered_message(
+        Ok(session)
+    }
+
+    /**
+     * Saves the session into a file, so the check can be resumed later.
+     *
+     * # Arguments
+     *
+     * `path` - File where the session is saved
+     */
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(&mut file, self)?;
+        file.flush()?;
+
+        Ok(())
+    }
+
+    /**
+     * Returns the index of the next processed entry in each peer's dot sequence.
+     */
+    pub fn positions(&self) -> &Vec<usize> {
+        &self.peer_dot_sequence_indexes
+    }
+
+    /**
+     * Returns the graph built from the entries processed so far. When pruning, the
+     * slots of the removed nodes are kept until the graph is compacted by `run`.
+     */
+    pub fn graph(&self) -> &ArrayMap<CheckNode> {
+        &self.global_causal_dag
+    }
+
+    /**
+     * Processes the dot sequences until the end or until an error is thrown. The graph
+     * of a pruned check is compacted, so it only has the remaining nodes.
+     */
+    pub fn run(mut self) -> CausalityChecker {
+        loop {
+            match self.step() {
+                Ok(CheckerStep::Processed { .. }) => {}
+                Ok(CheckerStep::Finished) if self.options.prune_processed => {
+                    return CausalityChecker::Ok(compact_graph(
+                        &self.global_causal_dag,
+                        &self.dot_to_index_map,
+                    ))
+                }
+                Ok(CheckerStep::Finished) => return CausalityChecker::Ok(self.global_causal_dag),
+                Err(error) => return CausalityChecker::Error(*error),
+            }
+        }
+    }
+
+    /**
+     * Processes the next entry of the dot sequences, along with the entries of the
+     * other peers it depends on. An error ends the session and is returned again by
+     * the later steps, except a cancellation, after which the session can be saved
+     * or stepped again once the flag is cleared.
+     */
+    pub fn step(&mut self) -> Result<CheckerStep, Box<CausalityCheckerError>> {
+        if let Some(failure) = &self.failure {
+            return Err(self.failure_error(failure));
+        }
+
+        //Moving to the next peer once its dot sequence was processed
+        while self.current_peer < self.peer_number
+            && self.peer_dot_sequence_indexes[self.current_peer]
+                >= self.peer_dot_sequences[self.current_peer].len()
+        {
+            self.current_peer += 1;
+
+            if self.current_peer == self.peer_number {
+                report_progress(&self.options, &self.peer_dot_sequence_indexes, self.total);
+            }
+        }
+
+        if self.current_peer >= self.peer_number {
+            return Ok(CheckerStep::Finished);
+        }
+
+        let i = self.current_peer;
+        let j = self.peer_dot_sequence_indexes[i];
+        let entry_dot = CausalCheck::get_dot(&self.peer_dot_sequences[i][j]);
+
+        let cancelled = match &self.options.cancelled {
+            Some(cancelled) => cancelled.load(Ordering::Relaxed),
+            None => false,
+        };
+
+        if cancelled {
+            return Err(self.error(
+                CausalityCheckerErrorEnum::Cancelled,
+                "The check was cancelled".to_string(),
+                entry_dot,
+                i,
+                j,
+            ));
+        }
+
+        //The recursive calls also advance the other peers' positions
+        let processed: usize = self.peer_dot_sequence_indexes.iter().sum();
+
+        if processed >= self.next_report {
+            report_progress(&self.options, &self.peer_dot_sequence_indexes, self.total);
+            self.next_report = processed + self.options.progress_interval.max(1);
+        }
+
+        if self.options.prune_processed && processed >= self.next_prune {
+            prune_processed_dots(
+                &mut self.global_causal_dag,
+                &mut self.dot_to_index_map,
+                &mut self.dot_version_vector_map,
+                &self.peer_version_vectors,
+                &mut self.stable_counts,
+                &mut self.pruned_counters,
+                &self.options,
+            );
+
+            //Dropping the processed entries, except each peer's previous sent message
+            for (i, sequence) in self.peer_dot_sequences.iter_mut().enumerate() {
+                sequence.drop_before(
+                    self.peer_dot_sequence_indexes[i].min(self.peer_dot_sequence_prev_indexes[i]),
+                );
+            }
+
+            self.next_prune = processed + Self::prune_interval(self.peer_number);
+        }
+
+        let current_peer_dot_sequence = &self.peer_dot_sequences[i];
+
+        match current_peer_dot_sequence
+            .get(j)
+            .expect("ERROR: When getting the dot of current peer dot sequence")
+        {
+            CausalCheck::Send { sent_dot, context } => {
+                let current_peer_dot = *sent_dot;
+
+                if current_peer_dot.id != i {
+                    return Err(self.error(
+                        CausalityCheckerErrorEnum::Send,
+                        "A Dot's id and a peer's id don't match!".to_string(),
                         current_peer_dot,
-                        &mut global_causal_dag,
-                        &mut dot_to_index_map,
-                        &mut peer_version_vectors,
-                        &mut dot_version_vector_map,
-                        &mut peer_dot_sequence_indexes,
-                        &mut peer_dot_sequence_prev_indexes,
-                        current_peer_dot_sequence,
-                        &mut peer_version_matrices,
+                        i,
+                        j,
+                    ));
+                }
+
+                if self.graph_implementation && self.options.strict_context {
+                    match check_send_context(
+                        &current_peer_dot,
                         &context,
-                        &graph_implementation,
+                        &self.peer_version_vectors[i],
+                        &self.dot_version_vector_map,
                     ) {
-                        return CausalityChecker::Error(CausalityCheckerError::new(
-                            CausalityCheckerErrorEnum::Delivery,
-                            "The Sender's Dot was already in the graph!".to_string(),
-                            global_causal_dag,
-                            peer_dot_sequences,
-                            dot_to_index_map,
-                            peer_version_vectors,
-                            dot_version_vector_map,
-                            peer_dot_sequence_indexes,
-                            peer_dot_sequence_prev_indexes,
-                            current_peer_dot.clone(),
-                            i,
-                            j,
-                        ));
+                        None => {}
+                        Some(message) => {
+                            return Err(self.error(
+                                CausalityCheckerErrorEnum::Context,
+                                message,
+                                current_peer_dot,
+                                i,
+                                j,
+                            ));
+                        }
                     }
                 }
 
-                CausalCheck::Delivery { dev_dot } => {
-                    let current_peer_dot = dev_dot.clone();
-
-                    if !dot_to_index_map.contains_key(&current_peer_dot) {
-                        let mut sender_bits = BitVec::from_elem(peer_number, false);
-                        sender_bits.set(i, true);
-
-                        match handle_peer_dot(
-                            &current_peer_dot,
-                            &peer_dot_sequences,
-                            &mut global_causal_dag,
-                            &mut dot_to_index_map,
-                            &mut peer_version_vectors,
-                            &mut dot_version_vector_map,
-                            &mut peer_dot_sequence_indexes,
-                            &mut peer_dot_sequence_prev_indexes,
-                            &mut peer_version_matrices,
-                            &mut sender_bits,
-                            &graph_implementation,
-                        ) {
-                            HandlePeerDotCausalError::Ok => {}
-                            HandlePeerDotCausalError::CausalDeliveryError {
+                if !handle_sender_delivered_message(
+                    current_peer_dot,
+                    &mut self.global_causal_dag,
+                    &mut self.dot_to_index_map,
+                    &mut self.peer_version_vectors,
+                    &mut self.dot_version_vector_map,
+                    &mut self.peer_dot_sequence_indexes,
+                    &mut self.peer_dot_sequence_prev_indexes,
+                    current_peer_dot_sequence,
+                    &mut self.peer_version_matrices,
+                    context,
+                    &self.graph_implementation,
+                    &self.options,
+                ) {
+                    return Err(self.error(
+                        CausalityCheckerErrorEnum::Delivery,
+                        "The Sender's Dot was already in the graph!".to_string(),
+                        current_peer_dot,
+                        i,
+                        j,
+                    ));
+                }
+            }
+
+            CausalCheck::Delivery { dev_dot } => {
+                let current_peer_dot = *dev_dot;
+
+                if !self.dot_to_index_map.contains_key(&current_peer_dot) {
+                    let mut sender_bits = BitVec::from_elem(self.peer_number, false);
+                    sender_bits.set(i, true);
+
+                    match handle_peer_dot(
+                        &current_peer_dot,
+                        &self.peer_dot_sequences,
+                        &mut self.global_causal_dag,
+                        &mut self.dot_to_index_map,
+                        &mut self.peer_version_vectors,
+                        &mut self.dot_version_vector_map,
+                        &mut self.peer_dot_sequence_indexes,
+                        &mut self.peer_dot_sequence_prev_indexes,
+                        &mut self.peer_version_matrices,
+                        &mut self.stable_counts,
+                        &mut sender_bits,
+                        &self.graph_implementation,
+                        &self.options,
+                    ) {
+                        HandlePeerDotCausalError::Ok => {}
+                        HandlePeerDotCausalError::CausalDeliveryError {
+                            message,
+                            current_dot,
+                            current_peer,
+                            current_peer_dot_sequence_index,
+                        } => {
+                            return Err(self.error(
+                                CausalityCheckerErrorEnum::Delivery,
                                 message,
                                 current_dot,
                                 current_peer,
                                 current_peer_dot_sequence_index,
-                            } => {
-                                return CausalityChecker::Error(CausalityCheckerError::new(
-                                    CausalityCheckerErrorEnum::Delivery,
-                                    message,
-                                    global_causal_dag,
-                                    peer_dot_sequences,
-                                    dot_to_index_map,
-                                    peer_version_vectors,
-                                    dot_version_vector_map,
-                                    peer_dot_sequence_indexes,
-                                    peer_dot_sequence_prev_indexes,
-                                    current_dot,
-                                    current_peer,
-                                    current_peer_dot_sequence_index,
-                                ));
-                            }
-                            HandlePeerDotCausalError::CausalStabilityError {
+                            ));
+                        }
+                        HandlePeerDotCausalError::CausalStabilityError {
+                            message,
+                            current_dot,
+                            current_peer,
+                            current_peer_dot_sequence_index,
+                        } => {
+                            return Err(self.error(
+                                CausalityCheckerErrorEnum::Stability,
                                 message,
                                 current_dot,
                                 current_peer,
                                 current_peer_dot_sequence_index,
-                            } => {
-                                return CausalityChecker::Error(CausalityCheckerError::new(
-                                    CausalityCheckerErrorEnum::Stability,
-                                    message,
-                                    global_causal_dag,
-                                    peer_dot_sequences,
-                                    dot_to_index_map,
-                                    peer_version_vectors,
-                                    dot_version_vector_map,
-                                    peer_dot_sequence_indexes,
-                                    peer_dot_sequence_prev_indexes,
-                                    current_dot,
-                                    current_peer,
-                                    current_peer_dot_sequence_index,
-                                ));
-                            }
-                        }
-                    }
-
-                    match handle_peer_delivered_message(
-                        i,
-                        current_peer_dot,
-                        &mut dot_version_vector_map,
-                        &mut peer_version_vectors,
-                        &mut peer_version_matrices,
-                    ) {
-                        true => {}
-                        false => {
-                            return CausalityChecker::Error(CausalityCheckerError::new(
-                                CausalityCheckerErrorEnum::Stability,
-                                format!(
-                                    "When comparing VVs of peer {} and dot {:?}",
-                                    i, current_peer_dot
-                                ),
-                                global_causal_dag,
-                                peer_dot_sequences,
-                                dot_to_index_map,
-                                peer_version_vectors,
-                                dot_version_vector_map,
-                                peer_dot_sequence_indexes,
-                                peer_dot_sequence_prev_indexes,
-                                current_peer_dot.clone(),
-                                i,
-                                j,
                             ));
                         }
-                    }
-                }
-                CausalCheck::Stable { stb_dot } => {
-                    let current_peer_version_matrix = &peer_version_matrices[i];
-                    match handle_stable_message(
-                        &stb_dot,
-                        current_peer_version_matrix,
-                        &dot_version_vector_map,
-                    ) {
-                        true => {}
-                        false => {
-                            let current_dot = stb_dot.clone();
-                            return CausalityChecker::Error(CausalityCheckerError::new(
-                                CausalityCheckerErrorEnum::Stability,
-                                "".to_string(),
-                                global_causal_dag,
-                                peer_dot_sequences,
-                                dot_to_index_map,
-                                peer_version_vectors,
-                                dot_version_vector_map,
-                                peer_dot_sequence_indexes,
-                                peer_dot_sequence_prev_indexes,
+                        HandlePeerDotCausalError::CausalContextError {
+                            message,
+                            current_dot,
+                            current_peer,
+                            current_peer_dot_sequence_index,
+                        } => {
+                            return Err(self.error(
+                                CausalityCheckerErrorEnum::Context,
+                                message,
                                 current_dot,
-                                i,
-                                j,
+                                current_peer,
+                                current_peer_dot_sequence_index,
                             ));
                         }
                     }
                 }
+
+                match handle_peer_delivered_message(
+                    i,
+                    current_peer_dot,
+                    &mut self.dot_version_vector_map,
+                    &mut self.peer_version_vectors,
+                    &mut self.peer_version_matrices,
+                    &self.options,
+                ) {
+                    true => {}
+                    false => {
+                        //Delivering a message before its dependencies breaks the causal
+                        //delivery, which is reported the same way by handle_peer_dot
+                        return Err(self.error(
+                            CausalityCheckerErrorEnum::Delivery,
+                            format!(
+                                "When comparing VVs of peer {} and dot {:?}",
+                                i, current_peer_dot
+                            ),
+                            current_peer_dot,
+                            i,
+                            j,
+                        ));
+                    }
+                }
             }
+            //The stable messages aren't tracked by every middleware run
+            CausalCheck::Stable { .. } if !self.options.check_stability => {}
+            CausalCheck::Stable { stb_dot } => {
+                let current_peer_version_matrix = &self.peer_version_matrices[i];
+                match handle_stable_message(
+                    stb_dot,
+                    current_peer_version_matrix,
+                    &self.dot_version_vector_map,
+                    &mut self.stable_counts,
+                    &self.options,
+                ) {
+                    true => {}
+                    false => {
+                        let current_dot = *stb_dot;
+                        return Err(self.error(
+                            CausalityCheckerErrorEnum::Stability,
+                            "".to_string(),
+                            current_dot,
+                            i,
+                            j,
+                        ));
+                    }
+                }
+            }
+        }
+
+        self.peer_dot_sequence_indexes[i] += 1;
+
+        Ok(CheckerStep::Processed {
+            peer: i,
+            index: j,
+            dot: entry_dot,
+        })
+    }
 
-            peer_dot_sequence_indexes[i] += 1;
+    /**
+     * Pruning looks at every pair of peers, so it only runs every peer_number^2 entries.
+     *
+     * # Arguments
+     *
+     * `peer_number` - group size
+     */
+    fn prune_interval(peer_number: usize) -> usize {
+        (peer_number * peer_number).max(1)
+    }
+
+    /**
+     * Builds an error with the checker's state, which ends the session unless the
+     * check was cancelled.
+     *
+     * # Arguments
+     *
+     * `error_type` - Type of the error
+     *
+     * `message` - Message detailing the cause of the error
+     *
+     * `current_dot` - Dot where the error was thrown
+     *
+     * `current_peer` - Peer where the error was thrown
+     *
+     * `current_peer_dot_sequence_index` - Index in the peer's dot sequence where the error was thrown
+     */
+    fn error(
+        &mut self,
+        error_type: CausalityCheckerErrorEnum,
+        message: String,
+        current_dot: Dot,
+        current_peer: usize,
+        current_peer_dot_sequence_index: usize,
+    ) -> Box<CausalityCheckerError> {
+        let failure = CheckerFailure {
+            error_type,
+            message,
+            current_dot,
+            current_peer,
+            current_peer_dot_sequence_index,
+        };
+
+        //The state of a cancelled check is kept, so it can be saved and resumed
+        if error_type != CausalityCheckerErrorEnum::Cancelled {
+            self.failure = Some(failure.clone());
         }
+
+        self.failure_error(&failure)
     }
 
-    CausalityChecker::Ok(global_causal_dag)
+    /**
+     * Builds the error of a failure with a copy of the checker's state.
+     *
+     * # Arguments
+     *
+     * `failure` - Cause of the error
+     */
+    fn failure_error(&self, failure: &CheckerFailure) -> Box<CausalityCheckerError> {
+        Box::new(CausalityCheckerError::new(
+            failure.error_type,
+            failure.message.clone(),
+            self.global_causal_dag.clone(),
+            self.peer_dot_sequences.clone(),
+            self.dot_to_index_map.clone(),
+            self.peer_version_vectors.clone(),
+            self.dot_version_vector_map.clone(),
+            self.peer_dot_sequence_indexes.clone(),
+            self.peer_dot_sequence_prev_indexes.clone(),
+            failure.current_dot,
+            failure.current_peer,
+            failure.current_peer_dot_sequence_index,
+        ))
+    }
+}
+
+/**
+ * Calls the progress function of the checker options, if any.
+ *
+ * # Arguments
+ *
+ * `options` - properties verified by the checker
+ *
+ * `peer_dot_sequence_indexes` - index of the next processed entry of each peer
+ *
+ * `total` - number of entries in the group's dot sequences
+ */
+fn report_progress(options: &CheckerOptions, peer_dot_sequence_indexes: &[usize], total: usize) {
+    if let Some(progress) = &options.progress {
+        progress(&CheckerProgress {
+            processed: peer_dot_sequence_indexes.iter().sum(),
+            total,
+            peer_positions: peer_dot_sequence_indexes.to_vec(),
+        });
+    }
 }
 
 fn handle_peer_dot(
     dot: &Dot,
-    peer_dot_sequences: &Vec<Vec<CausalCheck>>,
+    peer_dot_sequences: &Vec<DotSequence>,
     global_causal_dag: &mut ArrayMap<CheckNode>,
-    dot_to_index_map: &mut HashMap<Dot, usize>,
+    dot_to_index_map: &mut DotMap<usize>,
     peer_version_vectors: &mut Vec<VersionVector>,
-    dot_version_vector_map: &mut HashMap<Dot, VersionVector>,
+    dot_version_vector_map: &mut DotMap<VersionVector>,
     peer_dot_sequence_indexes: &mut Vec<usize>,
     peer_dot_sequence_prev_indexes: &mut Vec<usize>,
     peer_version_matrices: &mut Vec<VersionMatrix>,
+    stable_counts: &mut DotMap<usize>,
     sender_bits: &mut BitVec,
     graph_implementation: &bool,
+    options: &CheckerOptions,
 ) -> HandlePeerDotCausalError {
     let initial_vec_dot_index = peer_dot_sequence_indexes[dot.id];
     let current_peer_dot_sequence = &peer_dot_sequences[dot.id];
@@ -263,6 +648,25 @@ fn handle_peer_dot(
                     };
                 }
 
+                if *graph_implementation && options.strict_context {
+                    match check_send_context(
+                        &current_peer_dot,
+                        context,
+                        &peer_version_vectors[dot.id],
+                        dot_version_vector_map,
+                    ) {
+                        None => {}
+                        Some(message) => {
+                            return HandlePeerDotCausalError::CausalContextError {
+                                message,
+                                current_dot: current_peer_dot,
+                                current_peer: dot.id,
+                                current_peer_dot_sequence_index: j,
+                            };
+                        }
+                    }
+                }
+
                 if !handle_sender_delivered_message(
                     current_peer_dot,
                     global_causal_dag,
@@ -275,6 +679,7 @@ fn handle_peer_dot(
                     peer_version_matrices,
                     &context,
                     graph_implementation,
+                    options,
                 ) {
                     return HandlePeerDotCausalError::CausalDeliveryError {
                         message: "handle_peer_dot() - The Sender's Dot was already in the graph!"
@@ -317,8 +722,10 @@ fn handle_peer_dot(
                         peer_dot_sequence_indexes,
                         peer_dot_sequence_prev_indexes,
                         peer_version_matrices,
+                        stable_counts,
                         sender_bits,
                         graph_implementation,
+                        options,
                     ) {
                         HandlePeerDotCausalError::Ok => {}
                         HandlePeerDotCausalError::CausalDeliveryError {
@@ -347,6 +754,19 @@ fn handle_peer_dot(
                                 current_peer_dot_sequence_index: current_peer_dot_sequence_index,
                             };
                         }
+                        HandlePeerDotCausalError::CausalContextError {
+                            message,
+                            current_dot,
+                            current_peer,
+                            current_peer_dot_sequence_index,
+                        } => {
+                            return HandlePeerDotCausalError::CausalContextError {
+                                message,
+                                current_dot,
+                                current_peer,
+                                current_peer_dot_sequence_index,
+                            };
+                        }
                     }
                 }
 
@@ -356,6 +776,7 @@ fn handle_peer_dot(
                     dot_version_vector_map,
                     peer_version_vectors,
                     peer_version_matrices,
+                    options,
                 ) {
                     true => {}
                     false => {
@@ -372,6 +793,7 @@ fn handle_peer_dot(
                 }
             }
 
+            CausalCheck::Stable { .. } if !options.check_stability => {}
             CausalCheck::Stable { stb_dot } => {
                 let current_peer_version_matrix = &peer_version_matrices[dot.id];
 
@@ -379,6 +801,8 @@ fn handle_peer_dot(
                     &stb_dot,
                     current_peer_version_matrix,
                     dot_version_vector_map,
+                    stable_counts,
+                    options,
                 ) {
                     true => {}
                     false => {
@@ -402,15 +826,16 @@ fn handle_peer_dot(
 fn handle_sender_delivered_message(
     current_peer_dot: Dot,
     global_causal_dag: &mut ArrayMap<CheckNode>,
-    dot_to_index_map: &mut HashMap<Dot, usize>,
+    dot_to_index_map: &mut DotMap<usize>,
     peer_version_vectors: &mut Vec<VersionVector>,
-    dot_version_vector_map: &mut HashMap<Dot, VersionVector>,
+    dot_version_vector_map: &mut DotMap<VersionVector>,
     peer_dot_sequence_indexes: &mut Vec<usize>,
     peer_dot_sequence_prev_indexes: &mut Vec<usize>,
-    current_peer_dot_sequence: &Vec<CausalCheck>,
+    current_peer_dot_sequence: &DotSequence,
     peer_version_matrices: &mut Vec<VersionMatrix>,
     context: &Vec<Dot>,
     graph_implementation: &bool,
+    options: &CheckerOptions,
 ) -> bool {
     if !dot_to_index_map.contains_key(&current_peer_dot) {
         let peer_version_vector = peer_version_vectors
@@ -442,8 +867,11 @@ fn handle_sender_delivered_message(
         peer_dot_sequence_prev_indexes[current_peer_dot.id] =
             peer_dot_sequence_indexes[current_peer_dot.id];
 
-        let peer_version_matrix = &mut peer_version_matrices[current_peer_dot.id];
-        peer_version_matrix.update_peer_entry(current_peer_dot.id, dot_version_vector_clone);
+        //The version matrices are only used to verify the stable messages
+        if options.check_stability {
+            let peer_version_matrix = &mut peer_version_matrices[current_peer_dot.id];
+            peer_version_matrix.update_peer_entry(current_peer_dot.id, dot_version_vector_clone);
+        }
 
         true
     } else {
@@ -451,12 +879,75 @@ fn handle_sender_delivered_message(
     }
 }
 
+/**
+ * Compares the context of a sent message with the causal frontier of the messages
+ * delivered by its sender, which are the delivered messages that no other delivered
+ * message depends on. Returns the description of the mismatch, if any.
+ *
+ * # Arguments
+ *
+ * `sent_dot` - Dot of the sent message
+ *
+ * `context` - Context declared by the sender
+ *
+ * `sender_version_vector` - Sender's version vector before sending the message
+ *
+ * `dot_version_vector_map` - Version vectors of the messages in the graph
+ */
+fn check_send_context(
+    sent_dot: &Dot,
+    context: &[Dot],
+    sender_version_vector: &VersionVector,
+    dot_version_vector_map: &DotMap<VersionVector>,
+) -> Option<String> {
+    //The last delivered message of each peer, including the sender's previous message
+    let mut candidates: Vec<Dot> = Vec::new();
+
+    for id in 0..sender_version_vector.len() {
+        if sender_version_vector[id] > 0 {
+            candidates.push(Dot::new(id, sender_version_vector[id]));
+        }
+    }
+
+    let frontier: Vec<Dot> = candidates
+        .iter()
+        .filter(|dot| {
+            !candidates.iter().any(|other| {
+                let other_version_vector = dot_version_vector_map
+                    .get(other)
+                    .expect("ERROR: When getting the delivered dot's version vector");
+                other != *dot && other_version_vector[dot.id] >= dot.counter
+            })
+        })
+        .cloned()
+        .collect();
+
+    let missing: Vec<&Dot> = frontier
+        .iter()
+        .filter(|dot| !context.contains(dot))
+        .collect();
+    let extra: Vec<&Dot> = context
+        .iter()
+        .filter(|dot| !frontier.contains(dot))
+        .collect();
+
+    if missing.is_empty() && extra.is_empty() && context.len() == frontier.len() {
+        None
+    } else {
+        Some(format!(
+            "The context of {:?} doesn't match the causal frontier {:?} - missing {:?}, extra {:?}",
+            sent_dot, frontier, missing, extra
+        ))
+    }
+}
+
 fn handle_peer_delivered_message(
     i: usize,
     current_peer_dot: Dot,
-    dot_version_vector_map: &mut HashMap<Dot, VersionVector>,
+    dot_version_vector_map: &mut DotMap<VersionVector>,
     peer_version_vectors: &mut Vec<VersionVector>,
     peer_version_matrices: &mut Vec<VersionMatrix>,
+    options: &CheckerOptions,
 ) -> bool {
     match dot_version_vector_map.get(&current_peer_dot) {
         Some(dot_version_vector) => {
@@ -472,11 +963,13 @@ fn handle_peer_delivered_message(
                 true => {
                     (*peer_version_vector)[current_peer_dot.id] += 1;
 
-                    let peer_version_matrix = &mut peer_version_matrices[i];
-                    peer_version_matrix
-                        .update_peer_entry(current_peer_dot.id, dot_version_vector.clone());
+                    if options.check_stability {
+                        let peer_version_matrix = &mut peer_version_matrices[i];
+                        peer_version_matrix
+                            .update_peer_entry(current_peer_dot.id, dot_version_vector.clone());
 
-                    peer_version_matrix.update_peer_entry(i, (*peer_version_vector).clone());
+                        peer_version_matrix.update_peer_entry(i, (*peer_version_vector).clone());
+                    }
 
                     true
                 }
@@ -490,19 +983,155 @@ fn handle_peer_delivered_message(
 fn handle_stable_message(
     dot: &Dot,
     peer_version_matrix: &VersionMatrix,
-    dot_version_vector_map: &HashMap<Dot, VersionVector>,
+    dot_version_vector_map: &DotMap<VersionVector>,
+    stable_counts: &mut DotMap<usize>,
+    options: &CheckerOptions,
 ) -> bool {
     let stable_dot_version_vector = dot_version_vector_map
         .get(dot)
         .expect("ERROR: When getting the stable dot version vector");
-    peer_version_matrix.check_stability(stable_dot_version_vector)
+
+    if !peer_version_matrix.check_stability(stable_dot_version_vector) {
+        return false;
+    }
+
+    //The stable messages are only pruned once every peer marked them as stable
+    if options.prune_processed {
+        *stable_counts.entry(*dot).or_insert(0) += 1;
+    }
+
+    true
+}
+
+/**
+ * Removes the messages that every peer already processed, in the order of each
+ * peer's counters. A message is processed once every peer delivered a later message
+ * from its sender, so it's no longer the last delivered message of any peer and
+ * can't be a direct predecessor of the next sent messages, and, when the stability
+ * is verified, once every peer marked it as stable.
+ *
+ * # Arguments
+ *
+ * `global_causal_dag` - graph built from the dot sequences
+ *
+ * `dot_to_index_map` - graph index of each message
+ *
+ * `dot_version_vector_map` - version vector of each message
+ *
+ * `peer_version_vectors` - version vector of each peer
+ *
+ * `stable_counts` - number of peers that marked each message as stable
+ *
+ * `pruned_counters` - counter of the last pruned message of each peer
+ *
+ * `options` - properties verified by the checker
+ */
+fn prune_processed_dots(
+    global_causal_dag: &mut ArrayMap<CheckNode>,
+    dot_to_index_map: &mut DotMap<usize>,
+    dot_version_vector_map: &mut DotMap<VersionVector>,
+    peer_version_vectors: &[VersionVector],
+    stable_counts: &mut DotMap<usize>,
+    pruned_counters: &mut [usize],
+    options: &CheckerOptions,
+) {
+    let peer_number = peer_version_vectors.len();
+
+    for id in 0..peer_number {
+        let delivered = peer_version_vectors
+            .iter()
+            .map(|version_vector| version_vector[id])
+            .min()
+            .unwrap_or(0);
+
+        while pruned_counters[id] + 1 < delivered {
+            let dot = Dot::new(id, pruned_counters[id] + 1);
+
+            if options.check_stability {
+                match stable_counts.get(&dot) {
+                    Some(count) if *count >= peer_number => {
+                        stable_counts.remove(&dot);
+                    }
+                    _ => break,
+                }
+            }
+
+            if let Some(index) = dot_to_index_map.remove(&dot) {
+                //Removing the node's edges so its index can be reused
+                let predecessors = std::mem::take(&mut global_causal_dag[index].predecessors);
+                let successors = std::mem::take(&mut global_causal_dag[index].successors);
+
+                for predecessor in predecessors {
+                    global_causal_dag[predecessor]
+                        .successors
+                        .retain(|successor| *successor != index);
+                }
+
+                for successor in successors {
+                    global_causal_dag[successor]
+                        .predecessors
+                        .retain(|predecessor| *predecessor != index);
+                }
+
+                global_causal_dag.remove(index);
+            }
+
+            dot_version_vector_map.remove(&dot);
+            pruned_counters[id] += 1;
+        }
+    }
+}
+
+/**
+ * Rebuilds the graph with only the nodes still mapped by their dots, since the slots
+ * of the pruned nodes are only marked as available. The nodes keep their order and
+ * their dependencies are mapped to the new indexes.
+ *
+ * # Arguments
+ *
+ * `global_causal_dag` - Graph built by the causality checker
+ *
+ * `dot_to_index_map` - Indexes of the remaining nodes
+ */
+fn compact_graph(
+    global_causal_dag: &ArrayMap<CheckNode>,
+    dot_to_index_map: &DotMap<usize>,
+) -> ArrayMap<CheckNode> {
+    let mut indexes: Vec<usize> = dot_to_index_map.values().cloned().collect();
+    indexes.sort_unstable();
+
+    let mut new_indexes: Vec<usize> = vec![0; global_causal_dag.node_number()];
+    let mut compacted: ArrayMap<CheckNode> = ArrayMap::new(indexes.len());
+
+    for index in indexes.iter() {
+        new_indexes[*index] = compacted.push(CheckNode::new(global_causal_dag[*index].dot));
+    }
+
+    //The pruned nodes' edges were removed, so the edges only reach remaining nodes
+    for index in indexes {
+        let node = &global_causal_dag[index];
+        let new_index = new_indexes[index];
+
+        compacted[new_index].predecessors = node
+            .predecessors
+            .iter()
+            .map(|predecessor| new_indexes[*predecessor])
+            .collect();
+        compacted[new_index].successors = node
+            .successors
+            .iter()
+            .map(|successor| new_indexes[*successor])
+            .collect();
+    }
+
+    compacted
 }
 
 fn update_graph_dependencies(
     global_causal_dag: &mut ArrayMap<CheckNode>,
-    dot_to_index_map: &mut HashMap<Dot, usize>,
-    dot_version_vector_map: &mut HashMap<Dot, VersionVector>,
-    current_peer_dot_sequence: &Vec<CausalCheck>,
+    dot_to_index_map: &mut DotMap<usize>,
+    dot_version_vector_map: &mut DotMap<VersionVector>,
+    current_peer_dot_sequence: &DotSequence,
     dot: &Dot,
     current_sequence_index: usize,
     previous_sequence_index: usize,
@@ -593,7 +1222,7 @@ fn compare_dot_version_vectors(
     upper_dot: &Dot,
     lower_dot_version_vector: &VersionVector,
     upper_dot_version_vector: &VersionVector,
-    dot_to_index_map: &mut HashMap<Dot, usize>,
+    dot_to_index_map: &mut DotMap<usize>,
     global_causal_dag: &mut ArrayMap<CheckNode>,
 ) -> Vec<usize> {
     let mut predecessor_dot_graph_indexes: Vec<usize> = Vec::new();
@@ -648,3 +1277,106 @@ fn compare_dot_version_vectors(
 
     predecessor_dot_graph_indexes
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /**
+     * Builds the dot sequences of two peers that send messages after delivering
+     * each other's, so the checker prunes the first ones.
+     */
+    fn build_dot_sequences() -> Vec<Vec<CausalCheck>> {
+        let send = |id: usize, counter: usize, context: &[Dot]| CausalCheck::Send {
+            sent_dot: Dot::new(id, counter),
+            context: context.to_vec(),
+        };
+        let delivery = |id: usize, counter: usize| CausalCheck::Delivery {
+            dev_dot: Dot::new(id, counter),
+        };
+
+        vec![
+            vec![
+                send(0, 1, &[]),
+                send(0, 2, &[Dot::new(0, 1)]),
+                delivery(1, 1),
+                delivery(1, 2),
+                send(0, 3, &[Dot::new(1, 2)]),
+                delivery(1, 3),
+                send(0, 4, &[Dot::new(1, 3)]),
+            ],
+            vec![
+                delivery(0, 1),
+                delivery(0, 2),
+                send(1, 1, &[Dot::new(0, 2)]),
+                send(1, 2, &[Dot::new(1, 1)]),
+                delivery(0, 3),
+                send(1, 3, &[Dot::new(0, 3)]),
+                delivery(0, 4),
+            ],
+        ]
+    }
+
+    /**
+     * Returns the sorted edges of a graph by dot, from the successors or from the
+     * predecessors of its nodes.
+     */
+    fn edges(dag: &ArrayMap<CheckNode>, from_successors: bool) -> Vec<(Dot, Dot)> {
+        let mut edges: Vec<(Dot, Dot)> = Vec::new();
+
+        for node in dag.iter() {
+            if from_successors {
+                for successor in &node.successors {
+                    edges.push((node.dot, dag[*successor].dot));
+                }
+            } else {
+                for predecessor in &node.predecessors {
+                    edges.push((dag[*predecessor].dot, node.dot));
+                }
+            }
+        }
+
+        edges.sort_by_key(|(from, to)| (from.id, from.counter, to.id, to.counter));
+        edges
+    }
+
+    #[test]
+    fn pruned_check_returns_only_the_remaining_nodes() {
+        let options = CheckerOptions {
+            prune_processed: true,
+            ..CheckerOptions::delivery_only()
+        };
+
+        let dag = match check_causal_delivery_with(2, build_dot_sequences(), true, &options) {
+            CausalityChecker::Ok(dag) => dag,
+            CausalityChecker::Error(error) => panic!("ERROR: Unexpected check error {:?}", error),
+        };
+
+        assert_eq!(dag.node_number(), 3);
+        assert_eq!(dag.occupied(), 3);
+        assert_eq!(
+            edges(&dag, true),
+            vec![
+                (Dot::new(0, 3), Dot::new(1, 3)),
+                (Dot::new(1, 3), Dot::new(0, 4))
+            ]
+        );
+        assert_eq!(edges(&dag, false), edges(&dag, true));
+    }
+
+    #[test]
+    fn check_without_pruning_returns_every_node() {
+        let dag = match check_causal_delivery_with(
+            2,
+            build_dot_sequences(),
+            true,
+            &CheckerOptions::delivery_only(),
+        ) {
+            CausalityChecker::Ok(dag) => dag,
+            CausalityChecker::Error(error) => panic!("ERROR: Unexpected check error {:?}", error),
+        };
+
+        assert_eq!(dag.node_number(), 7);
+        assert_eq!(edges(&dag, true).len(), 7);
+    }
+}
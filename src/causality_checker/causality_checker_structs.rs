@@ -1,17 +1,22 @@
+use crate::clock::dot::{Dot, DotMap};
+use crate::clock::version_vector::VersionVector;
+pub use crate::clock::VersionMatrix;
 use crate::graph::middleware::dag::ArrayMap;
-use crate::graph::middleware::dot::Dot;
-use crate::vv::structs::version_vector::VersionVector;
 use smallvec::SmallVec;
-use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::Write;
-use std::io::BufWriter;
+use std::io::{self, BufWriter};
+use std::ops::Index;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 /**
  * Enum for the type of dots in the peer sequences.
  */
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CausalCheck {
     ///Sent message
     Send { sent_dot: Dot, context: Vec<Dot> },
@@ -38,6 +43,184 @@ impl CausalCheck {
     }
 }
 
+/**
+ * Dot sequence of a peer whose processed entries can be dropped from its start,
+ * while the remaining entries keep their index in the whole sequence.
+ */
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DotSequence {
+    ///Number of entries dropped from the start of the sequence
+    offset: usize,
+    ///Entries that weren't dropped
+    entries: VecDeque<CausalCheck>,
+}
+
+impl DotSequence {
+    /**
+     * Builds the dot sequence of a peer.
+     *
+     * # Arguments
+     *
+     * `entries` - Entries of the peer's dot sequence
+     */
+    pub fn new(entries: Vec<CausalCheck>) -> Self {
+        Self {
+            offset: 0,
+            entries: entries.into(),
+        }
+    }
+
+    /**
+     * Returns the length of the whole sequence, including the dropped entries.
+     */
+    pub fn len(&self) -> usize {
+        self.offset + self.entries.len()
+    }
+
+    /**
+     * Returns true if the sequence has no entries.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Returns the index of the first entry that wasn't dropped.
+     */
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /**
+     * Returns the entry at an index of the whole sequence, or `None` if it's out
+     * of bounds or was dropped.
+     *
+     * # Arguments
+     *
+     * `index` - Index of the entry in the whole sequence
+     */
+    pub fn get(&self, index: usize) -> Option<&CausalCheck> {
+        index
+            .checked_sub(self.offset)
+            .and_then(|index| self.entries.get(index))
+    }
+
+    /**
+     * Drops the entries before an index of the whole sequence.
+     *
+     * # Arguments
+     *
+     * `index` - Index of the first entry that is kept
+     */
+    pub fn drop_before(&mut self, index: usize) {
+        let dropped = index.saturating_sub(self.offset).min(self.entries.len());
+
+        self.entries.drain(..dropped);
+        self.offset += dropped;
+    }
+}
+
+impl Index<usize> for DotSequence {
+    type Output = CausalCheck;
+
+    fn index(&self, index: usize) -> &CausalCheck {
+        self.get(index)
+            .expect("ERROR: The dot sequence entry is out of bounds or was dropped")
+    }
+}
+
+/**
+ * Progress of the causality checker over the dot sequences.
+ */
+#[derive(Debug, Clone)]
+pub struct CheckerProgress {
+    ///Number of dot sequence entries already processed
+    pub processed: usize,
+    ///Number of dot sequence entries of the group
+    pub total: usize,
+    ///Index of the next processed entry in each peer's dot sequence
+    pub peer_positions: Vec<usize>,
+}
+
+/**
+ * Function called with the progress of the causality checker.
+ */
+pub type ProgressCallback = Arc<dyn Fn(&CheckerProgress) + Send + Sync>;
+
+/**
+ * Options of the causality checker.
+ */
+#[derive(Clone)]
+pub struct CheckerOptions {
+    ///Verifies the stable messages in the dot sequences. Must be disabled for the
+    ///traces of middleware runs with `track_causal_stability` set to false, whose
+    ///stable messages aren't tracked, so only their causal delivery is verified.
+    pub check_stability: bool,
+    ///Verifies, for graph implementation traces, that the context of every sent
+    ///message is exactly the causal frontier of the messages the sender delivered,
+    ///catching missing and extra dependencies. Traces with keyed sends or explicit
+    ///dependencies have smaller or larger contexts, so they must not use it.
+    pub strict_context: bool,
+    ///Function called with the checker's progress every `progress_interval`
+    ///processed entries and once the check finishes
+    pub progress: Option<ProgressCallback>,
+    ///Number of processed entries between progress reports
+    pub progress_interval: usize,
+    ///Flag that, once set by another thread, stops the check with a `Cancelled` error
+    pub cancelled: Option<Arc<AtomicBool>>,
+    ///Removes the nodes and version vectors of the messages every peer already
+    ///processed, so the memory is bounded by the messages that aren't delivered
+    ///everywhere, or stable everywhere when the stability is verified, and the
+    ///entries of the dot sequences already processed. The graph returned by the
+    ///checker is compacted so it only has the remaining nodes, while the graph of
+    ///a session being stepped keeps the freed slots of the removed nodes.
+    pub prune_processed: bool,
+}
+
+impl CheckerOptions {
+    /**
+     * Returns the options that only verify the causal delivery of the messages,
+     * ignoring any stable message in the dot sequences.
+     */
+    pub fn delivery_only() -> Self {
+        Self {
+            check_stability: false,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for CheckerOptions {
+    fn default() -> Self {
+        Self {
+            check_stability: true,
+            strict_context: false,
+            progress: None,
+            progress_interval: 10000,
+            cancelled: None,
+            prune_processed: false,
+        }
+    }
+}
+
+/**
+ * Result of a step of a causality checker session.
+ */
+#[derive(Debug)]
+pub enum CheckerStep {
+    ///An entry of a peer's dot sequence was processed.
+    Processed {
+        ///Peer of the dot sequence
+        peer: usize,
+        ///Index of the entry in the peer's dot sequence
+        index: usize,
+        ///Dot of the entry
+        dot: Dot,
+    },
+    ///Every dot sequence was processed.
+    Finished,
+}
+
 /**
  * Enum with the results for the causality checker.
  */
@@ -52,7 +235,7 @@ pub enum CausalityChecker {
 /**
  * Enum with type of causality checker errors thrown while traversing the dot sequences.
  */
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CausalityCheckerErrorEnum {
     ///Send error.
     Send,
@@ -60,6 +243,10 @@ pub enum CausalityCheckerErrorEnum {
     Delivery,
     ///Causal stability error.
     Stability,
+    ///Sent message context error.
+    Context,
+    ///The check was cancelled before the end of the dot sequences.
+    Cancelled,
 }
 
 impl fmt::Display for CausalityCheckerErrorEnum {
@@ -68,6 +255,31 @@ impl fmt::Display for CausalityCheckerErrorEnum {
             CausalityCheckerErrorEnum::Send => write!(f, "Send"),
             CausalityCheckerErrorEnum::Delivery => write!(f, "Delivery"),
             CausalityCheckerErrorEnum::Stability => write!(f, "Stability"),
+            CausalityCheckerErrorEnum::Context => write!(f, "Context"),
+            CausalityCheckerErrorEnum::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+/**
+ * Paths of the files written by the causality checker.
+ */
+#[derive(Debug, Clone)]
+pub struct CheckerOutputConfig {
+    ///Directory of the output files, created if it doesn't exist
+    pub directory: PathBuf,
+    ///File name of the error's state
+    pub error_file: String,
+    ///Prefix of the file names of each peer's dot sequence, followed by the peer's id
+    pub sequence_file_prefix: String,
+}
+
+impl Default for CheckerOutputConfig {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("tcb_output"),
+            error_file: "causal_error_output.txt".to_string(),
+            sequence_file_prefix: "causal_error_peer_sequence".to_string(),
         }
     }
 }
@@ -84,13 +296,13 @@ pub struct CausalityCheckerError {
     ///Graph built by the causality checker while traversing the dot sequences.
     global_causal_dag: ArrayMap<CheckNode>,
     ///Dot sequences of the group's peers.
-    peer_dot_sequences: Vec<Vec<CausalCheck>>,
+    peer_dot_sequences: Vec<DotSequence>,
     ///Struct that maps a dot to its index in the causal dependency graph mapped as an array.
-    dot_to_index_map: HashMap<Dot, usize>,
+    dot_to_index_map: DotMap<usize>,
     ///Version vector of each peer.
     peer_version_vectors: Vec<VersionVector>,
     ///Structs with the version vectors of each dot.
-    dot_version_vector_map: HashMap<Dot, VersionVector>,
+    dot_version_vector_map: DotMap<VersionVector>,
     ///Vector with each peer's current dot sequence index when the error was thrown.
     peer_dot_sequence_indexes: Vec<usize>,
     ///Vector with each peer's previous sent message dot sequence index when the error was thrown.
@@ -137,10 +349,10 @@ impl CausalityCheckerError {
         error_type: CausalityCheckerErrorEnum,
         message: String,
         global_causal_dag: ArrayMap<CheckNode>,
-        peer_dot_sequences: Vec<Vec<CausalCheck>>,
-        dot_to_index_map: HashMap<Dot, usize>,
+        peer_dot_sequences: Vec<DotSequence>,
+        dot_to_index_map: DotMap<usize>,
         peer_version_vectors: Vec<VersionVector>,
-        dot_version_vector_map: HashMap<Dot, VersionVector>,
+        dot_version_vector_map: DotMap<VersionVector>,
         peer_dot_sequence_indexes: Vec<usize>,
         peer_dot_sequence_prev_indexes: Vec<usize>,
         current_dot: Dot,
@@ -164,112 +376,118 @@ impl CausalityCheckerError {
     }
 
     /**
-     * Logs the causality checker error in a readable format and into multiple files.
+     * Logs the causality checker error in a readable format and into multiple files,
+     * where the dot sequences are written next to the base file.
      *
      * # Arguments
      *
      * `output_base_file_path` - Path to the base file in the output directory.
      */
-    pub fn log_causal_check_error(self, output_base_file_path: String) {
+    pub fn log_causal_check_error(self, output_base_file_path: String) -> io::Result<()> {
+        let path = Path::new(&output_base_file_path);
+        let mut output = CheckerOutputConfig {
+            directory: match path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => PathBuf::new(),
+            },
+            ..CheckerOutputConfig::default()
+        };
+
+        if let Some(file_name) = path.file_name() {
+            output.error_file = file_name.to_string_lossy().to_string();
+        }
+
+        self.write_causal_check_error(&output)
+    }
+
+    /**
+     * Logs the causality checker error in a readable format and into multiple files
+     * of the output directory, which is created if it doesn't exist.
+     *
+     * # Arguments
+     *
+     * `output` - Paths of the output files.
+     */
+    pub fn write_causal_check_error(self, output: &CheckerOutputConfig) -> io::Result<()> {
         println!("Message {}\n\n", self.message);
         println!("Error type {}\n\n", self.error_type);
 
-        let file = File::create(output_base_file_path).unwrap();
+        fs::create_dir_all(&output.directory)?;
+
+        let file = File::create(output.directory.join(&output.error_file))?;
         let mut file_buffer = BufWriter::new(file);
 
-        file_buffer
-            .write(
-                format!(
-                    "{:?} | Peer {} | Seq Index {} | Error Type {}\n",
-                    self.current_dot,
-                    self.current_peer,
-                    self.current_peer_dot_sequence_index,
-                    self.error_type
-                )
-                .as_bytes(),
+        file_buffer.write_all(
+            format!(
+                "{:?} | Peer {} | Seq Index {} | Error Type {}\n",
+                self.current_dot,
+                self.current_peer,
+                self.current_peer_dot_sequence_index,
+                self.error_type
             )
-            .unwrap();
-        file_buffer
-            .write(format!("--------------------------\n").as_bytes())
-            .unwrap();
-        file_buffer
-            .write(format!("Sequence Indexes\n\t{:?}\n", self.peer_dot_sequence_indexes).as_bytes())
-            .unwrap();
-        file_buffer
-            .write(format!("--------------------------\n").as_bytes())
-            .unwrap();
-
-        file_buffer
-            .write(
-                format!(
-                    "Sequence Prev Indexes\n\t{:?}\n",
-                    self.peer_dot_sequence_prev_indexes
-                )
-                .as_bytes(),
+            .as_bytes(),
+        )?;
+        file_buffer.write_all("--------------------------\n".as_bytes())?;
+        file_buffer.write_all(
+            format!("Sequence Indexes\n\t{:?}\n", self.peer_dot_sequence_indexes).as_bytes(),
+        )?;
+        file_buffer.write_all("--------------------------\n".as_bytes())?;
+
+        file_buffer.write_all(
+            format!(
+                "Sequence Prev Indexes\n\t{:?}\n",
+                self.peer_dot_sequence_prev_indexes
             )
-            .unwrap();
+            .as_bytes(),
+        )?;
 
-        file_buffer
-            .write(format!("--------------------------\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("--------------------------\n".as_bytes())?;
 
         for i in 0..self.peer_version_vectors.len() {
-            file_buffer
-                .write(format!("Peer {} VV:\n\t{:?}\n", i, self.peer_version_vectors[i]).as_bytes())
-                .unwrap();
+            file_buffer.write_all(
+                format!("Peer {} VV:\n\t{:?}\n", i, self.peer_version_vectors[i]).as_bytes(),
+            )?;
         }
 
-        file_buffer
-            .write(format!("--------------------------\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("--------------------------\n".as_bytes())?;
 
         for i in 0..self.peer_dot_sequences.len() {
-            let sequence_file =
-                File::create(format!("tcb_output/causal_error_peer_sequence{}.txt", i)).unwrap();
+            let sequence_file = File::create(
+                output
+                    .directory
+                    .join(format!("{}{}.txt", output.sequence_file_prefix, i)),
+            )?;
             let mut sequence_file_buffer = BufWriter::new(sequence_file);
-            sequence_file_buffer
-                .write(format!("Peer Dot Seq {}\n", i).as_bytes())
-                .unwrap();
-            for j in 0..self.peer_dot_sequences[i].len() {
-                sequence_file_buffer
-                    .write(format!("\n\t{} - {:?}\n", j, self.peer_dot_sequences[i][j]).as_bytes())
-                    .unwrap();
+            sequence_file_buffer.write_all(format!("Peer Dot Seq {}\n", i).as_bytes())?;
+            //The entries dropped by a pruned check aren't written
+            for j in self.peer_dot_sequences[i].offset()..self.peer_dot_sequences[i].len() {
+                sequence_file_buffer.write_all(
+                    format!("\n\t{} - {:?}\n", j, self.peer_dot_sequences[i][j]).as_bytes(),
+                )?;
             }
 
-            sequence_file_buffer.flush().unwrap();
+            sequence_file_buffer.flush()?;
         }
 
-        file_buffer
-            .write(format!("--------------------------\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("--------------------------\n".as_bytes())?;
 
-        file_buffer
-            .write(format!("\t Dot Version Vector\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("\t Dot Version Vector\n".as_bytes())?;
         for (dot, version_vector) in self.dot_version_vector_map.iter() {
             let line = format!("\t{:?} - {:?}\n", dot, version_vector);
-            file_buffer.write_all(line.as_bytes()).unwrap();
+            file_buffer.write_all(line.as_bytes())?;
         }
 
-        file_buffer
-            .write(format!("--------------------------\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("--------------------------\n".as_bytes())?;
 
-        file_buffer
-            .write(format!("\t Dot to Index Map\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("\t Dot to Index Map\n".as_bytes())?;
         for (dot, index) in self.dot_to_index_map.iter() {
             let line = format!("\t{:?} - {}\n", dot, index);
-            file_buffer.write_all(line.as_bytes()).unwrap();
+            file_buffer.write_all(line.as_bytes())?;
         }
 
-        file_buffer
-            .write(format!("--------------------------\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("--------------------------\n".as_bytes())?;
 
-        file_buffer
-            .write(format!("\t Causal Graph\n").as_bytes())
-            .unwrap();
+        file_buffer.write_all("\t Causal Graph\n".as_bytes())?;
 
         for i in 0..self.global_causal_dag.len() {
             let temp_node = &self.global_causal_dag[i];
@@ -282,10 +500,12 @@ impl CausalityCheckerError {
                 temp_node.successors
             );
 
-            file_buffer.write_all(line.as_bytes()).unwrap();
+            file_buffer.write_all(line.as_bytes())?;
         }
 
-        file_buffer.flush().unwrap();
+        file_buffer.flush()?;
+
+        Ok(())
     }
 }
 
@@ -309,69 +529,19 @@ pub enum HandlePeerDotCausalError {
         current_peer: usize,
         current_peer_dot_sequence_index: usize,
     },
-}
-
-/**
- * Matrix where each row is a peer's version vector. This is used to determine causal stability.
- */
-#[derive(Debug)]
-pub struct VersionMatrix {
-    pub matrix: Vec<VersionVector>,
-}
-
-impl VersionMatrix {
-    /**
-     * Creates a new version matrix that is NxN where N is the group size.
-     * Each row will be a peer's version vector, where the peer's id is the
-     * row number.
-     *
-     * # Arguments
-     *
-     * `peer_number` - Number of peers in the group.
-     */
-    pub fn new(peer_number: usize) -> VersionMatrix {
-        let mut matrix: Vec<VersionVector> = Vec::with_capacity(peer_number);
-
-        for _ in 0..peer_number {
-            matrix.push(VersionVector::new(peer_number));
-        }
-
-        VersionMatrix { matrix: matrix }
-    }
-
-    /**
-     * Compares the causal stability in each row to a peer's version vector.
-     *
-     * # Arguments
-     *
-     * `dot_version_vector` - Peer's version vector to check stability.
-     */
-    pub fn check_stability(&self, dot_version_vector: &VersionVector) -> bool {
-        for i in 0..self.matrix.len() {
-            if !VersionVector::cmp(&self.matrix[i], dot_version_vector) {
-                return false;
-            }
-        }
-        true
-    }
-
-    /**
-     * Updates a peer's row in the version vector matrix.
-     *
-     * # Arguments
-     *
-     * `peer_id` - Peer's id that will be its row in the matrix.
-     * `dot_version_vector` - Peer's new version vector.
-     */
-    pub fn update_peer_entry(&mut self, peer_id: usize, dot_version_vector: VersionVector) {
-        self.matrix[peer_id] = dot_version_vector;
-    }
+    ///A sent message context error was thrown during the recursive call.
+    CausalContextError {
+        message: String,
+        current_dot: Dot,
+        current_peer: usize,
+        current_peer_dot_sequence_index: usize,
+    },
 }
 
 /**
  * Node of the causal graph built while looping through the dot sequences.
  */
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CheckNode {
     ///Message's dot
     pub dot: Dot,
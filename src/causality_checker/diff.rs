@@ -0,0 +1,182 @@
+use super::causality_checker_structs::CheckNode;
+use crate::clock::dot::Dot;
+use crate::graph::middleware::dag::ArrayMap;
+use std::collections::HashSet;
+use std::fmt;
+
+/**
+ * Differences between two graphs built by the causality checker, where the
+ * edges go from a message to each of its direct successors.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GraphDiff {
+    ///Messages of the expected graph missing from the actual graph
+    pub missing_nodes: Vec<Dot>,
+    ///Messages of the actual graph missing from the expected graph
+    pub extra_nodes: Vec<Dot>,
+    ///Edges of the expected graph missing from the actual graph
+    pub missing_edges: Vec<(Dot, Dot)>,
+    ///Edges of the actual graph missing from the expected graph
+    pub extra_edges: Vec<(Dot, Dot)>,
+}
+
+impl GraphDiff {
+    /**
+     * Returns true if both graphs have the same messages and edges.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.missing_nodes.is_empty()
+            && self.extra_nodes.is_empty()
+            && self.missing_edges.is_empty()
+            && self.extra_edges.is_empty()
+    }
+}
+
+impl fmt::Display for GraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Missing nodes {}", self.missing_nodes.len())?;
+        for dot in &self.missing_nodes {
+            writeln!(f, "\t- ({}, {})", dot.id, dot.counter)?;
+        }
+
+        writeln!(f, "Extra nodes {}", self.extra_nodes.len())?;
+        for dot in &self.extra_nodes {
+            writeln!(f, "\t- ({}, {})", dot.id, dot.counter)?;
+        }
+
+        writeln!(f, "Missing edges {}", self.missing_edges.len())?;
+        for (from, to) in &self.missing_edges {
+            writeln!(
+                f,
+                "\t- ({}, {}) -> ({}, {})",
+                from.id, from.counter, to.id, to.counter
+            )?;
+        }
+
+        writeln!(f, "Extra edges {}", self.extra_edges.len())?;
+        for (from, to) in &self.extra_edges {
+            writeln!(
+                f,
+                "\t- ({}, {}) -> ({}, {})",
+                from.id, from.counter, to.id, to.counter
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * Compares two graphs built by the causality checker, such as the ones of two
+ * middleware implementations or two runs, by the dots of their messages, since
+ * the same message can have different indexes in each graph. The slots freed by
+ * pruning are skipped, so only the remaining nodes of a pruned graph are compared.
+ *
+ * # Arguments
+ *
+ * `expected` - Reference graph.
+ *
+ * `actual` - Graph compared with the reference.
+ */
+pub fn diff_graphs(expected: &ArrayMap<CheckNode>, actual: &ArrayMap<CheckNode>) -> GraphDiff {
+    let (expected_nodes, expected_edges) = graph_elements(expected);
+    let (actual_nodes, actual_edges) = graph_elements(actual);
+
+    let mut diff = GraphDiff {
+        missing_nodes: expected_nodes.difference(&actual_nodes).cloned().collect(),
+        extra_nodes: actual_nodes.difference(&expected_nodes).cloned().collect(),
+        missing_edges: expected_edges.difference(&actual_edges).cloned().collect(),
+        extra_edges: actual_edges.difference(&expected_edges).cloned().collect(),
+    };
+
+    //Sorting the differences so the reports of the same graphs are equal
+    diff.missing_nodes.sort_by_key(|dot| (dot.id, dot.counter));
+    diff.extra_nodes.sort_by_key(|dot| (dot.id, dot.counter));
+    diff.missing_edges
+        .sort_by_key(|(from, to)| (from.id, from.counter, to.id, to.counter));
+    diff.extra_edges
+        .sort_by_key(|(from, to)| (from.id, from.counter, to.id, to.counter));
+
+    diff
+}
+
+/**
+ * Returns the dots of the messages in a graph and its edges by dot, skipping the
+ * freed slots, whose nodes have no edges left.
+ *
+ * # Arguments
+ *
+ * `dag` - Graph built by the causality checker.
+ */
+fn graph_elements(dag: &ArrayMap<CheckNode>) -> (HashSet<Dot>, HashSet<(Dot, Dot)>) {
+    let mut nodes: HashSet<Dot> = HashSet::with_capacity(dag.node_number());
+    let mut edges: HashSet<(Dot, Dot)> = HashSet::new();
+
+    for (_, node) in dag.iter_occupied() {
+        nodes.insert(node.dot);
+
+        for successor in &node.successors {
+            edges.insert((node.dot, dag[*successor].dot));
+        }
+    }
+
+    (nodes, edges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_graphs::build_graph;
+    use super::*;
+
+    #[test]
+    fn equal_graphs_have_an_empty_diff() {
+        let (a, b, c) = (Dot::new(0, 1), Dot::new(1, 1), Dot::new(0, 2));
+
+        let expected = build_graph(&[a, b, c], &[(0, 1), (1, 2)]);
+        //The same messages at other indexes
+        let actual = build_graph(&[c, a, b], &[(1, 2), (2, 0)]);
+
+        let diff = diff_graphs(&expected, &actual);
+
+        assert!(diff.is_empty());
+        assert_eq!(diff, GraphDiff::default());
+    }
+
+    #[test]
+    fn different_graphs_have_their_differences() {
+        let (a, b, c, d) = (
+            Dot::new(0, 1),
+            Dot::new(1, 1),
+            Dot::new(0, 2),
+            Dot::new(2, 1),
+        );
+
+        let expected = build_graph(&[a, b, c], &[(0, 1), (1, 2)]);
+        let actual = build_graph(&[a, b, d], &[(0, 1), (0, 2)]);
+
+        let diff = diff_graphs(&expected, &actual);
+
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff,
+            GraphDiff {
+                missing_nodes: vec![c],
+                extra_nodes: vec![d],
+                missing_edges: vec![(b, c)],
+                extra_edges: vec![(a, d)],
+            }
+        );
+    }
+
+    #[test]
+    fn freed_slots_are_not_compared() {
+        let (a, b, c) = (Dot::new(0, 1), Dot::new(1, 1), Dot::new(0, 2));
+
+        let expected = build_graph(&[b, c], &[(0, 1)]);
+        let mut actual = build_graph(&[a, b, c], &[(1, 2)]);
+        //A pruned node keeps its slot until it's reused
+        actual.remove(0);
+
+        assert!(diff_graphs(&expected, &actual).is_empty());
+    }
+}
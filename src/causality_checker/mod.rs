@@ -1,3 +1,7 @@
+/**
+ * Analysis of the graphs built by the checker.
+ */
+pub mod analysis;
 /**
  * Causality checker algorithm implementation.
  */
@@ -6,7 +10,16 @@ pub mod causality_checker;
  * Auxiliary structs for the causality checker.
  */
 pub mod causality_checker_structs;
+/**
+ * Comparison of the graphs built by the checker.
+ */
+pub mod diff;
 /**
  * Mapping the results of the checker to the petgraph format.
  */
 pub mod petgraph;
+/**
+ * Graphs shared by the tests of the checker.
+ */
+#[cfg(test)]
+mod test_graphs;
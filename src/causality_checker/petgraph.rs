@@ -3,8 +3,9 @@ use crate::graph::middleware::dag::ArrayMap;
 use petgraph::dot::{Config, Dot};
 use petgraph::graph::NodeIndex;
 use petgraph::Graph;
-use std::fs::File;
-use std::io::Write;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
 
 /**
  * Writes to a file the graph built by the causality checker using petgraph format.
@@ -15,9 +16,9 @@ use std::io::Write;
  *
  * `dag` - Graph built by the causality checker.
  *
- * `filename` - Filename to write the output into.
+ * `filename` - Filename to write the output into, whose directory is created if it doesn't exist.
  */
-pub fn plot_graph(dag: ArrayMap<CheckNode>, filename: &String) {
+pub fn plot_graph(dag: ArrayMap<CheckNode>, filename: &str) -> io::Result<()> {
     let mut graph = Graph::<_, ()>::new();
     let nmbr_nodes = dag.node_number();
 
@@ -34,7 +35,12 @@ pub fn plot_graph(dag: ArrayMap<CheckNode>, filename: &String) {
 
     let dot = Dot::with_config(&graph, &[Config::EdgeNoLabel]);
     let output = format!("{:?}", dot);
-    let mut file = File::create(filename.clone()).unwrap();
 
-    write!(file, "{}", output).unwrap();
+    if let Some(parent) = Path::new(filename).parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = File::create(filename)?;
+
+    write!(file, "{}", output)
 }
@@ -0,0 +1,27 @@
+use super::causality_checker_structs::CheckNode;
+use crate::clock::dot::Dot;
+use crate::graph::middleware::dag::ArrayMap;
+
+/**
+ * Builds a graph with a node for each dot and the edges between their indexes.
+ *
+ * # Arguments
+ *
+ * `dots` - Dots of the nodes, in the order of their indexes.
+ *
+ * `edges` - Edges from a node's index to its successor's index.
+ */
+pub fn build_graph(dots: &[Dot], edges: &[(usize, usize)]) -> ArrayMap<CheckNode> {
+    let mut dag = ArrayMap::new(dots.len());
+
+    for dot in dots {
+        dag.push(CheckNode::new(*dot));
+    }
+
+    for (from, to) in edges {
+        dag[*from].successors.push(*to);
+        dag[*to].predecessors.push(*from);
+    }
+
+    dag
+}
@@ -0,0 +1,67 @@
+use super::{ChannelBackend, RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use crossbeam::crossbeam_channel;
+use std::time::Duration;
+
+/**
+ * Channels of the crossbeam crate, the default backend.
+ */
+pub struct Crossbeam;
+
+impl ChannelBackend for Crossbeam {
+    type Sender<T> = crossbeam_channel::Sender<T>;
+    type Receiver<T> = crossbeam_channel::Receiver<T>;
+
+    fn bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>) {
+        crossbeam_channel::bounded(capacity)
+    }
+
+    fn unbounded<T>() -> (Self::Sender<T>, Self::Receiver<T>) {
+        crossbeam_channel::unbounded()
+    }
+
+    fn clone_sender<T>(sender: &Self::Sender<T>) -> Self::Sender<T> {
+        sender.clone()
+    }
+
+    fn clone_receiver<T>(receiver: &Self::Receiver<T>) -> Self::Receiver<T> {
+        receiver.clone()
+    }
+
+    fn send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), SendError<T>> {
+        sender.send(message).map_err(|e| SendError(e.0))
+    }
+
+    fn try_send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), TrySendError<T>> {
+        sender.try_send(message).map_err(|e| match e {
+            crossbeam_channel::TrySendError::Full(message) => TrySendError::Full(message),
+            crossbeam_channel::TrySendError::Disconnected(message) => {
+                TrySendError::Disconnected(message)
+            }
+        })
+    }
+
+    fn recv<T>(receiver: &Self::Receiver<T>) -> Result<T, RecvError> {
+        receiver.recv().map_err(|_| RecvError)
+    }
+
+    fn try_recv<T>(receiver: &Self::Receiver<T>) -> Result<T, TryRecvError> {
+        receiver.try_recv().map_err(|e| match e {
+            crossbeam_channel::TryRecvError::Empty => TryRecvError::Empty,
+            crossbeam_channel::TryRecvError::Disconnected => TryRecvError::Disconnected,
+        })
+    }
+
+    fn recv_timeout<T>(
+        receiver: &Self::Receiver<T>,
+        timeout: Duration,
+    ) -> Result<T, RecvTimeoutError> {
+        receiver.recv_timeout(timeout).map_err(|e| match e {
+            crossbeam_channel::RecvTimeoutError::Timeout => RecvTimeoutError::Timeout,
+            crossbeam_channel::RecvTimeoutError::Disconnected => RecvTimeoutError::Disconnected,
+        })
+    }
+
+    fn len<T>(receiver: &Self::Receiver<T>) -> usize {
+        receiver.len()
+    }
+}
@@ -0,0 +1,64 @@
+use super::{ChannelBackend, RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use std::time::Duration;
+
+/**
+ * Channels of the flume crate, selected by the `flume-channels` feature.
+ */
+pub struct Flume;
+
+impl ChannelBackend for Flume {
+    type Sender<T> = flume::Sender<T>;
+    type Receiver<T> = flume::Receiver<T>;
+
+    fn bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>) {
+        flume::bounded(capacity)
+    }
+
+    fn unbounded<T>() -> (Self::Sender<T>, Self::Receiver<T>) {
+        flume::unbounded()
+    }
+
+    fn clone_sender<T>(sender: &Self::Sender<T>) -> Self::Sender<T> {
+        sender.clone()
+    }
+
+    fn clone_receiver<T>(receiver: &Self::Receiver<T>) -> Self::Receiver<T> {
+        receiver.clone()
+    }
+
+    fn send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), SendError<T>> {
+        sender.send(message).map_err(|e| SendError(e.0))
+    }
+
+    fn try_send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), TrySendError<T>> {
+        sender.try_send(message).map_err(|e| match e {
+            flume::TrySendError::Full(message) => TrySendError::Full(message),
+            flume::TrySendError::Disconnected(message) => TrySendError::Disconnected(message),
+        })
+    }
+
+    fn recv<T>(receiver: &Self::Receiver<T>) -> Result<T, RecvError> {
+        receiver.recv().map_err(|_| RecvError)
+    }
+
+    fn try_recv<T>(receiver: &Self::Receiver<T>) -> Result<T, TryRecvError> {
+        receiver.try_recv().map_err(|e| match e {
+            flume::TryRecvError::Empty => TryRecvError::Empty,
+            flume::TryRecvError::Disconnected => TryRecvError::Disconnected,
+        })
+    }
+
+    fn recv_timeout<T>(
+        receiver: &Self::Receiver<T>,
+        timeout: Duration,
+    ) -> Result<T, RecvTimeoutError> {
+        receiver.recv_timeout(timeout).map_err(|e| match e {
+            flume::RecvTimeoutError::Timeout => RecvTimeoutError::Timeout,
+            flume::RecvTimeoutError::Disconnected => RecvTimeoutError::Disconnected,
+        })
+    }
+
+    fn len<T>(receiver: &Self::Receiver<T>) -> usize {
+        receiver.len()
+    }
+}
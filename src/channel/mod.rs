@@ -0,0 +1,355 @@
+//! Channels between the Client, the Middleware thread and the Reader and Sender
+//! threads. The backend is selected at compile time: crossbeam by default, flume
+//! with the `flume-channels` feature or the standard library's mpsc channels with
+//! the `std-channels` feature, which drops the channel dependencies. Every backend
+//! returns the errors defined here, so the middleware and its Clients don't
+//! depend on the selected one.
+
+#[cfg(all(
+    feature = "crossbeam-channels",
+    not(any(feature = "flume-channels", feature = "std-channels"))
+))]
+mod crossbeam_backend;
+#[cfg(feature = "flume-channels")]
+mod flume_backend;
+#[cfg(all(feature = "std-channels", not(feature = "flume-channels")))]
+mod std_backend;
+
+#[cfg(not(any(
+    feature = "crossbeam-channels",
+    feature = "flume-channels",
+    feature = "std-channels"
+)))]
+compile_error!(
+    "One of the crossbeam-channels, flume-channels or std-channels features must be enabled"
+);
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/**
+ * Backend selected by the enabled features.
+ */
+#[cfg(all(
+    feature = "crossbeam-channels",
+    not(any(feature = "flume-channels", feature = "std-channels"))
+))]
+pub type Selected = crossbeam_backend::Crossbeam;
+#[cfg(feature = "flume-channels")]
+pub type Selected = flume_backend::Flume;
+#[cfg(all(feature = "std-channels", not(feature = "flume-channels")))]
+pub type Selected = std_backend::Std;
+
+/**
+ * Operations of a channel implementation used by the middleware.
+ */
+pub trait ChannelBackend {
+    ///Sending end of a channel
+    type Sender<T>;
+    ///Receiving end of a channel
+    type Receiver<T>;
+
+    /**
+     * Creates a channel that holds at most `capacity` messages.
+     *
+     * # Arguments
+     *
+     * `capacity` - Maximum number of messages in the channel, 0 for a rendezvous channel
+     */
+    fn bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>);
+
+    /**
+     * Creates a channel without a maximum number of messages.
+     */
+    fn unbounded<T>() -> (Self::Sender<T>, Self::Receiver<T>);
+
+    /**
+     * Clones the sending end of a channel.
+     */
+    fn clone_sender<T>(sender: &Self::Sender<T>) -> Self::Sender<T>;
+
+    /**
+     * Clones the receiving end of a channel, which shares its messages with the original.
+     */
+    fn clone_receiver<T>(receiver: &Self::Receiver<T>) -> Self::Receiver<T>;
+
+    /**
+     * Sends a message, blocking while a bounded channel is full.
+     */
+    fn send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), SendError<T>>;
+
+    /**
+     * Sends a message if the channel isn't full.
+     */
+    fn try_send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), TrySendError<T>>;
+
+    /**
+     * Blocks until a message is received or every sender is dropped.
+     */
+    fn recv<T>(receiver: &Self::Receiver<T>) -> Result<T, RecvError>;
+
+    /**
+     * Receives a message if there is one in the channel.
+     */
+    fn try_recv<T>(receiver: &Self::Receiver<T>) -> Result<T, TryRecvError>;
+
+    /**
+     * Blocks until a message is received, every sender is dropped or the timeout ends.
+     */
+    fn recv_timeout<T>(
+        receiver: &Self::Receiver<T>,
+        timeout: Duration,
+    ) -> Result<T, RecvTimeoutError>;
+
+    /**
+     * Returns the number of messages in the channel.
+     */
+    fn len<T>(receiver: &Self::Receiver<T>) -> usize;
+}
+
+/**
+ * Sending end of a channel.
+ */
+pub struct Sender<T>(<Selected as ChannelBackend>::Sender<T>);
+
+/**
+ * Receiving end of a channel. Cloned receivers share the channel's messages.
+ */
+pub struct Receiver<T>(<Selected as ChannelBackend>::Receiver<T>);
+
+/**
+ * Creates a channel that holds at most `capacity` messages.
+ *
+ * # Arguments
+ *
+ * `capacity` - Maximum number of messages in the channel, 0 for a rendezvous channel
+ */
+pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = Selected::bounded(capacity);
+
+    (Sender(sender), Receiver(receiver))
+}
+
+/**
+ * Creates a channel without a maximum number of messages.
+ */
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let (sender, receiver) = Selected::unbounded();
+
+    (Sender(sender), Receiver(receiver))
+}
+
+impl<T> Sender<T> {
+    /**
+     * Sends a message, blocking while a bounded channel is full. Returns the
+     * message back if every receiver was dropped.
+     *
+     * # Arguments
+     *
+     * `message` - Sent message
+     */
+    pub fn send(&self, message: T) -> Result<(), SendError<T>> {
+        Selected::send(&self.0, message)
+    }
+
+    /**
+     * Sends a message if the channel isn't full.
+     *
+     * # Arguments
+     *
+     * `message` - Sent message
+     */
+    pub fn try_send(&self, message: T) -> Result<(), TrySendError<T>> {
+        Selected::try_send(&self.0, message)
+    }
+}
+
+impl<T> Receiver<T> {
+    /**
+     * Blocks until a message is received, or returns an error once the channel
+     * is empty and every sender was dropped.
+     */
+    pub fn recv(&self) -> Result<T, RecvError> {
+        Selected::recv(&self.0)
+    }
+
+    /**
+     * Receives a message if there is one in the channel.
+     */
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        Selected::try_recv(&self.0)
+    }
+
+    /**
+     * Blocks until a message is received, for a limited time.
+     *
+     * # Arguments
+     *
+     * `timeout` - Maximum waiting time
+     */
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        Selected::recv_timeout(&self.0, timeout)
+    }
+
+    /**
+     * Returns the number of messages in the channel.
+     */
+    pub fn len(&self) -> usize {
+        Selected::len(&self.0)
+    }
+
+    /**
+     * Checks if the channel has no messages.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Returns an iterator that blocks for each message until every sender was dropped.
+     */
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    /**
+     * Returns an iterator over the messages already in the channel.
+     */
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Sender(Selected::clone_sender(&self.0))
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Receiver(Selected::clone_receiver(&self.0))
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Receiver { .. }")
+    }
+}
+
+/**
+ * Error returned when sending to a channel whose receivers were dropped, with the sent message.
+ */
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/**
+ * Error returned when a message can't be sent without blocking, with the sent message.
+ */
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    ///The channel is full
+    Full(T),
+    ///Every receiver was dropped
+    Disconnected(T),
+}
+
+/**
+ * Error returned when receiving from an empty channel whose senders were dropped.
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+/**
+ * Error returned when a message can't be received without blocking.
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    ///The channel is empty
+    Empty,
+    ///The channel is empty and every sender was dropped
+    Disconnected,
+}
+
+/**
+ * Error returned when a message isn't received within a timeout.
+ */
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    ///No message was received before the timeout
+    Timeout,
+    ///The channel is empty and every sender was dropped
+    Disconnected,
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("sending on a disconnected channel")
+    }
+}
+
+impl<T> Error for SendError<T> {}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.pad("Full(..)"),
+            TrySendError::Disconnected(_) => f.pad("Disconnected(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TrySendError::Full(_) => f.pad("sending on a full channel"),
+            TrySendError::Disconnected(_) => f.pad("sending on a disconnected channel"),
+        }
+    }
+}
+
+impl<T> Error for TrySendError<T> {}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("receiving on an empty and disconnected channel")
+    }
+}
+
+impl Error for RecvError {}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.pad("receiving on an empty channel"),
+            TryRecvError::Disconnected => f.pad("receiving on an empty and disconnected channel"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => f.pad("timed out waiting on channel"),
+            RecvTimeoutError::Disconnected => f.pad("channel is empty and sending half is closed"),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
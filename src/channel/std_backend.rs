@@ -0,0 +1,187 @@
+use super::{ChannelBackend, RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
+use std::time::Duration;
+
+/**
+ * Channels of the standard library, selected by the `std-channels` feature.
+ * An mpsc receiver can't be shared, so cloned receivers take turns receiving
+ * through a mutex.
+ */
+pub struct Std;
+
+/**
+ * Sending end of an mpsc channel and the number of messages in the channel.
+ */
+pub struct StdSender<T> {
+    inner: StdSenderKind<T>,
+    len: Arc<AtomicUsize>,
+}
+
+enum StdSenderKind<T> {
+    Unbounded(mpsc::Sender<T>),
+    Bounded(mpsc::SyncSender<T>),
+}
+
+/**
+ * Receiving end of an mpsc channel shared by its clones and the number of
+ * messages in the channel.
+ */
+pub struct StdReceiver<T> {
+    inner: Arc<Mutex<mpsc::Receiver<T>>>,
+    len: Arc<AtomicUsize>,
+}
+
+impl<T> StdReceiver<T> {
+    fn new(receiver: mpsc::Receiver<T>, len: Arc<AtomicUsize>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(receiver)),
+            len,
+        }
+    }
+
+    /**
+     * Locks the receiver, which is still usable if another receiver panicked.
+     */
+    fn lock(&self) -> MutexGuard<'_, mpsc::Receiver<T>> {
+        match self.inner.lock() {
+            Ok(receiver) => receiver,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /**
+     * Decrements the number of messages in the channel once one was received.
+     */
+    fn received<E>(&self, result: Result<T, E>) -> Result<T, E> {
+        if result.is_ok() {
+            self.len.fetch_sub(1, Ordering::AcqRel);
+        }
+
+        result
+    }
+}
+
+impl ChannelBackend for Std {
+    type Sender<T> = StdSender<T>;
+    type Receiver<T> = StdReceiver<T>;
+
+    fn bounded<T>(capacity: usize) -> (Self::Sender<T>, Self::Receiver<T>) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let len = Arc::new(AtomicUsize::new(0));
+
+        (
+            StdSender {
+                inner: StdSenderKind::Bounded(sender),
+                len: len.clone(),
+            },
+            StdReceiver::new(receiver, len),
+        )
+    }
+
+    fn unbounded<T>() -> (Self::Sender<T>, Self::Receiver<T>) {
+        let (sender, receiver) = mpsc::channel();
+        let len = Arc::new(AtomicUsize::new(0));
+
+        (
+            StdSender {
+                inner: StdSenderKind::Unbounded(sender),
+                len: len.clone(),
+            },
+            StdReceiver::new(receiver, len),
+        )
+    }
+
+    fn clone_sender<T>(sender: &Self::Sender<T>) -> Self::Sender<T> {
+        let inner = match &sender.inner {
+            StdSenderKind::Unbounded(sender) => StdSenderKind::Unbounded(sender.clone()),
+            StdSenderKind::Bounded(sender) => StdSenderKind::Bounded(sender.clone()),
+        };
+
+        StdSender {
+            inner,
+            len: sender.len.clone(),
+        }
+    }
+
+    fn clone_receiver<T>(receiver: &Self::Receiver<T>) -> Self::Receiver<T> {
+        StdReceiver {
+            inner: receiver.inner.clone(),
+            len: receiver.len.clone(),
+        }
+    }
+
+    fn send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), SendError<T>> {
+        //Counting the message before it can be received
+        sender.len.fetch_add(1, Ordering::AcqRel);
+
+        let result = match &sender.inner {
+            StdSenderKind::Unbounded(inner) => inner.send(message),
+            StdSenderKind::Bounded(inner) => inner.send(message),
+        };
+
+        result.map_err(|e| {
+            sender.len.fetch_sub(1, Ordering::AcqRel);
+            SendError(e.0)
+        })
+    }
+
+    fn try_send<T>(sender: &Self::Sender<T>, message: T) -> Result<(), TrySendError<T>> {
+        sender.len.fetch_add(1, Ordering::AcqRel);
+
+        let result = match &sender.inner {
+            StdSenderKind::Unbounded(inner) => inner
+                .send(message)
+                .map_err(|e| TrySendError::Disconnected(e.0)),
+            StdSenderKind::Bounded(inner) => inner.try_send(message).map_err(|e| match e {
+                mpsc::TrySendError::Full(message) => TrySendError::Full(message),
+                mpsc::TrySendError::Disconnected(message) => TrySendError::Disconnected(message),
+            }),
+        };
+
+        if result.is_err() {
+            sender.len.fetch_sub(1, Ordering::AcqRel);
+        }
+
+        result
+    }
+
+    fn recv<T>(receiver: &Self::Receiver<T>) -> Result<T, RecvError> {
+        let result = receiver.lock().recv().map_err(|_| RecvError);
+        receiver.received(result)
+    }
+
+    fn try_recv<T>(receiver: &Self::Receiver<T>) -> Result<T, TryRecvError> {
+        //A receiver waiting for a message holds the lock, so the channel is empty
+        let inner = match receiver.inner.try_lock() {
+            Ok(inner) => inner,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => return Err(TryRecvError::Empty),
+        };
+
+        let result = inner.try_recv().map_err(|e| match e {
+            mpsc::TryRecvError::Empty => TryRecvError::Empty,
+            mpsc::TryRecvError::Disconnected => TryRecvError::Disconnected,
+        });
+        drop(inner);
+
+        receiver.received(result)
+    }
+
+    fn recv_timeout<T>(
+        receiver: &Self::Receiver<T>,
+        timeout: Duration,
+    ) -> Result<T, RecvTimeoutError> {
+        let result = receiver.lock().recv_timeout(timeout).map_err(|e| match e {
+            mpsc::RecvTimeoutError::Timeout => RecvTimeoutError::Timeout,
+            mpsc::RecvTimeoutError::Disconnected => RecvTimeoutError::Disconnected,
+        });
+
+        receiver.received(result)
+    }
+
+    fn len<T>(receiver: &Self::Receiver<T>) -> usize {
+        receiver.len.load(Ordering::Acquire)
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/**
+ * Map keyed by dots. Dots are small integer pairs, so with the `fast-hash`
+ * feature the map uses aHash, which is much faster than the default SipHash
+ * while still being keyed randomly per map.
+ */
+#[cfg(feature = "fast-hash")]
+pub type DotMap<V> = HashMap<Dot, V, ahash::RandomState>;
+
+/**
+ * Map keyed by dots, which uses the default SipHash hasher.
+ */
+#[cfg(not(feature = "fast-hash"))]
+pub type DotMap<V> = HashMap<Dot, V>;
+
+/**
+ * A dot is a pair id and counter, which are, respectivally, the peer's
+ * globally unique identifier and a monotonically increasing counter that
+ * grows with each sent message.
+ */
+#[derive(Default, Copy, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+pub struct Dot {
+    ///Peer's globally unique id
+    pub id: usize,
+    ///Message's counter
+    pub counter: usize,
+}
+
+impl Dot {
+    /**
+     * Creates a new Dot.
+     *
+     * # Arguments
+     *
+     * `id` - Peer's globally unique id
+     *
+     * `counter` - Message's counter
+     */
+    pub fn new(id: usize, counter: usize) -> Dot {
+        Dot {
+            id: id,
+            counter: counter,
+        }
+    }
+}
+
+/**
+ * Writes a dot in its compact form, id:counter, which is parsed back by `from_str`.
+ */
+impl fmt::Display for Dot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.id, self.counter)
+    }
+}
+
+impl FromStr for Dot {
+    type Err = ParseDotError;
+
+    /**
+     * Parses a dot in its compact form, id:counter, such as 3:42.
+     *
+     * # Arguments
+     *
+     * `text` - Text of the dot
+     */
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parts = text.trim().splitn(2, ':');
+
+        let (id, counter) = match (parts.next(), parts.next()) {
+            (Some(id), Some(counter)) => (id.trim(), counter.trim()),
+            _ => return Err(ParseDotError::new(text, "expected id:counter")),
+        };
+
+        let id = id
+            .parse::<usize>()
+            .map_err(|e| ParseDotError::new(text, &format!("invalid id - {}", e)))?;
+        let counter = counter
+            .parse::<usize>()
+            .map_err(|e| ParseDotError::new(text, &format!("invalid counter - {}", e)))?;
+
+        Ok(Dot::new(id, counter))
+    }
+}
+
+/**
+ * Error of a dot or a list of dots that couldn't be parsed.
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDotError {
+    ///Text that couldn't be parsed
+    pub text: String,
+    ///Cause of the error
+    pub reason: String,
+}
+
+impl ParseDotError {
+    /**
+     * Creates a new parsing error.
+     *
+     * # Arguments
+     *
+     * `text` - Text that couldn't be parsed
+     *
+     * `reason` - Cause of the error
+     */
+    fn new(text: &str, reason: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseDotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid dot \"{}\": {}", self.text, self.reason)
+    }
+}
+
+impl Error for ParseDotError {}
+
+/**
+ * Writes a list of dots, such as a message's context, as comma separated
+ * compact dots, such as 0:3,2:7, which is parsed back by `parse_dots`.
+ *
+ * # Arguments
+ *
+ * `dots` - Dots to write
+ */
+pub fn format_dots(dots: &[Dot]) -> String {
+    dots.iter()
+        .map(|dot| dot.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/**
+ * Parses a list of comma separated compact dots, where an empty text is an empty list.
+ *
+ * # Arguments
+ *
+ * `text` - Text of the dots
+ */
+pub fn parse_dots(text: &str) -> Result<Vec<Dot>, ParseDotError> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    text.split(',').map(|dot| dot.parse::<Dot>()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_displayed_dot() {
+        let dot = Dot::new(3, 42);
+
+        assert_eq!(dot.to_string(), "3:42");
+        assert_eq!(dot.to_string().parse::<Dot>(), Ok(dot));
+        assert_eq!(" 3 : 42 ".parse::<Dot>(), Ok(dot));
+    }
+
+    #[test]
+    fn parses_the_formatted_dots() {
+        let dots = vec![Dot::new(0, 3), Dot::new(2, 7), Dot::new(1, 1)];
+
+        assert_eq!(format_dots(&dots), "0:3,2:7,1:1");
+        assert_eq!(parse_dots(&format_dots(&dots)), Ok(dots));
+        assert_eq!(format_dots(&[]), "");
+        assert_eq!(parse_dots(" "), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn refuses_malformed_dots() {
+        for text in ["", "3", "3:", ":42", "a:42", "3:b", "-1:42", "3:42:1"].iter() {
+            let error = text.parse::<Dot>().unwrap_err();
+
+            assert_eq!(error.text, *text);
+        }
+
+        assert_eq!(
+            "3".parse::<Dot>().unwrap_err().reason,
+            "expected id:counter"
+        );
+        assert!("a:42"
+            .parse::<Dot>()
+            .unwrap_err()
+            .reason
+            .starts_with("invalid id"));
+        assert!("3:b"
+            .parse::<Dot>()
+            .unwrap_err()
+            .reason
+            .starts_with("invalid counter"));
+    }
+
+    #[test]
+    fn refuses_lists_with_a_malformed_dot() {
+        let error = parse_dots("0:3,2;7").unwrap_err();
+
+        assert_eq!(error.text, "2;7");
+        assert!(parse_dots("0:3,").is_err());
+    }
+}
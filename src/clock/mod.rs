@@ -0,0 +1,21 @@
+//! Logical clocks of the middleware, shared by both engines, which can be used
+//! by other projects for their own causal bookkeeping. The types are also
+//! available from their original paths in the GRAPH, VV and causality checker
+//! modules.
+
+/**
+ * Pair id, cntr that is tagged to each message before sending.
+ */
+pub mod dot;
+/**
+ * Version matrices of a group of peers.
+ */
+pub mod version_matrix;
+/**
+ * Version vector that is tagged to each message before sending.
+ */
+pub mod version_vector;
+
+pub use dot::{format_dots, parse_dots, Dot, DotMap, ParseDotError};
+pub use version_matrix::VersionMatrix;
+pub use version_vector::VersionVector;
@@ -0,0 +1,87 @@
+use crate::clock::version_vector::VersionVector;
+
+/**
+ * Matrix where each row is a peer's version vector. This is used to determine causal stability.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VersionMatrix {
+    ///Version vector of each peer, where the row is the peer's id
+    pub matrix: Vec<VersionVector>,
+}
+
+impl VersionMatrix {
+    /**
+     * Creates a new version matrix that is NxN where N is the group size.
+     * Each row will be a peer's version vector, where the peer's id is the
+     * row number.
+     *
+     * # Arguments
+     *
+     * `peer_number` - Number of peers in the group.
+     */
+    pub fn new(peer_number: usize) -> VersionMatrix {
+        let mut matrix: Vec<VersionVector> = Vec::with_capacity(peer_number);
+
+        for _ in 0..peer_number {
+            matrix.push(VersionVector::new(peer_number));
+        }
+
+        VersionMatrix { matrix }
+    }
+
+    /**
+     * Compares the causal stability in each row to a peer's version vector.
+     *
+     * # Arguments
+     *
+     * `dot_version_vector` - Peer's version vector to check stability.
+     */
+    pub fn check_stability(&self, dot_version_vector: &VersionVector) -> bool {
+        for i in 0..self.matrix.len() {
+            if !VersionVector::cmp(&self.matrix[i], dot_version_vector) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /**
+     * Updates a peer's row in the version vector matrix.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Peer's id that will be its row in the matrix.
+     * `dot_version_vector` - Peer's new version vector.
+     */
+    pub fn update_peer_entry(&mut self, peer_id: usize, dot_version_vector: VersionVector) {
+        self.matrix[peer_id] = dot_version_vector;
+    }
+
+    /**
+     * Merges a version vector into a peer's row, keeping the greatest value of each entry.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Peer's id that is its row in the matrix.
+     *
+     * `version_vector` - Version vector known by the peer.
+     */
+    pub fn merge_peer_entry(&mut self, peer_id: usize, version_vector: &VersionVector) {
+        self.matrix[peer_id].merge(version_vector);
+    }
+
+    /**
+     * Returns the smallest value of each entry among the rows, which are
+     * the messages of each peer that every peer delivered.
+     */
+    pub fn min(&self) -> VersionVector {
+        let mut rows = self.matrix.iter();
+
+        match rows.next() {
+            Some(first) => rows.fold(first.clone(), |stable, row| {
+                VersionVector::min(&stable, row)
+            }),
+            None => VersionVector::empty(),
+        }
+    }
+}
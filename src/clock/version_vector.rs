@@ -0,0 +1,353 @@
+use crate::clock::dot::Dot;
+use std::cmp::Ordering;
+use std::ops::{Deref, DerefMut};
+
+/**
+ * Version vector struct.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VersionVector(pub Vec<usize>);
+
+impl VersionVector {
+    /**
+     * Creates an empty version vector.
+     */
+    pub fn empty() -> VersionVector {
+        let vec: Vec<usize> = Vec::new();
+
+        VersionVector(vec)
+    }
+    /**
+     * Creates an empty version vector.
+     *
+     * # Arguments
+     *
+     * `length` - Length of the version vector to create.
+     */
+    pub fn new(length: usize) -> VersionVector {
+        VersionVector(vec![0; length])
+    }
+
+    /**
+     * Appends a value to the version vector.
+     *
+     * # Arguments
+     *
+     * `value` - Value to append to the version vector.
+     */
+    pub fn push(&mut self, value: usize) {
+        self.0.push(value);
+    }
+
+    /**
+     * Checks if every entry in the first version vector is equal
+     * or greater to the same entry in the second version vector.
+     *
+     * # Arguments
+     *
+     * `a` - Greater version vector.
+     *
+     * `b` - Smaller version vector.
+     */
+    pub fn cmp(a: &VersionVector, b: &VersionVector) -> bool {
+        let mut ret = true;
+
+        for i in 0..a.len() {
+            ret = ret && (a[i] >= b[i]);
+
+            if !ret {
+                break;
+            }
+        }
+
+        ret
+    }
+
+    /**
+     * Compares if a version vector is greater or equal to another.
+     *
+     * # Arguments
+     *
+     * `index` - Position in the greater version vector that should be -1 to the value in the other.
+     *
+     * `a` - Greater version vector
+     *
+     * `b` - Smaller version vector
+     */
+    pub fn compare_version_vectors(index: usize, a: &VersionVector, b: &VersionVector) -> bool {
+        let mut ret = true;
+
+        for i in 0..a.0.len() {
+            if i != index {
+                ret = ret && (b[i] <= a[i]);
+            } else {
+                ret = ret && (b[i] == a[i] + 1);
+            }
+
+            if !ret {
+                return false;
+            }
+        }
+
+        ret
+    }
+
+    /**
+     * Checks if two version vectors are equal.
+     *
+     * # Arguments
+     *
+     * `b` - Version vector to compare to.
+     */
+    pub fn equal(&self, b: &VersionVector) -> bool {
+        let compare = self.0.cmp(&b.0);
+        compare == Ordering::Equal
+    }
+
+    /**
+     * Returns the different values between two version vectors.
+     *
+     * # Arguments
+     *
+     * `greater` - Greater version vector
+     *
+     * `lesser` - Smaller version vector
+     *
+     * `length` - Length of the version vectors
+     */
+    pub fn dif(
+        greater: &VersionVector,
+        lesser: &VersionVector,
+        length: usize,
+    ) -> Vec<(usize, usize)> {
+        let mut dots = Vec::new();
+
+        for i in 0..length {
+            if greater[i] - lesser[i] > 0 {
+                for cntr in lesser[i] + 1..greater[i] + 1 {
+                    dots.push((i, cntr));
+                }
+            }
+        }
+
+        dots
+    }
+
+    /**
+     * Returns the value of an entry, where the entries past the end are zero.
+     *
+     * # Arguments
+     *
+     * `index` - Position of the entry
+     */
+    pub fn entry(&self, index: usize) -> usize {
+        self.0.get(index).cloned().unwrap_or(0)
+    }
+
+    /**
+     * Checks if the message of a dot is included in the version vector.
+     *
+     * # Arguments
+     *
+     * `dot` - Dot of the message
+     */
+    pub fn includes(&self, dot: &Dot) -> bool {
+        dot.counter <= self.entry(dot.id)
+    }
+
+    /**
+     * Merges another version vector, keeping the greatest value of each entry.
+     *
+     * # Arguments
+     *
+     * `other` - Version vector to merge
+     */
+    pub fn merge(&mut self, other: &VersionVector) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+
+        for i in 0..other.0.len() {
+            self.0[i] = self.0[i].max(other.0[i]);
+        }
+    }
+
+    /**
+     * Returns the smallest value of each entry of two version vectors.
+     *
+     * # Arguments
+     *
+     * `a` - First version vector
+     *
+     * `b` - Second version vector
+     */
+    pub fn min(a: &VersionVector, b: &VersionVector) -> VersionVector {
+        let length = a.0.len().max(b.0.len());
+
+        VersionVector((0..length).map(|i| a.entry(i).min(b.entry(i))).collect())
+    }
+
+    /**
+     * Returns true if neither version vector is included in the other.
+     *
+     * # Arguments
+     *
+     * `other` - Version vector to compare to
+     */
+    pub fn concurrent(&self, other: &VersionVector) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+}
+
+impl PartialEq for VersionVector {
+    /**
+     * Compares the version vectors entry by entry, where the entries past the
+     * end are zero, like `partial_cmp`.
+     */
+    fn eq(&self, other: &VersionVector) -> bool {
+        (0..self.0.len().max(other.0.len())).all(|i| self.entry(i) == other.entry(i))
+    }
+}
+
+impl Eq for VersionVector {}
+
+impl PartialOrd for VersionVector {
+    /**
+     * Compares the version vectors entry by entry, where the entries past the
+     * end are zero. Returns `None` for concurrent version vectors.
+     */
+    fn partial_cmp(&self, other: &VersionVector) -> Option<Ordering> {
+        let mut ordering = Ordering::Equal;
+
+        for i in 0..self.0.len().max(other.0.len()) {
+            match (ordering, self.entry(i).cmp(&other.entry(i))) {
+                (_, Ordering::Equal) => {}
+                (Ordering::Equal, entry_ordering) => ordering = entry_ordering,
+                (ordering, entry_ordering) if ordering != entry_ordering => return None,
+                _ => {}
+            }
+        }
+
+        Some(ordering)
+    }
+}
+
+impl Deref for VersionVector {
+    type Target = Vec<usize>;
+
+    fn deref(&self) -> &Vec<usize> {
+        &self.0
+    }
+}
+
+impl DerefMut for VersionVector {
+    fn deref_mut(&mut self) -> &mut Vec<usize> {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_keeps_the_greatest_entries() {
+        let mut version_vector = VersionVector(vec![3, 0, 2]);
+        version_vector.merge(&VersionVector(vec![1, 4, 2]));
+        assert_eq!(version_vector.0, vec![3, 4, 2]);
+
+        let mut shorter = VersionVector(vec![1]);
+        shorter.merge(&VersionVector(vec![0, 5]));
+        assert_eq!(shorter.0, vec![1, 5]);
+
+        let mut longer = VersionVector(vec![1, 2, 3]);
+        longer.merge(&VersionVector(vec![4]));
+        assert_eq!(longer.0, vec![4, 2, 3]);
+    }
+
+    #[test]
+    fn min_keeps_the_smallest_entries() {
+        assert_eq!(
+            VersionVector::min(&VersionVector(vec![3, 0, 2]), &VersionVector(vec![1, 4, 2])).0,
+            vec![1, 0, 2]
+        );
+        assert_eq!(
+            VersionVector::min(&VersionVector(vec![3]), &VersionVector(vec![1, 4])).0,
+            vec![1, 0]
+        );
+    }
+
+    #[test]
+    fn partial_cmp_orders_the_included_version_vectors() {
+        let a = VersionVector(vec![1, 2]);
+
+        assert_eq!(
+            a.partial_cmp(&VersionVector(vec![1, 2])),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            a.partial_cmp(&VersionVector(vec![1, 3])),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            a.partial_cmp(&VersionVector(vec![0, 2])),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(a.partial_cmp(&VersionVector(vec![2, 1])), None);
+
+        //The entries past the end are zero
+        assert_eq!(
+            a.partial_cmp(&VersionVector(vec![1, 2, 0])),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            a.partial_cmp(&VersionVector(vec![1, 2, 1])),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            a.partial_cmp(&VersionVector(vec![1])),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(a.partial_cmp(&VersionVector(vec![0, 3, 1])), None);
+    }
+
+    #[test]
+    fn equality_matches_partial_cmp() {
+        assert_eq!(VersionVector(vec![1, 0]), VersionVector(vec![1]));
+        assert_eq!(VersionVector::empty(), VersionVector::new(3));
+        assert_ne!(VersionVector(vec![1, 1]), VersionVector(vec![1]));
+
+        for (a, b) in [
+            (vec![1, 0], vec![1]),
+            (vec![1, 1], vec![1]),
+            (vec![0, 1], vec![1]),
+            (vec![2, 3], vec![2, 3]),
+        ]
+        .iter()
+        {
+            let (a, b) = (VersionVector(a.clone()), VersionVector(b.clone()));
+
+            assert_eq!(a == b, a.partial_cmp(&b) == Some(Ordering::Equal));
+        }
+    }
+
+    #[test]
+    fn concurrent_version_vectors_are_not_ordered() {
+        assert!(VersionVector(vec![1, 0]).concurrent(&VersionVector(vec![0, 1])));
+        assert!(VersionVector(vec![1]).concurrent(&VersionVector(vec![0, 1])));
+        assert!(!VersionVector(vec![1, 1]).concurrent(&VersionVector(vec![0, 1])));
+        assert!(!VersionVector(vec![1, 0]).concurrent(&VersionVector(vec![1])));
+    }
+
+    #[test]
+    fn includes_the_delivered_dots() {
+        let version_vector = VersionVector(vec![2, 0]);
+
+        assert!(version_vector.includes(&Dot::new(0, 1)));
+        assert!(version_vector.includes(&Dot::new(0, 2)));
+        assert!(!version_vector.includes(&Dot::new(0, 3)));
+        assert!(!version_vector.includes(&Dot::new(1, 1)));
+        //The entries past the end are zero
+        assert!(!version_vector.includes(&Dot::new(5, 1)));
+    }
+}
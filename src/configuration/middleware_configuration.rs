@@ -1,8 +1,19 @@
+use crate::transport::get_shm_segment;
+use std::env::{self, VarError};
 use std::error::Error;
+use std::fmt::Display;
 use std::fs::File;
 use std::io::prelude::*;
+use std::net::IpAddr;
+use std::str::FromStr;
 use std::time::Duration;
 
+///Prefix of the environment variables that override the configuration
+const ENV_PREFIX: &str = "TCB_";
+
+///Maximum number of messages the buffers are pre-allocated for
+const MAX_WARM_UP_MESSAGES: u64 = 1 << 20;
+
 /**
  * Wrapper for the middleware configurations.
 */
@@ -20,17 +31,481 @@ pub struct Configuration {
     ///Stability calculation flag.
     pub track_causal_stability: bool,
 
+    ///Routes the stability notifications to their own channel, returned by
+    ///`TCB::stable_receiver`, instead of interleaving them with the deliveries.
+    #[serde(default)]
+    pub separate_stable_channel: bool,
+
+    ///Acks the stable messages automatically from a background thread, once they
+    ///were stable for `stable_hold_time`, instead of notifying the Client.
+    #[serde(default)]
+    pub auto_stable_ack: bool,
+
+    ///Time in microseconds a stable message is kept before being automatically acked.
+    #[serde(default)]
+    pub stable_hold_time: u64,
+
+    ///Time in microseconds the VV middleware coalesces the advances of the stable
+    ///frontier into a single notification, instead of notifying each stable message.
+    ///Each message is notified if set to 0.
+    #[serde(default)]
+    pub stable_frontier_interval: u64,
+
     ///Parameters that set message batching.
     pub batching: Batching,
+
+    ///Retention of the delivered messages, which are relayed to the peers
+    ///catching up with the group or reconnecting to it. Messages aren't retained
+    ///if not enabled, and are only relayed by the GRAPH middleware in groups
+    ///that aren't signed or Byzantine-tolerant.
+    #[serde(default)]
+    pub retention: Retention,
+
+    ///Expected load used to pre-allocate the middleware's buffers at startup,
+    ///instead of growing them during the first seconds of a run.
+    #[serde(default)]
+    pub warm_up: WarmUp,
+
+    ///Ids and addresses of the peers in the group.
+    #[serde(default)]
+    pub topology: Topology,
+
+    ///Epoch of the group. Frames stamped with an older epoch than the local one
+    ///are discarded by the Reader threads. The middleware never increases it, so
+    ///operators must increase it by hand, e.g. with `TCB_EPOCH`, on every peer
+    ///after each reconfiguration or restart. A peer restarted with the same epoch
+    ///doesn't reject the stale frames of its previous run.
+    #[serde(default)]
+    pub epoch: u64,
+
+    ///Interval in microseconds between the probes that measure the round trip
+    ///time to each peer. Probing is disabled if set to 0.
+    #[serde(default)]
+    pub probe_interval: u64,
+
+    ///Number of threads that deserialize the messages read from the peers,
+    ///leaving the Reader threads to drain the sockets. The Reader threads
+    ///deserialize the messages themselves if set to 0.
+    #[serde(default)]
+    pub deserialization_workers: usize,
+
+    ///Queues the messages received from each peer and handles them in a
+    ///round-robin order, so a peer sending much faster than the others
+    ///doesn't delay the delivery of their messages.
+    #[serde(default)]
+    pub fair_inbound: bool,
+
+    ///Time in microseconds the Middleware thread spins on its channel before
+    ///parking, trading CPU for a lower wakeup latency. The thread always parks
+    ///if set to 0.
+    #[serde(default)]
+    pub busy_poll: u64,
+
+    ///Maximum number of messages delivered before the Middleware thread handles
+    ///its next message, when a message unlocks a long chain of deliveries. The
+    ///rest of the chain is delivered in the next iterations. Unlimited if set to 0.
+    #[serde(default)]
+    pub delivery_chunk_size: usize,
+
+    ///Time in microseconds the middleware keeps receiving and delivering the peers'
+    ///messages after the Client ends it, until every peer closed its connections.
+    ///The middleware ends right away if set to 0.
+    #[serde(default)]
+    pub shutdown_grace_period: u64,
+
+    ///Time in microseconds after which a dependency that wasn't received is
+    ///reported to the Client as missing, alongside the messages it blocks.
+    ///Only supported by the GRAPH middleware and disabled if set to 0.
+    #[serde(default)]
+    pub missing_dependency_timeout: u64,
+
+    ///Requests the messages above the delivered version vector from the sender
+    ///of a missing dependency, which requires the sender to retain its messages.
+    ///Only supported by the GRAPH middleware.
+    #[serde(default)]
+    pub retransmit_missing: bool,
+
+    ///Encoding of the integers in the frames and messages sent by this peer,
+    ///announced to the other peers in the handshake.
+    #[serde(default)]
+    pub wire_encoding: WireEncoding,
+
+    ///Transport of the connections between the peers. The UDP transport is
+    ///experimental and must be set in every peer.
+    #[serde(default)]
+    pub transport: Transport,
+
+    ///Settings of the UDP transport.
+    #[serde(default)]
+    pub udp: Udp,
+
+    ///Maximum number of messages of each sender held by the Reader or Deserializer
+    ///threads while an earlier message of the same sender is missing, to restore
+    ///the FIFO order of each sender under mild reordering. Messages are handed to
+    ///the Middleware as they're read if set to 0, which is overridden by each peer's window.
+    #[serde(default)]
+    pub reorder_window: usize,
+
+    ///Latency, jitter and loss added on purpose to the messages sent to every
+    ///peer, to study the algorithms under a slower network. Requires the
+    ///`link-simulation` feature and is overridden by each peer's link.
+    #[serde(default)]
+    pub link: Option<LinkSimulation>,
+
+    ///Priorities and CPU affinities of the middleware threads. Requires
+    ///the `affinity` feature and is only applied on Linux.
+    #[serde(default)]
+    pub threads: Threads,
+
+    ///Hexadecimal 256 bit group key used to encrypt the payloads end-to-end.
+    ///Payloads are sent in plaintext if not set. Requires the `encryption` feature.
+    #[serde(default)]
+    pub group_key: Option<String>,
+
+    ///Keys used to sign and verify every message. Messages aren't signed
+    ///if not set. Requires the `signing` feature.
+    #[serde(default)]
+    pub signing: Option<Signing>,
+
+    ///Byzantine-tolerant causal broadcast flag. Messages carry the history
+    ///hashes of their dependencies and equivocations are reported as faults.
+    ///Requires the `bcb` feature and the same value in every peer.
+    #[serde(default)]
+    pub byzantine_tolerance: bool,
+
+    ///Keys of the Noise_XX handshake. Connections are encrypted with per-connection
+    ///session keys if set. Requires the `noise` feature and the same setting in every peer.
+    #[serde(default)]
+    pub noise: Option<Noise>,
+
+    ///Ranges of the entity keys whose messages are delivered by this peer,
+    ///announced to the other peers in the handshake. The other peers send the
+    ///messages of the remaining keys without their payloads. Every message is
+    ///delivered if empty. Only supported by the GRAPH middleware.
+    #[serde(default)]
+    pub interests: Vec<KeyRange>,
+
+    ///Delivery receipts flag. Each peer acknowledges the delivered messages to
+    ///their senders, which can query the peers that delivered each of their messages.
+    #[serde(default)]
+    pub delivery_receipts: bool,
+
+    ///Delivery acks flag. The Client acks each delivery once it processed it,
+    ///and the unacked deliveries are kept by the middleware to be sent again.
+    #[serde(default)]
+    pub delivery_acks: bool,
+
+    ///Behaviour of the middleware once the Client dropped its receiver, e.g. because
+    ///the application stopped consuming the deliveries without ending the middleware.
+    #[serde(default)]
+    pub dropped_client: DroppedClientPolicy,
+
+    ///Maximum number of delivered messages sent to the Client in one channel
+    ///message, when a message unlocks the delivery of its successors. Every
+    ///delivery is sent on its own if set to 0 or 1. Only supported by the
+    ///GRAPH middleware.
+    #[serde(default)]
+    pub delivery_batch_size: usize,
+
+    ///Returns the middleware instance before it's connected to every peer, instead
+    ///of blocking until then. The messages sent to a peer are buffered until it's
+    ///connected and `wait_ready` tells when the setup ended.
+    #[serde(default)]
+    pub lazy_setup: bool,
+
+    ///Time in microseconds the middleware waits to be connected to every peer
+    ///when it's created, after which `try_new` returns `TcbError::StartupTimeout`
+    ///listing the missing connections and `new` panics with it. The threads of
+    ///the middleware are stopped and its listener is closed before. The middleware
+    ///waits forever if set to 0 and this is ignored by a lazy setup.
+    #[serde(default)]
+    pub startup_timeout: u64,
+
+    ///Connects each pair of peers with a single socket that carries the messages
+    ///in both directions, instead of one connection per direction. The peer with
+    ///the lower id makes the connection. Only supported by plain TCP connections,
+    ///so it's ignored with the UDP or shared memory transports, TLS or Noise.
+    #[serde(default)]
+    pub shared_connections: bool,
+
+    ///Payload checksums flag. Each message carries the checksum of its payload,
+    ///which the receiving peers verify before delivering it, and the corrupted
+    ///payloads are reported as faults and kept pending until received again.
+    ///It's ignored if the group key is set, since the cipher authenticates the payloads.
+    #[serde(default)]
+    pub payload_checksums: bool,
 }
 
 impl Configuration {
+    /**
+     * Returns a configuration that flushes every message as soon as it's
+     * broadcast, trading throughput for the lowest delivery latency.
+     */
+    pub fn low_latency() -> Self {
+        Self {
+            stream_sender_timeout: 100,
+            batching: Batching {
+                size: 1,
+                message_number: 1,
+                lower_timeout: 100,
+                upper_timeout: 10_000,
+                mode: BatchingMode::Static,
+            },
+            ..Self::default()
+        }
+    }
+
+    /**
+     * Returns a configuration that buffers large batches of messages, adapted
+     * to the arrival rate, trading delivery latency for throughput.
+     */
+    pub fn high_throughput() -> Self {
+        Self {
+            stream_sender_timeout: 10_000,
+            batching: Batching {
+                size: 1_000_000,
+                message_number: 1_000,
+                lower_timeout: 10_000,
+                upper_timeout: 1_000_000,
+                mode: BatchingMode::Adaptive,
+            },
+            deserialization_workers: 2,
+            ..Self::default()
+        }
+    }
+
+    /**
+     * Returns a configuration for tests and examples running every peer
+     * in the same machine, with small thread stacks and no batching delays.
+     */
+    pub fn testing() -> Self {
+        Self {
+            thread_stack_size: 256 * 1024,
+            middleware_thread_stack_size: 2 * 1024 * 1024,
+            ..Self::low_latency()
+        }
+    }
+
     /**
      * Returns the timeout wrapped in a Duration.
      */
     pub fn get_stream_sender_timeout(&self) -> Duration {
         Duration::from_micros(self.stream_sender_timeout)
     }
+
+    /**
+     * Returns the probe interval wrapped in a Duration.
+     */
+    pub fn get_probe_interval(&self) -> Duration {
+        Duration::from_micros(self.probe_interval)
+    }
+
+    /**
+     * Returns the busy polling spin time wrapped in a Duration.
+     */
+    pub fn get_busy_poll(&self) -> Duration {
+        Duration::from_micros(self.busy_poll)
+    }
+
+    /**
+     * Returns the maximum time the stable frontier advances are coalesced, wrapped in a Duration.
+     */
+    pub fn get_stable_frontier_interval(&self) -> Duration {
+        Duration::from_micros(self.stable_frontier_interval)
+    }
+
+    /**
+     * Returns the maximum time draining the peers' messages at shutdown, wrapped in a Duration.
+     */
+    pub fn get_shutdown_grace_period(&self) -> Duration {
+        Duration::from_micros(self.shutdown_grace_period)
+    }
+
+    /**
+     * Returns the age of a missing dependency before it's reported, wrapped in a Duration.
+     */
+    pub fn get_missing_dependency_timeout(&self) -> Duration {
+        Duration::from_micros(self.missing_dependency_timeout)
+    }
+
+    /**
+     * Returns the maximum time waiting for the connections at startup, wrapped in a Duration.
+     */
+    pub fn get_startup_timeout(&self) -> Duration {
+        Duration::from_micros(self.startup_timeout)
+    }
+
+    /**
+     * Checks if each pair of peers is connected by a single socket, which
+     * requires plain TCP connections between every peer in the group.
+     */
+    pub fn shares_connections(&self) -> bool {
+        self.shared_connections
+            && self.transport == Transport::Tcp
+            && self.topology.tls.is_none()
+            && self.noise.is_none()
+            && self
+                .topology
+                .peers
+                .iter()
+                .all(|peer| get_shm_segment(&peer.address).is_none())
+    }
+
+    /**
+     * Returns the batching of the messages sent to a peer, which is
+     * the peer's batching in the topology if set or the global batching.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Destination peer's globally unique id.
+     */
+    pub fn get_batching(&self, peer_id: usize) -> &Batching {
+        self.topology
+            .peers
+            .iter()
+            .find(|peer| peer.id == peer_id)
+            .and_then(|peer| peer.batching.as_ref())
+            .unwrap_or(&self.batching)
+    }
+
+    /**
+     * Returns the simulated link to a peer, which is the peer's link in the
+     * topology if set or the global link, or None if the link isn't simulated.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Destination peer's globally unique id.
+     */
+    pub fn get_link_simulation(&self, peer_id: usize) -> Option<&LinkSimulation> {
+        self.topology
+            .peers
+            .iter()
+            .find(|peer| peer.id == peer_id)
+            .and_then(|peer| peer.link.as_ref())
+            .or(self.link.as_ref())
+    }
+
+    /**
+     * Overrides the configuration with the values of the environment variables
+     * that are set. Each variable is the field name in uppercase prefixed by `TCB_`,
+     * and the batching, retention and warm up fields are prefixed by `TCB_BATCHING_`,
+     * `TCB_RETENTION_`, `TCB_WARM_UP_` and `TCB_UDP_`, e.g. `TCB_BATCHING_SIZE`.
+     * The stability flag is set by `TCB_TRACK_STABILITY`. An error is returned
+     * if a variable has an invalid value.
+     */
+    pub fn apply_env_overrides(&mut self) -> Result<(), Box<dyn Error>> {
+        env_override("THREAD_STACK_SIZE", &mut self.thread_stack_size)?;
+        env_override(
+            "MIDDLEWARE_THREAD_STACK_SIZE",
+            &mut self.middleware_thread_stack_size,
+        )?;
+        env_override("STREAM_SENDER_TIMEOUT", &mut self.stream_sender_timeout)?;
+        env_override("TRACK_STABILITY", &mut self.track_causal_stability)?;
+        env_override("SEPARATE_STABLE_CHANNEL", &mut self.separate_stable_channel)?;
+        env_override("AUTO_STABLE_ACK", &mut self.auto_stable_ack)?;
+        env_override("STABLE_HOLD_TIME", &mut self.stable_hold_time)?;
+        env_override(
+            "STABLE_FRONTIER_INTERVAL",
+            &mut self.stable_frontier_interval,
+        )?;
+        env_override("LOCAL_ID", &mut self.topology.local_id)?;
+        env_override("EPOCH", &mut self.epoch)?;
+        env_override("PROBE_INTERVAL", &mut self.probe_interval)?;
+        env_override("DESERIALIZATION_WORKERS", &mut self.deserialization_workers)?;
+        env_override("FAIR_INBOUND", &mut self.fair_inbound)?;
+        env_override("BUSY_POLL", &mut self.busy_poll)?;
+        env_override("DELIVERY_CHUNK_SIZE", &mut self.delivery_chunk_size)?;
+        env_override("SHUTDOWN_GRACE_PERIOD", &mut self.shutdown_grace_period)?;
+        env_override(
+            "MISSING_DEPENDENCY_TIMEOUT",
+            &mut self.missing_dependency_timeout,
+        )?;
+        env_override("RETRANSMIT_MISSING", &mut self.retransmit_missing)?;
+        env_override("WIRE_ENCODING", &mut self.wire_encoding)?;
+        env_override("TRANSPORT", &mut self.transport)?;
+        env_override("BYZANTINE_TOLERANCE", &mut self.byzantine_tolerance)?;
+        env_override("DELIVERY_RECEIPTS", &mut self.delivery_receipts)?;
+        env_override("DELIVERY_ACKS", &mut self.delivery_acks)?;
+        env_override("DROPPED_CLIENT", &mut self.dropped_client)?;
+        env_override("DELIVERY_BATCH_SIZE", &mut self.delivery_batch_size)?;
+        env_override("LAZY_SETUP", &mut self.lazy_setup)?;
+        env_override("STARTUP_TIMEOUT", &mut self.startup_timeout)?;
+        env_override("SHARED_CONNECTIONS", &mut self.shared_connections)?;
+        env_override("PAYLOAD_CHECKSUMS", &mut self.payload_checksums)?;
+
+        if let Some(group_key) = env_value("GROUP_KEY")? {
+            self.group_key = Some(group_key);
+        }
+
+        env_override("BATCHING_SIZE", &mut self.batching.size)?;
+        env_override("BATCHING_MESSAGE_NUMBER", &mut self.batching.message_number)?;
+        env_override("BATCHING_LOWER_TIMEOUT", &mut self.batching.lower_timeout)?;
+        env_override("BATCHING_UPPER_TIMEOUT", &mut self.batching.upper_timeout)?;
+        env_override("BATCHING_MODE", &mut self.batching.mode)?;
+        env_override("RETENTION_ENABLED", &mut self.retention.enabled)?;
+        env_override("RETENTION_WINDOW", &mut self.retention.window)?;
+        env_override("RETENTION_MAX_MESSAGES", &mut self.retention.max_messages)?;
+        env_override("RETENTION_MAX_BYTES", &mut self.retention.max_bytes)?;
+        env_override("WARM_UP_MESSAGE_RATE", &mut self.warm_up.message_rate)?;
+        env_override("WARM_UP_STABILITY_DELAY", &mut self.warm_up.stability_delay)?;
+        env_override("UDP_RETRANSMIT_TIMEOUT", &mut self.udp.retransmit_timeout)?;
+        env_override("UDP_WINDOW", &mut self.udp.window)?;
+        env_override("UDP_LOSS", &mut self.udp.loss)?;
+        env_override("REORDER_WINDOW", &mut self.reorder_window)?;
+
+        Ok(())
+    }
+}
+
+impl Default for Configuration {
+    /**
+     * Returns a balanced configuration that tracks causal stability and
+     * buffers messages for at most a millisecond.
+     */
+    fn default() -> Self {
+        Self {
+            thread_stack_size: 1024 * 1024,
+            middleware_thread_stack_size: 8 * 1024 * 1024,
+            stream_sender_timeout: 1_000,
+            track_causal_stability: true,
+            separate_stable_channel: false,
+            auto_stable_ack: false,
+            stable_hold_time: 0,
+            stable_frontier_interval: 0,
+            batching: Batching::default(),
+            retention: Retention::default(),
+            warm_up: WarmUp::default(),
+            topology: Topology::default(),
+            epoch: 0,
+            probe_interval: 0,
+            deserialization_workers: 0,
+            fair_inbound: false,
+            busy_poll: 0,
+            delivery_chunk_size: 0,
+            shutdown_grace_period: 0,
+            missing_dependency_timeout: 0,
+            retransmit_missing: false,
+            wire_encoding: WireEncoding::default(),
+            transport: Transport::default(),
+            udp: Udp::default(),
+            reorder_window: 0,
+            link: None,
+            threads: Threads::default(),
+            group_key: None,
+            signing: None,
+            byzantine_tolerance: false,
+            noise: None,
+            interests: Vec::new(),
+            delivery_receipts: false,
+            delivery_acks: false,
+            dropped_client: DroppedClientPolicy::default(),
+            delivery_batch_size: 0,
+            lazy_setup: false,
+            startup_timeout: 0,
+            shared_connections: false,
+            payload_checksums: false,
+        }
+    }
 }
 
 /**
@@ -53,6 +528,341 @@ pub fn read_configuration_file(
     Ok(configuration)
 }
 
+/**
+ * Reads the middleware configuration from a TOML file and overrides it with
+ * the environment variables that are set, as in `Configuration::apply_env_overrides`.
+ * An error is returned if not successful.
+ *
+ * # Arguments
+ *
+ * `configuration_file_path` - path to the TOML configuration file.
+ */
+pub fn read_configuration_file_with_env(
+    configuration_file_path: String,
+) -> Result<Configuration, Box<dyn Error>> {
+    let mut configuration = read_configuration_file(configuration_file_path)?;
+    configuration.apply_env_overrides()?;
+
+    Ok(configuration)
+}
+
+/**
+ * Returns the value of a configuration environment variable, or None if it isn't set.
+ *
+ * # Arguments
+ *
+ * `name` - Variable name without the prefix.
+ */
+fn env_value(name: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let variable = format!("{}{}", ENV_PREFIX, name);
+
+    match env::var(&variable) {
+        Ok(value) => Ok(Some(value)),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(e) => Err(format!("{} - {}", variable, e).into()),
+    }
+}
+
+/**
+ * Overrides a configuration field with the parsed value of an environment
+ * variable, if it's set.
+ *
+ * # Arguments
+ *
+ * `name` - Variable name without the prefix.
+ *
+ * `field` - Configuration field to be overridden.
+ */
+fn env_override<T>(name: &str, field: &mut T) -> Result<(), Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match env_value(name)? {
+        Some(value) => match value.trim().parse::<T>() {
+            Ok(parsed_value) => {
+                *field = parsed_value;
+                Ok(())
+            }
+            Err(e) => Err(format!("Invalid value of {}{} - {}", ENV_PREFIX, name, e).into()),
+        },
+        None => Ok(()),
+    }
+}
+
+/**
+ * Ids and addresses of the peers in the group.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Topology {
+    ///Local peer's globally unique id.
+    pub local_id: usize,
+
+    ///Every peer in the group, including the local peer.
+    pub peers: Vec<Peer>,
+
+    ///Certificates of the mutual TLS connections between the peers. Requires
+    ///the `tls` feature and the same setting in every peer.
+    #[serde(default)]
+    pub tls: Option<Tls>,
+}
+
+impl Topology {
+    /**
+     * Creates a new topology.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `peers` - Every peer in the group, including the local peer.
+     */
+    pub fn new(local_id: usize, peers: Vec<Peer>) -> Self {
+        Self {
+            local_id,
+            peers,
+            tls: None,
+        }
+    }
+
+    /**
+     * Validates that the peer ids are unique and dense, starting at 0,
+     * and that the local peer's address has the port or the shared memory
+     * segment to listen on. An error is returned if not valid.
+     */
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let mut ids: Vec<usize> = self.peers.iter().map(|peer| peer.id).collect();
+        ids.sort_unstable();
+
+        for (index, id) in ids.iter().enumerate() {
+            if index > 0 && ids[index - 1] == *id {
+                return Err(format!("Peer id {} is repeated", id).into());
+            }
+            if *id != index {
+                return Err(format!("Peer ids must be dense, but {} is missing", index).into());
+            }
+        }
+
+        //Shared memory addresses can't be used without the transport
+        #[cfg(not(feature = "shm"))]
+        if let Some(peer) = self
+            .peers
+            .iter()
+            .find(|peer| get_shm_segment(&peer.address).is_some())
+        {
+            return Err(format!("Address {} requires the shm feature", peer.address).into());
+        }
+
+        match self.peers.iter().find(|peer| peer.id == self.local_id) {
+            Some(local_peer) => match (local_peer.get_port(), get_shm_segment(&local_peer.address))
+            {
+                (None, None) => {
+                    Err(format!("Local address {} has no port", local_peer.address).into())
+                }
+                _ => Ok(()),
+            },
+            None => Err(format!("Local id {} isn't in the peers", self.local_id).into()),
+        }
+    }
+
+    /**
+     * Returns the number of peers in the group.
+     */
+    pub fn get_peer_number(&self) -> usize {
+        self.peers.len()
+    }
+
+    /**
+     * Returns the port of the local peer's address, where the middleware
+     * will be listening for connections, or 0 if it listens on a shared
+     * memory segment. Panics if the topology isn't valid.
+     */
+    pub fn get_local_port(&self) -> usize {
+        match self.get_local_segment() {
+            Some(_) => 0,
+            None => self
+                .peers
+                .iter()
+                .find(|peer| peer.id == self.local_id)
+                .and_then(|peer| peer.get_port())
+                .expect("ERROR: The local peer has no port in the topology"),
+        }
+    }
+
+    /**
+     * Returns the shared memory segment the local peer listens on, if its
+     * address is `shm://<segment>`.
+     */
+    pub fn get_local_segment(&self) -> Option<&str> {
+        self.peers
+            .iter()
+            .find(|peer| peer.id == self.local_id)
+            .and_then(|peer| get_shm_segment(&peer.address))
+    }
+
+    /**
+     * Checks if a peer is allowed to connect from an address, which is
+     * the case if the peer doesn't restrict its source addresses.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Id claimed by the connecting peer.
+     *
+     * `source` - Address the connection was made from.
+     */
+    pub fn is_allowed_source(&self, peer_id: usize, source: &IpAddr) -> bool {
+        match self.peers.iter().find(|peer| peer.id == peer_id) {
+            Some(peer) => match &peer.allowed_sources {
+                Some(allowed_sources) => allowed_sources.contains(source),
+                None => true,
+            },
+            None => false,
+        }
+    }
+
+    /**
+     * Returns the name a peer's TLS certificate must be valid for, which
+     * is `peer-<id>` unless the peer sets its own name.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Peer's globally unique id.
+     */
+    pub fn get_tls_name(&self, peer_id: usize) -> String {
+        self.peers
+            .iter()
+            .find(|peer| peer.id == peer_id)
+            .and_then(|peer| peer.tls_name.clone())
+            .unwrap_or_else(|| format!("peer-{}", peer_id))
+    }
+
+    /**
+     * Returns the addresses of the other peers ordered by their id.
+     */
+    pub fn get_peer_addresses(&self) -> Vec<String> {
+        let mut peers: Vec<&Peer> = self
+            .peers
+            .iter()
+            .filter(|peer| peer.id != self.local_id)
+            .collect();
+        peers.sort_unstable_by_key(|peer| peer.id);
+
+        peers.into_iter().map(|peer| peer.address.clone()).collect()
+    }
+}
+
+/**
+ * Id and address of a peer in the group.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Peer {
+    ///Peer's globally unique id.
+    pub id: usize,
+
+    ///Address and port where the peer is listening for connections, or
+    ///`shm://<segment>` if it listens on a shared memory segment.
+    pub address: String,
+
+    ///Batching of the messages sent to the peer, overriding the global batching.
+    #[serde(default)]
+    pub batching: Option<Batching>,
+
+    ///Reordering window of the messages read from the peer, overriding the global window.
+    #[serde(default)]
+    pub reorder_window: Option<usize>,
+
+    ///Simulated latency, jitter and loss of the messages sent to the peer,
+    ///overriding the global link.
+    #[serde(default)]
+    pub link: Option<LinkSimulation>,
+
+    ///IP addresses the peer is allowed to connect from. Connections claiming
+    ///the peer's id from other addresses are refused. Any address is allowed
+    ///if not set.
+    #[serde(default)]
+    pub allowed_sources: Option<Vec<IpAddr>>,
+
+    ///DNS name the peer's TLS certificate must be valid for, which binds the
+    ///certificate to the peer's id. Defaults to `peer-<id>`.
+    #[serde(default)]
+    pub tls_name: Option<String>,
+}
+
+impl Peer {
+    /**
+     * Creates a new peer.
+     *
+     * # Arguments
+     *
+     * `id` - Peer's globally unique id.
+     *
+     * `address` - Address and port where the peer is listening for connections.
+     */
+    pub fn new(id: usize, address: String) -> Self {
+        Self {
+            id,
+            address,
+            batching: None,
+            reorder_window: None,
+            link: None,
+            allowed_sources: None,
+            tls_name: None,
+        }
+    }
+
+    /**
+     * Returns the port of the peer's address.
+     */
+    pub fn get_port(&self) -> Option<usize> {
+        self.address
+            .rsplit(':')
+            .next()
+            .and_then(|port| port.parse::<usize>().ok())
+    }
+}
+
+/**
+ * Keys of the per-message ed25519 signatures.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Signing {
+    ///Hexadecimal private key of the local peer.
+    pub private_key: String,
+
+    ///Hexadecimal public keys of every peer, indexed by the peer id.
+    pub public_keys: Vec<String>,
+}
+
+/**
+ * Paths of the PEM files used by the mutual TLS connections.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tls {
+    ///Certificate of the authority that signed every peer's certificate.
+    pub ca_certificate: String,
+
+    ///Certificate chain of the local peer, valid for the local peer's TLS name.
+    pub certificate: String,
+
+    ///Private key of the local peer's certificate.
+    pub private_key: String,
+}
+
+/**
+ * Keys of the Noise_XX handshake.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Noise {
+    ///Hexadecimal x25519 private key of the local peer, generated for each connection if not set.
+    #[serde(default)]
+    pub private_key: Option<String>,
+
+    ///Hexadecimal x25519 public keys of every peer, indexed by the peer id.
+    ///The peers' static keys aren't verified if not set.
+    #[serde(default)]
+    pub public_keys: Vec<String>,
+}
+
 /**
  * Configuration parameters for the Sender threads message batching.
  */
@@ -69,6 +879,10 @@ pub struct Batching {
 
     ///Upper value of the ACK timeout in microseconds.
     pub upper_timeout: u64,
+
+    ///Batching mode, static if not set.
+    #[serde(default)]
+    pub mode: BatchingMode,
 }
 
 impl Batching {
@@ -85,4 +899,364 @@ impl Batching {
     pub fn get_upper_timeout(&self) -> Duration {
         Duration::from_micros(self.upper_timeout)
     }
+
+    /**
+     * Returns the number of messages to be buffered before calling Write.
+     * In the adaptive mode, this is the number of messages expected to arrive
+     * during the lower timeout at the observed arrival rate, bounded by the
+     * configured message number. Without observations, messages aren't buffered.
+     *
+     * # Arguments
+     *
+     * `average_interval` - Average interval in microseconds between arriving messages.
+     */
+    pub fn get_message_threshold(&self, average_interval: Option<f64>) -> usize {
+        match self.mode {
+            BatchingMode::Static => self.message_number,
+            BatchingMode::Adaptive => match average_interval {
+                Some(interval) if interval > 0.0 => {
+                    let expected_messages = (self.lower_timeout as f64 / interval) as usize;
+                    expected_messages.max(1).min(self.message_number)
+                }
+                Some(_) => self.message_number,
+                None => 1,
+            },
+        }
+    }
+}
+
+impl Default for Batching {
+    fn default() -> Self {
+        Self {
+            size: 64_000,
+            message_number: 64,
+            lower_timeout: 1_000,
+            upper_timeout: 100_000,
+            mode: BatchingMode::Static,
+        }
+    }
+}
+
+/**
+ * Configuration parameters for the retention of the delivered messages.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Retention {
+    ///Retention flag.
+    #[serde(default)]
+    pub enabled: bool,
+
+    ///Minimum time in microseconds a delivered message is kept. Messages are
+    ///also kept until they are stable.
+    #[serde(default)]
+    pub window: u64,
+
+    ///Maximum number of retained messages, even if they aren't stable.
+    ///The number of messages isn't bounded if set to 0.
+    #[serde(default)]
+    pub max_messages: usize,
+
+    ///Maximum number of retained payload bytes, even if the messages aren't stable.
+    ///The number of bytes isn't bounded if set to 0.
+    #[serde(default)]
+    pub max_bytes: u64,
+}
+
+impl Retention {
+    /**
+     * Returns the window wrapped in a Duration.
+     */
+    pub fn get_window(&self) -> Duration {
+        Duration::from_micros(self.window)
+    }
+}
+
+/**
+ * Configuration parameters for the expected load, used to pre-allocate the
+ * buffers sized by the number of messages in flight. The group size is taken
+ * from the topology.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WarmUp {
+    ///Expected number of messages broadcast per second by the whole group.
+    ///The buffers aren't pre-allocated if set to 0.
+    #[serde(default)]
+    pub message_rate: u64,
+
+    ///Expected time in microseconds from the broadcast of a message until
+    ///it's stable and deleted by the middleware.
+    #[serde(default)]
+    pub stability_delay: u64,
+}
+
+impl WarmUp {
+    /**
+     * Returns the expected number of messages kept by the middleware at once,
+     * which is the number of messages broadcast until they are stable.
+     */
+    pub fn expected_messages(&self) -> usize {
+        let messages = self.message_rate.saturating_mul(self.stability_delay) / 1_000_000;
+
+        messages.min(MAX_WARM_UP_MESSAGES) as usize
+    }
+}
+
+/**
+ * Priorities and CPU affinities of each kind of middleware thread.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Threads {
+    ///Settings of the Middleware thread
+    #[serde(default)]
+    pub middleware: ThreadSettings,
+    ///Settings of the Reader and Deserializer threads
+    #[serde(default)]
+    pub reader: ThreadSettings,
+    ///Settings of the Sender threads
+    #[serde(default)]
+    pub sender: ThreadSettings,
+}
+
+/**
+ * Scheduling settings applied to a thread when it starts.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ThreadSettings {
+    ///Nice value of the thread, from -20 (highest priority) to 19 (lowest).
+    ///Negative values require the CAP_SYS_NICE capability.
+    #[serde(default)]
+    pub priority: Option<i32>,
+    ///Ids of the CPU cores the thread can run on, any core if empty
+    #[serde(default)]
+    pub cores: Vec<usize>,
+}
+
+impl ThreadSettings {
+    /**
+     * Checks if the settings change the thread's default scheduling.
+     */
+    pub fn is_set(&self) -> bool {
+        self.priority.is_some() || !self.cores.is_empty()
+    }
+}
+
+/**
+ * Modes of the Sender threads message batching.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BatchingMode {
+    ///Fixed thresholds set in the configuration file
+    #[default]
+    Static,
+    ///Message threshold adjusted to the observed arrival rate
+    Adaptive,
+}
+
+impl FromStr for BatchingMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_lowercase().as_str() {
+            "static" => Ok(BatchingMode::Static),
+            "adaptive" => Ok(BatchingMode::Adaptive),
+            _ => Err(format!("unknown batching mode {}", mode)),
+        }
+    }
+}
+
+/**
+ * Encodings of the integers sent over the TCP streams.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WireEncoding {
+    ///Integers encoded in a fixed number of bytes
+    Fixint,
+    ///Integers encoded in as few bytes as their values need
+    #[default]
+    Varint,
+}
+
+impl FromStr for WireEncoding {
+    type Err = String;
+
+    fn from_str(encoding: &str) -> Result<Self, Self::Err> {
+        match encoding.to_lowercase().as_str() {
+            "fixint" => Ok(WireEncoding::Fixint),
+            "varint" => Ok(WireEncoding::Varint),
+            _ => Err(format!("unknown wire encoding {}", encoding)),
+        }
+    }
+}
+
+/**
+ * Transports of the connections between the peers.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Transport {
+    ///TCP streams
+    #[default]
+    Tcp,
+    ///Experimental UDP datagrams with acknowledgements and retransmissions
+    Udp,
+}
+
+impl FromStr for Transport {
+    type Err = String;
+
+    fn from_str(transport: &str) -> Result<Self, Self::Err> {
+        match transport.to_lowercase().as_str() {
+            "tcp" => Ok(Transport::Tcp),
+            "udp" => Ok(Transport::Udp),
+            _ => Err(format!("unknown transport {}", transport)),
+        }
+    }
+}
+
+/**
+ * Behaviours of the middleware once the Client dropped its receiver.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DroppedClientPolicy {
+    ///Ends the middleware as if the Client ended it
+    #[default]
+    Shutdown,
+    ///Keeps the middleware running for the other peers, which can still catch
+    ///up from the retention log, and removes the stable messages the Client
+    ///can no longer ack
+    Retain,
+    ///Keeps the middleware running and drops the messages sent to the Client
+    Drop,
+}
+
+impl FromStr for DroppedClientPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy.to_lowercase().as_str() {
+            "shutdown" => Ok(DroppedClientPolicy::Shutdown),
+            "retain" => Ok(DroppedClientPolicy::Retain),
+            "drop" => Ok(DroppedClientPolicy::Drop),
+            _ => Err(format!("unknown dropped client policy {}", policy)),
+        }
+    }
+}
+
+/**
+ * Inclusive range of entity keys in the interest set of a peer.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyRange {
+    ///First key of the range
+    pub start: u64,
+    ///Last key of the range
+    pub end: u64,
+}
+
+impl KeyRange {
+    /**
+     * Returns true if a key is in the range.
+     *
+     * # Arguments
+     *
+     * `key` - Entity key of a message
+     */
+    pub fn contains(&self, key: u64) -> bool {
+        self.start <= key && key <= self.end
+    }
+}
+
+/**
+ * Returns true if a peer delivers the messages of a key, which is the case for
+ * every key if the peer has no interest set.
+ *
+ * # Arguments
+ *
+ * `interests` - Interest set of the peer
+ *
+ * `key` - Entity key of a message
+ */
+pub fn is_interested(interests: &[KeyRange], key: u64) -> bool {
+    interests.is_empty() || interests.iter().any(|range| range.contains(key))
+}
+
+/**
+ * Configuration parameters of the experimental UDP transport.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Udp {
+    ///Time in microseconds after which an unacknowledged datagram is sent again.
+    ///Defaults to 20 milliseconds if set to 0.
+    #[serde(default)]
+    pub retransmit_timeout: u64,
+
+    ///Maximum number of unacknowledged datagrams of a connection, after which
+    ///the Sender thread waits. Defaults to 256 if set to 0.
+    #[serde(default)]
+    pub window: usize,
+
+    ///Probability of dropping a received datagram on purpose, between 0 and 1,
+    ///to simulate a lossy network.
+    #[serde(default)]
+    pub loss: f64,
+}
+
+/**
+ * Latency, jitter and loss added to the messages sent through a link, which
+ * are held by the link before being handed to the link's Sender thread.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LinkSimulation {
+    ///Time in microseconds each message is held before being sent.
+    #[serde(default)]
+    pub latency: u64,
+
+    ///Maximum time in microseconds randomly added to or removed from the
+    ///latency of each message. Messages are still sent in order.
+    #[serde(default)]
+    pub jitter: u64,
+
+    ///Probability of dropping a message on purpose, between 0 and 1. Dropped
+    ///messages are only recovered by retransmitting the missing messages.
+    #[serde(default)]
+    pub loss: f64,
+}
+
+impl LinkSimulation {
+    /**
+     * Returns the latency wrapped in a Duration.
+     */
+    pub fn get_latency(&self) -> Duration {
+        Duration::from_micros(self.latency)
+    }
+
+    /**
+     * Returns the jitter wrapped in a Duration.
+     */
+    pub fn get_jitter(&self) -> Duration {
+        Duration::from_micros(self.jitter)
+    }
+}
+
+impl Udp {
+    /**
+     * Returns the retransmission timeout wrapped in a Duration.
+     */
+    pub fn get_retransmit_timeout(&self) -> Duration {
+        if self.retransmit_timeout == 0 {
+            Duration::from_millis(20)
+        } else {
+            Duration::from_micros(self.retransmit_timeout)
+        }
+    }
+
+    /**
+     * Returns the maximum number of unacknowledged datagrams of a connection.
+     */
+    pub fn get_window(&self) -> usize {
+        if self.window == 0 {
+            256
+        } else {
+            self.window
+        }
+    }
 }
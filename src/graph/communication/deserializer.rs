@@ -0,0 +1,230 @@
+use super::reader::{self, ReceiveContext};
+use crate::channel::unbounded;
+use crate::channel::{Receiver, Sender};
+use crate::clock::dot::Dot;
+use crate::configuration::middleware_configuration::{Configuration, WireEncoding};
+use crate::graph::structs::message_type::ClientPeerMiddleware;
+use crate::platform::threads::apply_thread_settings;
+use crate::transport::encoding;
+use crate::transport::reordering::ReorderBuffer;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+#[cfg(feature = "signing")]
+use crate::security::message_signing::MessageSigner;
+#[cfg(feature = "encryption")]
+use crate::security::payload_encryption::PayloadCipher;
+
+/**
+ * Serialized message read from a peer's stream, handed off by the
+ * Reader thread to a Deserializer thread.
+ */
+pub struct RawMessage {
+    ///Id of the peer the message was read from
+    pub peer_id: usize,
+    ///Serialized message
+    pub msg: Vec<u8>,
+    ///Signature of the serialized message
+    pub signature: Option<Vec<u8>>,
+    ///Encoding of the serialized message
+    pub encoding: WireEncoding,
+    ///Set if the peer closed the connection, in which case there's no message,
+    ///with the number of Deserializer threads that didn't handle the closing yet
+    pub closed: Option<Arc<AtomicUsize>>,
+}
+
+/**
+ * Starts the Deserializer threads set in the configuration and returns
+ * the channels to each of them. No threads are started if the configuration
+ * sets 0 workers, in which case the Reader threads deserialize the messages.
+ *
+ * # Arguments
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `middleware_channel` - Channel to the Middleware.
+ *
+ * `configuration` - Middleware's configuration file.
+ */
+pub fn start_workers(
+    local_id: usize,
+    middleware_channel: &Sender<ClientPeerMiddleware>,
+    configuration: &Arc<Configuration>,
+) -> Vec<Sender<RawMessage>> {
+    let mut workers = Vec::with_capacity(configuration.deserialization_workers);
+
+    for worker_index in 0..configuration.deserialization_workers {
+        let (worker_send_channel, worker_receive_channel) = unbounded::<RawMessage>();
+
+        let middleware_channel_temp = middleware_channel.clone();
+        let configuration_temp = Arc::clone(configuration);
+
+        let thread_name = format!("deserializer_{}_{}", local_id, worker_index);
+        let builder = thread::Builder::new()
+            .name(thread_name)
+            .stack_size(configuration.thread_stack_size);
+
+        builder
+            .spawn(move || {
+                start(
+                    worker_receive_channel,
+                    middleware_channel_temp,
+                    local_id,
+                    configuration_temp,
+                );
+            })
+            .unwrap();
+
+        workers.push(worker_send_channel);
+    }
+
+    workers
+}
+
+/**
+ * Returns the channel to the Deserializer thread of a message's sender, read
+ * from the dot at the start of the serialized message. Every message of a
+ * sender goes to the same thread, whichever peer sent or relayed it, so the
+ * messages of each sender are verified and reordered by a single thread while
+ * the messages of different senders are handled in parallel. A message whose
+ * dot can't be read goes to the thread of the peer it was read from, which
+ * reports it.
+ *
+ * # Arguments
+ *
+ * `workers` - Channels to the Deserializer threads, which can't be empty.
+ *
+ * `msg` - Serialized message.
+ *
+ * `encoding` - Encoding of the serialized message.
+ *
+ * `peer_id` - Id of the peer the message was read from.
+ */
+pub fn get_worker<'a>(
+    workers: &'a [Sender<RawMessage>],
+    msg: &[u8],
+    encoding: WireEncoding,
+    peer_id: usize,
+) -> &'a Sender<RawMessage> {
+    let sender_id = match encoding::deserialize_from::<_, Dot>(encoding, msg) {
+        Ok(dot) => dot.id,
+        Err(_) => peer_id,
+    };
+
+    &workers[sender_id % workers.len()]
+}
+
+/**
+ * Notifies every Deserializer thread that a peer closed its connection. Since
+ * the peer's messages are spread by sender over the threads, the Middleware is
+ * only notified by the last thread to handle the closing, once every thread
+ * handed it the peer's messages.
+ *
+ * # Arguments
+ *
+ * `workers` - Channels to the Deserializer threads.
+ *
+ * `peer_id` - Peer's globally unique id.
+ *
+ * `encoding` - Encoding of the frames and messages sent by the peer.
+ */
+pub fn notify_closed(
+    workers: &[Sender<RawMessage>],
+    peer_id: usize,
+    encoding: WireEncoding,
+) -> bool {
+    let remaining = Arc::new(AtomicUsize::new(workers.len()));
+
+    workers.iter().all(|worker| {
+        worker
+            .send(RawMessage {
+                peer_id,
+                msg: Vec::new(),
+                signature: None,
+                encoding,
+                closed: Some(Arc::clone(&remaining)),
+            })
+            .is_ok()
+    })
+}
+
+/**
+ * Starts a Deserializer thread that deserializes, verifies and decrypts the
+ * messages handed off by the Reader threads and sends them to the Middleware.
+ * The thread ends when every Reader thread using it has ended.
+ *
+ * # Arguments
+ *
+ * `receive_channel` - Channel from the Reader threads.
+ *
+ * `middleware_channel` - Channel to the Middleware.
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `configuration` - Middleware's configuration file.
+ */
+pub fn start(
+    receive_channel: Receiver<RawMessage>,
+    middleware_channel: Sender<ClientPeerMiddleware>,
+    local_id: usize,
+    configuration: Arc<Configuration>,
+) {
+    apply_thread_settings(&configuration.threads.reader);
+
+    #[cfg(feature = "signing")]
+    let signer = MessageSigner::from_configuration(&configuration);
+
+    #[cfg(feature = "encryption")]
+    let cipher = PayloadCipher::from_configuration(&configuration);
+
+    //Messages held until the previous messages of their senders were read
+    let mut reorder = ReorderBuffer::from_configuration(&configuration);
+
+    let receive_context = ReceiveContext {
+        middleware_channel: &middleware_channel,
+        local_id,
+        #[cfg(feature = "signing")]
+        signer: &signer,
+        #[cfg(feature = "encryption")]
+        cipher: &cipher,
+    };
+
+    for raw_message in receive_channel.iter() {
+        //Notifying the closed connection after the peer's last messages
+        if let Some(remaining) = &raw_message.closed {
+            if let Some(reorder) = &mut reorder {
+                reorder.release_peer(raw_message.peer_id);
+                reader::send_released(reorder, &middleware_channel);
+            }
+
+            //Waiting for the other threads to hand over their messages of the peer
+            if remaining.fetch_sub(1, Ordering::SeqCst) > 1 {
+                continue;
+            }
+
+            match middleware_channel.send(ClientPeerMiddleware::Closed {
+                peer_id: raw_message.peer_id,
+            }) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!(
+                        "ERROR: Failed to send the closed connection to the middleware\n\t- {}",
+                        e
+                    );
+                }
+            }
+
+            continue;
+        }
+
+        reader::handle_received_peer_msg(
+            raw_message.msg,
+            raw_message.signature,
+            raw_message.peer_id,
+            raw_message.encoding,
+            &receive_context,
+            &mut reorder,
+        );
+    }
+}
@@ -1,35 +1,90 @@
 use super::msg_types::*;
+use crate::configuration::middleware_configuration::{KeyRange, WireEncoding};
+use crate::transport::{encoding, protocol};
 use bincode::{deserialize_from, serialize_into};
-use std::net::TcpStream;
+use std::io::{Read, Write};
+
+#[cfg(feature = "signing")]
+use crate::security::message_signing::MessageSigner;
 
 /**
- * Sends a handshake message to a peer.
+ * Sends a handshake message to a peer, after the range of the protocol versions
+ * spoken by the local peer. The handshake is always encoded with fixed size
+ * integers, since the peer doesn't know the local encoding yet.
  *
  * # Arguments
  *
- * `stream` - TCP stream to write the handshake message into.
+ * `stream` - Stream to write the handshake message into.
  *
  * `local_id` - Local peer's globally unique id.
+ *
+ * `encoding` - Encoding of the frames and messages sent by the local peer.
+ *
+ * `interests` - Ranges of the keys whose messages the local peer delivers.
  */
-pub fn send_handshake(mut stream: &TcpStream, local_id: usize) {
+pub fn send_handshake<W: Write>(
+    mut stream: W,
+    local_id: usize,
+    encoding: WireEncoding,
+    interests: &[KeyRange],
+) {
+    protocol::send_version(&mut stream)
+        .expect("ERROR: Couldn't write the protocol version to peer socket");
+
     serialize_into::<_, StreamMessages>(
         &mut stream,
-        &StreamMessages::Handshake { index: local_id },
+        &StreamMessages::Handshake {
+            index: local_id,
+            encoding,
+            interests: interests.to_vec(),
+        },
     )
     .expect("ERROR: Couldn't write handshake message to peer socket");
+
+    stream
+        .flush()
+        .expect("ERROR: Couldn't write handshake message to peer socket");
 }
 
 /**
- * Finishes the handshake process.
+ * Finishes the handshake process, returning the peer's id and the protocol
+ * version used with it.
  *
  * # Arguments
  *
- * `stream` - TCP stream to read the handshake message from.
+ * `stream` - Stream to read the handshake message from.
  */
-pub fn finish_protocol(stream: &TcpStream) -> usize {
-    match deserialize_from::<_, StreamMessages>(stream) {
+pub fn finish_protocol<R: Read>(stream: R) -> (usize, u16) {
+    match read_versioned_handshake(stream) {
+        Ok((version, StreamMessages::Handshake { index, .. })) => (index, version),
+        Ok((_, m)) => {
+            panic!("ERROR: Handshake received unexpected type - {:?}", m);
+        }
+        Err(e) => {
+            panic!(
+                "ERROR: Occurred when handling the receiver handshake message - {}",
+                e
+            );
+        }
+    }
+}
+
+/**
+ * Reads a peer's handshake message, returning its id, the encoding of the
+ * frames and messages it sends and the ranges of the keys it delivers.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to read the handshake message from.
+ */
+pub fn receive_handshake<R: Read>(stream: R) -> (usize, WireEncoding, Vec<KeyRange>) {
+    match read_handshake(stream) {
         Ok(decoded_handshake) => match decoded_handshake {
-            StreamMessages::Handshake { index } => index,
+            StreamMessages::Handshake {
+                index,
+                encoding,
+                interests,
+            } => (index, encoding, interests),
             m => {
                 panic!("ERROR: Handshake received unexpected type - {:?}", m);
             }
@@ -42,3 +97,71 @@ pub fn finish_protocol(stream: &TcpStream) -> usize {
         }
     }
 }
+
+/**
+ * Reads a peer's protocol version preamble and its handshake message, returning
+ * the reason the connection can't be used if the peers don't speak a common
+ * protocol version or the handshake couldn't be read.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to read the handshake message from.
+ */
+pub fn read_handshake<R: Read>(stream: R) -> Result<StreamMessages, String> {
+    read_versioned_handshake(stream).map(|(_, handshake)| handshake)
+}
+
+/**
+ * Reads a peer's protocol version preamble and its handshake message, returning
+ * the protocol version used with the peer and the handshake message.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to read the handshake message from.
+ */
+pub fn read_versioned_handshake<R: Read>(mut stream: R) -> Result<(u16, StreamMessages), String> {
+    let version = protocol::receive_version(&mut stream)?;
+
+    deserialize_from::<_, StreamMessages>(stream)
+        .map(|handshake| (version, handshake))
+        .map_err(|e| e.to_string())
+}
+
+/**
+ * Returns the bytes of a delivered version vector covered by its signature.
+ * They're encoded with fixed size integers, independently of the wire encoding.
+ *
+ * # Arguments
+ *
+ * `vector` - Delivered version vector.
+ *
+ * `reply` - If the vector answers the one received from the peer.
+ */
+pub fn vector_bytes(vector: &[usize], reply: bool) -> Vec<u8> {
+    encoding::serialize(WireEncoding::Fixint, &(vector, reply))
+        .expect("ERROR: Couldn't serialize the delivered version vector")
+}
+
+/**
+ * Signs a delivered version vector with the local private key, if the
+ * messages are signed. The peer verifies the signature, but relaying the
+ * missed messages isn't supported in signed groups, so none are relayed.
+ *
+ * # Arguments
+ *
+ * `vector` - Delivered version vector.
+ *
+ * `reply` - If the vector answers the one received from the peer.
+ *
+ * `signer` - Signer of the local peer's messages.
+ */
+#[cfg(feature = "signing")]
+pub fn sign_vector(
+    vector: &[usize],
+    reply: bool,
+    signer: &Option<MessageSigner>,
+) -> Option<Vec<u8>> {
+    signer
+        .as_ref()
+        .map(|signer| signer.sign(&vector_bytes(vector, reply)))
+}
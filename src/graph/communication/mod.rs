@@ -6,6 +6,14 @@ pub mod acceptor;
  * Connects to another peer's acceptor thread.
  */
 pub mod connector;
+/**
+ * Deserializes messages read by the Reader threads.
+ */
+pub mod deserializer;
+/**
+ * Wire encoding of the frames and messages sent to other peers.
+ */
+pub use crate::transport::encoding;
 /**
  * Initial handshake process between peers.
  */
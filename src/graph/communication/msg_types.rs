@@ -1,12 +1,53 @@
+use crate::configuration::middleware_configuration::{KeyRange, WireEncoding};
+use crate::transport::batching::{BatchFrame, BatchedMessage};
+
 /**
  * Enum of the messages sent/received in the streams between peers.
  * */
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum StreamMessages {
-    ///Handshake message
-    Handshake { index: usize },
-    ///Message payload
-    Message { msg: Vec<u8> },
+    ///Handshake message with the encoding of the sender's frames and messages
+    ///and the ranges of the keys whose messages it delivers
+    Handshake {
+        index: usize,
+        encoding: WireEncoding,
+        interests: Vec<KeyRange>,
+    },
+    ///Message payload stamped with the sender's epoch and optionally signed
+    Message {
+        epoch: u64,
+        msg: Vec<u8>,
+        signature: Option<Vec<u8>>,
+    },
+    ///Batch of message payloads stamped with the sender's epoch
+    Batch {
+        epoch: u64,
+        messages: Vec<BatchedMessage>,
+    },
+    ///Probe for measuring the round trip time
+    Ping { timestamp: u64 },
+    ///Response to a probe with the probe's timestamp
+    Pong { timestamp: u64 },
+    ///Receipt of the messages of the receiving peer delivered up to a counter
+    Receipt { counter: usize },
+    ///Request for the retained messages above the sender's delivered version vector
+    CatchUp { vector: Vec<usize> },
+    ///End of the relayed messages with the donor's delivered version vector,
+    ///or `None` if the donor no longer retains every requested message
+    CaughtUp { cut: Option<Vec<usize>> },
     ///Terminating the connection
     Close,
+    ///Sender's delivered version vector, sent once the connection is set up and
+    ///answered with the receiver's, both optionally signed
+    Vector {
+        vector: Vec<usize>,
+        reply: bool,
+        signature: Option<Vec<u8>>,
+    },
+}
+
+impl BatchFrame for StreamMessages {
+    fn batch(_sender_id: usize, epoch: u64, messages: Vec<BatchedMessage>) -> Self {
+        StreamMessages::Batch { epoch, messages }
+    }
 }
@@ -1,45 +1,285 @@
+use super::acceptor::AcceptorContext;
+use super::deserializer::{self, RawMessage};
 use super::msg_types::StreamMessages;
+use crate::broadcast::setup::SetupBarrier;
+use crate::channel::Sender;
+use crate::configuration::middleware_configuration::WireEncoding;
+use crate::graph::middleware::node::Node;
 use crate::graph::structs::message::Message;
 use crate::graph::structs::message_type::ClientPeerMiddleware;
-use bincode::{deserialize, deserialize_from};
-use crossbeam::Sender;
-use std::net::TcpStream;
-use std::sync::{Arc, Barrier};
+use crate::metrics::metered_stream::MeteredStream;
+use crate::metrics::middleware_metrics::probe_timestamp;
+use crate::platform::threads::apply_thread_settings;
+use crate::security::peer_stream::PeerStream;
+use crate::transport::encoding;
+use crate::transport::reordering::ReorderBuffer;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
 use std::usize;
 
+#[cfg(feature = "tls")]
+use crate::security::tls;
+
+#[cfg(feature = "signing")]
+use super::handshake;
+#[cfg(feature = "signing")]
+use crate::security::message_signing::MessageSigner;
+#[cfg(feature = "noise")]
+use crate::security::noise::NoiseSession;
+#[cfg(feature = "encryption")]
+use crate::security::payload_encryption::{decrypt_message, PayloadCipher};
+
 /**
  * Starts a Reader thread that receives messages from a stream
  * and sends them to the middleware.
  *
  * # Arguments
  *
- * `stream` - TCP stream between the peers.
- *
- * `middleware_channel` - Channel from the the Reader to the Middleware.
+ * `stream` - Stream between the peers.
  *
- * `local_id` - Local peer's globally unique id.
+ * `context` - State shared with the Acceptor thread.
  *
  * `peer_id` - Other peer's globally unique id.
  *
- * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ * `encoding` - Encoding of the frames and messages sent by the peer.
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer,
+ * or `None` for a connection that replaced a closed one after the setup.
  */
 pub fn start(
-    stream: TcpStream,
-    middleware_channel: Sender<ClientPeerMiddleware>,
-    local_id: usize,
+    #[cfg_attr(not(any(feature = "noise", feature = "tls")), allow(unused_mut))]
+    mut stream: PeerStream,
+    context: AcceptorContext,
     peer_id: usize,
-    setup_end_barrier: Arc<Barrier>,
+    encoding: WireEncoding,
+    setup_end_barrier: Option<Arc<SetupBarrier>>,
 ) {
-    setup_end_barrier.wait();
+    let AcceptorContext {
+        local_id,
+        middleware_channel,
+        configuration,
+        metrics,
+        deserializers,
+        ..
+    } = context;
+
+    apply_thread_settings(&configuration.threads.reader);
+
+    #[cfg(feature = "signing")]
+    let signer = MessageSigner::from_configuration(&configuration);
+
+    #[cfg(feature = "encryption")]
+    let cipher = PayloadCipher::from_configuration(&configuration);
+
+    //Establishing the session keys of the connection
+    #[cfg(feature = "noise")]
+    let mut noise_session = NoiseSession::respond(&mut stream, peer_id, &configuration)
+        .expect("ERROR: Failed the Noise handshake with the peer");
+
+    //Securing the connection with mutual TLS, if set
+    #[cfg(feature = "tls")]
+    {
+        stream = tls::accept(stream, peer_id, &configuration)
+            .expect("ERROR: Failed the TLS handshake with the peer");
+    }
+
+    //Stopping if the startup timed out before the setup ended
+    if let Some(setup_end_barrier) = &setup_end_barrier {
+        if !setup_end_barrier.wait() {
+            return;
+        }
+    }
+
+    //Counting the bytes read from the peer
+    let mut stream = MeteredStream::new(stream, peer_id, Arc::clone(&metrics));
+
+    //Messages held until the previous messages of their senders were read
+    let mut reorder = ReorderBuffer::from_configuration(&configuration);
+
+    let receive_context = ReceiveContext {
+        middleware_channel: &middleware_channel,
+        local_id,
+        #[cfg(feature = "signing")]
+        signer: &signer,
+        #[cfg(feature = "encryption")]
+        cipher: &cipher,
+    };
+
+    //Messages unpacked from the last batch frame that weren't handled yet
+    let mut batched: VecDeque<StreamMessages> = VecDeque::new();
 
     loop {
-        match deserialize_from::<_, StreamMessages>(&stream) {
+        let frame = match batched.pop_front() {
+            Some(frame) => Ok(frame),
+            None => encoding::deserialize_from::<_, StreamMessages>(encoding, &mut stream),
+        };
+
+        match frame {
             Ok(decoded_msg_type) => match decoded_msg_type {
-                StreamMessages::Message { msg } => {
-                    handle_received_peer_msg(msg, &middleware_channel);
+                StreamMessages::Message {
+                    epoch,
+                    msg,
+                    signature,
+                } => {
+                    //Decrypting every message in order, since the nonces are sequential
+                    #[cfg(feature = "noise")]
+                    let msg = match &mut noise_session {
+                        Some(session) => match session.decrypt(&msg) {
+                            Ok(msg) => msg,
+                            Err(e) => {
+                                println!(
+                                    "ERROR: {} failed to decrypt a message from {}\n\t- {}",
+                                    local_id, peer_id, e
+                                );
+                                break;
+                            }
+                        },
+                        None => msg,
+                    };
+
+                    if epoch < configuration.epoch {
+                        //Discarding frames from a stale epoch
+                        println!(
+                            "WARN: {} discarded a message from {} with stale epoch {}",
+                            local_id, peer_id, epoch
+                        );
+                    } else if deserializers.is_empty() {
+                        handle_received_peer_msg(
+                            msg,
+                            signature,
+                            peer_id,
+                            encoding,
+                            &receive_context,
+                            &mut reorder,
+                        );
+                    } else {
+                        //Handing off the message without copying it to the
+                        //Deserializer thread of its sender
+                        let deserializer =
+                            deserializer::get_worker(&deserializers, &msg, encoding, peer_id);
+
+                        let raw_message = RawMessage {
+                            peer_id,
+                            msg,
+                            signature,
+                            encoding,
+                            closed: None,
+                        };
+
+                        match deserializer.send(raw_message) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                println!(
+                                    "ERROR: Failed to send message to the deserializer\n\t- {}",
+                                    e
+                                );
+                            }
+                        }
+                    }
+                }
+
+                StreamMessages::Batch { epoch, messages } => {
+                    //Unpacking the batch so each message is handled as if it was sent in its own frame
+                    batched.extend(messages.into_iter().map(|message| StreamMessages::Message {
+                        epoch,
+                        msg: message.msg,
+                        signature: message.signature,
+                    }));
+                }
+                StreamMessages::Ping { timestamp } => {
+                    //Answering the probe through the Sender thread to this peer
+                    match middleware_channel.send(ClientPeerMiddleware::Ping { peer_id, timestamp })
+                    {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!("ERROR: Failed to send probe to the middleware\n\t- {}", e);
+                        }
+                    }
+                }
+                StreamMessages::Pong { timestamp } => {
+                    let rtt = probe_timestamp().saturating_sub(timestamp);
+                    metrics.update_rtt(peer_id, Duration::from_micros(rtt));
                 }
+                StreamMessages::Receipt { counter } => {
+                    metrics.update_receipt(peer_id, counter);
+                }
+                StreamMessages::CatchUp { vector } => {
+                    //Relaying the retained messages the peer is missing through the Middleware
+                    let catch_up = ClientPeerMiddleware::CatchUpRequest { peer_id, vector };
+
+                    match middleware_channel.send(catch_up) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!(
+                                "ERROR: Failed to send catch up request to the middleware\n\t- {}",
+                                e
+                            );
+                        }
+                    }
+                }
+                StreamMessages::CaughtUp { cut } => {
+                    match middleware_channel.send(ClientPeerMiddleware::CaughtUp { peer_id, cut }) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!(
+                                "ERROR: Failed to send catch up end to the middleware\n\t- {}",
+                                e
+                            );
+                        }
+                    }
+                }
+                StreamMessages::Vector {
+                    vector,
+                    reply,
+                    signature,
+                } => {
+                    //Discarding vectors that weren't signed by the peer
+                    #[cfg(feature = "signing")]
+                    match &signer {
+                        Some(signer)
+                            if !signer.verify(
+                                peer_id,
+                                &handshake::vector_bytes(&vector, reply),
+                                &signature,
+                            ) =>
+                        {
+                            println!(
+                                "ERROR: {} discarded a delivered version vector from {} with an invalid signature",
+                                local_id, peer_id
+                            );
+                            continue;
+                        }
+                        _ => {}
+                    }
+
+                    #[cfg(not(feature = "signing"))]
+                    let _ = signature;
 
+                    let vector = ClientPeerMiddleware::Vector {
+                        peer_id,
+                        vector,
+                        reply,
+                    };
+
+                    match middleware_channel.send(vector) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!(
+                                "ERROR: Failed to send delivered version vector to the middleware\n\t- {}",
+                                e
+                            );
+                        }
+                    }
+                }
                 StreamMessages::Close => {
+                    //Releasing the held messages, since the missing ones won't be read anymore
+                    if let Some(reorder) = &mut reorder {
+                        reorder.release_peer(peer_id);
+                        send_released(reorder, &middleware_channel);
+                    }
+
+                    notify_closed(peer_id, &middleware_channel, &deserializers, encoding);
                     break;
                 }
                 m => {
@@ -56,17 +296,155 @@ pub fn start(
             }
         }
     }
+
+    //Releasing the messages still held once the connection ended
+    if let Some(reorder) = &mut reorder {
+        reorder.release_peer(peer_id);
+        send_released(reorder, &middleware_channel);
+    }
+}
+
+/**
+ * Notifies the Middleware that a peer closed its connection. The notification
+ * goes through the Deserializer threads, if there are any, so it's received
+ * after the peer's last messages.
+ *
+ * # Arguments
+ *
+ * `peer_id` - Other peer's globally unique id.
+ *
+ * `middleware_channel` - Channel from the the Reader to the Middleware.
+ *
+ * `deserializers` - Channels to the Deserializer threads, or empty if the messages are deserialized by the Reader.
+ *
+ * `encoding` - Encoding of the frames and messages sent by the peer.
+ */
+fn notify_closed(
+    peer_id: usize,
+    middleware_channel: &Sender<ClientPeerMiddleware>,
+    deserializers: &[Sender<RawMessage>],
+    encoding: WireEncoding,
+) {
+    let sent = if deserializers.is_empty() {
+        middleware_channel
+            .send(ClientPeerMiddleware::Closed { peer_id })
+            .is_ok()
+    } else {
+        deserializer::notify_closed(deserializers, peer_id, encoding)
+    };
+
+    if !sent {
+        println!(
+            "ERROR: Failed to send the closed connection of {} to the middleware",
+            peer_id
+        );
+    }
 }
 
-fn handle_received_peer_msg(msg: Vec<u8>, send_main_mid: &Sender<ClientPeerMiddleware>) {
+/**
+ * State of the Reader or Deserializer thread used to handle the received messages.
+ */
+pub struct ReceiveContext<'a> {
+    ///Channel to the Middleware
+    pub middleware_channel: &'a Sender<ClientPeerMiddleware>,
+    ///Local peer's globally unique id
+    pub local_id: usize,
+    ///Verifier of the message signatures
+    #[cfg(feature = "signing")]
+    pub signer: &'a Option<MessageSigner>,
+    ///Cipher of the received payloads
+    #[cfg(feature = "encryption")]
+    pub cipher: &'a Option<PayloadCipher>,
+}
+
+/**
+ * Handles a message received from a peer. The work that only depends on the
+ * message, such as verifying, decrypting and fingerprinting it, is done here so
+ * it runs in parallel in the Reader or Deserializer threads, and the Middleware
+ * thread is left with the causal delivery.
+ */
+pub fn handle_received_peer_msg(
+    msg: Vec<u8>,
+    signature: Option<Vec<u8>>,
+    peer_id: usize,
+    encoding: WireEncoding,
+    context: &ReceiveContext,
+    reorder: &mut Option<ReorderBuffer<ClientPeerMiddleware>>,
+) {
+    let send_main_mid = context.middleware_channel;
+    let local_id = context.local_id;
+
     //Deserializing the vec of bytes to Message struct
-    let decoded_msg: Message = deserialize(&msg)
+    #[cfg_attr(not(feature = "encryption"), allow(unused_mut))]
+    let mut decoded_msg: Message = encoding::deserialize_exact(encoding, &msg)
         .expect("ERROR: Couldn't deserialize the Message type after reading from the stream");
 
-    let peer_msg = ClientPeerMiddleware::Peer { msg: decoded_msg };
+    //Discarding messages that weren't signed by the peer in their dot
+    #[cfg(feature = "signing")]
+    match context.signer {
+        Some(signer) if !signer.verify(decoded_msg.dot.id, &msg, &signature) => {
+            println!(
+                "ERROR: {} discarded a message from {} with an invalid signature for {:?}",
+                local_id, peer_id, decoded_msg.dot
+            );
+            return;
+        }
+        _ => {}
+    }
+
+    //Signatures are ignored without the signing feature
+    #[cfg(not(feature = "signing"))]
+    let _ = (signature, local_id, peer_id);
+
+    #[cfg(feature = "encryption")]
+    {
+        decoded_msg.payload = match decrypt_message(decoded_msg.payload, context.cipher) {
+            Some(payload) => payload,
+            None => return,
+        };
+    }
+
+    //Hashing the message for the equivocation checks of the causal graph
+    let fingerprint = Node::calculate_fingerprint(&decoded_msg.payload, &decoded_msg.context);
+
+    let (sender_id, counter) = (decoded_msg.dot.id, decoded_msg.dot.counter);
+
+    let peer_msg = ClientPeerMiddleware::Peer {
+        msg: decoded_msg,
+        fingerprint,
+    };
+
+    match reorder {
+        Some(reorder) => {
+            //Holding the message until the previous messages of its sender were read
+            reorder.push(peer_id, sender_id, counter, peer_msg);
+            send_released(reorder, send_main_mid);
+        }
+        None => {
+            //Sending the payload to the main middleware thread
+            send_main_mid
+                .send(peer_msg)
+                .expect("ERROR: Failed to send message to main middleware thread");
+        }
+    }
+}
 
-    //Sending the payload to the main middleware thread
-    send_main_mid
-        .send(peer_msg)
-        .expect("ERROR: Failed to send message to main middleware thread");
+/**
+ * Sends the messages released in order by the reordering window to the Middleware.
+ *
+ * # Arguments
+ *
+ * `reorder` - Reordering window of the Reader or Deserializer thread.
+ *
+ * `send_main_mid` - Channel to the Middleware.
+ */
+pub fn send_released(
+    reorder: &mut ReorderBuffer<ClientPeerMiddleware>,
+    send_main_mid: &Sender<ClientPeerMiddleware>,
+) {
+    while let Some(peer_msg) = reorder.pop_ready() {
+        send_main_mid
+            .send(peer_msg)
+            .expect("ERROR: Failed to send message to main middleware thread");
+    }
 }
@@ -1,42 +1,212 @@
 use super::handshake;
 use super::msg_types::StreamMessages;
+use crate::channel::Receiver;
+use crate::channel::RecvTimeoutError;
 use crate::configuration::middleware_configuration::Configuration;
-use bincode::{serialize_into, serialized_size};
-use crossbeam::crossbeam_channel::RecvTimeoutError;
-use crossbeam::Receiver;
+use crate::metrics::metered_stream::MeteredStream;
+use crate::metrics::middleware_metrics::{probe_timestamp, MiddlewareMetrics};
+use crate::platform::threads::apply_thread_settings;
+use crate::security::peer_stream::PeerStream;
+use crate::transport::batching::*;
+use crate::transport::encoding;
+use crate::transport::messages::MiddlewareSender;
+use crate::transport::protocol::PROTOCOL_VERSION;
 use std::io::{BufWriter, Write};
-use std::net::TcpStream;
-use std::ops::Mul;
-use std::sync::{Arc, Barrier};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tls")]
+use crate::security::tls;
+
+#[cfg(feature = "noise")]
+use crate::security::noise::NoiseSession;
+
+#[cfg(feature = "signing")]
+use crate::security::message_signing::MessageSigner;
 
 /**
  * Starts a Sender thread that sends messages to a peer.
  *
  * # Arguments
  *
- * `stream` - TCP stream between the peers.
+ * `stream` - Stream between the peers.
  *
  * `middleware_channel` - Channel from the the Middleware to the Sender.
  *
  * `local_id` - Local peer's globally unique id.
  *
  * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics shared with the Client.
  */
 pub fn start(
-    stream: TcpStream,
-    middleware_channel: Receiver<(Arc<Barrier>, Arc<Vec<u8>>)>,
+    mut stream: PeerStream,
+    middleware_channel: Receiver<MiddlewareSender>,
     local_id: usize,
     configuration: Arc<Configuration>,
+    metrics: Arc<MiddlewareMetrics>,
 ) {
+    apply_thread_settings(&configuration.threads.sender);
+
     //Starting handshake protocol
-    handshake::send_handshake(&stream, local_id);
+    handshake::send_handshake(
+        &mut stream,
+        local_id,
+        configuration.wire_encoding,
+        &configuration.interests,
+    );
 
     //Receiving the id from the peer
-    let peer_id = handshake::finish_protocol(&stream);
+    let (peer_id, version) = handshake::finish_protocol(&mut stream);
+
+    //The peer runs an older crate version until the group is upgraded
+    if version < PROTOCOL_VERSION {
+        println!(
+            "WARN: Peer {} speaks protocol version {}, older than {}",
+            peer_id, version, PROTOCOL_VERSION
+        );
+    }
+
+    //Establishing the session keys of the connection
+    #[cfg(feature = "noise")]
+    let noise_session = NoiseSession::initiate(&mut stream, peer_id, &configuration)
+        .expect("ERROR: Failed the Noise handshake with the peer");
+
+    //Securing the connection with mutual TLS, if set
+    #[cfg(feature = "tls")]
+    let stream = tls::connect(stream, peer_id, &configuration)
+        .expect("ERROR: Failed the TLS handshake with the peer");
+
+    //Telling the peer what was delivered, so each side relays what the other is missing
+    let vector = metrics.delivered_vector();
+
+    #[cfg(feature = "signing")]
+    let signature = handshake::sign_vector(
+        &vector,
+        false,
+        &MessageSigner::from_configuration(&configuration),
+    );
+    #[cfg(not(feature = "signing"))]
+    let signature = None;
 
-    let mut buffered_messages: usize = 0;
-    let mut buffered_bytes: u64 = 0;
+    let vector = Some(StreamMessages::Vector {
+        vector,
+        reply: false,
+        signature,
+    });
+
+    let connection = PeerConnection {
+        stream,
+        peer_id,
+        #[cfg(feature = "noise")]
+        noise_session,
+    };
+
+    run(
+        connection,
+        middleware_channel,
+        local_id,
+        vector,
+        configuration,
+        metrics,
+    );
+}
+
+/**
+ * Starts a Sender thread that sends messages to a peer over a connection
+ * shared with the Reader thread, whose handshake was already exchanged.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream between the peers.
+ *
+ * `middleware_channel` - Channel from the the Middleware to the Sender.
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `peer_id` - Other peer's globally unique id.
+ *
+ * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics shared with the Client.
+ */
+pub fn start_shared(
+    stream: PeerStream,
+    middleware_channel: Receiver<MiddlewareSender>,
+    local_id: usize,
+    peer_id: usize,
+    configuration: Arc<Configuration>,
+    metrics: Arc<MiddlewareMetrics>,
+) {
+    apply_thread_settings(&configuration.threads.sender);
+
+    let connection = PeerConnection {
+        stream,
+        peer_id,
+        #[cfg(feature = "noise")]
+        noise_session: None,
+    };
+
+    run(
+        connection,
+        middleware_channel,
+        local_id,
+        None,
+        configuration,
+        metrics,
+    );
+}
+
+/**
+ * Connection to a peer whose handshake was exchanged.
+ */
+pub struct PeerConnection {
+    ///Stream between the peers
+    pub stream: PeerStream,
+    ///Other peer's globally unique id
+    pub peer_id: usize,
+    ///Session keys of the connection
+    #[cfg(feature = "noise")]
+    pub noise_session: Option<NoiseSession>,
+}
+
+/**
+ * Sends the messages from the Middleware to a peer once the connection is set up,
+ * after the local delivered version vector if it's exchanged with the peer.
+ */
+fn run(
+    connection: PeerConnection,
+    middleware_channel: Receiver<MiddlewareSender>,
+    local_id: usize,
+    vector: Option<StreamMessages>,
+    configuration: Arc<Configuration>,
+    metrics: Arc<MiddlewareMetrics>,
+) {
+    let PeerConnection {
+        stream,
+        peer_id,
+        #[cfg(feature = "noise")]
+        mut noise_session,
+    } = connection;
+
+    //Batching of the messages sent to this peer
+    let batching = configuration.get_batching(peer_id);
+
+    //Pre-allocating the batch for the messages batched at once, if the load is known
+    let batch_capacity = if configuration.warm_up.message_rate > 0 {
+        batching.message_number
+    } else {
+        0
+    };
+
+    let mut batch = PendingBatch::new(
+        local_id,
+        configuration.epoch,
+        configuration.wire_encoding,
+        batch_capacity,
+    );
+    let mut arrival_rate = ArrivalRate::new();
+    let mut last_probe = Instant::now();
 
     //Flag that determines if the thread is in the new messages period
     //True  - NEW MESSAGES timeout
@@ -44,30 +214,67 @@ pub fn start(
     let mut sender_timeout_flag: bool = true;
     let mut timeout: Duration = configuration.get_stream_sender_timeout();
 
-    let mut stream = BufWriter::new(stream);
+    //Counting the bytes written to the peer
+    let mut stream = BufWriter::new(MeteredStream::new(stream, peer_id, metrics));
+
+    if let Some(vector) = vector {
+        if !write_after_batch(&mut stream, &mut batch, &vector) {
+            println!(
+                "WARN: Stream was closed between {} and {}",
+                local_id, peer_id
+            );
+            return;
+        }
+    }
 
     loop {
         match middleware_channel.recv_timeout(timeout) {
-            Ok((message_barrier, msg)) => {
+            Ok(MiddlewareSender::Message {
+                barrier: message_barrier,
+                msg,
+                signature,
+            }) => {
                 if !sender_timeout_flag {
                     sender_timeout_flag = true;
                     timeout = configuration.get_stream_sender_timeout();
                 }
 
+                arrival_rate.update();
                 message_barrier.wait();
 
-                let stream_msg = StreamMessages::Message {
-                    msg: (*msg).clone(),
+                let msg = (*msg).clone();
+
+                //Encrypting the message with the connection's session keys
+                #[cfg(feature = "noise")]
+                let msg = match &mut noise_session {
+                    Some(session) => session
+                        .encrypt(&msg)
+                        .expect("ERROR: Failed to encrypt the message"),
+                    None => msg,
                 };
 
-                //Sending the message type and message payload as a single array of bytes
-                match serialize_into::<_, StreamMessages>(&mut stream, &stream_msg) {
+                //Buffering the message until the batch is written to the stream as a single frame
+                batch.push(BatchedMessage {
+                    msg,
+                    signature: signature.map(|signature| (*signature).clone()),
+                });
+            }
+            Ok(MiddlewareSender::Flush) => {
+                flush_buffer::<StreamMessages, _>(&mut stream, &mut batch);
+            }
+            Ok(MiddlewareSender::Pong { timestamp }) => {
+                let stream_msg = StreamMessages::Pong { timestamp };
+
+                //Answering the probe without waiting for the batching thresholds
+                match encoding::serialize_into(
+                    configuration.wire_encoding,
+                    &mut stream,
+                    &stream_msg,
+                ) {
                     Ok(_) => {
-                        buffered_messages += 1;
-                        buffered_bytes += serialized_size::<StreamMessages>(&stream_msg).unwrap();
+                        flush_buffer::<StreamMessages, _>(&mut stream, &mut batch);
                     }
                     Err(_) => {
-                        //When the stream is closed, a warning is printed
                         println!(
                             "WARN: Stream was closed between {} and {}",
                             local_id, peer_id
@@ -76,113 +283,157 @@ pub fn start(
                     }
                 }
             }
+            Ok(MiddlewareSender::Receipt { counter }) => {
+                let stream_msg = StreamMessages::Receipt { counter };
+
+                //Receipts are already coalesced by the middleware, so they're written right away
+                match encoding::serialize_into(
+                    configuration.wire_encoding,
+                    &mut stream,
+                    &stream_msg,
+                ) {
+                    Ok(_) => {
+                        flush_buffer::<StreamMessages, _>(&mut stream, &mut batch);
+                    }
+                    Err(_) => {
+                        println!(
+                            "WARN: Stream was closed between {} and {}",
+                            local_id, peer_id
+                        );
+                        break;
+                    }
+                }
+            }
+            Ok(MiddlewareSender::CatchUp { vector }) => {
+                let stream_msg = StreamMessages::CatchUp { vector };
+
+                if !write_after_batch(&mut stream, &mut batch, &stream_msg) {
+                    println!(
+                        "WARN: Stream was closed between {} and {}",
+                        local_id, peer_id
+                    );
+                    break;
+                }
+            }
+            Ok(MiddlewareSender::Vector { vector, signature }) => {
+                let stream_msg = StreamMessages::Vector {
+                    vector,
+                    reply: true,
+                    signature,
+                };
+
+                if !write_after_batch(&mut stream, &mut batch, &stream_msg) {
+                    println!(
+                        "WARN: Stream was closed between {} and {}",
+                        local_id, peer_id
+                    );
+                    break;
+                }
+            }
+            Ok(MiddlewareSender::CaughtUp { cut }) => {
+                let stream_msg = StreamMessages::CaughtUp { cut };
+
+                //Ending the relayed messages, which are written before the frame
+                if !write_after_batch(&mut stream, &mut batch, &stream_msg) {
+                    println!(
+                        "WARN: Stream was closed between {} and {}",
+                        local_id, peer_id
+                    );
+                    break;
+                }
+            }
             Err(e) => {
                 match e {
                     RecvTimeoutError::Disconnected => {
+                        //Writing the buffered messages before closing the connection
+                        write_batch::<StreamMessages, _>(&mut stream, &mut batch).unwrap();
+
                         //Creating and serializing close message
                         let stream_msg = StreamMessages::Close;
 
-                        serialize_into::<_, StreamMessages>(&mut stream, &stream_msg).unwrap();
+                        encoding::serialize_into(
+                            configuration.wire_encoding,
+                            &mut stream,
+                            &stream_msg,
+                        )
+                        .unwrap();
 
                         break;
                     }
                     _ => {}
                 }
 
-                check_buffer_flush(
+                check_buffer_flush::<StreamMessages, _>(
                     &mut sender_timeout_flag,
                     &mut stream,
-                    &mut buffered_messages,
-                    &mut buffered_bytes,
+                    &mut batch,
                     &mut timeout,
-                    &configuration,
+                    batching,
+                    &arrival_rate,
                     true,
                 );
             }
         }
-        check_buffer_flush(
+        check_buffer_flush::<StreamMessages, _>(
             &mut sender_timeout_flag,
             &mut stream,
-            &mut buffered_messages,
-            &mut buffered_bytes,
+            &mut batch,
             &mut timeout,
-            &configuration,
+            batching,
+            &arrival_rate,
             false,
         );
-    }
-}
 
-pub fn calculate_timeout(
-    timeout_flag: bool,
-    timeout: Duration,
-    config: &Arc<Configuration>,
-) -> Duration {
-    let ret_timeout: Duration;
-    //True  - NEW MESSAGES timeout
-    //False - NO MESSAGES timeout
+        //Probing the peer to measure the round trip time
+        if configuration.probe_interval > 0
+            && last_probe.elapsed() >= configuration.get_probe_interval()
+        {
+            let stream_msg = StreamMessages::Ping {
+                timestamp: probe_timestamp(),
+            };
 
-    if timeout_flag {
-        ret_timeout = config.batching.get_lower_timeout();
-    } else {
-        if timeout.as_micros() * 2 <= config.batching.get_upper_timeout().as_micros() {
-            ret_timeout = timeout.mul(2);
-        } else {
-            ret_timeout = config.batching.get_upper_timeout();
+            match encoding::serialize_into(configuration.wire_encoding, &mut stream, &stream_msg) {
+                Ok(_) => {
+                    flush_buffer::<StreamMessages, _>(&mut stream, &mut batch);
+                }
+                Err(_) => {
+                    println!(
+                        "WARN: Stream was closed between {} and {}",
+                        local_id, peer_id
+                    );
+                    break;
+                }
+            }
+
+            last_probe = Instant::now();
         }
     }
-
-    ret_timeout
 }
 
 /**
- * Checks if its necessary to write the bytes from the buffer to the TCP stream.
+ * Writes a frame to the TCP stream after the buffered messages, so the peer reads
+ * it after them, and flushes the stream. Returns false if the stream was closed.
  *
  * # Arguments
  *
- * `sender_timeout_flag` - Flag for determining if the reading timeout has expired.
- *
  * `stream` - TCP stream between the peers.
  *
- * `buffered_messages` - Number of buffered messages.
- *
- * `buffered_bytes` - Number of buffered bytes.
- *
- * `timeout` - Timeout duration.
+ * `batch` - Buffered messages.
  *
- * `configuration` - Middleware configuration.
- *
- * `error` - Flag for determining if the reading from the channel threw an error.
+ * `frame` - Frame written after the buffered messages.
  */
-pub fn check_buffer_flush(
-    sender_timeout_flag: &mut bool,
-    stream: &mut BufWriter<TcpStream>,
-    buffered_messages: &mut usize,
-    buffered_bytes: &mut u64,
-    timeout: &mut Duration,
-    configuration: &Arc<Configuration>,
-    error: bool,
-) {
-    if *buffered_messages >= configuration.batching.message_number
-        || *buffered_bytes > configuration.batching.size
-        || (error && *buffered_messages > 0)
-    {
-        //Check if the error happened because of the SEND or the NO MESSAGES timeout
-        if error && *sender_timeout_flag {
-            //Change to false if it was the SEND timeout
-            *sender_timeout_flag = false;
-        }
+fn write_after_batch(
+    stream: &mut BufWriter<MeteredStream<PeerStream>>,
+    batch: &mut PendingBatch,
+    frame: &StreamMessages,
+) -> bool {
+    let encoding = batch.get_encoding();
 
-        stream.flush().expect("ERROR: Could not flush stream!");
-        *buffered_messages = 0;
-        *buffered_bytes = 0;
-    } else {
-        //Check if the error happened because of the SEND or the NO MESSAGES timeout
-        if error && *sender_timeout_flag {
-            //Change to false if it was the SEND timeout
-            *sender_timeout_flag = false;
-        }
-        if error {
-            *timeout = calculate_timeout(*sender_timeout_flag, *timeout, configuration);
-        }
+    match write_batch::<StreamMessages, _>(stream, batch) {
+        Ok(_) => match encoding::serialize_into(encoding, &mut *stream, frame) {
+            Ok(_) => stream.flush().is_ok(),
+            Err(_) => false,
+        },
+        Err(_) => false,
     }
 }
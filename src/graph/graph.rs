@@ -1,16 +1,50 @@
-use crate::broadcast::broadcast_trait::{GenericReturn, TCB};
-use crate::configuration::middleware_configuration::Configuration;
-use crate::graph::communication::{acceptor, connector};
-use crate::graph::middleware::dot::Dot;
+use crate::broadcast::broadcast_trait::{
+    DeliveryFilter, Explanation, GenericReturn, GraphFormat, Headers, MessageTimestamps, Snapshot,
+    TcbError, TCB,
+};
+use crate::broadcast::hooks::{Hooks, NoHooks};
+use crate::broadcast::setup::{self, SetupBarrier};
+use crate::channel::{bounded, unbounded};
+use crate::channel::{Receiver, Sender};
+use crate::clock::dot::Dot;
+use crate::configuration::middleware_configuration::{Configuration, Transport};
+use crate::graph::communication::acceptor::{self, AcceptorContext};
+use crate::graph::communication::connector;
 use crate::graph::middleware::message_types::ClientMessage;
-use crate::graph::middleware::middleware_thread;
+use crate::graph::middleware::middleware_thread::{self, MiddlewareContext};
+use crate::graph::stable_receiver::StableReceiver;
 use crate::graph::structs::message_type::ClientPeerMiddleware;
-use crossbeam::crossbeam_channel::unbounded;
-use crossbeam::{Receiver, RecvError, RecvTimeoutError, SendError, Sender, TryRecvError};
-use std::sync::{Arc, Barrier};
-use std::time::Duration;
+use crate::graph::tcb_sender::{SendReceipt, TcbSender};
+use crate::metrics::middleware_metrics::{Metrics, MiddlewareMetrics, ShutdownReport};
+#[cfg(feature = "otel")]
+use crate::telemetry::otel::Telemetry;
+use crate::transport::udp::UdpTransport;
+use std::collections::VecDeque;
+use std::fs;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{thread, usize};
 
+/**
+ * Function that decides if the messages of two entity keys are related, so a
+ * message sent with the first key must be ordered after the messages of the second.
+ */
+pub type ConflictFunction = Box<dyn Fn(u64, u64) -> bool + Send>;
+
+/**
+ * Channels and handles of a started middleware service, as returned to the client.
+ */
+type ServiceChannels = (
+    Sender<ClientPeerMiddleware>,
+    Receiver<ClientMessage>,
+    Option<Receiver<ClientMessage>>,
+    Arc<SetupBarrier>,
+    Arc<MiddlewareMetrics>,
+    Option<SocketAddr>,
+);
+
 /**
  * Client side of the graph based middleware service.
  * Maintains the API and necessary state to send and deliver messages.
@@ -20,53 +54,398 @@ pub struct GRAPH {
     receive_channel: Receiver<ClientMessage>,
     ///Sender end of the channel between the client and the middleware thread
     middleware_channel: Sender<ClientPeerMiddleware>,
-    ///Dot of the next sent message
-    dot: Dot,
-    ///Context of the next sent message
-    context: Vec<Dot>,
+    ///Metrics shared with the middleware threads
+    metrics: Arc<MiddlewareMetrics>,
+    ///Receiver of the stable messages, if they are routed to their own channel
+    stable_receiver: Option<StableReceiver>,
+    ///Barrier signalling the middleware connected to every peer
+    ready: Arc<SetupBarrier>,
+    ///OpenTelemetry instruments of the send, delivery and stable events
+    #[cfg(feature = "otel")]
+    telemetry: Telemetry,
+    ///Handle that broadcasts the messages sent by the client
+    sender: TcbSender,
+    ///Local peer's globally unique id
+    local_id: usize,
+    ///Messages of the last batch of deliveries that weren't handled yet
+    batched: VecDeque<ClientMessage>,
+    ///Address the middleware listens on, if it's bound to a TCP port
+    local_addr: Option<SocketAddr>,
 }
 
 impl GRAPH {
     /**
-     * Updates the next sent message's context upon a delivery.
+     * Creates a new middleware instance whose Middleware thread fires the passed
+     * hooks when messages are sent, received, delivered and stable. Like `new`,
+     * this function only returns after the middleware has a connection to every
+     * other peer in both directions, unless the configuration sets a lazy setup.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     *
+     * `hooks` - Callbacks for custom metrics, audit logging or test assertions.
+     */
+    pub fn with_hooks(configuration: Configuration, hooks: Arc<dyn Hooks>) -> Self {
+        Self::build(configuration, hooks, None).unwrap_or_else(|e| panic!("ERROR: {}", e))
+    }
+
+    /**
+     * Creates a new middleware instance that accepts the connections of the other
+     * peers on a listener bound by the application, e.g. to port 0 so the OS picks
+     * a free port. The bound addresses of every peer must be exchanged before, since
+     * they're set in the configuration's topology. Like `new`, this function only
+     * returns after the middleware has a connection to every other peer in both
+     * directions, unless the configuration sets a lazy setup.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     *
+     * `listener` - TCP listener bound to the local peer's address.
+     */
+    pub fn with_listener(configuration: Configuration, listener: TcpListener) -> Self {
+        Self::build(configuration, Arc::new(NoHooks), Some(listener))
+            .unwrap_or_else(|e| panic!("ERROR: {}", e))
+    }
+
+    /**
+     * Creates a new middleware instance with the passed hooks, accepting the
+     * connections on the passed listener or on the configured local port. Returns
+     * `TcbError::StartupTimeout` if the startup timed out.
+     */
+    fn build(
+        configuration: Configuration,
+        hooks: Arc<dyn Hooks>,
+        listener: Option<TcpListener>,
+    ) -> Result<Self, TcbError> {
+        configuration
+            .topology
+            .validate()
+            .expect("ERROR: Invalid peer topology in the configuration");
+
+        let local_id = configuration.topology.local_id;
+        let local_port = configuration.topology.get_local_port();
+        let peer_addresses = configuration.topology.get_peer_addresses();
+        let configuration = Arc::new(configuration);
+
+        let (middleware_channel, receive_channel, stable_channel, ready, metrics, local_addr) =
+            Self::start_service(
+                local_id,
+                local_port,
+                listener,
+                peer_addresses,
+                Arc::clone(&configuration),
+                hooks,
+            )?;
+
+        let stable_receiver = match stable_channel {
+            Some(stable_channel) => {
+                let stable_receiver =
+                    StableReceiver::new(local_id, stable_channel, middleware_channel.clone());
+
+                if configuration.auto_stable_ack {
+                    Self::start_auto_ack(local_id, stable_receiver, &configuration);
+                    None
+                } else {
+                    Some(stable_receiver)
+                }
+            }
+            None => None,
+        };
+
+        let sender = TcbSender::new(local_id, middleware_channel.clone());
+
+        Ok(GRAPH {
+            receive_channel,
+            middleware_channel,
+            metrics,
+            stable_receiver,
+            ready,
+            #[cfg(feature = "otel")]
+            telemetry: Telemetry::new(local_id),
+            sender,
+            local_id,
+            batched: VecDeque::new(),
+            local_addr,
+        })
+    }
+
+    /**
+     * Writes a request of the Client to the middleware thread, or returns
+     * `TcbError::Terminated` if the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `message` - Request to the middleware thread.
+     */
+    fn request(&self, message: ClientPeerMiddleware) -> Result<(), TcbError> {
+        if self.sender.is_terminated() {
+            return Err(TcbError::Terminated);
+        }
+
+        self.middleware_channel.send(message)?;
+        Ok(())
+    }
+
+    /**
+     * Returns the next message of the last batch of deliveries, or reads the
+     * next message from the middleware's channel once the batch was handled.
+     *
+     * # Arguments
+     *
+     * `receive` - Function that reads a message from the channel.
+     */
+    fn next_message<E>(
+        &mut self,
+        receive: impl FnOnce(&Receiver<ClientMessage>) -> Result<ClientMessage, E>,
+    ) -> Result<ClientMessage, TcbError>
+    where
+        TcbError: From<E>,
+    {
+        match self.batched.pop_front() {
+            Some(message) => Ok(message),
+            None if self.sender.is_terminated() => Err(TcbError::Terminated),
+            None => Ok(receive(&self.receive_channel)?),
+        }
+    }
+
+    /**
+     * Converts a message from the Middleware into the return of a receive call.
+     * Returns `None` for the messages that aren't returned on their own, such as
+     * the batched deliveries and the cuts.
      *
      * # Arguments
      *
      * `message` - Delivered or stable message.
      */
-    fn handle_delivery(&mut self, message: ClientMessage) -> GenericReturn {
-        match message {
+    fn handle_delivery(&mut self, message: ClientMessage) -> Option<GenericReturn> {
+        let generic_return = match message {
             ClientMessage::Delivery {
-                ref payload,
+                payload,
                 dot,
-                ref context,
+                trace_id,
+                headers,
+                ..
             } => {
-                Self::update_context(&dot, context, &mut self.context);
+                #[cfg(feature = "otel")]
+                self.telemetry
+                    .on_deliver(dot.id, dot.counter, payload.len(), trace_id);
+
+                match (headers, trace_id) {
+                    (Some(headers), _) => {
+                        GenericReturn::HeaderedDelivery(payload, dot.id, dot.counter, headers)
+                    }
+                    (None, Some(trace_id)) => {
+                        GenericReturn::TracedDelivery(payload, dot.id, dot.counter, trace_id)
+                    }
+                    (None, None) => GenericReturn::Delivery(payload, dot.id, dot.counter),
+                }
+            }
+            ClientMessage::Stable { dot } => {
+                #[cfg(feature = "otel")]
+                self.telemetry.on_stable(dot.id, dot.counter);
 
-                GenericReturn::Delivery(payload.to_vec(), dot.id, dot.counter)
+                GenericReturn::Stable(dot.id, dot.counter)
             }
-            ClientMessage::Stable { dot } => GenericReturn::Stable(dot.id, dot.counter),
+            //The batched messages are handled one at a time by the next calls
+            ClientMessage::Deliveries { messages } => {
+                self.batched.extend(messages);
+                return None;
+            }
+            ClientMessage::Evicted { id } => GenericReturn::Evicted(id),
+            ClientMessage::Fault { fault } => GenericReturn::Fault(fault),
+            ClientMessage::SendFailed { failure } => GenericReturn::SendFailed(failure),
+            //Cuts are only expected by the snapshot and catch up calls
+            ClientMessage::Snapshot { .. } | ClientMessage::CaughtUp { .. } => return None,
             _ => {
                 panic!("ERROR: Received an EMPTY when it shouldn't!");
             }
+        };
+
+        Some(generic_return)
+    }
+
+    /**
+     * Sets the function that decides if the messages of two entity keys are related.
+     * By default, only the messages of the same key are related.
+     *
+     * # Arguments
+     *
+     * `conflicts` - Function over the key of the sent message and the key of a delivered message
+     */
+    pub fn set_conflict_function(&mut self, conflicts: ConflictFunction) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Conflicts { conflicts })
+    }
+
+    /**
+     * Broadcasts a message tagged with an entity key to every peer in the group.
+     * The message is only ordered after the messages of related keys and the
+     * messages sent without a key, instead of every delivered message, which
+     * reduces false dependencies and the size of the context for workloads such
+     * as per-object updates. Messages sent without a key are still ordered after
+     * every message. A message only becomes stable once every peer sent a message
+     * that depends on it, so the messages of keys a peer never writes only become
+     * stable after that peer sends a message without a key. The peers whose
+     * interest sets don't have the key receive the message without its payload.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `key` - Entity key of the message
+     */
+    pub fn send_keyed(&mut self, msg: Vec<u8>, key: u64) -> Result<SendReceipt, TcbError> {
+        self.sender.broadcast(msg, None, None, Some(key), None, &[])
+    }
+
+    /**
+     * Sends a message that is only delivered by a subset of the peers, for workloads
+     * with partial replication. The other peers receive the message without its
+     * payload and skip it, so the messages each peer delivers stay causally ordered
+     * and the message still becomes stable. In the Byzantine-tolerant mode the payload
+     * is sent to every peer, since it's needed to verify the history digests.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `recipients` - Ids of the peers that deliver the message
+     *
+     * `msg` - Serialized message to be sent
+     */
+    pub fn send_to(&mut self, recipients: &[usize], msg: Vec<u8>) -> Result<SendReceipt, TcbError> {
+        self.sender
+            .broadcast(msg, None, None, None, Some(recipients.to_vec()), &[])
+    }
+
+    /**
+     * Returns the ids of the peers that acknowledged the delivery of a message
+     * sent by the local peer, when delivery receipts are enabled. Unlike causal
+     * stability, a peer is reported as soon as its receipt arrives, without
+     * waiting for every other peer. Receipts are only received for the local
+     * messages, so the list is empty for the messages of other peers.
+     *
+     * # Arguments
+     *
+     * `dot` - Dot of a message sent by the local peer
+     */
+    pub fn delivery_status(&self, dot: &Dot) -> Vec<usize> {
+        if dot.id == self.local_id {
+            self.metrics.delivered_by(dot.counter)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /**
+     * Writes the causal graph of the middleware to a file, in the DOT language to be
+     * drawn with Graphviz or as JSON, with the stage, bit string and successors of
+     * each node, to inspect the causal state of a wedged peer without a debugger.
+     * Returns `TcbError::Terminated` if the middleware terminated, or `TcbError::Io`
+     * if the file couldn't be written.
+     *
+     * # Arguments
+     *
+     * `path` - Path of the written file
+     *
+     * `format` - Format of the serialized graph
+     */
+    pub fn dump_graph<P: AsRef<Path>>(&self, path: P, format: GraphFormat) -> Result<(), TcbError> {
+        match self.render_graph(format)? {
+            Some(graph) => Ok(fs::write(path, graph)?),
+            None => Ok(()),
+        }
+    }
+
+    /**
+     * Catches up with the group from a donor peer, which relays its retained messages
+     * above the local delivered version vector, including the messages previously
+     * sent by the local peer, e.g. when rejoining after a recovery without its state.
+     * The relayed messages are delivered in a causal order, before the donor's later
+     * messages, and are returned with the delivered version vector once every message
+     * delivered by the donor was delivered locally. The counter of the next sent
+     * message continues after the local messages relayed by the donor.
+     * Returns `TcbError::CatchUpFailed` if the donor no longer retains every missing
+     * message or messages aren't retained by the donor, `TcbError::Timeout` if the
+     * timeout ends first and `TcbError::Terminated` if the middleware terminated.
+     * The deliveries received while waiting are then returned by the next calls to
+     * `recv` and its variants.
+     *
+     * # Arguments
+     *
+     * `donor` - Id of the peer that relays the missing messages
+     *
+     * `timeout` - Maximum waiting time
+     */
+    pub fn catch_up(&mut self, donor: usize, timeout: Duration) -> Result<Snapshot, TcbError> {
+        self.request(ClientPeerMiddleware::CatchUp { donor })?;
+
+        let deadline = Instant::now() + timeout;
+        let mut received: Vec<ClientMessage> = Vec::new();
+
+        //Receiving the relayed messages until the middleware delivered the donor's cut
+        let result = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            match self.next_message(|channel| channel.recv_timeout(remaining)) {
+                Ok(ClientMessage::CaughtUp { cut: Some(cut) }) => break Ok(cut),
+                Ok(ClientMessage::CaughtUp { cut: None }) => break Err(TcbError::CatchUpFailed),
+                Ok(message) => received.push(message),
+                Err(e) => break Err(e),
+            }
+        };
+
+        match result {
+            Ok(cut) => {
+                let deliveries = received
+                    .into_iter()
+                    .filter_map(|message| self.handle_delivery(message))
+                    .collect();
+
+                Ok(Snapshot { cut, deliveries })
+            }
+            Err(e) => {
+                //Keeping the deliveries for the next calls to recv, in their order
+                for message in received.into_iter().rev() {
+                    self.batched.push_front(message);
+                }
+
+                Err(e)
+            }
         }
     }
 
     /**
-     * Updates the Peer's context upon delivery. A dot's context dots are removed
-     * from the Peer's context when adding it.
+     * Spawns the thread that acks the stable messages once they were stable
+     * for the configured hold time, when stable messages are acked automatically.
      *
      * # Arguments
      *
-     * `dot` - Delivered message dot.
+     * `local_id` - Local peer's globally unique id.
      *
-     * `message_context` - Delivered message context.
+     * `stable_receiver` - Receiver of the stable messages.
      *
-     * `local_context` - Next sent message message context.
+     * `configuration` - Middleware's configuration file.
      */
-    fn update_context(dot: &Dot, message_context: &Vec<Dot>, local_context: &mut Vec<Dot>) {
-        local_context.retain(|&client_dot| !message_context.contains(&client_dot));
-        local_context.push(dot.clone());
+    fn start_auto_ack(
+        local_id: usize,
+        stable_receiver: StableReceiver,
+        configuration: &Configuration,
+    ) {
+        let hold_time = Duration::from_micros(configuration.stable_hold_time);
+
+        //Formatting the peer's stable ack thread name
+        let thread_name = format!("stable_ack_thread_{}", local_id);
+        let builder = thread::Builder::new()
+            .name(thread_name)
+            .stack_size(configuration.thread_stack_size);
+
+        builder
+            .spawn(move || {
+                stable_receiver.ack_after(hold_time);
+            })
+            .unwrap();
     }
 
     /**
@@ -79,17 +458,28 @@ impl GRAPH {
      *
      * `local_port` - Port where the middleware will be listening for connections.
      *
+     * `listener` - TCP listener bound by the application, used instead of the local port.
+     *
      * `peer_addresses` - Addresses the middleware will connect to.
      *
      * `configuration` - Middleware's configuration file.
+     *
+     * `hooks` - Callbacks fired by the Middleware thread.
      */
     fn start_service(
         local_id: usize,
         local_port: usize,
+        listener: Option<TcpListener>,
         peer_addresses: Vec<String>,
         configuration: Arc<Configuration>,
-    ) -> (Sender<ClientPeerMiddleware>, Receiver<ClientMessage>) {
-        let setup_end_barrier = Arc::new(Barrier::new(peer_addresses.len() + 1));
+        hooks: Arc<dyn Hooks>,
+    ) -> Result<ServiceChannels, TcbError> {
+        //Creating the metrics shared between the middleware threads and the client
+        let metrics = Arc::new(MiddlewareMetrics::new(local_id, peer_addresses.len() + 1));
+        let metrics_clone = Arc::clone(&metrics);
+        let middleware_metrics = Arc::clone(&metrics);
+
+        let setup_end_barrier = Arc::new(SetupBarrier::new(peer_addresses.len() + 1));
 
         //Creating the clone of the middleware configuration arc
         let configuration_clone = Arc::clone(&configuration);
@@ -98,6 +488,16 @@ impl GRAPH {
         //and the client reads from
         let (middleware_send_channel, peer_receive_channel) = unbounded::<ClientMessage>();
 
+        //Creating the channel where the middleware writes the stable messages to,
+        //which is the channel of the deliveries unless they are routed to their own
+        let (stable_send_channel, stable_receive_channel) =
+            if configuration.separate_stable_channel || configuration.auto_stable_ack {
+                let (stable_send_channel, stable_receive_channel) = unbounded::<ClientMessage>();
+                (stable_send_channel, Some(stable_receive_channel))
+            } else {
+                (middleware_send_channel.clone(), None)
+            };
+
         //Creating the channel where the main middleware thread reads from
         //and the peer threads and client write to
         let (peer_reader_send_channel, middleware_receive_channel) =
@@ -116,24 +516,88 @@ impl GRAPH {
 
         let setup_end_barrier_clone = Arc::clone(&setup_end_barrier);
 
+        //Binding the socket shared by every connection, if the UDP transport is set
+        let udp_transport = match configuration.transport {
+            Transport::Tcp => None,
+            Transport::Udp => Some(Arc::new(
+                UdpTransport::bind(local_id, local_port, &peer_addresses, &configuration)
+                    .expect("ERROR: Failed to bind the UDP transport"),
+            )),
+        };
+        let acceptor_udp_transport = udp_transport.clone();
+
+        //Binding the TCP listener before the acceptor thread, so its address is known
+        let tcp_listener = match &udp_transport {
+            Some(_) => None,
+            None => match listener {
+                Some(listener) => Some(listener),
+                None => acceptor::bind(local_port, &configuration),
+            },
+        };
+        let local_addr = match &tcp_listener {
+            Some(listener) => listener.local_addr().ok(),
+            None => None,
+        };
+
+        //Connecting to the peers' ports and getting the channels sender ends
+        //between the middleware and the sender thread, which are made in the background
+        let (channels_to_socket_threads, connected, shared) = match &udp_transport {
+            Some(transport) => (
+                connector::start_udp(
+                    local_id,
+                    transport,
+                    &peer_addresses,
+                    &configuration_clone,
+                    &metrics,
+                ),
+                None,
+                None,
+            ),
+            //Handing the acceptor thread the connections it starts the threads of
+            None if configuration.shares_connections() => {
+                let (channels, connected, shared) = connector::start_shared(
+                    local_id,
+                    &peer_addresses,
+                    &configuration_clone,
+                    &metrics,
+                    &setup_end_barrier,
+                );
+                (channels, Some(connected), Some(shared))
+            }
+            None => {
+                let (channels, connected) = connector::start(
+                    local_id,
+                    &peer_addresses,
+                    &configuration_clone,
+                    &metrics,
+                    &setup_end_barrier,
+                );
+                (channels, Some(connected), None)
+            }
+        };
+
         //Spawning the acceptor thread
         builder
             .spawn(move || {
-                acceptor::start(
+                let context = AcceptorContext::new(
                     local_id,
-                    local_port,
                     acceptor_thread_peer_addresses,
                     peer_reader_send_channel_clone,
                     configuration,
-                    setup_end_barrier_clone,
+                    metrics_clone,
                 );
+
+                match acceptor_udp_transport {
+                    Some(transport) => {
+                        acceptor::start_udp(context, &transport, setup_end_barrier_clone)
+                    }
+                    None => acceptor::start(context, tcp_listener, setup_end_barrier_clone, shared),
+                }
             })
             .unwrap();
 
-        //Connecting to the peers' ports and getting the channels sender ends
-        //between the middleware and the sender thread
-        let channels_to_socket_threads: Vec<Sender<(Arc<Barrier>, Arc<Vec<u8>>)>> =
-            connector::start(local_id, &peer_addresses, &configuration_clone);
+        let setup_configuration = Arc::clone(&configuration_clone);
+        let setup_peer_addresses = peer_addresses.clone();
 
         //Formatting the peer's middlware thread name
         let thread_name = format!("middleware_thread_{}", local_id);
@@ -149,153 +613,254 @@ impl GRAPH {
                     peer_addresses,
                     middleware_receive_channel,
                     middleware_send_channel,
+                    stable_send_channel,
                     channels_to_socket_threads,
-                    configuration_clone,
+                    MiddlewareContext {
+                        configuration: configuration_clone,
+                        metrics: middleware_metrics,
+                        hooks,
+                    },
                 )
             })
             .unwrap();
 
-        setup_end_barrier.wait();
+        setup::wait_for_setup(
+            local_id,
+            connected,
+            setup_peer_addresses.len(),
+            Arc::clone(&setup_end_barrier),
+            Arc::clone(&metrics),
+        );
+
+        //Blocking until the middleware is connected to every peer, unless the setup is lazy
+        if !setup_configuration.lazy_setup {
+            let startup = setup::wait_for_startup(
+                &setup_end_barrier,
+                local_id,
+                &setup_peer_addresses,
+                &setup_configuration,
+                &metrics,
+            );
+
+            //Stopping the threads waiting for the aborted setup and closing the listener
+            if let Err(e) = startup {
+                setup::wake_acceptor(local_addr);
+                let _ = peer_reader_send_channel.send(ClientPeerMiddleware::End);
+
+                return Err(e);
+            }
+        }
+
         //Return the channels the peer writes and reads from to the middleware
-        (peer_reader_send_channel, peer_receive_channel)
+        Ok((
+            peer_reader_send_channel,
+            peer_receive_channel,
+            stable_receive_channel,
+            setup_end_barrier,
+            metrics,
+            local_addr,
+        ))
     }
 }
 
 impl TCB for GRAPH {
     /**
-     * Type of the return from a send call, which is the sent message context or an error.
+     * Type of the return from a send call, which is the receipt of the sent message's
+     * dot and context or an error.
+     */
+    type SendCallReturn = Result<SendReceipt, TcbError>;
+
+    /**
+     * Type of the receiver of the stability notifications.
      */
-    type SendCallReturn = Result<Vec<Dot>, SendError<ClientPeerMiddleware>>;
+    type StableReceiver = StableReceiver;
+
+    /**
+     * Type of the cloneable handle that broadcasts messages from other threads.
+     */
+    type Sender = TcbSender;
 
     /**
      * Creates a new middleware instance. This function only returns after the middleware
-     * has a connection to every other peer in both directions.
+     * has a connection to every other peer in both directions, unless the configuration
+     * sets a lazy setup. The local id, the port where the middleware will be listening
+     * for connections and the addresses of the other peers are set by the configuration's
+     * topology, which must be valid.
      *
      * # Arguments
      *
-     * `local_id` - Peer's globally unique id in the group.
-     *
-     * `local_port` - Port where the middleware will be listening for connections.
+     * `configuration` - Middleware's configuration file.
+     */
+    fn new(configuration: Configuration) -> Self {
+        Self::with_hooks(configuration, Arc::new(NoHooks))
+    }
+
+    /**
+     * Creates a new middleware instance like `new`, but returns
+     * `TcbError::StartupTimeout` with the connections that weren't made instead of
+     * panicking if the configured startup timeout ends first.
      *
-     * `peer_addresses` - Addresses the middleware will connect to.
+     * # Arguments
      *
      * `configuration` - Middleware's configuration file.
      */
-    fn new(
-        local_id: usize,
-        local_port: usize,
-        peer_addresses: Vec<String>,
-        configuration: Configuration,
-    ) -> Self {
-        let configuration = Arc::new(configuration);
-
-        let (middleware_channel, receive_channel) =
-            Self::start_service(local_id, local_port, peer_addresses, configuration);
-
-        //Initializing the context and dot variables
-        let context: Vec<Dot> = Vec::new();
-        let dot = Dot::new(local_id, 0);
-
-        GRAPH {
-            receive_channel,
-            middleware_channel,
-            dot,
-            context,
-        }
+    fn try_new(configuration: Configuration) -> Result<Self, TcbError> {
+        Self::build(configuration, Arc::new(NoHooks), None)
     }
 
     /**
      * Broadcasts a message to every peer in the group.
-     * Returns the sent message context if successfull.
+     * Returns the receipt of the sent message's dot and context if successfull.
      *
      * # Arguments
      *
      * `msg` - Serialized message to be broadcast
      */
     fn send(&mut self, msg: Vec<u8>) -> Self::SendCallReturn {
-        //Incrementing the dot's counter entry
-        self.dot.counter += 1;
-
-        //Building the enum of the new message
-        let client_message = ClientPeerMiddleware::Client {
-            dot: self.dot.clone(),
-            msg,
-            context: self.context.clone(),
-        };
-
-        //Sending the enum to the middleware thread
-        self.middleware_channel.send(client_message)?;
-        //.expect("ERROR: Client could not send message to main middleware");
+        self.sender.broadcast(msg, None, None, None, None, &[])
+    }
 
-        //Clearing the context for the next sent message
-        let context: Vec<Dot> = self.context.drain(..).collect();
+    /**
+     * Broadcasts a message tagged with a trace id to every peer in the group.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `trace_id` - Trace or correlation id of the message
+     */
+    fn send_traced(&mut self, msg: Vec<u8>, trace_id: u128) -> Self::SendCallReturn {
+        self.sender
+            .broadcast(msg, Some(trace_id), None, None, None, &[])
+    }
 
-        //Adding the last sent message's dot to the new context
-        self.context.push(self.dot.clone());
+    /**
+     * Broadcasts a message with metadata headers to every peer in the group.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `headers` - Metadata headers of the message
+     */
+    fn send_with_headers(&mut self, msg: Vec<u8>, headers: Headers) -> Self::SendCallReturn {
+        self.sender
+            .broadcast(msg, None, Some(headers), None, None, &[])
+    }
 
-        //Returning the previous message's context
-        Ok(context)
+    /**
+     * Broadcasts a message that is ordered after the explicit dependencies, besides
+     * the messages already delivered by the Client. The middleware only broadcasts
+     * the message once every dependency was delivered locally, and the messages
+     * sent after it wait for it to keep their order. Returns the same as `send`.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `deps` - Dots of the messages this message must be delivered after
+     */
+    fn send_after(&mut self, msg: Vec<u8>, deps: &[Dot]) -> Self::SendCallReturn {
+        self.sender.broadcast(msg, None, None, None, None, deps)
     }
 
     /**
-     * Signals and waits for the middleware to terminate.
+     * Signals and waits for the middleware to terminate. Every later call that
+     * needs the middleware returns `TcbError::Terminated`.
      */
     fn end(&self) {
+        //Ending the middleware only once, since its thread is gone afterwards
+        if !self.sender.mark_terminated() {
+            return;
+        }
+
         let end_message = ClientPeerMiddleware::End;
-        self.middleware_channel.send(end_message).unwrap();
+
+        //The middleware thread may have terminated on its own
+        if self.middleware_channel.send(end_message).is_err() {
+            return;
+        }
 
         loop {
             match self.receive_channel.recv() {
-                Ok(msg) => match msg {
-                    ClientMessage::Empty => {
-                        break;
-                    }
-                    _ => {}
-                },
-                Err(_) => {}
+                Ok(ClientMessage::Empty) | Err(_) => break,
+                Ok(_) => {}
             }
         }
     }
 
+    /**
+     * Ends the middleware like `end`, then returns the summary of its activity,
+     * recorded by the Middleware thread before it terminated.
+     */
+    fn shutdown(&self) -> ShutdownReport {
+        self.end();
+        self.metrics.report()
+    }
+
     /**
      * Delivers a message from the middleware. Blocks the calling thread
-     * until a message is delivered or the channel to the middleware is
-     * empty or disconnected.
+     * until a message is delivered, or returns `TcbError::Terminated` once
+     * the middleware terminated.
      */
-    fn recv(&mut self) -> Result<GenericReturn, RecvError> {
-        match self.receive_channel.recv() {
-            Ok(message) => Ok(self.handle_delivery(message)),
-            Err(e) => Err(e),
+    fn recv(&mut self) -> Result<GenericReturn, TcbError> {
+        loop {
+            match self.next_message(|channel| channel.recv()) {
+                Ok(message) => {
+                    if let Some(generic_return) = self.handle_delivery(message) {
+                        return Ok(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
     /**
      * Attempts to deliver a message from the middleware without blocking
-     * the caller thread. Either a message is immeadiately delivered
-     * from the channel or an error is returned if the channel is empty.
+     * the caller thread. Either a message is immeadiately delivered from
+     * the channel or `TcbError::Empty` is returned if the channel is empty.
      */
-    fn try_recv(&mut self) -> Result<GenericReturn, TryRecvError> {
-        match self.receive_channel.try_recv() {
-            Ok(message) => Ok(self.handle_delivery(message)),
-            Err(e) => Err(e),
+    fn try_recv(&mut self) -> Result<GenericReturn, TcbError> {
+        loop {
+            match self.next_message(|channel| channel.try_recv()) {
+                Ok(message) => {
+                    if let Some(generic_return) = self.handle_delivery(message) {
+                        return Ok(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
     /**
      * Waits for a message to be delivered from the middleware for a
-     * limited time. If the channel is empty and not disconnected, the
-     * caller thread is blocked until a message is received in the channel
-     * or the timeout ends. If there are no messages until the timeout ends or
-     * the channel becomes disconnected, an error is returned.
+     * limited time. If the channel is empty, the caller thread is blocked
+     * until a message is received in the channel or the timeout ends, in
+     * which case `TcbError::Timeout` is returned.
      *
      * # Arguments
      *
      * `duration` - Timeout duration
      */
-    fn recv_timeout(&mut self, duration: Duration) -> Result<GenericReturn, RecvTimeoutError> {
-        match self.receive_channel.recv_timeout(duration) {
-            Ok(message) => Ok(self.handle_delivery(message)),
-            Err(e) => Err(e),
+    fn recv_timeout(&mut self, duration: Duration) -> Result<GenericReturn, TcbError> {
+        //The filtered messages don't extend the timeout
+        let deadline = Instant::now() + duration;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            match self.next_message(|channel| channel.recv_timeout(remaining)) {
+                Ok(message) => {
+                    if let Some(generic_return) = self.handle_delivery(message) {
+                        return Ok(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
@@ -315,12 +880,230 @@ impl TCB for GRAPH {
      *
      * `counter` - Stable dot counter field
      */
-    fn tcbstable(&mut self, id: usize, counter: usize) {
+    fn tcbstable(&mut self, id: usize, counter: usize) -> Result<(), TcbError> {
         let dot = Dot::new(id, counter);
-        let stable_dot = ClientPeerMiddleware::Stable { dot };
 
-        self.middleware_channel
-            .send(stable_dot)
-            .expect("ERROR: When the Client sends a STABLE message");
+        self.request(ClientPeerMiddleware::Stable { dot })
+    }
+
+    /**
+     * Evicts a peer that is known to be permanently gone from the group. The
+     * middleware closes the connections to the peer, ignores any further messages
+     * from it and stops waiting for it when calculating causal stability, so the
+     * remaining peers can resume making stability progress. The client is notified
+     * with a `GenericReturn::Evicted` once the eviction was handled by the middleware.
+     *
+     * # Arguments
+     *
+     * `id` - Evicted peer's globally unique id
+     */
+    fn evict_peer(&mut self, id: usize) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Evict { id })
+    }
+
+    /**
+     * Forces every Sender thread to immediately write its buffered messages
+     * into the stream, instead of waiting for the batching thresholds or
+     * timeouts. Useful at latency-critical moments, such as the end of a
+     * client transaction.
+     */
+    fn flush(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Flush)
+    }
+
+    /**
+     * Returns the receiver of the stability notifications if the configuration
+     * routes them to their own channel. The receiver can be moved to another
+     * thread, e.g. a garbage collection thread that acks the stable messages,
+     * so the deliveries aren't interleaved with the stability bookkeeping.
+     * Returns `None` if the notifications are returned by `recv`, if they are
+     * acked automatically or if the receiver was already taken.
+     */
+    fn stable_receiver(&mut self) -> Option<StableReceiver> {
+        self.stable_receiver.take()
+    }
+
+    /**
+     * Returns a handle that broadcasts messages from other threads, which can be
+     * cloned and shared without a lock around the middleware instance. The dots
+     * and contexts of the messages sent through every handle are assigned by the
+     * middleware thread, so they are ordered after the delivered messages.
+     */
+    fn sender(&self) -> TcbSender {
+        self.sender.clone()
+    }
+
+    /**
+     * Takes a consistent cut of the messages delivered by the middleware, for
+     * applications implementing consistent backups. The Sender threads are flushed
+     * and the middleware returns its delivered version vector, including the local
+     * messages. The messages delivered up to the cut that the Client didn't receive
+     * yet are returned with it, so once the Client applies them its state is the
+     * state at the cut, and every later delivery is above the cut.
+     */
+    fn snapshot(&mut self) -> Result<Snapshot, TcbError> {
+        self.request(ClientPeerMiddleware::Snapshot)?;
+
+        let mut deliveries: Vec<GenericReturn> = Vec::new();
+
+        //Receiving the deliveries written by the middleware before the cut
+        loop {
+            match self.next_message(|channel| channel.recv()) {
+                Ok(ClientMessage::Snapshot { cut }) => {
+                    return Ok(Snapshot { cut, deliveries });
+                }
+                Ok(message) => {
+                    if let Some(generic_return) = self.handle_delivery(message) {
+                        deliveries.push(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /**
+     * Registers a predicate that selects which deliveries are returned to the
+     * Client, or removes the current one if `None` is passed. The messages that
+     * don't match are still delivered inside the middleware, so the causal order
+     * of the matching messages and of the Client's own messages is kept, and they
+     * are still notified when stable.
+     *
+     * # Arguments
+     *
+     * `filter` - Predicate over the sender id, message id and payload
+     */
+    fn set_delivery_filter(&mut self, filter: Option<DeliveryFilter>) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Filter { filter })
+    }
+
+    /**
+     * Stops the middleware from delivering messages to the Client, e.g. while
+     * it does a slow operation. The messages received from the peers keep being
+     * handled and wait in the middleware until the deliveries are resumed, and the
+     * deliveries already sent to the Client can still be received.
+     */
+    fn pause_delivery(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Pause)
+    }
+
+    /**
+     * Resumes the deliveries to the Client after `pause_delivery`, starting with
+     * the messages that were held back while paused.
+     */
+    fn resume_delivery(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Resume)
+    }
+
+    /**
+     * Acknowledges that the Client processed a delivered message, when delivery
+     * acks are enabled in the configuration. The middleware keeps every delivery
+     * until it's acked, so the Client can receive the unacked ones again with
+     * `replay_unacked`. Does nothing if delivery acks aren't enabled.
+     *
+     * # Arguments
+     *
+     * `id` - Delivered message's sender id
+     *
+     * `counter` - Delivered message's counter
+     */
+    fn ack(&mut self, id: usize, counter: usize) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Ack {
+            dot: Dot::new(id, counter),
+        })
+    }
+
+    /**
+     * Sends the deliveries that weren't acked to the Client again, in the order
+     * they were delivered, e.g. after the thread processing them crashed and
+     * was restarted. They are returned by `recv` and its variants after the
+     * deliveries already in the channel, and stay pending until they are acked.
+     */
+    fn replay_unacked(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::Replay)
+    }
+
+    /**
+     * Returns a snapshot of the middleware metrics, such as the round
+     * trip time measured to each peer.
+     */
+    fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
+    }
+
+    /**
+     * Returns the timestamps of the internal events of the retained messages, in
+     * the order they were delivered, to break down their latency offline. Empty
+     * unless messages are retained in the configuration. Returns
+     * `TcbError::Terminated` if the middleware terminated.
+     */
+    fn debug_dump(&self) -> Result<Vec<MessageTimestamps>, TcbError> {
+        let (reply, timestamps) = bounded::<Vec<MessageTimestamps>>(1);
+
+        self.request(ClientPeerMiddleware::Dump { reply })?;
+
+        Ok(timestamps.recv()?)
+    }
+
+    /**
+     * Explains why a message wasn't delivered yet, for debugging: whether it
+     * wasn't received, or which messages of which peers it waits for, from the
+     * middleware's current state. Returns `TcbError::Terminated` if the
+     * middleware terminated.
+     *
+     * # Arguments
+     *
+     * `id` - Message's sender id
+     *
+     * `counter` - Message's counter
+     */
+    fn explain(&self, id: usize, counter: usize) -> Result<Explanation, TcbError> {
+        let (reply, explanation) = bounded::<Explanation>(1);
+
+        self.request(ClientPeerMiddleware::Explain {
+            dot: Dot::new(id, counter),
+            reply,
+        })?;
+
+        Ok(explanation.recv()?)
+    }
+
+    /**
+     * Returns the causal graph of the middleware serialized in a format, with
+     * the stage and edges of each node, to inspect the causal state of a wedged
+     * peer. Returns `None` for the VV implementation, which has no causal graph,
+     * and `TcbError::Terminated` if the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `format` - Format of the serialized graph
+     */
+    fn render_graph(&self, format: GraphFormat) -> Result<Option<String>, TcbError> {
+        let (reply, graph) = bounded::<String>(1);
+
+        self.request(ClientPeerMiddleware::RenderGraph { format, reply })?;
+
+        Ok(Some(graph.recv()?))
+    }
+
+    /**
+     * Waits for the middleware to be connected to every peer in both directions,
+     * for a limited time. Returns true once connected, which is always the case
+     * unless the setup is lazy.
+     *
+     * # Arguments
+     *
+     * `timeout` - Maximum waiting time
+     */
+    fn wait_ready(&self, timeout: Duration) -> bool {
+        setup::wait_ready(&self.ready, timeout)
+    }
+
+    /**
+     * Returns the address the middleware listens on for connections, which has
+     * the port chosen by the OS if the configuration set the local port to 0.
+     */
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
     }
 }
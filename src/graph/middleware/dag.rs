@@ -1,14 +1,19 @@
+use crate::metrics::middleware_metrics::GraphMetrics;
 use std::ops::{Deref, DerefMut};
 
 /**
  * Struct of a directed acyclic graph mapped as an array.
  */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArrayMap<T> {
     ///Array with the nodes
     nodes: Vec<T>,
     ///Array with the available indexes
     available_indexes: Vec<usize>,
+    ///Number of softly deleted nodes whose slots weren't reused yet
+    free_slots: usize,
+    ///Highest number of nodes kept in the graph at once
+    high_water: usize,
 }
 
 impl<T> ArrayMap<T> {
@@ -31,6 +36,8 @@ impl<T> ArrayMap<T> {
         ArrayMap {
             nodes,
             available_indexes,
+            free_slots: 0,
+            high_water: 0,
         }
     }
 
@@ -42,11 +49,25 @@ impl<T> ArrayMap<T> {
      * `node` - New node to add to the graph.
      * */
     pub fn push(&mut self, node: T) -> usize {
+        let index = self.insert(node);
+        self.high_water = self.high_water.max(self.occupied());
+        index
+    }
+
+    /**
+     * Stores a node in the first available slot and returns its index.
+     *
+     * # Arguments
+     *
+     * `node` - New node to add to the graph.
+     * */
+    fn insert(&mut self, node: T) -> usize {
         match self.available_indexes.pop() {
             Some(index) => match self.nodes.get(index) {
                 Some(_) => {
                     //Its a node softly deleted
                     self.nodes[index] = node;
+                    self.free_slots -= 1;
                     index
                 }
                 None => {
@@ -79,6 +100,7 @@ impl<T> ArrayMap<T> {
             .get(index)
             .expect("ERROR: Was expecting a node in this position");
         self.available_indexes.push(index);
+        self.free_slots += 1;
     }
 
     /**
@@ -87,6 +109,44 @@ impl<T> ArrayMap<T> {
     pub fn node_number(&self) -> usize {
         self.nodes.len()
     }
+
+    /**
+     * Returns the number of nodes in the graph that weren't softly deleted.
+     * */
+    pub fn occupied(&self) -> usize {
+        self.nodes.len() - self.free_slots
+    }
+
+    /**
+     * Returns the nodes that weren't softly deleted along with their indexes.
+     * */
+    pub fn iter_occupied(&self) -> impl Iterator<Item = (usize, &T)> {
+        let mut free = vec![false; self.nodes.len()];
+
+        //The available indexes past the end were never used
+        for index in self.available_indexes.iter() {
+            if *index < self.nodes.len() {
+                free[*index] = true;
+            }
+        }
+
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(move |(index, _)| !free[*index])
+    }
+
+    /**
+     * Returns the occupancy statistics of the graph.
+     * */
+    pub fn stats(&self) -> GraphMetrics {
+        GraphMetrics {
+            occupied: self.occupied(),
+            free: self.free_slots,
+            high_water: self.high_water,
+            capacity: self.nodes.capacity(),
+        }
+    }
 }
 
 impl<T> Deref for ArrayMap<T> {
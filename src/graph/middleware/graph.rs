@@ -1,14 +1,32 @@
 use super::dag::ArrayMap;
-use super::dot::Dot;
 use super::message_types::ClientMessage;
 use super::node::{Node, Stage};
-use crate::configuration::middleware_configuration::Configuration;
+use super::node_pool::NodePool;
+use super::send_context::SendContext;
+use crate::broadcast::acks::PendingAcks;
+use crate::broadcast::broadcast_trait::{
+    DeliveryFilter, Explanation, Fault, GraphFormat, MessageTimestamps, PendingDependency,
+    SendFailure,
+};
+use crate::broadcast::hooks::Hooks;
+use crate::broadcast::retention::RetentionLog;
+use crate::channel::Sender;
+use crate::clock::dot::{Dot, DotMap};
+use crate::configuration::middleware_configuration::{Configuration, DroppedClientPolicy};
+use crate::graph::graph::ConflictFunction;
 use crate::graph::structs::message::Message;
+use crate::metrics::middleware_metrics::{
+    GraphMetrics, MessageCounts, PoolMetrics, RetentionMetrics,
+};
+#[cfg(feature = "bcb")]
+use crate::security::history::{HistoryDigest, HistoryDigests};
+use crate::transport::checksum;
 use bit_vec::BitVec;
-use crossbeam::Sender;
-use smallvec::SmallVec;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::VecDeque;
+use std::mem;
 use std::sync::Arc;
+use std::time::Instant;
 
 type BV = BitVec<u64>;
 
@@ -19,11 +37,52 @@ type BV = BitVec<u64>;
 pub struct GRAPH {
     G: ArrayMap<Node>,
     V: Vec<usize>,
-    dot_to_index_map: HashMap<Dot, usize>,
+    ///Highest counter received from each peer
+    received: Vec<usize>,
+    ///Numbers of the sent, delivered, stable and pending messages
+    counts: MessageCounts,
+    ///Indexes of the oldest and newest delivered messages of each sender still in the
+    ///graph, the ends of the list linked by the nodes' sender indexes
+    sender_first: Vec<Option<usize>>,
+    sender_last: Vec<Option<usize>>,
+    dot_to_index_map: DotMap<usize>,
     peer_number: usize,
     peer_index: usize,
+    evicted: BV,
+    pool: NodePool,
+    #[cfg(feature = "bcb")]
+    history: Option<HistoryDigests>,
     client: Sender<ClientMessage>,
+    stable_client: Sender<ClientMessage>,
     configuration: Arc<Configuration>,
+    hooks: Arc<dyn Hooks>,
+    filter: Option<DeliveryFilter>,
+    retained: Option<RetentionLog<Message>>,
+    ///Dependencies that weren't received, with the time they were first
+    ///referenced and whether they were already reported to the Client
+    missing: DotMap<(Instant, bool)>,
+    ///Messages delivered while handling a message, sent to the Client
+    ///together once it was handled when deliveries are batched
+    deliveries: Vec<ClientMessage>,
+    ///Contexts of the next messages sent by the Client
+    sends: SendContext,
+    ///Graph indexes of the received messages whose predecessors were delivered,
+    ///delivered in chunks of `delivery_chunk_size` messages
+    ready: VecDeque<usize>,
+    ///Set while the Client paused the deliveries
+    paused: bool,
+    ///Deliveries the Client didn't ack yet, if delivery acks are enabled
+    acks: Option<PendingAcks<ClientMessage>>,
+    ///Set once the Client dropped the receiver of the deliveries
+    client_dropped: bool,
+    ///Set once the Client dropped the receiver of the stable messages
+    stable_dropped: bool,
+    ///Stable messages the Client can no longer ack, removed by the Middleware
+    ///thread when the dropped client policy retains the messages
+    orphaned_stable: Vec<Dot>,
+    ///Messages whose payload didn't match their checksum, kept pending until the
+    ///Middleware thread asks their senders to retransmit them
+    corrupted: Vec<Dot>,
 }
 
 #[allow(non_snake_case)]
@@ -39,29 +98,105 @@ impl GRAPH {
      *
      * `client` - Channel between the Middleware and the Peer that will be used to send delivered/stable messages to Peer.
      *
+     * `stable_client` - Channel used to send stable messages to Peer, which is `client` unless they are routed to their own channel.
+     *
      * `configuration` - Middleware's configuration file.
+     *
+     * `hooks` - Callbacks fired when messages are sent, received, delivered and stable.
      */
     pub fn new(
         peer_index: usize,
         peer_number: usize,
         client: Sender<ClientMessage>,
+        stable_client: Sender<ClientMessage>,
         configuration: Arc<Configuration>,
+        hooks: Arc<dyn Hooks>,
     ) -> GRAPH {
-        let G: ArrayMap<Node> = ArrayMap::new(3 * peer_number);
-        let dot_to_index_map: HashMap<Dot, usize> = HashMap::new();
+        //Pre-allocating the graph for the messages expected until stability
+        let expected_messages = configuration.warm_up.expected_messages();
+
+        let G: ArrayMap<Node> = ArrayMap::new(expected_messages.max(3 * peer_number));
+        let dot_to_index_map: DotMap<usize> =
+            DotMap::with_capacity_and_hasher(expected_messages, Default::default());
         let V: Vec<usize> = vec![0; peer_number];
+        let mut evicted = BV::default();
+        evicted.grow(peer_number, false);
+
+        #[cfg(feature = "bcb")]
+        let history = if configuration.byzantine_tolerance {
+            Some(HistoryDigests::new())
+        } else {
+            None
+        };
+
+        let retained = if configuration.retention.enabled {
+            Some(RetentionLog::new(peer_number, &configuration.retention))
+        } else {
+            None
+        };
+
+        let sends = SendContext::new(peer_index, configuration.interests.clone());
+        let acks = PendingAcks::from_configuration(&configuration);
 
         GRAPH {
             G,
             V,
+            received: vec![0; peer_number],
+            counts: MessageCounts::default(),
+            sender_first: vec![None; peer_number],
+            sender_last: vec![None; peer_number],
             dot_to_index_map,
             peer_number,
             peer_index,
+            evicted,
+            pool: NodePool::with_capacity(expected_messages, peer_number),
+            #[cfg(feature = "bcb")]
+            history,
             client,
+            stable_client,
             configuration,
+            hooks,
+            filter: None,
+            retained,
+            missing: DotMap::default(),
+            deliveries: Vec::new(),
+            sends,
+            ready: VecDeque::new(),
+            paused: false,
+            acks,
+            client_dropped: false,
+            stable_dropped: false,
+            orphaned_stable: Vec::new(),
+            corrupted: Vec::new(),
         }
     }
 
+    /**
+     * Assigns the dot and context of a message sent by the Client, which depends
+     * on the messages delivered by the middleware, including the ones the Client
+     * didn't receive yet. Returns `None` once the message counter is exhausted.
+     *
+     * # Arguments
+     *
+     * `key` - Entity key of the sent message
+     *
+     * `deps` - Explicit dependencies of the sent message
+     */
+    pub fn next_send(&mut self, key: Option<u64>, deps: &[Dot]) -> Option<(Dot, Vec<Dot>)> {
+        self.sends.next(key, deps)
+    }
+
+    /**
+     * Sets the function that decides if the messages of two entity keys are related.
+     *
+     * # Arguments
+     *
+     * `conflicts` - Function over the key of the sent message and the key of a delivered message
+     */
+    pub fn set_conflicts(&mut self, conflicts: ConflictFunction) {
+        self.sends.set_conflicts(conflicts);
+    }
+
     /**
      * Handler for a message sent by the Client to be broadcast. This function adds a
      * message to the causal graph.
@@ -71,24 +206,19 @@ impl GRAPH {
      * `message` - Message received from the Client.
      */
     pub fn dequeue(&mut self, message: Message) {
+        self.hooks
+            .on_send(message.dot.id, message.dot.counter, message.payload.len());
+
         //Updating the this sender's version vector entry
         self.V[message.dot.id] = message.dot.counter;
+        self.counts.sent += 1;
 
         if self.configuration.track_causal_stability {
-            //Calculating the message's predecessors indexes in the VecMap struct
-            //that aren't causally stable
-            let p_line: Vec<&Dot> = message
-                .context
-                .iter()
-                .filter(|p| !Self::stable(self, &p))
-                .collect();
-
             //Creating a new Node struct
             let mut new_node = Node::new(message.dot);
             //Calculating the bstr of the new message
-            new_node.bits.grow(self.peer_number, true);
-            //Setting the bit representing the local peer to true
-            new_node.bits.set(message.dot.id, false);
+            let b = self.pool.bits(self.peer_number, true);
+            new_node.bits = self.stability_bits(message.dot.id, b);
             //Setting the node as delivered
             new_node.stage = Stage::DLV;
 
@@ -97,12 +227,18 @@ impl GRAPH {
 
             //Adding the node (dot, index) to the hashmap
             self.dot_to_index_map.insert(message.dot, new_graph_index);
+            self.link_sender(new_graph_index);
 
-            let mut predecessors_graph_indexes: Vec<usize> = Vec::new();
+            let mut predecessors_graph_indexes = self.pool.indexes(message.context.len());
 
             //Iterating over the message's causal predecessors that aren't stable
-            for p in p_line {
-                let pred_graph_index: usize = *self.dot_to_index_map.get(p).unwrap();
+            for p in &message.context {
+                //Stable dependencies and dependencies on undelivered messages of
+                //evicted peers, which have no node, are ignored
+                let pred_graph_index: usize = match self.dot_to_index_map.get(p) {
+                    Some(index) if !self.is_stable_node(p, *index) => *index,
+                    _ => continue,
+                };
 
                 //Adding the predecessor's graph index to a Vec
                 predecessors_graph_indexes.push(pred_graph_index);
@@ -113,11 +249,145 @@ impl GRAPH {
 
             //Setting the new node's with the predecessors graph indexes
             let temp_new_node = &mut self.G[new_graph_index];
-            (*temp_new_node).predecessors = SmallVec::from(predecessors_graph_indexes);
-            temp_new_node.payload = Some(message.payload);
-            temp_new_node.context = Some(message.context);
+            temp_new_node.predecessors = predecessors_graph_indexes;
+            //The payload isn't kept, since the Client already has it
+            temp_new_node.fingerprint = Some(Node::calculate_fingerprint(
+                &message.payload,
+                &message.context,
+            ));
+            temp_new_node.trace_id = message.trace_id;
 
             self.updatestability(self.peer_index, new_graph_index);
+            self.flush_deliveries();
+        }
+
+        self.retain(message, None);
+    }
+
+    /**
+     * Sets the history digests of a message sent by the Client and records
+     * its digest, in the Byzantine-tolerant mode. Must be called before the
+     * message is dequeued.
+     *
+     * # Arguments
+     *
+     * `message` - Message received from the Client.
+     */
+    #[cfg(feature = "bcb")]
+    pub fn attach_history(&mut self, mut message: Message) -> Message {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return message,
+        };
+
+        let dependencies: Vec<(usize, usize, HistoryDigest)> = message
+            .context
+            .iter()
+            .map(|dot| {
+                let digest = history.get(dot.id, dot.counter).unwrap_or_default();
+                (dot.id, dot.counter, digest)
+            })
+            .collect();
+
+        let digest = HistoryDigests::digest(
+            message.dot.id,
+            message.dot.counter,
+            &dependencies,
+            &message.payload,
+        );
+
+        match history.record(message.dot.id, message.dot.counter, digest) {
+            Ok(_) => {}
+            Err(fault) => {
+                println!(
+                    "ERROR: Local message conflicts with its history - {:?}",
+                    fault
+                );
+            }
+        }
+
+        message.context_digests = dependencies
+            .into_iter()
+            .map(|(_, _, digest)| digest)
+            .collect();
+
+        message
+    }
+
+    /**
+     * Validates the history digests of a message received from a peer and records
+     * its digest, in the Byzantine-tolerant mode. If an equivocation is detected,
+     * the Client is notified with a fault and false is returned.
+     *
+     * # Arguments
+     *
+     * `message` - Message received from a peer in the group.
+     */
+    #[cfg(feature = "bcb")]
+    fn validate_history(&mut self, message: &Message) -> bool {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return true,
+        };
+
+        let result = if message.context_digests.len() != message.context.len() {
+            Err(Fault::MissingHistory {
+                id: message.dot.id,
+                counter: message.dot.counter,
+            })
+        } else {
+            let dependencies: Vec<(usize, usize, HistoryDigest)> = message
+                .context
+                .iter()
+                .zip(message.context_digests.iter())
+                .map(|(dot, digest)| (dot.id, dot.counter, *digest))
+                .collect();
+
+            let digest = HistoryDigests::digest(
+                message.dot.id,
+                message.dot.counter,
+                &dependencies,
+                &message.payload,
+            );
+
+            dependencies
+                .iter()
+                .try_for_each(|&(id, counter, digest)| history.expect(id, counter, digest))
+                .and_then(|_| history.record(message.dot.id, message.dot.counter, digest))
+        };
+
+        match result {
+            Ok(_) => true,
+            Err(fault) => {
+                self.send_to_client(ClientMessage::Fault { fault });
+                false
+            }
+        }
+    }
+
+    /**
+     * Checks if a received message has the dot of a message in the causal graph,
+     * but a different payload or context. Repeated copies of the same message
+     * aren't equivocations. Messages that already left the causal graph, because
+     * they are stable or stability isn't tracked, can't be compared.
+     *
+     * # Arguments
+     *
+     * `message` - Message received from a peer in the group.
+     *
+     * `fingerprint` - Fingerprint of the received message.
+     */
+    fn is_equivocation(&self, message: &Message, fingerprint: u64) -> bool {
+        match self.dot_to_index_map.get(&message.dot) {
+            Some(index) => {
+                let node = &self.G[*index];
+
+                match node.fingerprint {
+                    Some(node_fingerprint) => node_fingerprint != fingerprint,
+                    None => false,
+                }
+            }
+            None => false,
         }
     }
 
@@ -127,8 +397,52 @@ impl GRAPH {
      * # Arguments
      *
      * `message` - Message received from a peer in the group.
+     *
+     * `fingerprint` - Fingerprint of the message, calculated by the Reader thread.
      */
-    pub fn receive(&mut self, message: Message) {
+    pub fn receive(&mut self, message: Message, fingerprint: u64) {
+        self.hooks
+            .on_receive(message.dot.id, message.dot.counter, message.payload.len());
+
+        //Discarding messages with dots of ids outside the group
+        if message.dot.id >= self.peer_number
+            || message.context.iter().any(|dot| dot.id >= self.peer_number)
+        {
+            println!(
+                "ERROR: Discarded a message with an id outside the group - {:?}",
+                message.dot
+            );
+            return;
+        }
+
+        //Ignoring messages from evicted peers
+        if self.evicted[message.dot.id] {
+            return;
+        }
+
+        if self.received[message.dot.id] < message.dot.counter {
+            self.received[message.dot.id] = message.dot.counter;
+        }
+
+        //Discarding messages whose history doesn't match the received messages
+        #[cfg(feature = "bcb")]
+        {
+            if !self.validate_history(&message) {
+                return;
+            }
+        }
+
+        //Reporting a different message received with the dot of a known message
+        if self.is_equivocation(&message, fingerprint) {
+            let fault = Fault::Equivocation {
+                id: message.dot.id,
+                counter: message.dot.counter,
+            };
+
+            self.send_to_client(ClientMessage::Fault { fault });
+            return;
+        }
+
         //Comparing the peer's entry in the version vector to the message's dot counter
         if self.V[message.dot.id] < message.dot.counter {
             let received_message_index: usize;
@@ -150,43 +464,45 @@ impl GRAPH {
 
             //Checking the message's node stage
             if !(self.G[received_message_index].stage == Stage::RCV) {
-                //Calculating the message's predecessors indexes in the VecMap struct
-                //that aren't causally stable
-                let p_line: Vec<&Dot> = message
-                    .context
-                    .iter()
-                    .filter(|p| !Self::stable(self, &p))
-                    .collect();
+                self.missing.remove(&message.dot);
 
-                //let mut predecessors_indexes = SmallVec::<[usize; 4]>::new();
-                let smallvec_size = message.context.len();
-                let mut predecessors_indexes = SmallVec::with_capacity(smallvec_size);
+                let mut predecessors_indexes = self.pool.indexes(message.context.len());
 
                 //Creating and setting every position of the bstr to 0
-                let mut b = BV::default();
-                b.grow(self.peer_number, false);
+                let mut b = self.pool.bits(self.peer_number, false);
+
+                //Iterating over the message's causal predecessors that aren't stable,
+                //looking up each predecessor's index in the VecMap graph only once
+                for p in &message.context {
+                    let delivered = p.counter <= self.V[p.id];
 
-                //Iterating over the message's causal predecessors that aren't stable
-                for p in &p_line {
-                    let pred_index: usize;
+                    let pred_index: usize = match self.dot_to_index_map.entry(*p) {
+                        Entry::Occupied(entry) => {
+                            let graph_index = *entry.get();
 
-                    //Getting the predecessor's index in the VecMap graph
-                    match self.dot_to_index_map.get(p) {
-                        Some(graph_index) => {
-                            pred_index = *graph_index;
+                            if delivered && self.G[graph_index].stage == Stage::STB {
+                                continue;
+                            }
+
+                            graph_index
                         }
-                        None => {
+                        //Delivered dots without a node were stable and acked
+                        Entry::Vacant(_) if delivered => continue,
+                        Entry::Vacant(entry) => {
                             //If it doesn't exist add an empty node with stage SLT
-                            let p_dot = *p;
-                            let pred_empty_node = Node::new(*p_dot);
-
-                            //Adding the empty node to the causal graph
-                            pred_index = self.G.push(pred_empty_node);
+                            let pred_index = self.G.push(Node::new(*p));
 
                             //Adding the predecessor node (dot, index) to the hashmap
-                            self.dot_to_index_map.insert(*p_dot, pred_index);
+                            entry.insert(pred_index);
+
+                            //Watching the dependency until it's received
+                            if self.configuration.missing_dependency_timeout > 0 {
+                                self.missing.insert(*p, (Instant::now(), false));
+                            }
+
+                            pred_index
                         }
-                    }
+                    };
 
                     //Push the received message's graph index to the predessor's sucessors vec
                     let pred_temp_node = &mut self.G[pred_index];
@@ -205,75 +521,221 @@ impl GRAPH {
                 let received_temp_node = &mut self.G[received_message_index];
                 received_temp_node.bits = b;
                 received_temp_node.stage = Stage::RCV;
+                self.counts.pending += 1;
+                received_temp_node.fingerprint = Some(fingerprint);
+                received_temp_node.checksum = message.checksum;
                 received_temp_node.payload = Some(message.payload);
                 received_temp_node.context = Some(message.context);
+                received_temp_node.trace_id = message.trace_id;
+                received_temp_node.headers = message.headers;
+                received_temp_node.key = message.key;
+                received_temp_node.recipients = message.recipients;
+                received_temp_node.received_at = Some(Instant::now());
                 //Setting the predecessors graph indexes to the
                 //received message's predecessors vec
                 received_temp_node.predecessors = predecessors_indexes;
 
                 //Checking if the message's bstr is 0
                 if received_temp_node.bits.none() {
-                    //Calling the deliver function
-                    self.deliver(received_message_index);
+                    self.ready.push_back(received_message_index);
+                    self.deliver_ready();
                 }
             }
         }
     }
 
     /**
-     * Function that checks if a message is causally stable.
+     * Checks if every dot in a context was delivered, or was sent by an evicted
+     * peer, so a message from the Client with that context can be broadcast.
+     *
+     * # Arguments
+     *
+     * `context` - Context of a message from the Client.
+     */
+    pub fn is_delivered(&self, context: &[Dot]) -> bool {
+        context
+            .iter()
+            .all(|dot| dot.counter <= self.V[dot.id] || self.evicted[dot.id])
+    }
+
+    /**
+     * Function that checks if a message whose node was looked up in the graph is causally stable.
      *
      * A message is stable if the dot's counter is lower than the number in the version vector
-     * and if the dot doesn't exist in the domain of the causal graph.
+     * and its node has the STB stage. Dots without a node in the graph are stable once delivered.
+     */
+    fn is_stable_node(&self, dot: &Dot, index: usize) -> bool {
+        dot.counter <= self.V[dot.id] && self.G[index].stage == Stage::STB
+    }
+
+    /**
+     * Returns true if there are messages whose predecessors were delivered
+     * left for the next chunk of deliveries.
      */
-    fn stable(&mut self, dot: &Dot) -> bool {
-        match self.dot_to_index_map.get(dot) {
-            Some(index) => dot.counter <= self.V[dot.id] && self.G[*index].stage == Stage::STB,
-            None => dot.counter <= self.V[dot.id],
+    pub fn has_ready(&self) -> bool {
+        !self.paused && !self.ready.is_empty()
+    }
+
+    /**
+     * Pauses or resumes the deliveries to the Client. While paused, the received
+     * messages whose predecessors were delivered wait in the graph.
+     *
+     * # Arguments
+     *
+     * `paused` - Whether the deliveries are paused
+     */
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /**
+     * Delivers the messages whose predecessors were delivered, including the ones
+     * they unlock, up to `delivery_chunk_size` messages, so a long chain of
+     * deliveries doesn't hold back the Middleware thread.
+     */
+    pub fn deliver_ready(&mut self) {
+        if self.paused {
+            return;
+        }
+
+        let mut remaining = match self.configuration.delivery_chunk_size {
+            0 => usize::MAX,
+            chunk_size => chunk_size,
+        };
+
+        while remaining > 0 {
+            match self.ready.pop_front() {
+                Some(index) => self.deliver(index),
+                None => break,
+            }
+
+            remaining -= 1;
         }
+
+        self.flush_deliveries();
     }
 
     /**
      * Function that delivers a message to the client.
      *
-     * A message will be delivered when its predecessors have been delivered.
+     * A message will be delivered when its predecessors have been delivered,
+     * and the successors it unlocks are queued to be delivered next.
      */
     fn deliver(&mut self, msg_graph_index: usize) {
+        //Payloads that don't match their sender's checksum aren't delivered
+        if self.is_corrupted(msg_graph_index) {
+            self.keep_corrupted(msg_graph_index);
+            return;
+        }
+
+        self.counts.pending -= 1;
+        self.counts.delivered += 1;
+
         let delivered_node = &mut self.G[msg_graph_index];
+        let payload = delivered_node.payload.take().unwrap();
+
+        self.hooks.on_deliver(
+            delivered_node.dot.id,
+            delivered_node.dot.counter,
+            payload.len(),
+        );
 
-        // Building a Message struct to be sent
-        let delivered_message = ClientMessage::Delivery {
-            payload: delivered_node.payload.as_ref().unwrap().to_vec(),
-            dot: delivered_node.dot,
-            context: delivered_node.context.as_ref().unwrap().to_vec(),
+        //Messages sent to a subset of the group are skipped by the other peers
+        let is_recipient = match &delivered_node.recipients {
+            Some(recipients) => recipients.contains(&self.peer_index),
+            None => true,
         };
 
-        // Writing the message to the Client channel
-        self.client
-            .send(delivered_message)
-            .expect("ERROR: Failed to deliver a message to the Client");
+        let matches = is_recipient
+            && match &self.filter {
+                Some(filter) => filter(delivered_node.dot.id, delivered_node.dot.counter, &payload),
+                None => true,
+            };
+
+        self.sends.on_delivery(
+            &delivered_node.dot,
+            delivered_node.context.as_ref().unwrap(),
+            delivered_node.key,
+        );
+
+        //Keeping a copy of the message for the peers catching up with the group
+        let retained_message = match &self.retained {
+            Some(_) => {
+                let mut message = Message::new(
+                    payload.clone(),
+                    delivered_node.dot,
+                    delivered_node.context.clone().unwrap_or_default(),
+                    delivered_node.trace_id,
+                );
+                message.headers = delivered_node.headers.clone();
+                message.key = delivered_node.key;
+                message.recipients = delivered_node.recipients.clone();
+                message.checksum = delivered_node.checksum;
+                Some(message)
+            }
+            None => None,
+        };
+
+        // Building a Message struct to be sent, moving the payload and context
+        // to the Client instead of keeping them in the graph until stability.
+        // Filtered messages aren't pushed to the Client
+        let context = delivered_node.context.take().unwrap();
+
+        let delivered_message = if matches {
+            Some(ClientMessage::Delivery {
+                payload,
+                dot: delivered_node.dot,
+                context,
+                trace_id: delivered_node.trace_id,
+                headers: delivered_node.headers.take(),
+                key: delivered_node.key,
+            })
+        } else {
+            None
+        };
 
         //let temp_node = &mut self.G[msg_graph_index];
         let delivered_dot = delivered_node.dot;
+        let received_at = delivered_node.received_at;
+
+        //Keeping a copy of the delivery until the Client acks it
+        match (&mut self.acks, &delivered_message) {
+            (Some(acks), Some(message)) if !self.client_dropped => {
+                acks.push(delivered_dot.id, delivered_dot.counter, message.clone());
+            }
+            _ => {}
+        }
+
+        // Writing the message to the Client channel
+        if let Some(message) = delivered_message {
+            self.emit(message);
+        }
 
         let (j, n) = (delivered_dot.id, delivered_dot.counter);
 
         self.V[j] = n;
+        self.link_sender(msg_graph_index);
+
+        if let Some(message) = retained_message {
+            self.retain(message, received_at);
+        }
 
         if self.configuration.track_causal_stability {
+            //Reusing the delivered message's cleared bstr instead of replacing it
+            let delivered_node = &mut self.G[msg_graph_index];
             delivered_node.stage = Stage::DLV;
 
-            let mut b = BV::default();
-            b.grow(self.peer_number, true);
-            delivered_node.bits = b;
-            delivered_node.bits.set(self.peer_index, false);
-            delivered_node.bits.set(j, false);
-        }
+            let mut b = mem::take(&mut delivered_node.bits);
+
+            if b.len() == self.peer_number {
+                b.fill(true);
+            } else {
+                b.truncate(0);
+                b.grow(self.peer_number, true);
+            }
 
-        //Dropping the borrowing temp_node has on G before calling updatestability()
-        drop(delivered_node);
+            self.G[msg_graph_index].bits = self.stability_bits(j, b);
 
-        if self.configuration.track_causal_stability {
             //Updating the message's stability
             self.updatestability(j, msg_graph_index);
         }
@@ -290,7 +752,7 @@ impl GRAPH {
 
             //Check if the sucessor can be delivered
             if temp_successor_node.bits.none() {
-                self.deliver(s);
+                self.ready.push_back(s);
             }
         }
 
@@ -298,8 +760,63 @@ impl GRAPH {
             let temp_node = &self.G[msg_graph_index];
             let temp_node_dot = temp_node.dot;
 
-            self.deletestable(temp_node_dot);
+            self.delete_node(msg_graph_index, temp_node_dot);
+        }
+    }
+
+    /**
+     * Builds the bstr of a delivered message, where the positions still set to 1
+     * are the peers that must deliver a successor for the message to become stable.
+     * The local peer, the sender and the evicted peers are set to 0.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `b` - Bit string with every position set to 1, reused from the pool or the node
+     */
+    fn stability_bits(&self, j: usize, mut b: BV) -> BV {
+        b.difference(&self.evicted);
+        b.set(self.peer_index, false);
+        b.set(j, false);
+
+        b
+    }
+
+    /**
+     * Evicts a peer from the group. Further messages from the peer are ignored
+     * and the peer is no longer waited for when calculating the stability of
+     * delivered messages, which can make some of them stable. The Client is
+     * notified after the eviction.
+     *
+     * # Arguments
+     *
+     * `j` - Evicted peer's id
+     */
+    pub fn evict(&mut self, j: usize) {
+        self.evicted.set(j, true);
+        self.missing.retain(|dot, _| dot.id != j);
+
+        if self.configuration.track_causal_stability {
+            let graph_indexes: Vec<usize> = self.dot_to_index_map.values().cloned().collect();
+
+            for index in graph_indexes {
+                let temp_node: &mut Node = &mut self.G[index];
+
+                //The node may have been stabilized by a successor in a previous iteration
+                if temp_node.stage == Stage::DLV && temp_node.bits[j] {
+                    temp_node.bits.set(j, false);
+
+                    if temp_node.bits.none() {
+                        self.stabilize(index);
+                    }
+                }
+            }
         }
+
+        self.flush_deliveries();
+
+        self.send_to_client(ClientMessage::Evicted { id: j });
     }
 
     /**
@@ -336,38 +853,750 @@ impl GRAPH {
 
         let stable_node = &mut self.G[msg_idx];
         stable_node.stage = Stage::STB;
+        self.counts.stable += 1;
 
         let stable_msg = ClientMessage::Stable {
             dot: stable_node.dot,
         };
 
-        //Sending STABLE message to client
-        self.client
-            .send(stable_msg)
-            .expect("ERROR: Couldn't send a stable message to Client");
+        self.hooks
+            .on_stable(stable_node.dot.id, stable_node.dot.counter);
+
+        if let Some(retained) = &mut self.retained {
+            retained.mark_stable(stable_node.dot.id, stable_node.dot.counter);
+        }
+
+        //Sending STABLE message to client, after the batched deliveries
+        //if they share the same channel
+        if self.shares_stable_channel() {
+            self.emit(stable_msg);
+        } else {
+            self.send_stable(stable_msg);
+        }
+    }
+
+    /**
+     * Checks if the payload of a received message sent to the local peer
+     * doesn't match its sender's checksum.
+     *
+     * # Arguments
+     *
+     * `index` - Graph index of the message whose predecessors were delivered
+     */
+    fn is_corrupted(&self, index: usize) -> bool {
+        let node = &self.G[index];
+
+        let is_recipient = match &node.recipients {
+            Some(recipients) => recipients.contains(&self.peer_index),
+            None => true,
+        };
+
+        match (node.checksum, &node.payload) {
+            (Some(expected), Some(payload)) => {
+                is_recipient && checksum::checksum(payload) != expected
+            }
+            _ => false,
+        }
+    }
+
+    /**
+     * Drops the contents of a message whose payload doesn't match its checksum,
+     * turning its node back into a dependency that wasn't received. Its successors
+     * stay blocked until a copy is received, which the Middleware thread asks its
+     * sender to retransmit.
+     *
+     * # Arguments
+     *
+     * `index` - Graph index of the corrupted message
+     */
+    fn keep_corrupted(&mut self, index: usize) {
+        let node = &mut self.G[index];
+        let dot = node.dot;
+
+        node.stage = Stage::SLT;
+        self.counts.pending -= 1;
+        node.payload = None;
+        node.context = None;
+        node.fingerprint = None;
+        node.checksum = None;
+        node.trace_id = None;
+        node.headers = None;
+        node.key = None;
+        node.recipients = None;
+        node.received_at = None;
+
+        //The copy links the node to its predecessors again once it's received
+        let predecessors = mem::take(&mut node.predecessors);
+
+        for p in predecessors {
+            self.G[p].successors.retain(|s| *s != index);
+        }
+
+        println!(
+            "ERROR: {} dropped the corrupted payload of {:?} until it's received again",
+            self.peer_index, dot
+        );
+
+        //Watching the message like any other dependency that wasn't received
+        if self.configuration.missing_dependency_timeout > 0 {
+            self.missing.insert(dot, (Instant::now(), false));
+        }
+
+        self.corrupted.push(dot);
+
+        let fault = Fault::CorruptedPayload {
+            id: dot.id,
+            counter: dot.counter,
+        };
+
+        self.send_to_client(ClientMessage::Fault { fault });
+    }
+
+    /**
+     * Returns the corrupted messages kept pending since the last call, whose
+     * senders are asked by the Middleware thread to retransmit them.
+     */
+    pub fn take_corrupted(&mut self) -> Vec<Dot> {
+        mem::take(&mut self.corrupted)
+    }
+
+    /**
+     * Checks if the stable messages are sent in the channel of the deliveries.
+     */
+    fn shares_stable_channel(&self) -> bool {
+        !(self.configuration.separate_stable_channel || self.configuration.auto_stable_ack)
+    }
+
+    /**
+     * Sends a delivered or stable message to the Client, or adds it to the current
+     * batch of deliveries, which is sent once it reaches the maximum batch size.
+     *
+     * # Arguments
+     *
+     * `message` - Message sent to the Client.
+     */
+    fn emit(&mut self, message: ClientMessage) {
+        if self.configuration.delivery_batch_size <= 1 {
+            self.send_to_client(message);
+            return;
+        }
+
+        self.deliveries.push(message);
 
-        drop(stable_node);
+        if self.deliveries.len() >= self.configuration.delivery_batch_size {
+            self.flush_deliveries();
+        }
+    }
+
+    /**
+     * Sends a message to the Client. Once the Client dropped its receiver, the
+     * message is dropped and the middleware follows the dropped client policy.
+     * Deliveries go through `emit` instead, so they're batched.
+     *
+     * # Arguments
+     *
+     * `message` - Message sent to the Client.
+     */
+    pub fn send_to_client(&mut self, message: ClientMessage) {
+        if self.client_dropped {
+            self.drop_message(message);
+            return;
+        }
+
+        match self.client.send(message) {
+            Ok(_) => {}
+            Err(e) => {
+                println!(
+                    "WARN: The Client of {} dropped its receiver, applying the {:?} policy",
+                    self.peer_index, self.configuration.dropped_client
+                );
+
+                self.client_dropped = true;
+                self.drop_message(e.0);
+            }
+        }
+    }
+
+    /**
+     * Sends a stable message to the Client in the channel of the stable messages.
+     *
+     * # Arguments
+     *
+     * `message` - Stable message sent to the Client.
+     */
+    fn send_stable(&mut self, message: ClientMessage) {
+        if self.stable_dropped {
+            self.drop_message(message);
+            return;
+        }
+
+        match self.stable_client.send(message) {
+            Ok(_) => {}
+            Err(e) => {
+                println!(
+                    "WARN: The Client of {} dropped its stable receiver, applying the {:?} policy",
+                    self.peer_index, self.configuration.dropped_client
+                );
+
+                self.stable_dropped = true;
+                self.drop_message(e.0);
+            }
+        }
+    }
+
+    /**
+     * Drops a message the Client can no longer receive, keeping the dots of
+     * the stable messages to be removed if the policy retains the messages.
+     *
+     * # Arguments
+     *
+     * `message` - Message that wasn't received by the Client.
+     */
+    fn drop_message(&mut self, message: ClientMessage) {
+        if self.configuration.dropped_client != DroppedClientPolicy::Retain {
+            return;
+        }
+
+        match message {
+            ClientMessage::Stable { dot } => self.orphaned_stable.push(dot),
+            ClientMessage::Deliveries { messages } => {
+                for message in messages {
+                    self.drop_message(message);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /**
+     * Checks if the Client dropped the receiver of the deliveries or of the stable messages.
+     */
+    pub fn is_client_dropped(&self) -> bool {
+        self.client_dropped || self.stable_dropped
+    }
+
+    /**
+     * Returns the stable messages the Client can no longer ack, which are
+     * removed by the Middleware thread as if the Client acked them.
+     */
+    pub fn take_orphaned_stable(&mut self) -> Vec<Dot> {
+        mem::take(&mut self.orphaned_stable)
+    }
+
+    /**
+     * Marks a delivery as processed by the Client, so it's no longer sent again.
+     *
+     * # Arguments
+     *
+     * `dot` - Dot of the delivered message acked by the Client.
+     */
+    pub fn ack(&mut self, dot: Dot) {
+        if let Some(acks) = &mut self.acks {
+            if !acks.ack(dot.id, dot.counter) {
+                println!(
+                    "ERROR: Acked a delivery that isn't pending - ({}, {})",
+                    dot.id, dot.counter
+                );
+            }
+        }
+    }
+
+    /**
+     * Sends the deliveries the Client didn't ack again, in the order they were
+     * delivered, which are still pending until they are acked.
+     */
+    pub fn replay_unacked(&mut self) {
+        let unacked: Vec<ClientMessage> = match &self.acks {
+            Some(acks) => acks.unacked().cloned().collect(),
+            None => return,
+        };
+
+        for message in unacked {
+            self.emit(message);
+        }
+
+        self.flush_deliveries();
+    }
+
+    /**
+     * Sends the current batch of deliveries to the Client in one channel message,
+     * or on its own if it has a single message.
+     */
+    fn flush_deliveries(&mut self) {
+        let message = match self.deliveries.len() {
+            0 => return,
+            1 => self.deliveries.pop().unwrap(),
+            _ => ClientMessage::Deliveries {
+                messages: mem::take(&mut self.deliveries),
+            },
+        };
+
+        self.send_to_client(message);
     }
 
     /**
      * Softly deletes an acked stable message by marking its position in the array available.
+     * Acks of unknown or already deleted messages, and of messages that aren't stable, are ignored.
      *
      * # Arguments
      *
      * `dot` - Dot acked as stable by the Client.
      */
     pub fn deletestable(&mut self, dot: Dot) {
-        let dot_graph_index = self.dot_to_index_map.get(&dot).unwrap();
+        let dot_graph_index = match self.dot_to_index_map.get(&dot) {
+            Some(index) if self.G[*index].stage == Stage::STB => *index,
+            Some(_) => {
+                println!(
+                    "WARN: {} ignored the stable ack of {:?}, which isn't stable",
+                    self.peer_index, dot
+                );
+                return;
+            }
+            None => {
+                println!(
+                    "WARN: {} ignored the stable ack of {:?}, which isn't in the causal graph",
+                    self.peer_index, dot
+                );
+                return;
+            }
+        };
 
-        let successors_indexes = unsafe { &*(&self.G[*dot_graph_index].successors as *const _) };
+        self.delete_node(dot_graph_index, dot);
+    }
+
+    /**
+     * Deletes a node from the causal graph, keeping its buffers for the next nodes.
+     *
+     * # Arguments
+     *
+     * `dot_graph_index` - Graph index of the deleted node.
+     *
+     * `dot` - Dot of the deleted node.
+     */
+    fn delete_node(&mut self, dot_graph_index: usize, dot: Dot) {
+        let successors_indexes = unsafe { &*(&self.G[dot_graph_index].successors as *const _) };
 
         for &s in successors_indexes {
             let predecessor: &mut Node = &mut self.G[s];
             let predecessors_indexes = &mut predecessor.predecessors;
-            predecessors_indexes.retain(|idx| idx != dot_graph_index);
+            predecessors_indexes.retain(|idx| *idx != dot_graph_index);
         }
 
-        self.G.remove(*dot_graph_index);
+        self.unlink_sender(dot_graph_index);
+
+        //Keeping the node's buffers for the next nodes added to the graph
+        self.pool.recycle(&mut self.G[dot_graph_index]);
+
+        self.G.remove(dot_graph_index);
         self.dot_to_index_map.remove(&dot);
     }
+
+    /**
+     * Appends a delivered node to the list of its sender's nodes. The messages
+     * of each sender are delivered in the order of their counters, so the list
+     * stays sorted without searching it.
+     *
+     * # Arguments
+     *
+     * `index` - Graph index of the delivered node
+     */
+    fn link_sender(&mut self, index: usize) {
+        let j = self.G[index].dot.id;
+
+        match self.sender_last[j] {
+            Some(last) => self.G[last].sender_next = Some(index),
+            None => self.sender_first[j] = Some(index),
+        }
+
+        let node = &mut self.G[index];
+        node.sender_previous = self.sender_last[j];
+        node.sender_next = None;
+
+        self.sender_last[j] = Some(index);
+    }
+
+    /**
+     * Removes a node from the list of its sender's nodes before it's deleted.
+     *
+     * # Arguments
+     *
+     * `index` - Graph index of the deleted node
+     */
+    fn unlink_sender(&mut self, index: usize) {
+        let node = &self.G[index];
+        let (j, previous, next) = (node.dot.id, node.sender_previous, node.sender_next);
+
+        //Nodes that were never delivered aren't in the list
+        if previous.is_none() && self.sender_first[j] != Some(index) {
+            return;
+        }
+
+        match previous {
+            Some(previous) => self.G[previous].sender_next = next,
+            None => self.sender_first[j] = next,
+        }
+
+        match next {
+            Some(next) => self.G[next].sender_previous = previous,
+            None => self.sender_last[j] = previous,
+        }
+    }
+
+    /**
+     * Returns the dot of the previous delivered message from the same sender as a
+     * message, if both are still in the graph, without looking up the map of dots.
+     *
+     * # Arguments
+     *
+     * `dot` - Dot of a delivered message
+     */
+    pub fn previous_from_sender(&self, dot: &Dot) -> Option<Dot> {
+        let index = *self.dot_to_index_map.get(dot)?;
+
+        self.G[index]
+            .sender_previous
+            .map(|previous| self.G[previous].dot)
+    }
+
+    /**
+     * Returns the dots of the delivered messages of a sender that are still in the
+     * graph, in the order of their counters.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     */
+    pub fn sender_dots(&self, j: usize) -> Vec<Dot> {
+        let mut dots: Vec<Dot> = Vec::new();
+        let mut next = self.sender_first.get(j).copied().flatten();
+
+        while let Some(index) = next {
+            dots.push(self.G[index].dot);
+            next = self.G[index].sender_next;
+        }
+
+        dots
+    }
+
+    /**
+     * Adds a delivered message to the retention log, if messages are retained,
+     * and removes the oldest messages that no longer need to be kept.
+     *
+     * # Arguments
+     *
+     * `message` - Delivered or sent message.
+     *
+     * `received_at` - Instant when the message was received, `None` if it was sent by the local peer
+     */
+    fn retain(&mut self, message: Message, received_at: Option<Instant>) {
+        let V = &self.V;
+        let G = &self.G;
+        let dot_to_index_map = &self.dot_to_index_map;
+
+        if let Some(retained) = &mut self.retained {
+            retained.push(
+                message.dot.id,
+                message.dot.counter,
+                message.payload.len(),
+                received_at,
+                message,
+            );
+
+            //A message is stable once its node was deleted or has stage STB
+            retained.prune(|id, counter| {
+                counter <= V[id]
+                    && match dot_to_index_map.get(&Dot::new(id, counter)) {
+                        Some(index) => G[*index].stage == Stage::STB,
+                        None => true,
+                    }
+            });
+        }
+    }
+
+    /**
+     * Returns the retained messages above a peer's delivered version vector, in
+     * a causal order, or `None` if messages aren't retained or some message above
+     * the version vector was already removed from the retention log.
+     *
+     * # Arguments
+     *
+     * `vector` - Delivered version vector of the peer catching up
+     */
+    pub fn retained_above(&self, vector: &[usize]) -> Option<Vec<Message>> {
+        match &self.retained {
+            Some(retained) if vector.len() == self.peer_number && retained.covers(vector) => {
+                Some(retained.above(vector).cloned().collect())
+            }
+            _ => None,
+        }
+    }
+
+    /**
+     * Explains why a message wasn't delivered yet from the state of the causal
+     * graph. A received message waits for its predecessors that weren't delivered.
+     *
+     * # Arguments
+     *
+     * `dot` - Dot of the explained message.
+     */
+    pub fn explain(&self, dot: Dot) -> Explanation {
+        if dot.id >= self.peer_number {
+            return Explanation::NotReceived;
+        }
+
+        if dot.counter <= self.V[dot.id] {
+            return Explanation::Delivered;
+        }
+
+        if self.evicted[dot.id] {
+            return Explanation::Evicted;
+        }
+
+        //Dependencies that weren't received only have an SLT node
+        let node = match self.dot_to_index_map.get(&dot) {
+            Some(index) if self.G[*index].stage == Stage::RCV => &self.G[*index],
+            _ => return Explanation::NotReceived,
+        };
+
+        let dependencies: Vec<PendingDependency> = node
+            .predecessors
+            .iter()
+            .map(|&p| &self.G[p])
+            .filter(|predecessor| {
+                predecessor.stage == Stage::SLT || predecessor.stage == Stage::RCV
+            })
+            .map(|predecessor| PendingDependency {
+                id: predecessor.dot.id,
+                first: predecessor.dot.counter,
+                last: predecessor.dot.counter,
+                received: predecessor.stage == Stage::RCV,
+            })
+            .collect();
+
+        if dependencies.is_empty() {
+            Explanation::Ready
+        } else {
+            Explanation::Waiting { dependencies }
+        }
+    }
+
+    /**
+     * Serializes the nodes of the causal graph with their stages, bit strings and
+     * edges, from each node to its successors, in the order of their graph indexes.
+     *
+     * # Arguments
+     *
+     * `format` - Format of the serialized graph.
+     */
+    pub fn render(&self, format: GraphFormat) -> String {
+        let mut indexes: Vec<usize> = self.dot_to_index_map.values().cloned().collect();
+        indexes.sort_unstable();
+
+        let mut rendered = String::new();
+
+        match format {
+            GraphFormat::Dot => {
+                rendered.push_str("digraph causal_graph {\n");
+
+                for &index in &indexes {
+                    let node = &self.G[index];
+
+                    rendered.push_str(&format!(
+                        "    \"{}.{}\" [label=\"({}, {})\\n{:?}\"];\n",
+                        node.dot.id, node.dot.counter, node.dot.id, node.dot.counter, node.stage
+                    ));
+
+                    for &successor in &node.successors {
+                        let successor_dot = self.G[successor].dot;
+
+                        rendered.push_str(&format!(
+                            "    \"{}.{}\" -> \"{}.{}\";\n",
+                            node.dot.id, node.dot.counter, successor_dot.id, successor_dot.counter
+                        ));
+                    }
+                }
+
+                rendered.push_str("}\n");
+            }
+            GraphFormat::Json => {
+                let nodes: Vec<String> = indexes
+                    .iter()
+                    .map(|&index| {
+                        let node = &self.G[index];
+
+                        //Peers set in the bit string, which are the senders of the undelivered
+                        //predecessors of a received message, or the peers a delivered message
+                        //waits for to be stable
+                        let bits: Vec<String> = node
+                            .bits
+                            .iter()
+                            .enumerate()
+                            .filter(|(_, bit)| *bit)
+                            .map(|(peer_id, _)| peer_id.to_string())
+                            .collect();
+
+                        let successors: Vec<String> = node
+                            .successors
+                            .iter()
+                            .map(|&successor| {
+                                let dot = self.G[successor].dot;
+                                format!("[{},{}]", dot.id, dot.counter)
+                            })
+                            .collect();
+
+                        format!(
+                            "{{\"index\":{},\"id\":{},\"counter\":{},\"stage\":\"{:?}\",\"bits\":[{}],\"successors\":[{}]}}",
+                            index,
+                            node.dot.id,
+                            node.dot.counter,
+                            node.stage,
+                            bits.join(","),
+                            successors.join(",")
+                        )
+                    })
+                    .collect();
+
+                let delivered: Vec<String> = self.V.iter().map(|c| c.to_string()).collect();
+
+                rendered.push_str(&format!(
+                    "{{\"delivered\":[{}],\"nodes\":[{}]}}",
+                    delivered.join(","),
+                    nodes.join(",")
+                ));
+            }
+        }
+
+        rendered
+    }
+
+    /**
+     * Returns the timestamps of the retained messages, which are empty if messages aren't retained.
+     */
+    pub fn event_timestamps(&self) -> Vec<MessageTimestamps> {
+        match &self.retained {
+            Some(retained) => retained.timestamps(),
+            None => Vec::new(),
+        }
+    }
+
+    /**
+     * Returns the statistics of the retention log, which are empty if messages aren't retained.
+     */
+    pub fn retention_stats(&self) -> RetentionMetrics {
+        match &self.retained {
+            Some(retained) => retained.stats(),
+            None => RetentionMetrics::default(),
+        }
+    }
+
+    /**
+     * Returns the numbers of the sent, delivered, stable and pending messages.
+     */
+    pub fn message_counts(&self) -> MessageCounts {
+        self.counts
+    }
+
+    /**
+     * Returns the version vector of the delivered messages, including the local ones.
+     */
+    pub fn delivered_vector(&self) -> Vec<usize> {
+        self.V.clone()
+    }
+
+    /**
+     * Returns the highest counter received from each peer and the version vector
+     * of the delivered messages, whose difference is the delivery lag of each peer.
+     */
+    pub fn watermarks(&self) -> (&[usize], &[usize]) {
+        (&self.received, &self.V)
+    }
+
+    /**
+     * Reports a local message that wasn't sent to a peer, or to any peer, to the Client.
+     *
+     * # Arguments
+     *
+     * `dot` - Dot of the local message.
+     *
+     * `peer_id` - Peer the message wasn't sent to, or `None` if it wasn't sent to any peer.
+     *
+     * `error` - Description of the error.
+     */
+    pub fn send_failed(&mut self, dot: Dot, peer_id: Option<usize>, error: String) {
+        let failure = SendFailure {
+            id: dot.id,
+            counter: dot.counter,
+            peer_id,
+            error,
+        };
+
+        self.send_to_client(ClientMessage::SendFailed { failure });
+    }
+
+    /**
+     * Sets or removes the predicate that selects the messages delivered to the Client.
+     *
+     * # Arguments
+     *
+     * `filter` - Predicate over the sender id, message id and payload.
+     */
+    pub fn set_filter(&mut self, filter: Option<DeliveryFilter>) {
+        self.filter = filter;
+    }
+
+    /**
+     * Returns the statistics of the pool of buffers reused by the graph's nodes.
+     */
+    pub fn pool_stats(&self) -> PoolMetrics {
+        self.pool.stats()
+    }
+
+    /**
+     * Reports to the Client every dependency that wasn't received within the
+     * configured timeout, alongside the received messages it blocks. Each
+     * dependency is only reported once and the reported dots are returned.
+     */
+    pub fn check_missing(&mut self) -> Vec<Dot> {
+        let timeout = self.configuration.get_missing_dependency_timeout();
+        let mut reported: Vec<Dot> = Vec::new();
+
+        for (dot, (since, notified)) in self.missing.iter_mut() {
+            if *notified || since.elapsed() < timeout {
+                continue;
+            }
+
+            *notified = true;
+            reported.push(*dot);
+        }
+
+        for dot in &reported {
+            let blocked: Vec<(usize, usize)> = match self.dot_to_index_map.get(dot) {
+                Some(index) => self.G[*index]
+                    .successors
+                    .iter()
+                    .map(|&s| &self.G[s])
+                    .filter(|node| node.stage == Stage::RCV)
+                    .map(|node| (node.dot.id, node.dot.counter))
+                    .collect(),
+                None => Vec::new(),
+            };
+
+            let fault = Fault::MissingDependency {
+                id: dot.id,
+                counter: dot.counter,
+                blocked,
+            };
+
+            self.send_to_client(ClientMessage::Fault { fault });
+        }
+
+        reported
+    }
+
+    /**
+     * Returns the occupancy statistics of the causal graph.
+     */
+    pub fn graph_stats(&self) -> GraphMetrics {
+        self.G.stats()
+    }
 }
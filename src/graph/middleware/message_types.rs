@@ -1,4 +1,11 @@
-use super::dot::Dot;
+use crate::broadcast::broadcast_trait::{Fault, Headers, SendFailure, TcbError};
+use crate::clock::dot::Dot;
+
+/**
+ * Dot and context assigned by the Middleware to a message sent by the Client,
+ * or the error that kept it from being sent.
+ */
+pub type SentMessage = Result<(Dot, Vec<Dot>), TcbError>;
 
 /**
  * Enum that will be sent by the Middleware to the Client.
@@ -7,12 +14,29 @@ use super::dot::Dot;
 pub enum ClientMessage {
     ///Empty variation
     Empty,
-    ///Delivered message with its payload, dot and context
+    ///Delivered message with its payload, dot, context, trace id, headers and entity key
     Delivery {
         payload: Vec<u8>,
         dot: Dot,
         context: Vec<Dot>,
+        trace_id: Option<u128>,
+        headers: Option<Headers>,
+        key: Option<u64>,
     },
+    ///Delivered and stable messages unlocked by the same received message,
+    ///in the order they were delivered
+    Deliveries { messages: Vec<ClientMessage> },
     ///Stable message with its dot
     Stable { dot: Dot },
+    ///Peer that was evicted from the group
+    Evicted { id: usize },
+    ///Protocol fault detected in the messages of a peer
+    Fault { fault: Fault },
+    ///Local message that wasn't sent to some or every peer
+    SendFailed { failure: SendFailure },
+    ///Delivered version vector of the middleware, following every delivery up to it
+    Snapshot { cut: Vec<usize> },
+    ///Delivered version vector once the messages relayed by a donor were delivered,
+    ///or `None` if the donor couldn't relay them
+    CaughtUp { cut: Option<Vec<usize>> },
 }
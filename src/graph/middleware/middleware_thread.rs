@@ -1,112 +1,1365 @@
-use super::dot::Dot;
 use super::graph::GRAPH;
 use super::message_types::ClientMessage;
-use crate::configuration::middleware_configuration::Configuration;
+use crate::broadcast::broadcast_trait::TcbError;
+use crate::broadcast::hooks::Hooks;
+use crate::broadcast::inbound::{self, InboundQueues};
+use crate::broadcast::polling::BusyPoll;
+use crate::broadcast::receipts::DeliveryReceipts;
+use crate::channel::{Receiver, RecvTimeoutError, Sender};
+use crate::clock::dot::Dot;
+use crate::configuration::middleware_configuration::{
+    is_interested, Configuration, DroppedClientPolicy, KeyRange, WireEncoding,
+};
 use crate::graph::structs::message::Message;
 use crate::graph::structs::message_type::ClientPeerMiddleware;
-use bincode::serialize;
-use crossbeam::{Receiver, Sender};
+use crate::metrics::middleware_metrics::MiddlewareMetrics;
+use crate::platform::threads::apply_thread_settings;
+use crate::transport::messages::MiddlewareSender;
+use crate::transport::{checksum, encoding};
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::net::{Shutdown, TcpStream};
 use std::sync::{Arc, Barrier};
+use std::time::Instant;
+
+#[cfg(feature = "signing")]
+use crate::graph::communication::handshake;
+#[cfg(feature = "signing")]
+use crate::security::message_signing::MessageSigner;
+#[cfg(feature = "encryption")]
+use crate::security::payload_encryption::PayloadCipher;
+#[cfg(feature = "otel")]
+use crate::telemetry::otel::Telemetry;
 
 /**
- * Starts the Middleware thread that receives messages from the Client to
- * be broadcast, receives messages from other peers and handles the delivery
- * of messages to the Client.
- *
- * # Arguments
- *
- * `local_id` - Local peer's globally unique id.
- *
- * `peer_addresses` - Addresses the middleware will connect to.
- *
- * `receive_channel` - Channel where the middleware will receive messages from the Client and Peers.
- *
- * `client` - Channel where the middleware will send delivered/stable messages to the Client.
- *
- * `peer_channels` - Channels to the Sender threads to send broadcast messages.
- *
- * `configuration` - Middleware's configuration file.
- */
+* Configuration and handles shared between the Client and the Middleware thread.
+*/
+pub struct MiddlewareContext {
+    ///Middleware's configuration file
+    pub configuration: Arc<Configuration>,
+    ///Metrics shared with the Client
+    pub metrics: Arc<MiddlewareMetrics>,
+    ///Callbacks fired when messages are sent, received, delivered and stable
+    pub hooks: Arc<dyn Hooks>,
+}
+
+/**
+* Starts the Middleware thread that receives messages from the Client to
+* be broadcast, receives messages from other peers and handles the delivery
+* of messages to the Client.
+*
+* # Arguments
+*
+* `local_id` - Local peer's globally unique id.
+*
+* `peer_addresses` - Addresses the middleware will connect to.
+*
+* `receive_channel` - Channel where the middleware will receive messages from the Client and Peers.
+*
+* `client` - Channel where the middleware will send delivered/stable messages to the Client.
+*
+* `stable_client` - Channel where the middleware will send stable messages to the Client.
+
+*
+* `peer_channels` - Channels to the Sender threads to send broadcast messages.
+*
+* `context` - Configuration and handles shared with the Client.
+*/
 pub fn start(
     local_id: usize,
     peer_addresses: Vec<String>,
     receive_channel: Receiver<ClientPeerMiddleware>,
     client: Sender<ClientMessage>,
-    peer_channels: Vec<Sender<(Arc<Barrier>, Arc<Vec<u8>>)>>,
-    configuration: Arc<Configuration>,
+    stable_client: Sender<ClientMessage>,
+    mut peer_channels: Vec<Sender<MiddlewareSender>>,
+    context: MiddlewareContext,
 ) {
+    let MiddlewareContext {
+        configuration,
+        metrics,
+        hooks,
+    } = context;
+
+    apply_thread_settings(&configuration.threads.middleware);
+
+    //Ids of the peers connected to each channel in peer_channels
+    let mut channel_peer_ids: Vec<usize> = (0..peer_channels.len())
+        .map(|i| if i < local_id { i } else { i + 1 })
+        .collect();
+
+    //Streams read by the Reader threads, used to close the connections
+    let mut reader_streams: HashMap<usize, TcpStream> = HashMap::new();
+
+    //Interest sets announced by the peers in their handshakes
+    let mut peer_interests: HashMap<usize, Vec<KeyRange>> = HashMap::new();
+
+    #[cfg(feature = "encryption")]
+    let cipher = PayloadCipher::from_configuration(&configuration);
+
+    #[cfg(feature = "signing")]
+    let signer = MessageSigner::from_configuration(&configuration);
+
+    //OpenTelemetry instruments of the send events, recorded once the dots are assigned
+    #[cfg(feature = "otel")]
+    let telemetry = Telemetry::new(local_id);
+
+    let relay = RelayContext {
+        local_id,
+        configuration: &configuration,
+        #[cfg(feature = "encryption")]
+        cipher: &cipher,
+        #[cfg(feature = "signing")]
+        signer: &signer,
+    };
+
     let mut tcb = GRAPH::new(
         local_id,
         peer_addresses.len() + 1,
         client.clone(),
+        stable_client,
         Arc::clone(&configuration),
+        hooks,
     );
 
+    //Messages from the Client waiting for the delivery of their dependencies
+    let mut pending: VecDeque<Message> = VecDeque::new();
+
+    //Messages received from each peer, handled in a round-robin order
+    let mut inbound = if configuration.fair_inbound {
+        Some(InboundQueues::new(peer_addresses.len() + 1))
+    } else {
+        None
+    };
+
+    //Spins on the channel before parking, if busy polling is enabled
+    let mut poller = BusyPoll::from_configuration(&configuration);
+
+    //Delivered version vector of the donor the middleware is catching up with
+    let mut catching_up: Option<Vec<usize>> = None;
+
+    //Donor asked by the Client for its messages, until it sends its cut
+    let mut donor: Option<usize> = None;
+
+    //Peers asked to retransmit the messages above the delivered version vector
+    let mut retransmitting: Vec<usize> = Vec::new();
+
+    //Last time the missing dependencies were checked
+    let mut last_missing_check = Instant::now();
+
+    //Receipts of the delivered messages, if they are sent back to their senders
+    let mut receipts =
+        DeliveryReceipts::from_configuration(&configuration, peer_addresses.len() + 1);
+
+    //Peers that closed their connections
+    let mut closed = vec![false; peer_addresses.len() + 1];
+
+    //Instant when the shutdown ends, once the Client ended the middleware
+    let mut draining: Option<Instant> = None;
+
     loop {
-        match receive_channel.recv() {
-            Ok(ClientPeerMiddleware::Client { dot, msg, context }) => {
-                handle_message_from_client(&mut tcb, msg, &peer_channels, context, dot);
+        //Delivering the next chunk of a chain of deliveries unlocked by a received
+        //message, between the handling of the other messages
+        if tcb.has_ready() {
+            tcb.deliver_ready();
+
+            if catching_up.is_some() {
+                check_caught_up(&mut tcb, &mut catching_up);
             }
-            Ok(ClientPeerMiddleware::Peer { msg }) => {
-                tcb.receive(msg);
+
+            if !pending.is_empty() {
+                send_ready_messages(
+                    &mut tcb,
+                    &mut pending,
+                    &peer_channels,
+                    &channel_peer_ids,
+                    &configuration,
+                    #[cfg(feature = "encryption")]
+                    &cipher,
+                    #[cfg(feature = "signing")]
+                    &signer,
+                );
             }
-            Ok(ClientPeerMiddleware::Setup) => {}
-            Ok(ClientPeerMiddleware::Stable { dot }) => {
-                tcb.deletestable(dot);
+        }
+
+        //Only waiting for a message once the chain was delivered
+        let has_messages = !receive_channel.is_empty()
+            || match &inbound {
+                Some(queues) => !queues.is_empty(),
+                None => false,
+            };
+
+        if !tcb.has_ready() || has_messages {
+            //Waking up to check the missing dependencies even if no message arrives
+            if configuration.missing_dependency_timeout > 0 {
+                let watchdog = Instant::now() + configuration.get_missing_dependency_timeout() / 4;
+
+                poller.set_deadline(Some(
+                    draining.map_or(watchdog, |deadline| deadline.min(watchdog)),
+                ));
             }
-            Ok(ClientPeerMiddleware::End) => {
-                handle_finished_setup(&client);
-                break;
+
+            let next_message =
+                inbound::next_message(&receive_channel, &mut inbound, &mut poller, |message| {
+                    match message {
+                        ClientPeerMiddleware::Peer { msg, .. } => Some(msg.dot.id),
+                        _ => None,
+                    }
+                });
+
+            match next_message {
+                Ok(ClientPeerMiddleware::Client {
+                    msg,
+                    mut deps,
+                    trace_id,
+                    headers,
+                    key,
+                    recipients,
+                    reply,
+                }) => {
+                    //Discarding the dependencies with ids outside the group
+                    let peer_number = peer_addresses.len() + 1;
+
+                    if deps.iter().any(|dot| dot.id >= peer_number) {
+                        println!("ERROR: Discarded dependencies with ids outside the group");
+                        deps.retain(|dot| dot.id < peer_number);
+                    }
+
+                    //Assigning the message's dot and context, which are returned through
+                    //the receipt of the send call, unless the Client dropped it
+                    let (dot, context) = match tcb.next_send(key, &deps) {
+                        Some(sent) => sent,
+                        None => {
+                            println!("ERROR: The message counter of {} is exhausted", local_id);
+                            let _ = reply.send(Err(TcbError::CounterExhausted));
+                            continue;
+                        }
+                    };
+
+                    let _ = reply.send(Ok((dot, context.clone())));
+
+                    #[cfg(feature = "otel")]
+                    telemetry.on_send(dot.id, dot.counter, msg.len(), trace_id);
+
+                    //Creating a new struct Message
+                    let mut message = Message::new(msg, dot, context, trace_id);
+                    message.headers = headers;
+                    message.key = key;
+                    message.recipients = match (recipients, key) {
+                        (None, Some(key)) => {
+                            interested_peers(key, &peer_interests, &channel_peer_ids)
+                        }
+                        (recipients, _) => recipients,
+                    };
+                    pending.push_back(message);
+
+                    send_ready_messages(
+                        &mut tcb,
+                        &mut pending,
+                        &peer_channels,
+                        &channel_peer_ids,
+                        &configuration,
+                        #[cfg(feature = "encryption")]
+                        &cipher,
+                        #[cfg(feature = "signing")]
+                        &signer,
+                    );
+                }
+                Ok(ClientPeerMiddleware::Peer { msg, fingerprint }) => {
+                    tcb.receive(msg, fingerprint);
+
+                    if catching_up.is_some() {
+                        check_caught_up(&mut tcb, &mut catching_up);
+                    }
+
+                    if !pending.is_empty() {
+                        send_ready_messages(
+                            &mut tcb,
+                            &mut pending,
+                            &peer_channels,
+                            &channel_peer_ids,
+                            &configuration,
+                            #[cfg(feature = "encryption")]
+                            &cipher,
+                            #[cfg(feature = "signing")]
+                            &signer,
+                        );
+                    }
+                }
+                Ok(ClientPeerMiddleware::Setup) => {}
+                Ok(ClientPeerMiddleware::Stable { dot }) => {
+                    tcb.deletestable(dot);
+                }
+                Ok(ClientPeerMiddleware::Flush) => {
+                    handle_flush_from_client(&peer_channels);
+                }
+                Ok(ClientPeerMiddleware::Pause) => {
+                    tcb.set_paused(true);
+                }
+                Ok(ClientPeerMiddleware::Resume) => {
+                    tcb.set_paused(false);
+                }
+                Ok(ClientPeerMiddleware::Ack { dot }) => {
+                    tcb.ack(dot);
+                }
+                Ok(ClientPeerMiddleware::Replay) => {
+                    tcb.replay_unacked();
+                }
+                Ok(ClientPeerMiddleware::Filter { filter }) => {
+                    tcb.set_filter(filter);
+                }
+                Ok(ClientPeerMiddleware::Conflicts { conflicts }) => {
+                    tcb.set_conflicts(conflicts);
+                }
+                Ok(ClientPeerMiddleware::Snapshot) => {
+                    handle_flush_from_client(&peer_channels);
+
+                    let cut = ClientMessage::Snapshot {
+                        cut: tcb.delivered_vector(),
+                    };
+
+                    tcb.send_to_client(cut);
+                }
+                Ok(ClientPeerMiddleware::Dump { reply }) => {
+                    match reply.send(tcb.event_timestamps()) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!(
+                                "ERROR: Couldn't send the timestamps to the Client\n\t- {}",
+                                e
+                            );
+                        }
+                    }
+                }
+                Ok(ClientPeerMiddleware::RenderGraph { format, reply }) => {
+                    match reply.send(tcb.render(format)) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!(
+                                "ERROR: Couldn't send the causal graph to the Client\n\t- {}",
+                                e
+                            );
+                        }
+                    }
+                }
+                Ok(ClientPeerMiddleware::Explain { dot, reply }) => {
+                    match reply.send(tcb.explain(dot)) {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!(
+                                "ERROR: Couldn't send the explanation to the Client\n\t- {}",
+                                e
+                            );
+                        }
+                    }
+                }
+                Ok(ClientPeerMiddleware::Ping { peer_id, timestamp }) => {
+                    handle_probe_from_peer(peer_id, timestamp, &peer_channels, &channel_peer_ids);
+                }
+                Ok(ClientPeerMiddleware::CatchUp {
+                    donor: catch_up_donor,
+                }) => {
+                    if handle_catch_up_from_client(
+                        &mut tcb,
+                        catch_up_donor,
+                        &peer_channels,
+                        &channel_peer_ids,
+                    ) {
+                        donor = Some(catch_up_donor);
+                    }
+                }
+                Ok(ClientPeerMiddleware::CatchUpRequest { peer_id, vector }) => {
+                    handle_catch_up_request(
+                        &tcb,
+                        &relay,
+                        peer_id,
+                        &vector,
+                        &peer_channels,
+                        &channel_peer_ids,
+                    );
+                }
+                Ok(ClientPeerMiddleware::Vector {
+                    peer_id,
+                    vector,
+                    reply,
+                }) => {
+                    if vector.len() == peer_addresses.len() + 1 {
+                        handle_vector_from_peer(
+                            &tcb,
+                            &relay,
+                            peer_id,
+                            &vector,
+                            reply,
+                            &peer_channels,
+                            &channel_peer_ids,
+                        );
+                    } else {
+                        println!(
+                            "ERROR: {} received a delivered version vector of {} peers from {}",
+                            local_id,
+                            vector.len(),
+                            peer_id
+                        );
+                    }
+                }
+                Ok(ClientPeerMiddleware::CaughtUp { peer_id, .. })
+                    if retransmitting.contains(&peer_id) =>
+                {
+                    //The retransmitted messages were already handled as they were received
+                    retransmitting.retain(|&id| id != peer_id);
+                }
+                Ok(ClientPeerMiddleware::CaughtUp { cut, .. }) => {
+                    donor = None;
+
+                    match cut {
+                        Some(cut) if cut.len() == peer_addresses.len() + 1 => {
+                            //Waiting for the relayed messages to be delivered
+                            catching_up = Some(cut);
+                            check_caught_up(&mut tcb, &mut catching_up);
+                        }
+                        _ => {
+                            tcb.send_to_client(ClientMessage::CaughtUp { cut: None });
+                        }
+                    }
+                }
+                Ok(ClientPeerMiddleware::Connection { peer_id, stream }) => {
+                    reader_streams.insert(peer_id, stream);
+                }
+                Ok(ClientPeerMiddleware::Interests { peer_id, interests }) => {
+                    peer_interests.insert(peer_id, interests);
+                }
+                Ok(ClientPeerMiddleware::Evict { id }) => {
+                    handle_peer_eviction(
+                        &mut tcb,
+                        id,
+                        local_id,
+                        peer_addresses.len() + 1,
+                        &mut peer_channels,
+                        &mut channel_peer_ids,
+                        &mut reader_streams,
+                    );
+
+                    if id < closed.len() {
+                        closed[id] = true;
+                    }
+
+                    //Dependencies on the evicted peer may no longer be delivered
+                    if !pending.is_empty() {
+                        send_ready_messages(
+                            &mut tcb,
+                            &mut pending,
+                            &peer_channels,
+                            &channel_peer_ids,
+                            &configuration,
+                            #[cfg(feature = "encryption")]
+                            &cipher,
+                            #[cfg(feature = "signing")]
+                            &signer,
+                        );
+                    }
+                }
+                Ok(ClientPeerMiddleware::Closed { peer_id }) => {
+                    if peer_id < closed.len() {
+                        closed[peer_id] = true;
+                    }
+                }
+                Ok(ClientPeerMiddleware::End) => {
+                    if configuration.shutdown_grace_period == 0 {
+                        publish_metrics(&metrics, &tcb);
+                        handle_finished_setup(&client);
+                        break;
+                    }
+
+                    //Closing the connections to the peers, which are still read until
+                    //every peer closes its connections or the grace period ends
+                    peer_channels.clear();
+                    channel_peer_ids.clear();
+
+                    draining = Some(Instant::now() + configuration.get_shutdown_grace_period());
+                    poller.set_deadline(draining);
+                }
+                //Woken up by the missing dependency check before the grace period ended
+                Err(RecvTimeoutError::Timeout)
+                    if draining
+                        .filter(|deadline| Instant::now() >= *deadline)
+                        .is_none() => {}
+                Err(_) => {
+                    //The grace period ended while waiting for a message
+                    if draining.is_some() {
+                        publish_metrics(&metrics, &tcb);
+                        handle_finished_setup(&client);
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        //Asking the senders of the corrupted messages for a copy, which are otherwise
+        //watched like the missing dependencies while the middleware catches up
+        let corrupted = tcb.take_corrupted();
+
+        if !corrupted.is_empty() && donor.is_none() && catching_up.is_none() {
+            request_retransmissions(
+                &tcb,
+                &corrupted,
+                &peer_channels,
+                &channel_peer_ids,
+                &mut retransmitting,
+            );
+        }
+
+        if configuration.missing_dependency_timeout > 0
+            && last_missing_check.elapsed() * 4 >= configuration.get_missing_dependency_timeout()
+        {
+            last_missing_check = Instant::now();
+
+            let missing = tcb.check_missing();
+
+            //The catch up protocol can't tell a retransmission from the Client's catch up
+            if configuration.retransmit_missing && donor.is_none() && catching_up.is_none() {
+                request_retransmissions(
+                    &tcb,
+                    &missing,
+                    &peer_channels,
+                    &channel_peer_ids,
+                    &mut retransmitting,
+                );
             }
-            Err(_) => {
+        }
+
+        match &mut receipts {
+            Some(receipts) if receipts.is_due(receive_channel.is_empty()) => {
+                receipts.send(&tcb.delivered_vector(), &peer_channels, &channel_peer_ids);
+            }
+            _ => {}
+        }
+
+        publish_metrics(&metrics, &tcb);
+
+        //Following the dropped client policy once the Client dropped its receiver
+        if tcb.is_client_dropped() {
+            match configuration.dropped_client {
+                DroppedClientPolicy::Shutdown if draining.is_none() => {
+                    if configuration.shutdown_grace_period == 0 {
+                        break;
+                    }
+
+                    peer_channels.clear();
+                    channel_peer_ids.clear();
+
+                    draining = Some(Instant::now() + configuration.get_shutdown_grace_period());
+                    poller.set_deadline(draining);
+                }
+                DroppedClientPolicy::Retain => {
+                    for dot in tcb.take_orphaned_stable() {
+                        tcb.deletestable(dot);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        //Closed connections are handled before the peers' messages queued for fairness
+        match draining {
+            Some(deadline)
+                if Instant::now() >= deadline
+                    || (is_drained(&closed, local_id)
+                        && inbound.as_ref().map_or(true, |queues| queues.is_empty())) =>
+            {
+                handle_finished_setup(&client);
                 break;
             }
+            _ => {}
         }
     }
 }
 
+/**
+ * Records the current state of the middleware in the metrics shared with the
+ * Client, which are final once the middleware ended.
+ *
+ * # Arguments
+ *
+ * `metrics` - Metrics shared with the Client.
+ *
+ * `tcb` - Causal delivery state.
+ */
+fn publish_metrics(metrics: &MiddlewareMetrics, tcb: &GRAPH) {
+    let (received, delivered) = tcb.watermarks();
+    metrics.update_watermarks(received, delivered);
+    metrics.update_counts(tcb.message_counts());
+    metrics.update_pool(tcb.pool_stats());
+    metrics.update_graph(tcb.graph_stats());
+    metrics.update_retention(tcb.retention_stats());
+}
+
+/**
+ * Checks if every other peer closed its connections, so no more messages
+ * are received while shutting down.
+ *
+ * # Arguments
+ *
+ * `closed` - Peers that closed their connections.
+ *
+ * `local_id` - Local peer's globally unique id.
+ */
+fn is_drained(closed: &[bool], local_id: usize) -> bool {
+    closed
+        .iter()
+        .enumerate()
+        .all(|(peer_id, &closed)| closed || peer_id == local_id)
+}
+
+/**
+ * Broadcasts the messages from the Client whose dependencies were delivered,
+ * in the order they were sent. A message waiting for its dependencies holds
+ * the messages sent after it, so the local messages keep their order.
+ */
+fn send_ready_messages(
+    tcb: &mut GRAPH,
+    pending: &mut VecDeque<Message>,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &Vec<usize>,
+    configuration: &Configuration,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+    #[cfg(feature = "signing")] signer: &Option<MessageSigner>,
+) {
+    while let Some(message) = pending.front() {
+        if !tcb.is_delivered(&message.context) {
+            break;
+        }
+
+        let message = pending.pop_front().unwrap();
+
+        handle_message_from_client(
+            tcb,
+            message,
+            channels,
+            channel_peer_ids,
+            configuration,
+            #[cfg(feature = "encryption")]
+            cipher,
+            #[cfg(feature = "signing")]
+            signer,
+        );
+    }
+}
+
 /**
  * Handles a message from the client by writing it in the channels
  * connected to the sender threads.
  */
 fn handle_message_from_client(
     tcb: &mut GRAPH,
-    payload: Vec<u8>,
-    channels: &Vec<Sender<(Arc<Barrier>, Arc<Vec<u8>>)>>,
-    context: Vec<Dot>,
-    dot: Dot,
+    message: Message,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &Vec<usize>,
+    configuration: &Configuration,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+    #[cfg(feature = "signing")] signer: &Option<MessageSigner>,
 ) {
-    //Creating a new struct Message
-    let message = Message::new(payload, dot, context);
+    //Setting the history digests of the message's context
+    #[cfg(feature = "bcb")]
+    let message = tcb.attach_history(message);
+
+    //Serializing the message for the peers before it's moved into the graph,
+    //so the local delivery neither serializes nor copies it
+    let mut message = message;
+    let dot = message.dot;
+
+    //Encrypted payloads are already authenticated by the cipher, and the checksum
+    //of their plaintext would be sent in the clear
+    #[cfg(feature = "encryption")]
+    let payload_checksums = configuration.payload_checksums && cipher.is_none();
+    #[cfg(not(feature = "encryption"))]
+    let payload_checksums = configuration.payload_checksums;
+
+    if payload_checksums {
+        message.checksum = Some(checksum::checksum(&message.payload));
+    }
+
+    let encoded_message = serialize_for_peers(
+        &mut message,
+        configuration.wire_encoding,
+        #[cfg(feature = "encryption")]
+        cipher,
+    );
+
+    //Serializing the message without its payload for the peers outside its recipients,
+    //unless the payload is needed to verify the history digests
+    let skipped_message = match &message.recipients {
+        Some(recipients) if !configuration.byzantine_tolerance => Some((
+            recipients.clone(),
+            serialize_without_payload(
+                &mut message,
+                configuration.wire_encoding,
+                #[cfg(feature = "encryption")]
+                cipher,
+            ),
+        )),
+        _ => None,
+    };
+
+    //Reporting the messages that couldn't be serialized to the Client. Their dot is
+    //already in the context of the following local messages, so an empty message
+    //that no peer delivers is sent in their place and the peers don't wait for it
+    let (encoded_message, skipped_message) = match (encoded_message, skipped_message) {
+        (Ok(encoded_message), None) => (encoded_message, None),
+        (Ok(encoded_message), Some((recipients, Ok(skipped_message)))) => {
+            (encoded_message, Some((recipients, skipped_message)))
+        }
+        (Err(e), _) | (_, Some((_, Err(e)))) => {
+            println!("ERROR: Couldn't serialize the CLIENT message\n\t- {}", e);
+            tcb.send_failed(dot, None, format!("Couldn't serialize the message - {}", e));
+
+            message.payload = Vec::new();
+            message.headers = None;
+            message.recipients = Some(Vec::new());
+            message.checksum = None;
+
+            match serialize_for_peers(
+                &mut message,
+                configuration.wire_encoding,
+                #[cfg(feature = "encryption")]
+                cipher,
+            ) {
+                Ok(encoded_message) => (encoded_message, None),
+                Err(e) => {
+                    println!(
+                        "ERROR: Couldn't serialize the empty CLIENT message\n\t- {}",
+                        e
+                    );
+                    tcb.dequeue(message);
+                    return;
+                }
+            }
+        }
+    };
 
     //Calling the dequeue function
-    tcb.dequeue(message.clone());
+    tcb.dequeue(message);
+
+    //Signing the serialized message so the peers can verify its sender
+    #[cfg(feature = "signing")]
+    let sign = |encoded_message: &[u8]| {
+        signer
+            .as_ref()
+            .map(|signer| Arc::new(signer.sign(encoded_message)))
+    };
+    #[cfg(not(feature = "signing"))]
+    let sign = |_: &[u8]| None;
 
-    //Serializing the struct with the new message
-    let encoded_message: Vec<u8> =
-        serialize(&message).expect("ERROR: Couldn't serialize the CLIENT message");
+    let signature = sign(&encoded_message);
+    let skipped_message = skipped_message.map(|(recipients, encoded_message)| {
+        let signature = sign(&encoded_message);
+        (recipients, Arc::new(encoded_message), signature)
+    });
 
     //Creating a new arc with the serialized message
     let arc_msg = Arc::new(encoded_message);
     let stream_sender_barrier = Arc::new(Barrier::new(channels.len()));
     //Writing the message arc into the channels connected to each peer stream sender thread
 
-    for channel in channels {
-        match &channel.send((Arc::clone(&stream_sender_barrier), Arc::clone(&arc_msg))) {
+    for (channel, peer_id) in channels.iter().zip(channel_peer_ids) {
+        let (msg, signature) = match &skipped_message {
+            Some((recipients, skipped_msg, skipped_signature)) if !recipients.contains(peer_id) => {
+                (Arc::clone(skipped_msg), skipped_signature.clone())
+            }
+            _ => (Arc::clone(&arc_msg), signature.clone()),
+        };
+
+        let sender_message = MiddlewareSender::Message {
+            barrier: Arc::clone(&stream_sender_barrier),
+            msg,
+            signature,
+        };
+
+        match &channel.send(sender_message) {
             Ok(_) => {}
             Err(e) => {
                 println!("ERROR: Could not send message to sender threads\n\t- {}", e);
+                tcb.send_failed(
+                    dot,
+                    Some(*peer_id),
+                    String::from("The Sender thread of the peer terminated"),
+                );
             }
         }
     }
 }
 
+/**
+ * Serializes a message from the client once for every peer. If the group key is set, the payload is swapped
+ * for its ciphertext while serializing, so the message isn't copied and the
+ * local delivery still uses the plaintext.
+ */
+fn serialize_for_peers(
+    message: &mut Message,
+    encoding: WireEncoding,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+) -> bincode::Result<Vec<u8>> {
+    #[cfg(feature = "encryption")]
+    let plaintext = match cipher {
+        Some(cipher) => {
+            let ciphertext = cipher.encrypt(&message.payload);
+            Some(mem::replace(&mut message.payload, ciphertext))
+        }
+        None => None,
+    };
+
+    let encoded_message = encoding::serialize(encoding, &*message);
+
+    #[cfg(feature = "encryption")]
+    if let Some(plaintext) = plaintext {
+        message.payload = plaintext;
+    }
+
+    encoded_message
+}
+
+/**
+ * Serializes a message from the client without its payload, for the peers that
+ * aren't recipients of a message sent to a subset of the group. The empty payload
+ * is still encrypted if the group key is set, so the peers can decrypt it.
+ */
+fn serialize_without_payload(
+    message: &mut Message,
+    encoding: WireEncoding,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+) -> bincode::Result<Vec<u8>> {
+    #[cfg(feature = "encryption")]
+    let empty_payload = match cipher {
+        Some(cipher) => cipher.encrypt(&[]),
+        None => Vec::new(),
+    };
+    #[cfg(not(feature = "encryption"))]
+    let empty_payload = Vec::new();
+
+    let payload = mem::replace(&mut message.payload, empty_payload);
+
+    let encoded_message = encoding::serialize(encoding, &*message);
+
+    message.payload = payload;
+    encoded_message
+}
+
+/**
+ * Returns the peers that deliver the messages of a key according to their
+ * interest sets, or `None` if every peer delivers them, so the message is
+ * sent to the other peers without its payload.
+ *
+ * # Arguments
+ *
+ * `key` - Entity key of the message.
+ *
+ * `peer_interests` - Interest sets announced by the peers.
+ *
+ * `channel_peer_ids` - Ids of the connected peers.
+ */
+fn interested_peers(
+    key: u64,
+    peer_interests: &HashMap<usize, Vec<KeyRange>>,
+    channel_peer_ids: &[usize],
+) -> Option<Vec<usize>> {
+    let recipients: Vec<usize> = channel_peer_ids
+        .iter()
+        .copied()
+        .filter(|peer_id| match peer_interests.get(peer_id) {
+            Some(interests) => is_interested(interests, key),
+            None => true,
+        })
+        .collect();
+
+    if recipients.len() == channel_peer_ids.len() {
+        None
+    } else {
+        Some(recipients)
+    }
+}
+
+/**
+ * Handles a flush request from the client by signalling every
+ * Sender thread to write its buffered messages into the stream.
+ */
+fn handle_flush_from_client(channels: &Vec<Sender<MiddlewareSender>>) {
+    for channel in channels {
+        match channel.send(MiddlewareSender::Flush) {
+            Ok(_) => {}
+            Err(e) => {
+                println!("ERROR: Could not send flush to sender threads\n\t- {}", e);
+            }
+        }
+    }
+}
+
+/**
+ * Handles a probe from a peer by writing the response in the
+ * channel connected to the peer's Sender thread.
+ */
+fn handle_probe_from_peer(
+    peer_id: usize,
+    timestamp: u64,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &[usize],
+) {
+    if let Some(index) = channel_peer_ids.iter().position(|&id| id == peer_id) {
+        match channels[index].send(MiddlewareSender::Pong { timestamp }) {
+            Ok(_) => {}
+            Err(e) => {
+                println!(
+                    "ERROR: Could not send probe response to sender thread\n\t- {}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+/**
+ * Handles a catch up request from the client by asking the donor peer for the
+ * retained messages above the local delivered version vector. The client is
+ * immediately notified if the donor isn't connected. Returns if the messages
+ * were requested.
+ */
+fn handle_catch_up_from_client(
+    tcb: &mut GRAPH,
+    donor: usize,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &[usize],
+) -> bool {
+    let vector = tcb.delivered_vector();
+
+    let requested = match channel_peer_ids.iter().position(|&id| id == donor) {
+        Some(index) => channels[index]
+            .send(MiddlewareSender::CatchUp { vector })
+            .is_ok(),
+        None => false,
+    };
+
+    if !requested {
+        println!("ERROR: Couldn't request the retained messages of {}", donor);
+
+        tcb.send_to_client(ClientMessage::CaughtUp { cut: None });
+    }
+
+    requested
+}
+
+/**
+ * Asks the senders of the missing dependencies, or of the corrupted messages, for
+ * the retained messages above the local delivered version vector, reusing the catch
+ * up protocol. The relayed messages that were already received are ignored by the
+ * graph, and the donor's cut is discarded since the middleware isn't catching up with it.
+ *
+ * # Arguments
+ *
+ * `tcb` - Causal delivery algorithm.
+ *
+ * `missing` - Dots of the messages that weren't received, or were corrupted.
+ *
+ * `channels` - Channels to the Sender threads.
+ *
+ * `channel_peer_ids` - Ids of the peers connected to each channel.
+ *
+ * `retransmitting` - Peers already asked to retransmit their messages.
+ */
+fn request_retransmissions(
+    tcb: &GRAPH,
+    missing: &[Dot],
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &[usize],
+    retransmitting: &mut Vec<usize>,
+) {
+    for dot in missing {
+        if retransmitting.contains(&dot.id) {
+            continue;
+        }
+
+        if let Some(index) = channel_peer_ids.iter().position(|&id| id == dot.id) {
+            let vector = tcb.delivered_vector();
+
+            match channels[index].send(MiddlewareSender::CatchUp { vector }) {
+                Ok(_) => retransmitting.push(dot.id),
+                Err(e) => {
+                    println!(
+                        "ERROR: Couldn't request the retransmission of {:?}\n\t- {}",
+                        dot, e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/**
+ * State of the Middleware thread used to relay the retained messages to a peer.
+ */
+pub struct RelayContext<'a> {
+    ///Local peer's globally unique id
+    pub local_id: usize,
+    ///Middleware's configuration file
+    pub configuration: &'a Configuration,
+    ///Cipher of the relayed payloads
+    #[cfg(feature = "encryption")]
+    pub cipher: &'a Option<PayloadCipher>,
+    ///Signer of the local delivered version vector
+    #[cfg(feature = "signing")]
+    pub signer: &'a Option<MessageSigner>,
+}
+
+/**
+ * Handles a catch up request from a peer by relaying the retained messages above
+ * its delivered version vector to the peer's Sender thread, followed by the local
+ * delivered version vector.
+ */
+fn handle_catch_up_request(
+    tcb: &GRAPH,
+    relay: &RelayContext,
+    peer_id: usize,
+    vector: &[usize],
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &[usize],
+) {
+    let channel = match channel_peer_ids.iter().position(|&id| id == peer_id) {
+        Some(index) => &channels[index],
+        None => return,
+    };
+
+    let cut = match relay_retained(tcb, relay, peer_id, vector, channel) {
+        Ok(_) => Some(tcb.delivered_vector()),
+        Err(e) => {
+            println!(
+                "ERROR: Couldn't relay the messages {} is missing\n\t- {}",
+                peer_id, e
+            );
+            None
+        }
+    };
+
+    match channel.send(MiddlewareSender::CaughtUp { cut }) {
+        Ok(_) => {}
+        Err(e) => {
+            println!(
+                "ERROR: Could not send catch up end to sender thread\n\t- {}",
+                e
+            );
+        }
+    }
+}
+
+/**
+ * Handles the delivered version vector of a peer, sent once its connection was
+ * set up, by relaying the retained messages above it to the peer, so a peer that
+ * restarted receives what it missed without assuming a fresh start. The local
+ * delivered version vector is sent back, so the peer relays what the local peer
+ * is missing in turn.
+ *
+ * # Arguments
+ *
+ * `tcb` - Causal delivery algorithm.
+ *
+ * `relay` - State used to relay the retained messages.
+ *
+ * `peer_id` - Id of the peer that sent its delivered version vector.
+ *
+ * `vector` - Peer's delivered version vector.
+ *
+ * `reply` - If the peer's vector answers the local one.
+ *
+ * `channels` - Channels to the Sender threads.
+ *
+ * `channel_peer_ids` - Ids of the peers connected to each channel.
+ */
+fn handle_vector_from_peer(
+    tcb: &GRAPH,
+    relay: &RelayContext,
+    peer_id: usize,
+    vector: &[usize],
+    reply: bool,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &[usize],
+) {
+    let channel = match channel_peer_ids.iter().position(|&id| id == peer_id) {
+        Some(index) => &channels[index],
+        None => return,
+    };
+
+    if !reply {
+        let delivered = tcb.delivered_vector();
+
+        #[cfg(feature = "signing")]
+        let signature = handshake::sign_vector(&delivered, true, relay.signer);
+        #[cfg(not(feature = "signing"))]
+        let signature = None;
+
+        match channel.send(MiddlewareSender::Vector {
+            vector: delivered,
+            signature,
+        }) {
+            Ok(_) => {}
+            Err(e) => {
+                println!(
+                    "ERROR: Could not send delivered version vector to sender thread\n\t- {}",
+                    e
+                );
+                return;
+            }
+        }
+    }
+
+    //Nothing is relayed unless the messages are retained
+    if !relay.configuration.retention.enabled {
+        return;
+    }
+
+    match relay_retained(tcb, relay, peer_id, vector, channel) {
+        Ok(_) => {}
+        Err(e) => {
+            println!(
+                "ERROR: Couldn't relay the messages {} is missing\n\t- {}",
+                peer_id, e
+            );
+        }
+    }
+}
+
+/**
+ * Relays the retained messages above a peer's delivered version vector to the
+ * peer's Sender thread, in the order they were delivered. The messages keep their
+ * original dots and contexts, so the peer delivers them as if they were received from
+ * their senders. Relayed messages aren't signed by their senders, so nothing is
+ * relayed in signed or Byzantine-tolerant groups. Messages sent to a subset of the
+ * group are relayed without their payload to the other peers, and nothing is
+ * relayed if the peer is a recipient of a message whose payload wasn't received.
+ * The relay stops with an error at the first message that can't be serialized.
+ */
+fn relay_retained(
+    tcb: &GRAPH,
+    relay: &RelayContext,
+    peer_id: usize,
+    vector: &[usize],
+    channel: &Sender<MiddlewareSender>,
+) -> Result<(), String> {
+    let local_id = relay.local_id;
+    let configuration = relay.configuration;
+
+    let retained = if configuration.signing.is_some() || configuration.byzantine_tolerance {
+        None
+    } else {
+        tcb.retained_above(vector).filter(|messages| {
+            messages.iter().all(|message| match &message.recipients {
+                Some(recipients) => {
+                    message.dot.id == local_id
+                        || recipients.contains(&local_id)
+                        || !recipients.contains(&peer_id)
+                }
+                None => true,
+            })
+        })
+    };
+
+    match retained {
+        Some(messages) => {
+            for mut message in messages {
+                match &message.recipients {
+                    Some(recipients) if !recipients.contains(&peer_id) => {
+                        message.payload.clear();
+                        message.checksum = None;
+                    }
+                    _ => {}
+                }
+
+                //Encrypting the payload, which is retained in plaintext
+                #[cfg(feature = "encryption")]
+                let message = match relay.cipher {
+                    Some(cipher) => Message {
+                        payload: cipher.encrypt(&message.payload),
+                        ..message
+                    },
+                    None => message,
+                };
+
+                //The later messages may depend on it, so the relay stops here
+                let encoded_message: Vec<u8> =
+                    match encoding::serialize(configuration.wire_encoding, &message) {
+                        Ok(encoded_message) => encoded_message,
+                        Err(e) => {
+                            return Err(format!(
+                                "could not serialize the retained message {:?} - {}",
+                                message.dot, e
+                            ));
+                        }
+                    };
+
+                let sender_message = MiddlewareSender::Message {
+                    barrier: Arc::new(Barrier::new(1)),
+                    msg: Arc::new(encoded_message),
+                    signature: None,
+                };
+
+                match channel.send(sender_message) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        return Err(format!("could not relay message to sender thread - {}", e));
+                    }
+                }
+            }
+
+            Ok(())
+        }
+        None => Err(String::from(
+            "the messages aren't in the retention log or can't be relayed",
+        )),
+    }
+}
+
+/**
+ * Notifies the client once every message up to the donor's delivered version
+ * vector was delivered, when catching up with the group.
+ */
+fn check_caught_up(tcb: &mut GRAPH, catching_up: &mut Option<Vec<usize>>) {
+    let caught_up = match catching_up {
+        Some(cut) => {
+            let dots: Vec<Dot> = cut
+                .iter()
+                .enumerate()
+                .map(|(id, &counter)| Dot::new(id, counter))
+                .collect();
+
+            tcb.is_delivered(&dots)
+        }
+        None => false,
+    };
+
+    if caught_up {
+        *catching_up = None;
+
+        let caught_up = ClientMessage::CaughtUp {
+            cut: Some(tcb.delivered_vector()),
+        };
+
+        tcb.send_to_client(caught_up);
+    }
+}
+
+/**
+ * Handles the eviction of a peer requested by the client. The channel to the peer's
+ * Sender thread is dropped, which makes it close the connection, and the stream read
+ * by the peer's Reader thread is shut down.
+ */
+fn handle_peer_eviction(
+    tcb: &mut GRAPH,
+    id: usize,
+    local_id: usize,
+    peer_number: usize,
+    channels: &mut Vec<Sender<MiddlewareSender>>,
+    channel_peer_ids: &mut Vec<usize>,
+    reader_streams: &mut HashMap<usize, TcpStream>,
+) {
+    if id == local_id || id >= peer_number {
+        println!("ERROR: Peer {} can't be evicted by {}", id, local_id);
+        return;
+    }
+
+    if let Some(index) = channel_peer_ids.iter().position(|&peer_id| peer_id == id) {
+        channel_peer_ids.remove(index);
+        channels.remove(index);
+    }
+
+    if let Some(stream) = reader_streams.remove(&id) {
+        match stream.shutdown(Shutdown::Both) {
+            Ok(_) => {}
+            Err(e) => {
+                println!(
+                    "WARN: Failed to close the connection with {}\n\t- {}",
+                    id, e
+                );
+            }
+        }
+    }
+
+    tcb.evict(id);
+}
+
 /**
  * Handles the setup end from the transport layer. The Middleware informs
  * the Client about this by sending a message.
  */
 fn handle_finished_setup(client: &Sender<ClientMessage>) {
-    client
-        .send(ClientMessage::Empty)
-        .expect("ERROR: Failed to send the finishing SETUP message to client");
+    match client.send(ClientMessage::Empty) {
+        Ok(_) => {}
+        Err(e) => {
+            println!(
+                "ERROR: Failed to send the finishing SETUP message to client\n\t- {}",
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadcast::broadcast_trait::Fault;
+    use crate::broadcast::hooks::NoHooks;
+    use crate::channel::unbounded;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn missing_dependency_is_reported_without_more_traffic() {
+        let configuration = Configuration {
+            missing_dependency_timeout: 50_000,
+            ..Default::default()
+        };
+
+        let (middleware_send, middleware_receive) = unbounded::<ClientPeerMiddleware>();
+        let (client_send, client_receive) = unbounded::<ClientMessage>();
+        let (stable_send, _stable_receive) = unbounded::<ClientMessage>();
+
+        let middleware = thread::spawn(move || {
+            start(
+                0,
+                vec![String::from("peer_1")],
+                middleware_receive,
+                client_send,
+                stable_send,
+                Vec::new(),
+                MiddlewareContext {
+                    configuration: Arc::new(configuration),
+                    metrics: Arc::new(MiddlewareMetrics::new(0, 2)),
+                    hooks: Arc::new(NoHooks),
+                },
+            );
+        });
+
+        //The second message of peer 1 arrives without the first, and nothing follows
+        let msg = Message::new(vec![1], Dot::new(1, 2), vec![Dot::new(1, 1)], None);
+        middleware_send
+            .send(ClientPeerMiddleware::Peer {
+                msg,
+                fingerprint: 0,
+            })
+            .unwrap();
+
+        let fault = loop {
+            match client_receive.recv_timeout(Duration::from_secs(5)) {
+                Ok(ClientMessage::Fault { fault }) => break fault,
+                Ok(_) => {}
+                Err(e) => panic!("ERROR: The missing dependency wasn't reported\n\t- {:?}", e),
+            }
+        };
+
+        assert_eq!(
+            fault,
+            Fault::MissingDependency {
+                id: 1,
+                counter: 1,
+                blocked: vec![(1, 2)],
+            }
+        );
+
+        middleware_send.send(ClientPeerMiddleware::End).unwrap();
+        middleware.join().unwrap();
+    }
 }
@@ -3,22 +3,37 @@
  */
 pub mod dag;
 /**
- * Pair id, cntr that is tagged to each message before sending.
+ * Pair id, cntr that is tagged to each message before sending, shared with VV
+ * through the clock module.
  */
-pub mod dot;
+pub use crate::clock::dot;
 /**
  * Graph based causal delivery algorithm.
  */
+#[cfg(feature = "graph")]
 pub mod graph;
 /**
  * Necessary structs.
  */
+#[cfg(feature = "graph")]
 pub mod message_types;
 /**
  * Middleware thread that handles received and broadcast messages.
  */
+#[cfg(feature = "graph")]
 pub mod middleware_thread;
 /**
  * Message node in the DAG.
  */
+#[cfg(feature = "graph")]
 pub mod node;
+/**
+ * Pool of buffers reused by the nodes in the DAG.
+ */
+#[cfg(feature = "graph")]
+pub mod node_pool;
+/**
+ * Contexts of the messages sent by the Client.
+ */
+#[cfg(feature = "graph")]
+pub mod send_context;
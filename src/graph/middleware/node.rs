@@ -1,9 +1,28 @@
-use super::dot::Dot;
+use crate::broadcast::broadcast_trait::Headers;
+use crate::clock::dot::Dot;
 use bit_vec::BitVec;
 use smallvec::SmallVec;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Instant;
 
 type BV = BitVec<u64>;
 
+/**
+ * Number of predecessor/successor indexes stored inline in a node before
+ * they are moved to the heap. The `wide-nodes` feature raises it for groups
+ * where messages usually have wide contexts.
+ */
+#[cfg(not(feature = "wide-nodes"))]
+pub const NODE_INLINE_CAPACITY: usize = 4;
+#[cfg(feature = "wide-nodes")]
+pub const NODE_INLINE_CAPACITY: usize = 16;
+
+/**
+ * Graph indexes of the predecessors or successors of a node.
+ */
+pub type NodeIndexes = SmallVec<[usize; NODE_INLINE_CAPACITY]>;
+
 /**
  * Stages that a node in the causal dependency graph can have.
  */
@@ -30,14 +49,32 @@ pub struct Node {
     pub stage: Stage,
     ///Bit string
     pub bits: BV,
-    ///Serialized message payload
+    ///Serialized message payload, moved to the Client on delivery
     pub payload: Option<Vec<u8>>,
-    ///Message context
+    ///Message context, moved to the Client on delivery
     pub context: Option<Vec<Dot>>,
+    ///Hash of the payload and context, kept after delivery to detect equivocations
+    pub fingerprint: Option<u64>,
+    ///Checksum of the payload set by the sender, verified on delivery
+    pub checksum: Option<u64>,
+    ///Message trace id
+    pub trace_id: Option<u128>,
+    ///Message headers, moved to the Client on delivery
+    pub headers: Option<Headers>,
+    ///Message entity key
+    pub key: Option<u64>,
+    ///Peers the message is delivered to, every peer if not set
+    pub recipients: Option<Vec<usize>>,
+    ///Instant when the message was received, not set for the local messages
+    pub received_at: Option<Instant>,
     ///Indexes to the predecessors that are still in the graph
-    pub predecessors: SmallVec<[usize; 4]>,
+    pub predecessors: NodeIndexes,
     ///Indexes to the successors that are still in the graph
-    pub successors: SmallVec<[usize; 4]>,
+    pub successors: NodeIndexes,
+    ///Index of the previous delivered message from the same sender that's still in the graph
+    pub sender_previous: Option<usize>,
+    ///Index of the next delivered message from the same sender
+    pub sender_next: Option<usize>,
 }
 
 impl Node {
@@ -57,10 +94,36 @@ impl Node {
             payload: None,
             dot,
             context: None,
+            fingerprint: None,
+            checksum: None,
+            trace_id: None,
+            headers: None,
+            key: None,
+            recipients: None,
+            received_at: None,
             predecessors,
             successors,
+            sender_previous: None,
+            sender_next: None,
             stage: Stage::SLT,
             bits,
         }
     }
+
+    /**
+     * Calculates the fingerprint of a message's payload and context.
+     *
+     * # Arguments
+     *
+     * `payload` - Serialized message payload
+     *
+     * `context` - Message context
+     */
+    pub fn calculate_fingerprint(payload: &[u8], context: &[Dot]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        payload.hash(&mut hasher);
+        context.hash(&mut hasher);
+
+        hasher.finish()
+    }
 }
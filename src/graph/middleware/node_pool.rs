@@ -0,0 +1,164 @@
+use super::node::{Node, NodeIndexes};
+use crate::metrics::middleware_metrics::PoolMetrics;
+use bit_vec::BitVec;
+use std::mem;
+
+type BV = BitVec<u64>;
+
+/**
+ * Pool of the buffers used by the nodes of the causal dependency graph. The
+ * bit strings and the predecessor/successor indexes of the nodes deleted when
+ * stable are kept and reused by new nodes, instead of being freed and
+ * allocated again for every message.
+ */
+#[derive(Debug)]
+pub struct NodePool {
+    ///Bit strings of deleted nodes
+    bits: Vec<BV>,
+    ///Predecessor and successor indexes of deleted nodes that were moved to the heap
+    indexes: Vec<NodeIndexes>,
+    ///Number of buffers allocated because the pool was empty
+    allocated: u64,
+    ///Number of buffers reused from the pool
+    recycled: u64,
+}
+
+impl NodePool {
+    /**
+     * Creates an empty pool.
+     */
+    pub fn new() -> Self {
+        Self {
+            bits: Vec::new(),
+            indexes: Vec::new(),
+            allocated: 0,
+            recycled: 0,
+        }
+    }
+
+    /**
+     * Creates a pool with the bit strings of the nodes expected in the graph,
+     * which aren't counted as allocated.
+     *
+     * # Arguments
+     *
+     * `nodes` - Expected number of nodes in the graph.
+     *
+     * `peer_number` - Number of peers in the group, which is the length of the bit strings.
+     */
+    pub fn with_capacity(nodes: usize, peer_number: usize) -> Self {
+        let mut pool = Self::new();
+
+        pool.bits = (0..nodes)
+            .map(|_| {
+                let mut bits = BV::default();
+                bits.grow(peer_number, false);
+                bits
+            })
+            .collect();
+
+        pool
+    }
+
+    /**
+     * Returns a bit string with `len` positions set to `value`, reusing a pooled one if possible.
+     *
+     * # Arguments
+     *
+     * `len` - Number of positions of the bit string.
+     *
+     * `value` - Initial value of every position.
+     */
+    pub fn bits(&mut self, len: usize, value: bool) -> BV {
+        match self.bits.pop() {
+            //Resetting whole blocks of a bit string that already has the length
+            Some(mut bits) if bits.len() == len => {
+                self.recycled += 1;
+                bits.fill(value);
+                bits
+            }
+            Some(mut bits) => {
+                self.recycled += 1;
+                bits.truncate(0);
+                bits.grow(len, value);
+                bits
+            }
+            None => {
+                self.allocated += 1;
+                let mut bits = BV::default();
+                bits.grow(len, value);
+                bits
+            }
+        }
+    }
+
+    /**
+     * Returns an empty vec of graph indexes with room for at least `capacity`
+     * indexes, reusing a pooled one if the indexes don't fit inline.
+     *
+     * # Arguments
+     *
+     * `capacity` - Number of indexes that will be pushed.
+     */
+    pub fn indexes(&mut self, capacity: usize) -> NodeIndexes {
+        let mut indexes = NodeIndexes::new();
+
+        if capacity > indexes.inline_size() {
+            match self.indexes.pop() {
+                Some(pooled) => {
+                    self.recycled += 1;
+                    indexes = pooled;
+                }
+                None => {
+                    self.allocated += 1;
+                }
+            }
+
+            indexes.reserve(capacity);
+        }
+
+        indexes
+    }
+
+    /**
+     * Takes the buffers of a node deleted from the graph and keeps them for new nodes.
+     *
+     * # Arguments
+     *
+     * `node` - Node softly deleted from the graph.
+     */
+    pub fn recycle(&mut self, node: &mut Node) {
+        let bits = mem::take(&mut node.bits);
+
+        if bits.capacity() > 0 {
+            self.bits.push(bits);
+        }
+
+        for mut indexes in [
+            mem::take(&mut node.predecessors),
+            mem::take(&mut node.successors),
+        ] {
+            if indexes.spilled() {
+                indexes.clear();
+                self.indexes.push(indexes);
+            }
+        }
+    }
+
+    /**
+     * Returns the statistics of the pool.
+     */
+    pub fn stats(&self) -> PoolMetrics {
+        PoolMetrics {
+            allocated: self.allocated,
+            recycled: self.recycled,
+            available: self.bits.len() + self.indexes.len(),
+        }
+    }
+}
+
+impl Default for NodePool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,179 @@
+use crate::clock::dot::Dot;
+use crate::configuration::middleware_configuration::{is_interested, KeyRange};
+use crate::graph::graph::ConflictFunction;
+use std::collections::HashMap;
+
+/**
+ * Contexts of the next messages sent by the Client, kept by the Middleware thread
+ * and updated upon every delivery, so the Client's handle holds no causal state.
+ */
+pub struct SendContext {
+    ///Dot of the last sent message
+    dot: Dot,
+    ///Context of the next sent message
+    context: Vec<Dot>,
+    ///Context of the next message sent with each entity key, where the
+    ///messages without a key are kept in the `None` entry
+    key_contexts: HashMap<Option<u64>, Vec<Dot>>,
+    ///Function that decides if the messages of two entity keys are related
+    conflicts: ConflictFunction,
+    ///Ranges of the keys whose messages are delivered by the local peer
+    interests: Vec<KeyRange>,
+}
+
+impl SendContext {
+    /**
+     * Builds the contexts of a peer that didn't send or deliver any message.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `interests` - Ranges of the keys whose messages are delivered by the local peer.
+     */
+    pub fn new(local_id: usize, interests: Vec<KeyRange>) -> Self {
+        Self {
+            dot: Dot::new(local_id, 0),
+            context: Vec::new(),
+            key_contexts: HashMap::new(),
+            conflicts: Box::new(|key, other_key| key == other_key),
+            interests,
+        }
+    }
+
+    /**
+     * Sets the function that decides if the messages of two entity keys are related.
+     *
+     * # Arguments
+     *
+     * `conflicts` - Function over the key of the sent message and the key of a delivered message
+     */
+    pub fn set_conflicts(&mut self, conflicts: ConflictFunction) {
+        self.conflicts = conflicts;
+    }
+
+    /**
+     * Updates the contexts upon a delivery, including the deliveries skipped
+     * by the Client. The local messages relayed by a donor peer advance the
+     * counter of the next sent message.
+     *
+     * # Arguments
+     *
+     * `dot` - Delivered message dot.
+     *
+     * `message_context` - Delivered message context.
+     *
+     * `key` - Delivered message entity key.
+     */
+    pub fn on_delivery(&mut self, dot: &Dot, message_context: &[Dot], key: Option<u64>) {
+        if dot.id == self.dot.id && self.dot.counter < dot.counter {
+            self.dot.counter = dot.counter;
+        }
+
+        Self::update_context(dot, message_context, &mut self.context);
+
+        //The messages of keys outside the interest set were never seen by the
+        //local peer, so its messages of the same keys don't depend on them
+        match key {
+            Some(key) if !is_interested(&self.interests, key) => {}
+            _ => Self::update_context(
+                dot,
+                message_context,
+                self.key_contexts.entry(key).or_default(),
+            ),
+        }
+    }
+
+    /**
+     * Assigns the dot and context of a message sent by the Client and replaces the
+     * contexts by its dot. Returns `None` once the message counter is exhausted,
+     * instead of reusing the dots of previous messages.
+     *
+     * # Arguments
+     *
+     * `key` - Entity key of the sent message
+     *
+     * `deps` - Explicit dependencies of the sent message
+     */
+    pub fn next(&mut self, key: Option<u64>, deps: &[Dot]) -> Option<(Dot, Vec<Dot>)> {
+        //Messages without a key depend on every delivered message
+        let mut context = match key {
+            Some(key) => self.key_context(key),
+            None => self.context.clone(),
+        };
+
+        for dep in deps {
+            if !context.contains(dep) {
+                context.push(*dep);
+            }
+        }
+
+        self.dot.counter = self.dot.counter.checked_add(1)?;
+
+        //Replacing the sent context by the sent message's dot, which depends on it
+        Self::update_context(&self.dot, &context, &mut self.context);
+
+        //A message without a key depends on every delivered message,
+        //so the contexts of every key are replaced by its dot
+        if key.is_none() {
+            self.key_contexts.clear();
+        }
+
+        self.key_contexts.insert(key, vec![self.dot]);
+
+        Some((self.dot, context))
+    }
+
+    /**
+     * Builds the context of a message sent with an entity key, which has the
+     * delivered messages of the related keys and of the messages without a key,
+     * plus the previous message sent by the local peer. The middleware delivers
+     * the messages of each peer in the order of their counters, so every message
+     * must depend on the previous one from the same peer.
+     *
+     * # Arguments
+     *
+     * `key` - Entity key of the sent message
+     */
+    fn key_context(&self, key: u64) -> Vec<Dot> {
+        let mut context: Vec<Dot> = Vec::new();
+
+        if self.dot.counter > 0 {
+            context.push(self.dot);
+        }
+
+        for (other_key, key_context) in &self.key_contexts {
+            let related = match other_key {
+                Some(other_key) => *other_key == key || (self.conflicts)(key, *other_key),
+                None => true,
+            };
+
+            if related {
+                for dot in key_context {
+                    if !context.contains(dot) {
+                        context.push(*dot);
+                    }
+                }
+            }
+        }
+
+        context
+    }
+
+    /**
+     * Updates a context upon delivery. A dot's context dots are removed
+     * from the context when adding it.
+     *
+     * # Arguments
+     *
+     * `dot` - Delivered message dot.
+     *
+     * `message_context` - Delivered message context.
+     *
+     * `local_context` - Next sent message message context.
+     */
+    fn update_context(dot: &Dot, message_context: &[Dot], local_context: &mut Vec<Dot>) {
+        local_context.retain(|&client_dot| !message_context.contains(&client_dot));
+        local_context.push(*dot);
+    }
+}
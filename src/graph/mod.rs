@@ -1,16 +1,29 @@
 /**
  * Transport layer of the middleware service.
  */
+#[cfg(feature = "graph")]
 pub mod communication;
 /**
  * API and necessary state for the client to communicate with the middleware.
  */
+#[cfg(feature = "graph")]
 pub mod graph;
 /**
  * Middleware that ensures causal delivery.
  */
 pub mod middleware;
+/**
+ * Receiver of the stability notifications routed to their own channel.
+ */
+#[cfg(feature = "graph")]
+pub mod stable_receiver;
 /**
  * Common structs of the middleware service.
  */
+#[cfg(feature = "graph")]
 pub mod structs;
+/**
+ * Cloneable handle that broadcasts messages from any thread.
+ */
+#[cfg(feature = "graph")]
+pub mod tcb_sender;
@@ -0,0 +1,174 @@
+use crate::channel::{Receiver, RecvError, RecvTimeoutError, Sender, TryRecvError};
+use crate::clock::dot::Dot;
+use crate::graph::middleware::message_types::ClientMessage;
+use crate::graph::structs::message_type::ClientPeerMiddleware;
+#[cfg(feature = "otel")]
+use crate::telemetry::otel::Telemetry;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/**
+ * Receiver of the stability notifications, when they are routed to their own
+ * channel instead of being interleaved with the deliveries. It can be moved to
+ * a dedicated thread that acks the stable messages independently of the Client.
+ */
+pub struct StableReceiver {
+    ///Receiver end of the channel where the middleware thread writes the stable messages
+    receive_channel: Receiver<ClientMessage>,
+    ///Sender end of the channel between the client and the middleware thread
+    middleware_channel: Sender<ClientPeerMiddleware>,
+    ///OpenTelemetry instruments of the stable events
+    #[cfg(feature = "otel")]
+    telemetry: Telemetry,
+}
+
+impl StableReceiver {
+    /**
+     * Builds a new StableReceiver.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `receive_channel` - Channel where the middleware thread writes the stable messages.
+     *
+     * `middleware_channel` - Channel to the middleware thread, used to ack the stable messages.
+     */
+    pub fn new(
+        #[cfg_attr(not(feature = "otel"), allow(unused_variables))] local_id: usize,
+        receive_channel: Receiver<ClientMessage>,
+        middleware_channel: Sender<ClientPeerMiddleware>,
+    ) -> Self {
+        Self {
+            receive_channel,
+            middleware_channel,
+            #[cfg(feature = "otel")]
+            telemetry: Telemetry::new(local_id),
+        }
+    }
+
+    /**
+     * Returns the sender id and message id of a stable message.
+     *
+     * # Arguments
+     *
+     * `message` - Message written by the middleware thread.
+     */
+    fn handle_stable(&self, message: ClientMessage) -> (usize, usize) {
+        match message {
+            ClientMessage::Stable { dot } => {
+                #[cfg(feature = "otel")]
+                self.telemetry.on_stable(dot.id, dot.counter);
+
+                (dot.id, dot.counter)
+            }
+            _ => {
+                panic!("ERROR: Received a non STABLE message in the stable channel!");
+            }
+        }
+    }
+
+    /**
+     * Returns the dot of the next stable message. Blocks the calling thread
+     * until a message is stable or the channel to the middleware is disconnected.
+     */
+    pub fn recv(&self) -> Result<(usize, usize), RecvError> {
+        match self.receive_channel.recv() {
+            Ok(message) => Ok(self.handle_stable(message)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /**
+     * Attempts to return the dot of the next stable message without blocking
+     * the caller thread. An error is returned if the channel is empty.
+     */
+    pub fn try_recv(&self) -> Result<(usize, usize), TryRecvError> {
+        match self.receive_channel.try_recv() {
+            Ok(message) => Ok(self.handle_stable(message)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /**
+     * Waits for a message to be stable for a limited time. If there are no
+     * stable messages until the timeout ends or the channel becomes
+     * disconnected, an error is returned.
+     *
+     * # Arguments
+     *
+     * `duration` - Timeout duration
+     */
+    pub fn recv_timeout(&self, duration: Duration) -> Result<(usize, usize), RecvTimeoutError> {
+        match self.receive_channel.recv_timeout(duration) {
+            Ok(message) => Ok(self.handle_stable(message)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /**
+     * ACKS a stable message, so the node with the message's information can be
+     * deleted from the graph, as in `TCB::tcbstable`.
+     *
+     * # Arguments
+     *
+     * `id` - Stable dot id field
+     *
+     * `counter` - Stable dot counter field
+     */
+    pub fn tcbstable(&self, id: usize, counter: usize) {
+        let dot = Dot::new(id, counter);
+        let stable_dot = ClientPeerMiddleware::Stable { dot };
+
+        self.middleware_channel
+            .send(stable_dot)
+            .expect("ERROR: When the Client sends a STABLE message");
+    }
+
+    /**
+     * Acks every stable message once it was stable for the hold time, until the
+     * channel to the middleware is disconnected. Used by the background thread
+     * that acks the stable messages for the applications that don't need to
+     * delay their deletion.
+     *
+     * # Arguments
+     *
+     * `hold_time` - Time a stable message is kept before being acked
+     */
+    pub fn ack_after(self, hold_time: Duration) {
+        //Stable messages waiting for the hold time, in the order they became stable
+        let mut held: VecDeque<(Instant, usize, usize)> = VecDeque::new();
+
+        loop {
+            let received = match held.front() {
+                Some(&(deadline, _, _)) => {
+                    match self.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(dot) => Some(dot),
+                        Err(RecvTimeoutError::Timeout) => None,
+                        Err(RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match self.recv() {
+                    Ok(dot) => Some(dot),
+                    Err(_) => break,
+                },
+            };
+
+            if let Some((id, counter)) = received {
+                held.push_back((Instant::now() + hold_time, id, counter));
+            }
+
+            //Acking the stable messages whose hold time has ended
+            let now = Instant::now();
+
+            while let Some(&(deadline, id, counter)) = held.front() {
+                if deadline > now {
+                    break;
+                }
+
+                self.tcbstable(id, counter);
+                held.pop_front();
+            }
+        }
+    }
+}
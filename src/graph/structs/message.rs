@@ -1,4 +1,5 @@
-use crate::graph::middleware::dot::Dot;
+use crate::broadcast::broadcast_trait::Headers;
+use crate::clock::dot::Dot;
 
 /**
  * Struct for the message sent over the network.
@@ -11,6 +12,18 @@ pub struct Message {
     pub payload: Vec<u8>,
     ///Message context
     pub context: Vec<Dot>,
+    ///Trace id set by the sender
+    pub trace_id: Option<u128>,
+    ///Metadata headers set by the sender
+    pub headers: Option<Headers>,
+    ///Entity key set by the sender, whose context only has messages of related keys
+    pub key: Option<u64>,
+    ///History digests of the context dots, in the Byzantine-tolerant mode
+    pub context_digests: Vec<[u8; 32]>,
+    ///Peers the message is delivered to, besides its sender, or every peer if not set
+    pub recipients: Option<Vec<usize>>,
+    ///Checksum of the payload, if enabled and the payload isn't encrypted
+    pub checksum: Option<u64>,
 }
 
 impl Message {
@@ -22,17 +35,29 @@ impl Message {
             dot: Dot::new(0, 0),
             payload: Vec::new(),
             context: Vec::new(),
+            trace_id: None,
+            headers: None,
+            key: None,
+            context_digests: Vec::new(),
+            recipients: None,
+            checksum: None,
         }
     }
 
     /**
-     * Creates a message with payload, dot and context.
+     * Creates a message with payload, dot, context and an optional trace id.
      */
-    pub fn new(payload: Vec<u8>, dot: Dot, context: Vec<Dot>) -> Self {
+    pub fn new(payload: Vec<u8>, dot: Dot, context: Vec<Dot>, trace_id: Option<u128>) -> Self {
         Self {
             payload,
             dot,
             context,
+            trace_id,
+            headers: None,
+            key: None,
+            context_digests: Vec::new(),
+            recipients: None,
+            checksum: None,
         }
     }
 }
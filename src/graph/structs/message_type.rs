@@ -1,23 +1,120 @@
 use super::message::Message;
-use crate::graph::middleware::dot::Dot;
+use crate::broadcast::broadcast_trait::{
+    DeliveryFilter, Explanation, GraphFormat, Headers, MessageTimestamps,
+};
+use crate::channel::Sender;
+use crate::clock::dot::Dot;
+use crate::configuration::middleware_configuration::KeyRange;
+use crate::graph::graph::ConflictFunction;
+use crate::graph::middleware::message_types::SentMessage;
+use std::net::TcpStream;
 
 /**
  * Enum for the messages that will be sent/received in the channels between
  * the main middleware, stream reader and client
  */
 pub enum ClientPeerMiddleware {
-    ///Message sent by the Client to broadcast
+    ///Message sent by the Client to broadcast, with its explicit dependencies and
+    ///the channel where its dot and context are returned
     Client {
-        dot: Dot,
         msg: Vec<u8>,
-        context: Vec<Dot>,
+        deps: Vec<Dot>,
+        trace_id: Option<u128>,
+        headers: Option<Headers>,
+        key: Option<u64>,
+        recipients: Option<Vec<usize>>,
+        reply: Sender<SentMessage>,
+    },
+    ///Message received from a peer and its fingerprint
+    Peer {
+        msg: Message,
+        fingerprint: u64,
     },
-    ///Message received from a peer
-    Peer { msg: Message },
     ///Indicates that the Middleware has finished the starting up
     Setup,
     ///ACK by the Client that a message is causally stable
-    Stable { dot: Dot },
+    Stable {
+        dot: Dot,
+    },
+    ///Stream of a Reader thread connected to a peer
+    Connection {
+        peer_id: usize,
+        stream: TcpStream,
+    },
+    ///Interest set announced by a peer in its handshake
+    Interests {
+        peer_id: usize,
+        interests: Vec<KeyRange>,
+    },
+    ///Request by the Client to evict a peer from the group
+    Evict {
+        id: usize,
+    },
+    ///Request by the Client to flush the Sender threads
+    Flush,
+    ///Request by the Client to stop or restart the deliveries
+    Pause,
+    Resume,
+    ///Delivery processed by the Client
+    Ack {
+        dot: Dot,
+    },
+    ///Request by the Client to send the unacked deliveries again
+    Replay,
+    ///Predicate set by the Client to select the delivered messages
+    Filter {
+        filter: Option<DeliveryFilter>,
+    },
+    ///Function set by the Client that decides if the messages of two entity keys are related
+    Conflicts {
+        conflicts: ConflictFunction,
+    },
+    ///Request by the Client for a consistent cut of the delivered messages
+    Snapshot,
+    ///Request by the Client for the timestamps of the retained messages
+    Dump {
+        reply: Sender<Vec<MessageTimestamps>>,
+    },
+    ///Request by the Client for the causal graph serialized in a format
+    RenderGraph {
+        format: GraphFormat,
+        reply: Sender<String>,
+    },
+    ///Request by the Client for the reason a message wasn't delivered
+    Explain {
+        dot: Dot,
+        reply: Sender<Explanation>,
+    },
+    ///Probe received from a peer that must be answered
+    Ping {
+        peer_id: usize,
+        timestamp: u64,
+    },
+    ///Request by the Client to catch up with the group from a donor peer
+    CatchUp {
+        donor: usize,
+    },
+    ///Request from a peer for the retained messages above its delivered version vector
+    CatchUpRequest {
+        peer_id: usize,
+        vector: Vec<usize>,
+    },
+    ///End of the messages relayed by a donor peer
+    CaughtUp {
+        peer_id: usize,
+        cut: Option<Vec<usize>>,
+    },
+    ///Delivered version vector of a peer, sent once its connection was set up
+    ///or in reply to the local one
+    Vector {
+        peer_id: usize,
+        vector: Vec<usize>,
+        reply: bool,
+    },
+    ///Peer that closed its connection, so it won't send more messages
+    Closed {
+        peer_id: usize,
+    },
     ///Connection end
     End,
 }
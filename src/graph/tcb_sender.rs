@@ -0,0 +1,242 @@
+use crate::broadcast::broadcast_trait::{Headers, TcbError};
+use crate::channel::bounded;
+use crate::channel::{Receiver, Sender};
+use crate::clock::dot::Dot;
+use crate::graph::middleware::message_types::SentMessage;
+use crate::graph::structs::message_type::ClientPeerMiddleware;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/**
+ * Receipt of a message sent by the Client. The send calls return once the message
+ * is handed to the Middleware thread, which assigns its dot and context and returns
+ * them through the receipt. Dropping the receipt doesn't affect the message.
+ */
+pub struct SendReceipt {
+    ///Channel where the middleware thread returns the message's dot and context
+    sent_channel: Receiver<SentMessage>,
+}
+
+impl SendReceipt {
+    /**
+     * Waits for the middleware thread to assign the message's dot and context and
+     * returns them, or the error that kept the message from being sent.
+     */
+    pub fn wait(self) -> Result<(Dot, Vec<Dot>), TcbError> {
+        match self.sent_channel.recv() {
+            Ok(sent) => sent,
+            Err(_) => Err(TcbError::Terminated),
+        }
+    }
+
+    /**
+     * Waits for the middleware thread to assign the message's context and returns it.
+     */
+    pub fn context(self) -> Result<Vec<Dot>, TcbError> {
+        self.wait().map(|(_, context)| context)
+    }
+}
+
+/**
+ * Handle that broadcasts messages from any thread, without a lock around the whole
+ * GRAPH struct. The dots and contexts are assigned by the Middleware thread, which
+ * handles the messages of every handle in the order they were sent, and returned
+ * through the receipt of each sent message, so the threads sharing a handle don't
+ * take each other's contexts.
+ */
+pub struct TcbSender {
+    ///Sender end of the channel between the client and the middleware thread
+    middleware_channel: Sender<ClientPeerMiddleware>,
+    ///Local peer's globally unique id
+    local_id: usize,
+    ///Flag set once the middleware was ended, shared by every handle
+    terminated: Arc<AtomicBool>,
+}
+
+impl TcbSender {
+    /**
+     * Builds a new sender handle.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `middleware_channel` - Channel to the middleware thread.
+     */
+    pub fn new(local_id: usize, middleware_channel: Sender<ClientPeerMiddleware>) -> Self {
+        Self {
+            middleware_channel,
+            local_id,
+            terminated: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /**
+     * Marks the middleware as ended, so the sends of every handle return
+     * `TcbError::Terminated`. Returns false if it was already ended.
+     */
+    pub fn mark_terminated(&self) -> bool {
+        !self.terminated.swap(true, Ordering::SeqCst)
+    }
+
+    /**
+     * Checks if the middleware was ended.
+     */
+    pub fn is_terminated(&self) -> bool {
+        self.terminated.load(Ordering::SeqCst)
+    }
+
+    /**
+     * Broadcasts a message to every peer in the group.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     */
+    pub fn send(&self, msg: Vec<u8>) -> Result<SendReceipt, TcbError> {
+        self.broadcast(msg, None, None, None, None, &[])
+    }
+
+    /**
+     * Broadcasts a message tagged with a trace id to every peer in the group.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `trace_id` - Trace or correlation id of the message
+     */
+    pub fn send_traced(&self, msg: Vec<u8>, trace_id: u128) -> Result<SendReceipt, TcbError> {
+        self.broadcast(msg, Some(trace_id), None, None, None, &[])
+    }
+
+    /**
+     * Broadcasts a message with metadata headers to every peer in the group.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `headers` - Metadata headers of the message
+     */
+    pub fn send_with_headers(
+        &self,
+        msg: Vec<u8>,
+        headers: Headers,
+    ) -> Result<SendReceipt, TcbError> {
+        self.broadcast(msg, None, Some(headers), None, None, &[])
+    }
+
+    /**
+     * Broadcasts a message that is ordered after the explicit dependencies, besides
+     * the messages already delivered by the middleware. Returns the same as `send`.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `deps` - Dots of the messages this message must be delivered after
+     */
+    pub fn send_after(&self, msg: Vec<u8>, deps: &[Dot]) -> Result<SendReceipt, TcbError> {
+        self.broadcast(msg, None, None, None, None, deps)
+    }
+
+    /**
+     * Broadcasts a message tagged with an entity key, which is only ordered after
+     * the messages of related keys and the messages sent without a key.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `key` - Entity key of the message
+     */
+    pub fn send_keyed(&self, msg: Vec<u8>, key: u64) -> Result<SendReceipt, TcbError> {
+        self.broadcast(msg, None, None, Some(key), None, &[])
+    }
+
+    /**
+     * Sends a message that is only delivered by a subset of the peers.
+     * Returns the receipt of the sent message's dot and context if successfull.
+     *
+     * # Arguments
+     *
+     * `recipients` - Ids of the peers that deliver the message
+     *
+     * `msg` - Serialized message to be sent
+     */
+    pub fn send_to(&self, recipients: &[usize], msg: Vec<u8>) -> Result<SendReceipt, TcbError> {
+        self.broadcast(msg, None, None, None, Some(recipients.to_vec()), &[])
+    }
+
+    /**
+     * Broadcasts a message with an optional trace id, headers, entity key, recipients
+     * and explicit dependencies, without waiting for the middleware thread to assign
+     * its dot and context. Returns the receipt of the dot and context if successfull,
+     * or `TcbError::Terminated` once the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `msg` - Serialized message to be broadcast
+     *
+     * `trace_id` - Trace id of the message
+     *
+     * `headers` - Metadata headers of the message
+     *
+     * `key` - Entity key of the message
+     *
+     * `recipients` - Ids of the peers that deliver the message
+     *
+     * `deps` - Explicit dependencies of the message
+     */
+    pub fn broadcast(
+        &self,
+        msg: Vec<u8>,
+        trace_id: Option<u128>,
+        headers: Option<Headers>,
+        key: Option<u64>,
+        recipients: Option<Vec<usize>>,
+        deps: &[Dot],
+    ) -> Result<SendReceipt, TcbError> {
+        if self.is_terminated() {
+            return Err(TcbError::Terminated);
+        }
+
+        //Creating the channel where the middleware thread returns the message's context
+        let (reply, sent_channel) = bounded::<SentMessage>(1);
+
+        //Building the enum of the new message, whose dot and context
+        //are assigned by the middleware thread
+        let client_message = ClientPeerMiddleware::Client {
+            msg,
+            deps: deps.to_vec(),
+            trace_id,
+            headers,
+            key,
+            recipients,
+            reply,
+        };
+
+        //Sending the enum to the middleware thread
+        self.middleware_channel.send(client_message)?;
+
+        Ok(SendReceipt { sent_channel })
+    }
+}
+
+impl Clone for TcbSender {
+    /**
+     * Creates another handle to the same middleware.
+     */
+    fn clone(&self) -> Self {
+        Self {
+            middleware_channel: self.middleware_channel.clone(),
+            local_id: self.local_id,
+            terminated: Arc::clone(&self.terminated),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 //! A middleware service for delivering messages in a causal order.
 extern crate bincode;
 extern crate bit_vec;
+#[cfg(feature = "crossbeam-channels")]
 extern crate crossbeam;
 extern crate serde;
 #[macro_use]
@@ -13,6 +14,15 @@ pub mod broadcast;
  * Causal verification from a broadcast results.
  */
 pub mod causality_checker;
+/**
+ * Channels used by the middleware, with a backend selected by the `crossbeam-channels`,
+ * `flume-channels` or `std-channels` features.
+ */
+pub mod channel;
+/**
+ * Logical clocks used by the middleware.
+ */
+pub mod clock;
 /**
  * Middleware configuration.
  */
@@ -21,7 +31,39 @@ pub mod configuration;
  * Causal delivery middleware that uses a graph approach.
  */
 pub mod graph;
+/**
+ * Runtime metrics of the middleware service.
+ */
+pub mod metrics;
+/**
+ * Platform specific thread scheduling.
+ */
+pub mod platform;
+/**
+ * Two connected middleware instances on the loopback interface for examples and tests.
+ */
+pub mod quickstart;
+/**
+ * Security features of the middleware.
+ */
+pub mod security;
+/**
+ * Sidecar process interfaces for non-Rust services, enabled by the `grpc` and `http` features.
+ */
+#[cfg(any(feature = "grpc", feature = "http"))]
+pub mod sidecar;
+/**
+ * OpenTelemetry integration, enabled by the `otel` feature.
+ */
+#[cfg(feature = "otel")]
+pub mod telemetry;
+/**
+ * Transports of the connections between the peers, including the experimental
+ * ones, and the framing shared by both engines.
+ */
+pub mod transport;
 /**
  * Causal delivery middleware that uses version vectors.
  */
+#[cfg(feature = "vv")]
 pub mod vv;
@@ -0,0 +1,66 @@
+use crate::metrics::middleware_metrics::MiddlewareMetrics;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+/**
+ * Stream of a connection to a peer that counts the bytes written into and read
+ * from it in the peer's metrics. It wraps the stream once the connection is set
+ * up, so the counters include the batching and compression of the frames but
+ * not the handshakes, the TLS records or the datagram headers of the UDP transport.
+ */
+pub struct MeteredStream<S> {
+    ///Wrapped stream
+    stream: S,
+    ///Id of the peer at the other end of the connection
+    peer_id: usize,
+    ///Metrics where the bytes are counted
+    metrics: Arc<MiddlewareMetrics>,
+}
+
+impl<S> MeteredStream<S> {
+    /**
+     * Wraps the stream of a connection to a peer.
+     *
+     * # Arguments
+     *
+     * `stream` - Stream between the peers.
+     *
+     * `peer_id` - Other peer's globally unique id.
+     *
+     * `metrics` - Metrics where the bytes are counted.
+     */
+    pub fn new(stream: S, peer_id: usize, metrics: Arc<MiddlewareMetrics>) -> Self {
+        Self {
+            stream,
+            peer_id,
+            metrics,
+        }
+    }
+
+    /**
+     * Returns the wrapped stream.
+     */
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+}
+
+impl<S: Read> Read for MeteredStream<S> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.stream.read(buffer)?;
+        self.metrics.update_bytes_received(self.peer_id, read);
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for MeteredStream<S> {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let written = self.stream.write(buffer)?;
+        self.metrics.update_bytes_sent(self.peer_id, written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
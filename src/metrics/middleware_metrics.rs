@@ -0,0 +1,574 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/**
+ * Weight of the latest measurement in the smoothed round trip time.
+ */
+const RTT_WEIGHT: f64 = 0.125;
+
+/**
+ * Metrics of the connection to a peer.
+ */
+#[derive(Debug, Clone)]
+pub struct PeerMetrics {
+    ///Peer's globally unique id
+    pub peer_id: usize,
+    ///Last measured round trip time
+    pub last_rtt: Option<Duration>,
+    ///Exponentially weighted moving average of the round trip time
+    pub smoothed_rtt: Option<Duration>,
+    ///Time of the last received probe response
+    pub last_probe_response: Option<SystemTime>,
+    ///Highest counter received from the peer
+    pub highest_received: usize,
+    ///Highest counter delivered from the peer
+    pub highest_delivered: usize,
+    ///Difference between the highest received and delivered counters, which
+    ///grows while the peer's messages wait for a missing dependency
+    pub delivery_lag: usize,
+    ///Whether the local peer connected to the peer
+    pub outbound_connected: bool,
+    ///Whether the peer is connected to the local peer, until its connection is closed
+    pub inbound_connected: bool,
+    ///Bytes written into the connections to the peer, after batching and compression
+    pub bytes_sent: u64,
+    ///Bytes read from the connections from the peer, after batching and compression
+    pub bytes_received: u64,
+}
+
+impl PeerMetrics {
+    /**
+     * Creates the metrics of a peer without measurements.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Peer's globally unique id.
+     */
+    pub fn new(peer_id: usize) -> Self {
+        Self {
+            peer_id,
+            last_rtt: None,
+            smoothed_rtt: None,
+            last_probe_response: None,
+            highest_received: 0,
+            highest_delivered: 0,
+            delivery_lag: 0,
+            outbound_connected: false,
+            inbound_connected: false,
+            bytes_sent: 0,
+            bytes_received: 0,
+        }
+    }
+
+    /**
+     * Checks if the peer answered a probe in the last `timeout`.
+     *
+     * # Arguments
+     *
+     * `timeout` - Maximum time since the last probe response.
+     */
+    pub fn is_healthy(&self, timeout: Duration) -> bool {
+        match self.last_probe_response {
+            Some(time) => match time.elapsed() {
+                Ok(elapsed) => elapsed <= timeout,
+                Err(_) => true,
+            },
+            None => false,
+        }
+    }
+}
+
+/**
+ * Statistics of the pool of buffers reused by the nodes of the causal graph.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct PoolMetrics {
+    ///Number of buffers allocated because the pool was empty
+    pub allocated: u64,
+    ///Number of buffers reused from the pool
+    pub recycled: u64,
+    ///Number of buffers currently kept in the pool
+    pub available: usize,
+}
+
+/**
+ * Occupancy statistics of the causal graph of the GRAPH middleware. Nodes are
+ * only removed once the Client acks them as stable, so an occupancy that keeps
+ * growing means the stable messages aren't being acked.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct GraphMetrics {
+    ///Number of nodes currently in the graph
+    pub occupied: usize,
+    ///Number of slots of deleted nodes waiting to be reused
+    pub free: usize,
+    ///Highest number of nodes kept in the graph at once
+    pub high_water: usize,
+    ///Number of nodes the graph can hold without reallocating
+    pub capacity: usize,
+}
+
+/**
+ * Statistics of the log of retained delivered messages.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RetentionMetrics {
+    ///Number of retained messages
+    pub messages: usize,
+    ///Number of retained payload bytes
+    pub bytes: u64,
+    ///Number of messages dropped because the log exceeded its bounds
+    pub dropped: u64,
+}
+
+/**
+ * Snapshot of the middleware metrics returned to the Client.
+ */
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    ///Metrics of the connections to every other peer
+    pub peers: Vec<PeerMetrics>,
+    ///Statistics of the node pool, which is only used by the GRAPH middleware
+    pub pool: PoolMetrics,
+    ///Occupancy of the causal graph, which is only used by the GRAPH middleware
+    pub graph: GraphMetrics,
+    ///Statistics of the retained messages, which are empty if retention isn't enabled
+    pub retention: RetentionMetrics,
+}
+
+/**
+ * Numbers of the messages handled by the middleware, counted as they are
+ * sent, delivered and become stable.
+ */
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageCounts {
+    ///Number of messages sent by the local peer
+    pub sent: u64,
+    ///Number of messages delivered from every other peer, including the filtered ones
+    pub delivered: u64,
+    ///Number of messages that became stable, including the local ones
+    pub stable: u64,
+    ///Number of messages received from the other peers that weren't delivered yet
+    pub pending: usize,
+}
+
+/**
+ * Summary of a middleware's activity returned by `shutdown`, once it ended.
+ */
+#[derive(Debug, Clone)]
+pub struct ShutdownReport {
+    ///Number of messages sent by the local peer
+    pub sent: u64,
+    ///Number of messages delivered from every other peer, including the filtered ones
+    pub delivered: u64,
+    ///Number of messages that became stable, including the local ones
+    pub stable: u64,
+    ///Number of messages received from the other peers that were never delivered
+    pub pending: usize,
+    ///Bytes written into the connections to every other peer
+    pub bytes_sent: u64,
+    ///Bytes read from the connections from every other peer
+    pub bytes_received: u64,
+    ///Final metrics of the connections to every other peer
+    pub peers: Vec<PeerMetrics>,
+}
+
+/**
+ * Metrics shared between the middleware threads and the Client.
+ */
+#[derive(Debug)]
+pub struct MiddlewareMetrics {
+    ///Local peer's globally unique id
+    local_id: usize,
+    ///Metrics of each peer, indexed by the peer's id
+    peers: Vec<Mutex<PeerMetrics>>,
+    ///Buffers allocated by the node pool
+    pool_allocated: AtomicU64,
+    ///Buffers reused from the node pool
+    pool_recycled: AtomicU64,
+    ///Buffers kept in the node pool
+    pool_available: AtomicUsize,
+    ///Nodes in the causal graph
+    graph_occupied: AtomicUsize,
+    ///Free slots in the causal graph
+    graph_free: AtomicUsize,
+    ///Highest number of nodes in the causal graph
+    graph_high_water: AtomicUsize,
+    ///Capacity of the causal graph
+    graph_capacity: AtomicUsize,
+    ///Messages in the retention log
+    retained_messages: AtomicUsize,
+    ///Payload bytes in the retention log
+    retained_bytes: AtomicU64,
+    ///Messages dropped because the retention log exceeded its bounds
+    retention_dropped: AtomicU64,
+    ///Messages sent by the local peer
+    sent: AtomicU64,
+    ///Messages delivered from the other peers
+    delivered_messages: AtomicU64,
+    ///Messages that became stable
+    stable: AtomicU64,
+    ///Messages received from the other peers that weren't delivered yet
+    pending: AtomicUsize,
+    ///Counter of the last local message each peer acknowledged as delivered
+    receipts: Vec<AtomicUsize>,
+    ///Highest counter received from each peer
+    received: Vec<AtomicUsize>,
+    ///Highest counter delivered from each peer
+    delivered: Vec<AtomicUsize>,
+    ///Bytes written into the connections to each peer
+    bytes_sent: Vec<AtomicU64>,
+    ///Bytes read from the connections from each peer
+    bytes_received: Vec<AtomicU64>,
+}
+
+impl MiddlewareMetrics {
+    /**
+     * Creates the metrics for a group.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `peer_number` - Number of peers in the group.
+     */
+    pub fn new(local_id: usize, peer_number: usize) -> Self {
+        let peers = (0..peer_number)
+            .map(|peer_id| Mutex::new(PeerMetrics::new(peer_id)))
+            .collect();
+
+        Self {
+            local_id,
+            peers,
+            pool_allocated: AtomicU64::new(0),
+            pool_recycled: AtomicU64::new(0),
+            pool_available: AtomicUsize::new(0),
+            graph_occupied: AtomicUsize::new(0),
+            graph_free: AtomicUsize::new(0),
+            graph_high_water: AtomicUsize::new(0),
+            graph_capacity: AtomicUsize::new(0),
+            retained_messages: AtomicUsize::new(0),
+            retained_bytes: AtomicU64::new(0),
+            retention_dropped: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            delivered_messages: AtomicU64::new(0),
+            stable: AtomicU64::new(0),
+            pending: AtomicUsize::new(0),
+            receipts: (0..peer_number).map(|_| AtomicUsize::new(0)).collect(),
+            received: (0..peer_number).map(|_| AtomicUsize::new(0)).collect(),
+            delivered: (0..peer_number).map(|_| AtomicUsize::new(0)).collect(),
+            bytes_sent: (0..peer_number).map(|_| AtomicU64::new(0)).collect(),
+            bytes_received: (0..peer_number).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /**
+     * Records a round trip time measured with a probe to a peer.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Probed peer's globally unique id.
+     *
+     * `rtt` - Measured round trip time.
+     */
+    pub fn update_rtt(&self, peer_id: usize, rtt: Duration) {
+        let mut peer = self.peers[peer_id]
+            .lock()
+            .expect("ERROR: Peer metrics lock was poisoned");
+
+        peer.smoothed_rtt = match peer.smoothed_rtt {
+            Some(smoothed_rtt) => {
+                Some(smoothed_rtt.mul_f64(1.0 - RTT_WEIGHT) + rtt.mul_f64(RTT_WEIGHT))
+            }
+            None => Some(rtt),
+        };
+        peer.last_rtt = Some(rtt);
+        peer.last_probe_response = Some(SystemTime::now());
+    }
+
+    /**
+     * Records the latest statistics of the node pool.
+     *
+     * # Arguments
+     *
+     * `pool` - Statistics of the node pool.
+     */
+    pub fn update_pool(&self, pool: PoolMetrics) {
+        self.pool_allocated.store(pool.allocated, Ordering::Relaxed);
+        self.pool_recycled.store(pool.recycled, Ordering::Relaxed);
+        self.pool_available.store(pool.available, Ordering::Relaxed);
+    }
+
+    /**
+     * Records the latest occupancy statistics of the causal graph.
+     *
+     * # Arguments
+     *
+     * `graph` - Occupancy statistics of the causal graph.
+     */
+    pub fn update_graph(&self, graph: GraphMetrics) {
+        self.graph_occupied.store(graph.occupied, Ordering::Relaxed);
+        self.graph_free.store(graph.free, Ordering::Relaxed);
+        self.graph_high_water
+            .store(graph.high_water, Ordering::Relaxed);
+        self.graph_capacity.store(graph.capacity, Ordering::Relaxed);
+    }
+
+    /**
+     * Records the latest statistics of the retention log.
+     *
+     * # Arguments
+     *
+     * `retention` - Statistics of the retention log.
+     */
+    pub fn update_retention(&self, retention: RetentionMetrics) {
+        self.retained_messages
+            .store(retention.messages, Ordering::Relaxed);
+        self.retained_bytes
+            .store(retention.bytes, Ordering::Relaxed);
+        self.retention_dropped
+            .store(retention.dropped, Ordering::Relaxed);
+    }
+
+    /**
+     * Records the highest counters received and delivered from each peer.
+     *
+     * # Arguments
+     *
+     * `received` - Highest counter received from each peer.
+     *
+     * `delivered` - Version vector of the delivered messages.
+     */
+    pub fn update_watermarks(&self, received: &[usize], delivered: &[usize]) {
+        for (watermark, counter) in self.received.iter().zip(received) {
+            watermark.store(*counter, Ordering::Relaxed);
+        }
+
+        for (watermark, counter) in self.delivered.iter().zip(delivered) {
+            watermark.store(*counter, Ordering::Relaxed);
+        }
+    }
+
+    /**
+     * Records the numbers of the messages handled by the middleware.
+     *
+     * # Arguments
+     *
+     * `counts` - Numbers of the sent, delivered, stable and pending messages.
+     */
+    pub fn update_counts(&self, counts: MessageCounts) {
+        self.sent.store(counts.sent, Ordering::Relaxed);
+        self.delivered_messages
+            .store(counts.delivered, Ordering::Relaxed);
+        self.stable.store(counts.stable, Ordering::Relaxed);
+        self.pending.store(counts.pending, Ordering::Relaxed);
+    }
+
+    /**
+     * Adds the bytes written into a connection to a peer.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Peer's globally unique id.
+     *
+     * `bytes` - Number of bytes written.
+     */
+    pub fn update_bytes_sent(&self, peer_id: usize, bytes: usize) {
+        if let Some(counter) = self.bytes_sent.get(peer_id) {
+            counter.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    /**
+     * Adds the bytes read from a connection from a peer.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Peer's globally unique id.
+     *
+     * `bytes` - Number of bytes read.
+     */
+    pub fn update_bytes_received(&self, peer_id: usize, bytes: usize) {
+        if let Some(counter) = self.bytes_received.get(peer_id) {
+            counter.fetch_add(bytes as u64, Ordering::Relaxed);
+        }
+    }
+
+    /**
+     * Returns the version vector of the delivered messages, as last recorded
+     * by the Middleware thread.
+     */
+    pub fn delivered_vector(&self) -> Vec<usize> {
+        self.delivered
+            .iter()
+            .map(|watermark| watermark.load(Ordering::Relaxed))
+            .collect()
+    }
+
+    /**
+     * Records a connection to or from a peer.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Connected peer's globally unique id.
+     *
+     * `outbound` - Whether the local peer connected to the peer.
+     */
+    pub fn update_connection(&self, peer_id: usize, outbound: bool) {
+        if let Some(peer) = self.peers.get(peer_id) {
+            let mut peer = peer.lock().expect("ERROR: Peer metrics lock was poisoned");
+
+            if outbound {
+                peer.outbound_connected = true;
+            } else {
+                peer.inbound_connected = true;
+            }
+        }
+    }
+
+    /**
+     * Records that the connection from a peer was closed.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Disconnected peer's globally unique id.
+     */
+    pub fn update_disconnection(&self, peer_id: usize) {
+        if let Some(peer) = self.peers.get(peer_id) {
+            let mut peer = peer.lock().expect("ERROR: Peer metrics lock was poisoned");
+            peer.inbound_connected = false;
+        }
+    }
+
+    /**
+     * Checks if a peer is connected to the local peer.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Peer's globally unique id.
+     */
+    pub fn is_inbound_connected(&self, peer_id: usize) -> bool {
+        match self.peers.get(peer_id) {
+            Some(peer) => {
+                peer.lock()
+                    .expect("ERROR: Peer metrics lock was poisoned")
+                    .inbound_connected
+            }
+            None => false,
+        }
+    }
+
+    /**
+     * Records a receipt of the local messages delivered by a peer.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Globally unique id of the peer that sent the receipt.
+     *
+     * `counter` - Counter of the last local message delivered by the peer.
+     */
+    pub fn update_receipt(&self, peer_id: usize, counter: usize) {
+        if let Some(receipt) = self.receipts.get(peer_id) {
+            receipt.fetch_max(counter, Ordering::Relaxed);
+        }
+    }
+
+    /**
+     * Returns the ids of the peers that acknowledged the delivery of a local message.
+     *
+     * # Arguments
+     *
+     * `counter` - Counter of the local message.
+     */
+    pub fn delivered_by(&self, counter: usize) -> Vec<usize> {
+        self.receipts
+            .iter()
+            .enumerate()
+            .filter(|(peer_id, receipt)| {
+                *peer_id != self.local_id && receipt.load(Ordering::Relaxed) >= counter
+            })
+            .map(|(peer_id, _)| peer_id)
+            .collect()
+    }
+
+    /**
+     * Returns a snapshot of the current metrics.
+     */
+    pub fn snapshot(&self) -> Metrics {
+        let peers = self
+            .peers
+            .iter()
+            .enumerate()
+            .map(|(peer_id, peer)| {
+                let mut peer = peer
+                    .lock()
+                    .expect("ERROR: Peer metrics lock was poisoned")
+                    .clone();
+
+                peer.highest_received = self.received[peer_id].load(Ordering::Relaxed);
+                peer.highest_delivered = self.delivered[peer_id].load(Ordering::Relaxed);
+                peer.delivery_lag = peer.highest_received.saturating_sub(peer.highest_delivered);
+                peer.bytes_sent = self.bytes_sent[peer_id].load(Ordering::Relaxed);
+                peer.bytes_received = self.bytes_received[peer_id].load(Ordering::Relaxed);
+                peer
+            })
+            .filter(|peer| peer.peer_id != self.local_id)
+            .collect();
+
+        let pool = PoolMetrics {
+            allocated: self.pool_allocated.load(Ordering::Relaxed),
+            recycled: self.pool_recycled.load(Ordering::Relaxed),
+            available: self.pool_available.load(Ordering::Relaxed),
+        };
+
+        let graph = GraphMetrics {
+            occupied: self.graph_occupied.load(Ordering::Relaxed),
+            free: self.graph_free.load(Ordering::Relaxed),
+            high_water: self.graph_high_water.load(Ordering::Relaxed),
+            capacity: self.graph_capacity.load(Ordering::Relaxed),
+        };
+
+        let retention = RetentionMetrics {
+            messages: self.retained_messages.load(Ordering::Relaxed),
+            bytes: self.retained_bytes.load(Ordering::Relaxed),
+            dropped: self.retention_dropped.load(Ordering::Relaxed),
+        };
+
+        Metrics {
+            peers,
+            pool,
+            graph,
+            retention,
+        }
+    }
+
+    /**
+     * Returns the summary of the middleware's activity, which is final once
+     * the Middleware thread ended.
+     */
+    pub fn report(&self) -> ShutdownReport {
+        let peers = self.snapshot().peers;
+
+        ShutdownReport {
+            sent: self.sent.load(Ordering::Relaxed),
+            delivered: self.delivered_messages.load(Ordering::Relaxed),
+            stable: self.stable.load(Ordering::Relaxed),
+            pending: self.pending.load(Ordering::Relaxed),
+            bytes_sent: peers.iter().map(|peer| peer.bytes_sent).sum(),
+            bytes_received: peers.iter().map(|peer| peer.bytes_received).sum(),
+            peers,
+        }
+    }
+}
+
+/**
+ * Returns the current time in microseconds since the UNIX epoch, used
+ * to timestamp the probes sent to other peers.
+ */
+pub fn probe_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as u64)
+        .unwrap_or(0)
+}
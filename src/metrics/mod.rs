@@ -0,0 +1,9 @@
+/**
+ * Metrics gathered by the middleware threads.
+ */
+pub mod middleware_metrics;
+
+/**
+ * Streams that count the bytes sent to and received from each peer.
+ */
+pub mod metered_stream;
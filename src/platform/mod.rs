@@ -0,0 +1,4 @@
+/**
+ * Priority and CPU affinity of the middleware threads.
+ */
+pub mod threads;
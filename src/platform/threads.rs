@@ -0,0 +1,63 @@
+use crate::configuration::middleware_configuration::ThreadSettings;
+
+/**
+ * Applies the priority and CPU affinity settings to the calling thread.
+ * Failures are printed as warnings, since the middleware still works
+ * with the default scheduling.
+ *
+ * # Arguments
+ *
+ * `settings` - Scheduling settings of the thread.
+ */
+#[cfg(all(feature = "affinity", target_os = "linux"))]
+pub fn apply_thread_settings(settings: &ThreadSettings) {
+    if !settings.cores.is_empty() {
+        //Restricting the thread to the configured cores
+        let result = unsafe {
+            let mut cpu_set: libc::cpu_set_t = std::mem::zeroed();
+            libc::CPU_ZERO(&mut cpu_set);
+
+            for &core in &settings.cores {
+                libc::CPU_SET(core, &mut cpu_set);
+            }
+
+            libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &cpu_set)
+        };
+
+        if result != 0 {
+            println!(
+                "WARN: Failed to set the affinity of a thread to the cores {:?}\n\t- {}",
+                settings.cores,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    if let Some(priority) = settings.priority {
+        //On Linux, the nice value of the process id 0 is the calling thread's
+        let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, priority) };
+
+        if result != 0 {
+            println!(
+                "WARN: Failed to set the priority of a thread to {}\n\t- {}",
+                priority,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/**
+ * Warns that the thread settings are ignored, since they require
+ * the `affinity` feature on Linux.
+ *
+ * # Arguments
+ *
+ * `settings` - Scheduling settings of the thread.
+ */
+#[cfg(not(all(feature = "affinity", target_os = "linux")))]
+pub fn apply_thread_settings(settings: &ThreadSettings) {
+    if settings.is_set() {
+        println!("WARN: Thread priority and affinity require the affinity feature on Linux");
+    }
+}
@@ -0,0 +1,121 @@
+use crate::configuration::middleware_configuration::{Configuration, Peer, Topology};
+use std::net::TcpListener;
+use std::thread;
+
+#[cfg(feature = "graph")]
+use crate::graph::graph::GRAPH;
+#[cfg(feature = "vv")]
+use crate::vv::version_vector::VV;
+
+/**
+ * Creates two connected GRAPH middleware instances on the loopback interface,
+ * with the default configuration and ports picked by the OS, so an example
+ * needs no configuration file and no port bookkeeping. The first instance is
+ * peer 0 and the second is peer 1.
+ *
+ * ```
+ * use tcb::broadcast::broadcast_trait::{GenericReturn, TCB};
+ *
+ * let (mut first, mut second) = tcb::quickstart::local_pair();
+ *
+ * first.send(b"hello".to_vec()).unwrap();
+ *
+ * match second.recv() {
+ *     Ok(GenericReturn::Delivery(payload, sender_id, _)) => {
+ *         assert_eq!(payload, b"hello");
+ *         assert_eq!(sender_id, 0);
+ *     }
+ *     _ => panic!("The message wasn't delivered"),
+ * }
+ *
+ * first.end();
+ * second.end();
+ * ```
+ */
+#[cfg(feature = "graph")]
+pub fn local_pair() -> (GRAPH, GRAPH) {
+    pair(GRAPH::with_listener)
+}
+
+/**
+ * Creates two connected VV middleware instances on the loopback interface,
+ * with the default configuration and ports picked by the OS. The first
+ * instance is peer 0 and the second is peer 1.
+ *
+ * ```
+ * use tcb::broadcast::broadcast_trait::{GenericReturn, TCB};
+ *
+ * let (mut first, mut second) = tcb::quickstart::local_vv_pair();
+ *
+ * second.send(b"hello".to_vec()).unwrap();
+ *
+ * match first.recv() {
+ *     Ok(GenericReturn::Delivery(payload, sender_id, _)) => {
+ *         assert_eq!(payload, b"hello");
+ *         assert_eq!(sender_id, 1);
+ *     }
+ *     _ => panic!("The message wasn't delivered"),
+ * }
+ *
+ * first.end();
+ * second.end();
+ * ```
+ */
+#[cfg(feature = "vv")]
+pub fn local_vv_pair() -> (VV, VV) {
+    pair(VV::with_listener)
+}
+
+/**
+ * Binds a listener to an ephemeral loopback port for each peer and creates
+ * both middleware instances at once, since each one only returns after it's
+ * connected to the other.
+ *
+ * # Arguments
+ *
+ * `build` - Creates a middleware instance that accepts connections on a listener.
+ */
+#[cfg_attr(not(any(feature = "graph", feature = "vv")), allow(dead_code))]
+fn pair<T: Send + 'static>(build: fn(Configuration, TcpListener) -> T) -> (T, T) {
+    let listeners: Vec<TcpListener> = (0..2)
+        .map(|_| {
+            TcpListener::bind("127.0.0.1:0")
+                .expect("ERROR: Couldn't bind a listener to a loopback port")
+        })
+        .collect();
+
+    let peers: Vec<Peer> = listeners
+        .iter()
+        .enumerate()
+        .map(|(id, listener)| {
+            let address = listener
+                .local_addr()
+                .expect("ERROR: Couldn't read the address of a loopback listener");
+
+            Peer::new(id, address.to_string())
+        })
+        .collect();
+
+    let mut listeners = listeners.into_iter();
+    let first_listener = listeners.next().unwrap();
+    let second_listener = listeners.next().unwrap();
+
+    let second_configuration = Configuration {
+        topology: Topology::new(1, peers.clone()),
+        ..Configuration::default()
+    };
+
+    let second = thread::spawn(move || build(second_configuration, second_listener));
+
+    let first_configuration = Configuration {
+        topology: Topology::new(0, peers),
+        ..Configuration::default()
+    };
+
+    let first = build(first_configuration, first_listener);
+    let second = second
+        .join()
+        .expect("ERROR: Failed to create the second middleware instance");
+
+    (first, second)
+}
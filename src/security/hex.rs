@@ -0,0 +1,21 @@
+/**
+ * Decodes a key written in hexadecimal, returning None if it
+ * doesn't have exactly N bytes or has invalid characters.
+ *
+ * # Arguments
+ *
+ * `hex_key` - Hexadecimal key.
+ */
+pub fn decode_key<const N: usize>(hex_key: &str) -> Option<[u8; N]> {
+    if hex_key.len() != N * 2 || !hex_key.is_ascii() {
+        return None;
+    }
+
+    let mut key = [0u8; N];
+
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex_key[2 * i..2 * i + 2], 16).ok()?;
+    }
+
+    Some(key)
+}
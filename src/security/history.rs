@@ -0,0 +1,158 @@
+use crate::broadcast::broadcast_trait::Fault;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/**
+ * Hash of a message and of its causal history.
+ */
+pub type HistoryDigest = [u8; 32];
+
+/**
+ * History hashes of the Byzantine-tolerant causal broadcast mode. The digest
+ * of a message covers its sender id, counter, payload and the digests of its
+ * causal dependencies, so it commits to the message's whole causal history.
+ * Messages carry the digests of their dependencies, which are signed with the
+ * message, and each peer validates them against the digests of the messages it
+ * received. A sender that broadcasts two different messages with the same dot
+ * is detected either when both reach a peer or when a peer receives a dependency
+ * digest that doesn't match the message it delivered.
+ */
+pub struct HistoryDigests {
+    ///Digests of the received or sent messages, indexed by sender id and counter
+    digests: HashMap<(usize, usize), HistoryDigest>,
+    ///Digests referenced by other messages of messages not yet received
+    expected: HashMap<(usize, usize), HistoryDigest>,
+}
+
+impl HistoryDigests {
+    /**
+     * Creates an empty set of history digests.
+     */
+    pub fn new() -> Self {
+        Self {
+            digests: HashMap::new(),
+            expected: HashMap::new(),
+        }
+    }
+
+    /**
+     * Calculates the digest of a message.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     *
+     * `dependencies` - Sender id, counter and digest of the message's dependencies
+     *
+     * `payload` - Message payload
+     */
+    pub fn digest(
+        id: usize,
+        counter: usize,
+        dependencies: &[(usize, usize, HistoryDigest)],
+        payload: &[u8],
+    ) -> HistoryDigest {
+        let mut hasher = Sha256::new();
+
+        hasher.update((id as u64).to_le_bytes());
+        hasher.update((counter as u64).to_le_bytes());
+
+        for (dependency_id, dependency_counter, dependency_digest) in dependencies {
+            hasher.update((*dependency_id as u64).to_le_bytes());
+            hasher.update((*dependency_counter as u64).to_le_bytes());
+            hasher.update(dependency_digest);
+        }
+
+        hasher.update(payload);
+        hasher.finalize().into()
+    }
+
+    /**
+     * Returns the digest of a received or sent message.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     */
+    pub fn get(&self, id: usize, counter: usize) -> Option<HistoryDigest> {
+        self.digests.get(&(id, counter)).copied()
+    }
+
+    /**
+     * Records the digest of a received or sent message. An equivocation is
+     * returned if a different digest was recorded or expected for its dot.
+     *
+     * # Arguments
+     *
+     * `id` - Sender id
+     *
+     * `counter` - Message counter
+     *
+     * `digest` - Message digest
+     */
+    pub fn record(
+        &mut self,
+        id: usize,
+        counter: usize,
+        digest: HistoryDigest,
+    ) -> Result<(), Fault> {
+        match self.digests.get(&(id, counter)) {
+            Some(recorded_digest) if *recorded_digest != digest => {
+                return Err(Fault::Equivocation { id, counter });
+            }
+            Some(_) => return Ok(()),
+            None => {}
+        }
+
+        match self.expected.remove(&(id, counter)) {
+            Some(expected_digest) if expected_digest != digest => {
+                Err(Fault::Equivocation { id, counter })
+            }
+            _ => {
+                self.digests.insert((id, counter), digest);
+                Ok(())
+            }
+        }
+    }
+
+    /**
+     * Validates the digest of a dependency referenced by a received message.
+     * An equivocation is returned if it differs from the recorded or
+     * previously expected digest of the dependency.
+     *
+     * # Arguments
+     *
+     * `id` - Dependency sender id
+     *
+     * `counter` - Dependency counter
+     *
+     * `digest` - Dependency digest referenced by the message
+     */
+    pub fn expect(
+        &mut self,
+        id: usize,
+        counter: usize,
+        digest: HistoryDigest,
+    ) -> Result<(), Fault> {
+        let known_digest = match self.digests.get(&(id, counter)) {
+            Some(recorded_digest) => *recorded_digest,
+            None => *self.expected.entry((id, counter)).or_insert(digest),
+        };
+
+        if known_digest == digest {
+            Ok(())
+        } else {
+            Err(Fault::Equivocation { id, counter })
+        }
+    }
+}
+
+impl Default for HistoryDigests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
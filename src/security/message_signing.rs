@@ -0,0 +1,103 @@
+use crate::configuration::middleware_configuration::Configuration;
+use crate::security::hex::decode_key;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::convert::TryInto;
+
+///Size in bytes of the private and public keys
+const KEY_SIZE: usize = 32;
+
+///Size in bytes of a signature
+const SIGNATURE_SIZE: usize = 64;
+
+/**
+ * Signs the messages broadcast by the local peer and verifies the messages
+ * received from other peers with ed25519. Each message is verified with
+ * the public key of its sender, the peer in its dot in GRAPH or the peer of
+ * the connection in VV, so a compromised or buggy peer can't inject messages
+ * claiming another peer's dot.
+ */
+pub struct MessageSigner {
+    ///Private key of the local peer
+    signing_key: SigningKey,
+    ///Public keys of every peer, indexed by the peer id
+    verifying_keys: Vec<VerifyingKey>,
+}
+
+impl MessageSigner {
+    /**
+     * Creates a signer with the keys of the configuration file,
+     * or returns None if the messages aren't signed.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn from_configuration(configuration: &Configuration) -> Option<Self> {
+        match &configuration.signing {
+            Some(signing) => {
+                let private_key = decode_key::<KEY_SIZE>(&signing.private_key)
+                    .expect("ERROR: The private key must have 64 hexadecimal characters");
+
+                let verifying_keys = signing
+                    .public_keys
+                    .iter()
+                    .map(|public_key| {
+                        let public_key = decode_key::<KEY_SIZE>(public_key)
+                            .expect("ERROR: The public keys must have 64 hexadecimal characters");
+
+                        VerifyingKey::from_bytes(&public_key)
+                            .expect("ERROR: Invalid ed25519 public key")
+                    })
+                    .collect();
+
+                Some(Self {
+                    signing_key: SigningKey::from_bytes(&private_key),
+                    verifying_keys,
+                })
+            }
+            None => None,
+        }
+    }
+
+    /**
+     * Signs a serialized message.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message broadcast by the local peer.
+     */
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.signing_key.sign(message).to_bytes().to_vec()
+    }
+
+    /**
+     * Verifies the signature of a serialized message with the sender's
+     * public key. Unsigned messages and unknown senders fail the verification.
+     *
+     * # Arguments
+     *
+     * `sender_id` - Id of the peer that created the message.
+     *
+     * `message` - Serialized message received from a peer.
+     *
+     * `signature` - Signature sent with the message.
+     */
+    pub fn verify(&self, sender_id: usize, message: &[u8], signature: &Option<Vec<u8>>) -> bool {
+        let verifying_key = match self.verifying_keys.get(sender_id) {
+            Some(verifying_key) => verifying_key,
+            None => return false,
+        };
+
+        let signature: [u8; SIGNATURE_SIZE] = match signature {
+            Some(signature) => match signature.as_slice().try_into() {
+                Ok(signature) => signature,
+                Err(_) => return false,
+            },
+            None => return false,
+        };
+
+        verifying_key
+            .verify(message, &Signature::from_bytes(&signature))
+            .is_ok()
+    }
+}
@@ -0,0 +1,33 @@
+/**
+ * Decoding of the hexadecimal keys set in the configuration file.
+ */
+pub mod hex;
+/**
+ * History hashes of the Byzantine-tolerant causal broadcast mode, enabled by the `bcb` feature.
+ */
+#[cfg(feature = "bcb")]
+pub mod history;
+/**
+ * Per-message ed25519 signatures, enabled by the `signing` feature.
+ */
+#[cfg(feature = "signing")]
+pub mod message_signing;
+/**
+ * Noise_XX handshake and per-connection session keys, enabled by the `noise` feature.
+ */
+#[cfg(feature = "noise")]
+pub mod noise;
+/**
+ * End-to-end payload encryption with a group key, enabled by the `encryption` feature.
+ */
+#[cfg(feature = "encryption")]
+pub mod payload_encryption;
+/**
+ * Stream between two peers, plain or secured with mutual TLS.
+ */
+pub mod peer_stream;
+/**
+ * Mutual TLS with certificates bound to the peer ids, enabled by the `tls` feature.
+ */
+#[cfg(feature = "tls")]
+pub mod tls;
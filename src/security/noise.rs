@@ -0,0 +1,237 @@
+use crate::configuration::middleware_configuration::Configuration;
+use crate::security::hex::decode_key;
+use snow::{Builder, HandshakeState, TransportState};
+use std::error::Error;
+use std::io::{Read, Write};
+
+///Noise protocol, cipher suite and hash used by the handshake
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+///Maximum size in bytes of a Noise message
+const MAX_MESSAGE_SIZE: usize = 65535;
+
+///Size in bytes of the authentication tag of each Noise message
+const TAG_SIZE: usize = 16;
+
+///Size in bytes of the private and public keys
+const KEY_SIZE: usize = 32;
+
+/**
+ * Session of a connection secured with the Noise_XX handshake, an alternative
+ * to a certificate infrastructure. The Sender thread initiates the handshake
+ * and the Reader thread of the other peer responds to it, after the id
+ * handshake. The resulting per-connection session keys encrypt the messages
+ * written into the stream, which are split in Noise messages if needed.
+ */
+pub struct NoiseSession {
+    ///Session keys and nonces of the connection
+    transport: TransportState,
+}
+
+impl NoiseSession {
+    /**
+     * Initiates the handshake with a peer, returning None if Noise isn't
+     * set in the configuration file.
+     *
+     * # Arguments
+     *
+     * `stream` - Stream between the peers.
+     *
+     * `peer_id` - Other peer's globally unique id.
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn initiate<S: Read + Write>(
+        stream: &mut S,
+        peer_id: usize,
+        configuration: &Configuration,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        if configuration.noise.is_none() {
+            return Ok(None);
+        }
+
+        let mut handshake = build_handshake(configuration, true)?;
+
+        //-> e
+        write_handshake_message(stream, &mut handshake)?;
+        //<- e, ee, s, es
+        read_handshake_message(stream, &mut handshake)?;
+        //-> s, se
+        write_handshake_message(stream, &mut handshake)?;
+
+        Self::finish(handshake, peer_id, configuration).map(Some)
+    }
+
+    /**
+     * Responds to the handshake initiated by a peer, returning None if Noise
+     * isn't set in the configuration file.
+     *
+     * # Arguments
+     *
+     * `stream` - Stream between the peers.
+     *
+     * `peer_id` - Other peer's globally unique id.
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn respond<S: Read + Write>(
+        stream: &mut S,
+        peer_id: usize,
+        configuration: &Configuration,
+    ) -> Result<Option<Self>, Box<dyn Error>> {
+        if configuration.noise.is_none() {
+            return Ok(None);
+        }
+
+        let mut handshake = build_handshake(configuration, false)?;
+
+        //-> e
+        read_handshake_message(stream, &mut handshake)?;
+        //<- e, ee, s, es
+        write_handshake_message(stream, &mut handshake)?;
+        //-> s, se
+        read_handshake_message(stream, &mut handshake)?;
+
+        Self::finish(handshake, peer_id, configuration).map(Some)
+    }
+
+    /**
+     * Verifies the peer's static key, if its public key is set in the
+     * configuration file, and derives the session keys.
+     */
+    fn finish(
+        handshake: HandshakeState,
+        peer_id: usize,
+        configuration: &Configuration,
+    ) -> Result<Self, Box<dyn Error>> {
+        let public_key = configuration
+            .noise
+            .as_ref()
+            .and_then(|noise| noise.public_keys.get(peer_id));
+
+        if let Some(public_key) = public_key {
+            let public_key = decode_key::<KEY_SIZE>(public_key)
+                .ok_or("The Noise public keys must have 64 hexadecimal characters")?;
+
+            match handshake.get_remote_static() {
+                Some(remote_static_key) if remote_static_key == &public_key[..] => {}
+                _ => return Err(format!("Peer {} has an unexpected static key", peer_id).into()),
+            }
+        }
+
+        Ok(Self {
+            transport: handshake.into_transport_mode()?,
+        })
+    }
+
+    /**
+     * Encrypts a serialized message with the session keys.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be written into the stream.
+     */
+    pub fn encrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, snow::Error> {
+        let chunk_size = MAX_MESSAGE_SIZE - TAG_SIZE;
+        let chunk_number = message.chunks(chunk_size).len();
+
+        let mut encrypted_message = Vec::with_capacity(message.len() + chunk_number * TAG_SIZE);
+        let mut buffer = vec![0u8; MAX_MESSAGE_SIZE];
+
+        for chunk in message.chunks(chunk_size) {
+            let length = self.transport.write_message(chunk, &mut buffer)?;
+            encrypted_message.extend_from_slice(&buffer[..length]);
+        }
+
+        Ok(encrypted_message)
+    }
+
+    /**
+     * Decrypts a message read from the stream with the session keys.
+     * Messages must be decrypted in the order they were encrypted.
+     *
+     * # Arguments
+     *
+     * `encrypted_message` - Encrypted message read from the stream.
+     */
+    pub fn decrypt(&mut self, encrypted_message: &[u8]) -> Result<Vec<u8>, snow::Error> {
+        let mut message = Vec::with_capacity(encrypted_message.len());
+        let mut buffer = vec![0u8; MAX_MESSAGE_SIZE];
+
+        for chunk in encrypted_message.chunks(MAX_MESSAGE_SIZE) {
+            let length = self.transport.read_message(chunk, &mut buffer)?;
+            message.extend_from_slice(&buffer[..length]);
+        }
+
+        Ok(message)
+    }
+}
+
+/**
+ * Builds the handshake state with the local static key, which is
+ * generated if it isn't set in the configuration file.
+ */
+fn build_handshake(
+    configuration: &Configuration,
+    initiator: bool,
+) -> Result<HandshakeState, Box<dyn Error>> {
+    let builder = Builder::new(NOISE_PARAMS.parse()?);
+
+    let configured_key = configuration
+        .noise
+        .as_ref()
+        .and_then(|noise| noise.private_key.as_ref());
+
+    let private_key = match configured_key {
+        Some(private_key) => decode_key::<KEY_SIZE>(private_key)
+            .ok_or("The Noise private key must have 64 hexadecimal characters")?
+            .to_vec(),
+        None => builder.generate_keypair()?.private,
+    };
+
+    let builder = builder.local_private_key(&private_key);
+
+    let handshake = if initiator {
+        builder.build_initiator()?
+    } else {
+        builder.build_responder()?
+    };
+
+    Ok(handshake)
+}
+
+/**
+ * Writes the next handshake message, prefixed by its length.
+ */
+fn write_handshake_message<W: Write>(
+    stream: &mut W,
+    handshake: &mut HandshakeState,
+) -> Result<(), Box<dyn Error>> {
+    let mut buffer = vec![0u8; MAX_MESSAGE_SIZE];
+    let length = handshake.write_message(&[], &mut buffer)?;
+
+    stream.write_all(&(length as u16).to_be_bytes())?;
+    stream.write_all(&buffer[..length])?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+/**
+ * Reads the next handshake message, prefixed by its length.
+ */
+fn read_handshake_message<R: Read>(
+    stream: &mut R,
+    handshake: &mut HandshakeState,
+) -> Result<(), Box<dyn Error>> {
+    let mut length = [0u8; 2];
+    stream.read_exact(&mut length)?;
+
+    let mut message = vec![0u8; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message)?;
+
+    let mut payload = vec![0u8; MAX_MESSAGE_SIZE];
+    handshake.read_message(&message, &mut payload)?;
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use crate::configuration::middleware_configuration::Configuration;
+use crate::security::hex::decode_key;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+
+///Size in bytes of the group key
+const KEY_SIZE: usize = 32;
+
+///Size in bytes of the nonce prepended to each encrypted payload
+const NONCE_SIZE: usize = 24;
+
+/**
+ * Encrypts and decrypts message payloads with XChaCha20-Poly1305 and the
+ * group key shared by every peer. Payloads are encrypted by the Middleware
+ * before serialization, so they are protected even if the transport is
+ * terminated by an untrusted proxy. An encrypted payload is the random nonce
+ * followed by the ciphertext and authentication tag.
+ */
+pub struct PayloadCipher {
+    ///AEAD cipher initialized with the group key
+    cipher: XChaCha20Poly1305,
+}
+
+impl PayloadCipher {
+    /**
+     * Creates a cipher with a group key.
+     *
+     * # Arguments
+     *
+     * `key` - 256 bit group key.
+     */
+    pub fn new(key: &[u8; KEY_SIZE]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key)),
+        }
+    }
+
+    /**
+     * Creates a cipher with the group key of the configuration file,
+     * or returns None if the payloads aren't encrypted.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn from_configuration(configuration: &Configuration) -> Option<Self> {
+        match &configuration.group_key {
+            Some(group_key) => {
+                let key = decode_key::<KEY_SIZE>(group_key)
+                    .expect("ERROR: The group key must have 64 hexadecimal characters");
+
+                Some(Self::new(&key))
+            }
+            None => None,
+        }
+    }
+
+    /**
+     * Encrypts a payload with a new random nonce.
+     *
+     * # Arguments
+     *
+     * `payload` - Serialized payload set by the Client.
+     */
+    pub fn encrypt(&self, payload: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, payload)
+            .expect("ERROR: Failed to encrypt the payload");
+
+        let mut encrypted_payload = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+        encrypted_payload.extend_from_slice(&nonce);
+        encrypted_payload.extend_from_slice(&ciphertext);
+
+        encrypted_payload
+    }
+
+    /**
+     * Decrypts a payload, returning None if it wasn't encrypted with
+     * the group key or was tampered with.
+     *
+     * # Arguments
+     *
+     * `encrypted_payload` - Nonce followed by the ciphertext.
+     */
+    pub fn decrypt(&self, encrypted_payload: &[u8]) -> Option<Vec<u8>> {
+        if encrypted_payload.len() < NONCE_SIZE {
+            return None;
+        }
+
+        let (nonce, ciphertext) = encrypted_payload.split_at(NONCE_SIZE);
+
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .ok()
+    }
+}
+
+/**
+ * Decrypts the payload of a message received from a peer, used by the Reader
+ * and Deserializer threads of both implementations. The payload is returned
+ * as is if the payloads aren't encrypted, and messages that can't be decrypted
+ * with the group key are discarded.
+ *
+ * # Arguments
+ *
+ * `payload` - Payload of the received message.
+ *
+ * `cipher` - Cipher with the group key, if the payloads are encrypted.
+ */
+pub fn decrypt_message(payload: Vec<u8>, cipher: &Option<PayloadCipher>) -> Option<Vec<u8>> {
+    match cipher {
+        Some(cipher) => match cipher.decrypt(&payload) {
+            Some(payload) => Some(payload),
+            None => {
+                println!("ERROR: Discarded a message whose payload couldn't be decrypted");
+                None
+            }
+        },
+        None => Some(payload),
+    }
+}
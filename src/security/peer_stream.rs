@@ -0,0 +1,75 @@
+use crate::transport::udp::UdpStream;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+#[cfg(feature = "tls")]
+use rustls::{ClientConnection, ServerConnection, StreamOwned};
+
+#[cfg(feature = "shm")]
+use crate::transport::shm::ShmStream;
+
+/**
+ * Stream read by a Reader thread or written by a Sender thread. Connections are
+ * plain TCP streams unless mutual TLS is set in the configuration, in which case
+ * the Sender thread is the TLS client and the Reader thread of the other peer is
+ * the TLS server, the UDP transport is set or the peer listens on a shared
+ * memory segment.
+ */
+pub enum PeerStream {
+    ///Plain TCP stream
+    Plain(TcpStream),
+    ///Stream over the experimental UDP transport
+    Udp(UdpStream),
+    ///Stream over shared memory with a peer on the same host
+    #[cfg(feature = "shm")]
+    Shm(ShmStream),
+    ///TLS stream of the Sender thread
+    #[cfg(feature = "tls")]
+    TlsClient(Box<StreamOwned<ClientConnection, TcpStream>>),
+    ///TLS stream of the Reader thread
+    #[cfg(feature = "tls")]
+    TlsServer(Box<StreamOwned<ServerConnection, TcpStream>>),
+}
+
+impl Read for PeerStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self {
+            PeerStream::Plain(stream) => stream.read(buffer),
+            PeerStream::Udp(stream) => stream.read(buffer),
+            #[cfg(feature = "shm")]
+            PeerStream::Shm(stream) => stream.read(buffer),
+            #[cfg(feature = "tls")]
+            PeerStream::TlsClient(stream) => stream.read(buffer),
+            #[cfg(feature = "tls")]
+            PeerStream::TlsServer(stream) => stream.read(buffer),
+        }
+    }
+}
+
+impl Write for PeerStream {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        match self {
+            PeerStream::Plain(stream) => stream.write(buffer),
+            PeerStream::Udp(stream) => stream.write(buffer),
+            #[cfg(feature = "shm")]
+            PeerStream::Shm(stream) => stream.write(buffer),
+            #[cfg(feature = "tls")]
+            PeerStream::TlsClient(stream) => stream.write(buffer),
+            #[cfg(feature = "tls")]
+            PeerStream::TlsServer(stream) => stream.write(buffer),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            PeerStream::Plain(stream) => stream.flush(),
+            PeerStream::Udp(stream) => stream.flush(),
+            #[cfg(feature = "shm")]
+            PeerStream::Shm(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            PeerStream::TlsClient(stream) => stream.flush(),
+            #[cfg(feature = "tls")]
+            PeerStream::TlsServer(stream) => stream.flush(),
+        }
+    }
+}
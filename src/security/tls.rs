@@ -0,0 +1,155 @@
+use super::peer_stream::PeerStream;
+use crate::configuration::middleware_configuration::{Configuration, Tls};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{
+    ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection, StreamOwned,
+};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use webpki::EndEntityCert;
+
+/**
+ * Starts the TLS session of a Sender thread, after the id handshake, returning
+ * the stream as is if TLS isn't set in the configuration file. The other peer's
+ * certificate must be signed by the authority and valid for the peer's TLS name.
+ *
+ * # Arguments
+ *
+ * `stream` - Plain stream between the peers.
+ *
+ * `peer_id` - Other peer's globally unique id.
+ *
+ * `configuration` - Middleware's configuration file.
+ */
+pub fn connect(
+    stream: PeerStream,
+    peer_id: usize,
+    configuration: &Configuration,
+) -> Result<PeerStream, Box<dyn Error>> {
+    let (tls, stream) = match (&configuration.topology.tls, stream) {
+        (None, stream) => return Ok(stream),
+        (Some(tls), PeerStream::Plain(stream)) => (tls, stream),
+        (Some(_), _) => return Err("TLS requires the TCP transport".into()),
+    };
+
+    let client_config = ClientConfig::builder()
+        .with_root_certificates(load_authority(tls)?)
+        .with_client_auth_cert(load_certificates(&tls.certificate)?, load_key(tls)?)?;
+
+    let server_name = ServerName::try_from(configuration.topology.get_tls_name(peer_id))?;
+    let connection = ClientConnection::new(Arc::new(client_config), server_name)?;
+
+    let mut stream = StreamOwned::new(connection, stream);
+
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+
+    Ok(PeerStream::TlsClient(Box::new(stream)))
+}
+
+/**
+ * Accepts the TLS session of a Reader thread, after the id handshake, returning
+ * the stream as is if TLS isn't set in the configuration file. The other peer's
+ * certificate must be signed by the authority and valid for the TLS name of the
+ * id it claimed in the id handshake.
+ *
+ * # Arguments
+ *
+ * `stream` - Plain stream between the peers.
+ *
+ * `peer_id` - Id claimed by the other peer in the id handshake.
+ *
+ * `configuration` - Middleware's configuration file.
+ */
+pub fn accept(
+    stream: PeerStream,
+    peer_id: usize,
+    configuration: &Configuration,
+) -> Result<PeerStream, Box<dyn Error>> {
+    let (tls, stream) = match (&configuration.topology.tls, stream) {
+        (None, stream) => return Ok(stream),
+        (Some(tls), PeerStream::Plain(stream)) => (tls, stream),
+        (Some(_), _) => return Err("TLS requires the TCP transport".into()),
+    };
+
+    let verifier = WebPkiClientVerifier::builder(Arc::new(load_authority(tls)?)).build()?;
+
+    let server_config = ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(load_certificates(&tls.certificate)?, load_key(tls)?)?;
+
+    let connection = ServerConnection::new(Arc::new(server_config))?;
+
+    let mut stream = StreamOwned::new(connection, stream);
+
+    while stream.conn.is_handshaking() {
+        stream.conn.complete_io(&mut stream.sock)?;
+    }
+
+    //Binding the verified certificate to the claimed id
+    let certificate = match stream.conn.peer_certificates() {
+        Some(certificates) if !certificates.is_empty() => &certificates[0],
+        _ => return Err(format!("Peer {} didn't present a certificate", peer_id).into()),
+    };
+
+    let tls_name = configuration.topology.get_tls_name(peer_id);
+
+    EndEntityCert::try_from(certificate)?
+        .verify_is_valid_for_subject_name(&ServerName::try_from(tls_name.as_str())?)
+        .map_err(|_| {
+            format!(
+                "Peer {} has a certificate that isn't valid for {}",
+                peer_id, tls_name
+            )
+        })?;
+
+    Ok(PeerStream::TlsServer(Box::new(stream)))
+}
+
+/**
+ * Reads the certificate of the authority that signed the peers' certificates.
+ */
+fn load_authority(tls: &Tls) -> Result<RootCertStore, Box<dyn Error>> {
+    let mut roots = RootCertStore::empty();
+
+    for certificate in load_certificates(&tls.ca_certificate)? {
+        roots.add(certificate)?;
+    }
+
+    Ok(roots)
+}
+
+/**
+ * Reads every certificate in a PEM file.
+ *
+ * # Arguments
+ *
+ * `path` - Path to the PEM file.
+ */
+fn load_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let certificates = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+
+    if certificates.is_empty() {
+        return Err(format!("{} has no certificates", path).into());
+    }
+
+    Ok(certificates)
+}
+
+/**
+ * Reads the private key of the local peer's certificate.
+ */
+fn load_key(tls: &Tls) -> Result<PrivateKeyDer<'static>, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(&tls.private_key)?);
+
+    match rustls_pemfile::private_key(&mut reader)? {
+        Some(key) => Ok(key),
+        None => Err(format!("{} has no private key", tls.private_key).into()),
+    }
+}
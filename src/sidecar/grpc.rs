@@ -0,0 +1,353 @@
+use crate::broadcast::broadcast_trait::{
+    Fault, GenericReturn, Headers, SendFailure, TcbError, TCB,
+};
+use crate::channel::{unbounded, TryRecvError};
+use crate::channel::{Receiver, Sender};
+use std::convert::TryInto;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+/**
+ * Types and service generated from `proto/tcb.proto`.
+ */
+pub mod proto {
+    tonic::include_proto!("tcb");
+}
+
+use proto::broadcast_server::{Broadcast, BroadcastServer};
+use proto::{
+    Delivery, Dot, Event, FaultKind, SendReply, SendRequest, StableAckReply, StableAckRequest,
+    SubscribeRequest,
+};
+
+/**
+ * Maximum time the middleware thread of the sidecar waits for a delivery
+ * before handling the requests of the gRPC clients.
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/**
+ * Requests of the gRPC clients handled by the thread that owns the middleware instance.
+ */
+enum SidecarRequest {
+    ///Payload to be broadcast and the channel where the handler waits for it to be sent
+    Send {
+        payload: Vec<u8>,
+        trace_id: Option<u128>,
+        headers: Option<Headers>,
+        sent: oneshot::Sender<()>,
+    },
+    ///Channel where the events of the middleware are streamed to a subscriber
+    Subscribe {
+        events: mpsc::UnboundedSender<Result<Event, Status>>,
+    },
+    ///Stable message acked by a gRPC client
+    StableAck { id: usize, counter: usize },
+}
+
+/**
+ * gRPC service that forwards the requests to the thread that owns the middleware instance.
+ */
+struct Sidecar {
+    ///Channel to the thread that owns the middleware instance
+    requests: Sender<SidecarRequest>,
+}
+
+impl Sidecar {
+    /**
+     * Hands a request to the thread that owns the middleware instance.
+     *
+     * # Arguments
+     *
+     * `request` - Request of a gRPC client.
+     */
+    fn forward(&self, request: SidecarRequest) -> Result<(), Status> {
+        self.requests
+            .send(request)
+            .map_err(|_| Status::unavailable("The middleware has terminated"))
+    }
+}
+
+#[tonic::async_trait]
+impl Broadcast for Sidecar {
+    type SubscribeStream = UnboundedReceiverStream<Result<Event, Status>>;
+
+    async fn send(&self, request: Request<SendRequest>) -> Result<Response<SendReply>, Status> {
+        let request = request.into_inner();
+
+        let trace_id = match request.trace_id.len() {
+            0 => None,
+            16 => Some(u128::from_be_bytes(
+                request.trace_id[..].try_into().unwrap(),
+            )),
+            _ => return Err(Status::invalid_argument("The trace id must have 16 bytes")),
+        };
+
+        let headers = match (request.headers.is_empty(), trace_id) {
+            (true, _) => None,
+            (false, None) => Some(request.headers.into_iter().collect()),
+            (false, Some(_)) => {
+                return Err(Status::invalid_argument(
+                    "The headers can't be combined with a trace id",
+                ))
+            }
+        };
+
+        let (sent, sent_receiver) = oneshot::channel();
+
+        self.forward(SidecarRequest::Send {
+            payload: request.payload,
+            trace_id,
+            headers,
+            sent,
+        })?;
+
+        //Replying once the middleware instance accepted the message
+        match sent_receiver.await {
+            Ok(_) => Ok(Response::new(SendReply {})),
+            Err(_) => Err(Status::unavailable("The middleware has terminated")),
+        }
+    }
+
+    async fn subscribe(
+        &self,
+        _request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let (events, events_receiver) = mpsc::unbounded_channel();
+
+        self.forward(SidecarRequest::Subscribe { events })?;
+
+        Ok(Response::new(UnboundedReceiverStream::new(events_receiver)))
+    }
+
+    async fn stable_ack(
+        &self,
+        request: Request<StableAckRequest>,
+    ) -> Result<Response<StableAckReply>, Status> {
+        let dot = match request.into_inner().dot {
+            Some(dot) => dot,
+            None => return Err(Status::invalid_argument("The stable dot is missing")),
+        };
+
+        self.forward(SidecarRequest::StableAck {
+            id: dot.id as usize,
+            counter: dot.counter as usize,
+        })?;
+
+        Ok(Response::new(StableAckReply {}))
+    }
+}
+
+/**
+ * Serves the Send, Subscribe and StableAck RPCs backed by a middleware instance,
+ * so services written in other languages can use it as a causal broadcast sidecar
+ * process. The middleware instance is moved to its own thread, which broadcasts
+ * the sent messages and streams every delivery, stable message, eviction and fault
+ * to the subscribers connected at that moment. Blocks the calling thread until
+ * the server terminates and returns an error if it fails.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance, GRAPH or VV.
+ *
+ * `address` - Address where the gRPC server listens for connections.
+ */
+pub fn serve<T>(tcb: T, address: SocketAddr) -> Result<(), Box<dyn Error>>
+where
+    T: TCB + Send + 'static,
+{
+    let (requests, requests_receiver) = unbounded::<SidecarRequest>();
+
+    let builder = thread::Builder::new().name(format!("grpc_sidecar_{}", address.port()));
+
+    builder.spawn(move || {
+        handle_requests(tcb, requests_receiver);
+    })?;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    runtime.block_on(
+        Server::builder()
+            .add_service(BroadcastServer::new(Sidecar { requests }))
+            .serve(address),
+    )?;
+
+    Ok(())
+}
+
+/**
+ * Handles the requests of the gRPC clients and streams the events of the
+ * middleware to the subscribers, until the gRPC server or the middleware terminates.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance.
+ *
+ * `requests` - Channel where the gRPC handlers write the requests.
+ */
+fn handle_requests<T: TCB>(mut tcb: T, requests: Receiver<SidecarRequest>) {
+    let mut subscribers: Vec<mpsc::UnboundedSender<Result<Event, Status>>> = Vec::new();
+
+    loop {
+        //Handling every pending request before waiting for the next delivery
+        loop {
+            match requests.try_recv() {
+                Ok(SidecarRequest::Send {
+                    payload,
+                    trace_id,
+                    headers,
+                    sent,
+                }) => {
+                    let _ = match (headers, trace_id) {
+                        (Some(headers), _) => tcb.send_with_headers(payload, headers),
+                        (None, Some(trace_id)) => tcb.send_traced(payload, trace_id),
+                        (None, None) => tcb.send(payload),
+                    };
+
+                    let _ = sent.send(());
+                }
+                Ok(SidecarRequest::Subscribe { events }) => {
+                    subscribers.push(events);
+                }
+                Ok(SidecarRequest::StableAck { id, counter }) => {
+                    if let Err(e) = tcb.tcbstable(id, counter) {
+                        println!(
+                            "ERROR: The gRPC sidecar couldn't ack a stable message\n\t- {}",
+                            e
+                        );
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    tcb.end();
+                    return;
+                }
+            }
+        }
+
+        match tcb.recv_timeout(POLL_INTERVAL) {
+            Ok(generic_return) => {
+                let event = to_event(generic_return);
+
+                //Dropping the subscribers whose streams were closed
+                subscribers.retain(|subscriber| subscriber.send(Ok(event.clone())).is_ok());
+            }
+            Err(TcbError::Timeout) => {}
+            Err(_) => {
+                println!("ERROR: The middleware of the gRPC sidecar terminated");
+                return;
+            }
+        }
+    }
+}
+
+/**
+ * Converts a delivery call return of the middleware into a gRPC event.
+ *
+ * # Arguments
+ *
+ * `generic_return` - Delivery, stable message, eviction, fault or send failure.
+ */
+fn to_event(generic_return: GenericReturn) -> Event {
+    let event = match generic_return {
+        GenericReturn::Delivery(payload, id, counter) => proto::event::Event::Delivery(Delivery {
+            payload,
+            dot: Some(to_dot(id, counter)),
+            trace_id: Vec::new(),
+            headers: Default::default(),
+        }),
+        GenericReturn::TracedDelivery(payload, id, counter, trace_id) => {
+            proto::event::Event::Delivery(Delivery {
+                payload,
+                dot: Some(to_dot(id, counter)),
+                trace_id: trace_id.to_be_bytes().to_vec(),
+                headers: Default::default(),
+            })
+        }
+        GenericReturn::HeaderedDelivery(payload, id, counter, headers) => {
+            proto::event::Event::Delivery(Delivery {
+                payload,
+                dot: Some(to_dot(id, counter)),
+                trace_id: Vec::new(),
+                headers: headers.into_iter().collect(),
+            })
+        }
+        GenericReturn::Stable(id, counter) => proto::event::Event::Stable(to_dot(id, counter)),
+        GenericReturn::StableFrontier(frontier) => {
+            proto::event::Event::StableFrontier(proto::StableFrontier {
+                counters: frontier.into_iter().map(|counter| counter as u64).collect(),
+            })
+        }
+        GenericReturn::Evicted(id) => proto::event::Event::Evicted(id as u64),
+        GenericReturn::Fault(fault) => {
+            let (kind, id, counter, last, blocked) = match fault {
+                Fault::Equivocation { id, counter } => {
+                    (FaultKind::Equivocation, id, counter, counter, Vec::new())
+                }
+                Fault::MissingHistory { id, counter } => {
+                    (FaultKind::MissingHistory, id, counter, counter, Vec::new())
+                }
+                Fault::MissingDependency {
+                    id,
+                    counter,
+                    blocked,
+                } => (FaultKind::MissingDependency, id, counter, counter, blocked),
+                Fault::MissingRange { id, first, last } => {
+                    (FaultKind::MissingRange, id, first, last, Vec::new())
+                }
+                Fault::CorruptedPayload { id, counter } => (
+                    FaultKind::CorruptedPayload,
+                    id,
+                    counter,
+                    counter,
+                    Vec::new(),
+                ),
+            };
+
+            proto::event::Event::Fault(proto::Fault {
+                kind: kind as i32,
+                dot: Some(to_dot(id, counter)),
+                last_counter: last as u64,
+                blocked: blocked
+                    .into_iter()
+                    .map(|(id, counter)| to_dot(id, counter))
+                    .collect(),
+            })
+        }
+        GenericReturn::SendFailed(SendFailure {
+            id,
+            counter,
+            peer_id,
+            error,
+        }) => proto::event::Event::SendFailed(proto::SendFailure {
+            dot: Some(to_dot(id, counter)),
+            every_peer: peer_id.is_none(),
+            peer_id: peer_id.unwrap_or(0) as u64,
+            error,
+        }),
+    };
+
+    Event { event: Some(event) }
+}
+
+/**
+ * Builds the gRPC dot of a message.
+ *
+ * # Arguments
+ *
+ * `id` - Sender id
+ *
+ * `counter` - Message id
+ */
+fn to_dot(id: usize, counter: usize) -> Dot {
+    Dot {
+        id: id as u64,
+        counter: counter as u64,
+    }
+}
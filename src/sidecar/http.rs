@@ -0,0 +1,622 @@
+use crate::broadcast::broadcast_trait::{
+    Fault, GenericReturn, GraphFormat, Headers, SendFailure, TcbError, TCB,
+};
+use crate::channel::{unbounded, TryRecvError};
+use crate::channel::{Receiver, Sender};
+use std::error::Error;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+/**
+ * Maximum time the middleware thread of the gateway waits for a delivery
+ * before handling the HTTP requests.
+ */
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/**
+ * Maximum size in bytes of the payload of a POST /send request.
+ */
+const MAX_PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+
+/**
+ * Prefix of the HTTP headers of a POST /send request that are sent as message headers.
+ */
+const HEADER_PREFIX: &str = "x-tcb-";
+
+/**
+ * Requests of the HTTP connections handled by the thread that owns the middleware instance.
+ */
+enum GatewayRequest {
+    ///Payload and headers to be broadcast and the channel where the connection waits for it to be sent
+    Send {
+        payload: Vec<u8>,
+        headers: Headers,
+        sent: Sender<()>,
+    },
+    ///Channel where the server-sent events of the middleware are written to a connection
+    Subscribe { events: Sender<String> },
+    ///Channel where the status of the gateway is written to a connection
+    Status { status: Sender<String> },
+    ///Channel where the causal graph of the middleware is written to a connection
+    Graph {
+        graph: Sender<Result<Option<String>, TcbError>>,
+    },
+}
+
+/**
+ * Counters of the messages that went through the gateway.
+ */
+#[derive(Default)]
+struct GatewayCounters {
+    ///Messages sent through POST /send
+    sent: u64,
+    ///Messages delivered by the middleware
+    delivered: u64,
+    ///Messages notified as stable by the middleware
+    stable: u64,
+}
+
+/**
+ * Serves a small HTTP/JSON gateway to a middleware instance, for demos, debugging
+ * with curl and scripting against a running peer. The gateway has four endpoints:
+ *
+ * - `POST /send` broadcasts the request body as the payload of a message, with
+ *   the `X-Tcb-<name>` HTTP headers as the message headers.
+ * - `GET /deliveries` streams the deliveries, stable messages, evictions and faults
+ *   as server-sent events, where the payloads are decoded as UTF-8 text.
+ * - `GET /status` returns the gateway's message counters and the middleware metrics.
+ * - `GET /graph` returns the causal graph of a GRAPH middleware as JSON.
+ *
+ * The middleware instance is moved to its own thread, and the stable messages are
+ * acked by the gateway once they were streamed. Blocks the calling thread while
+ * accepting connections and returns an error if the address can't be bound.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance, GRAPH or VV.
+ *
+ * `address` - Address where the gateway listens for connections.
+ */
+pub fn serve<T>(tcb: T, address: SocketAddr) -> Result<(), Box<dyn Error>>
+where
+    T: TCB + Send + 'static,
+{
+    let listener = TcpListener::bind(address)?;
+    let (requests, requests_receiver) = unbounded::<GatewayRequest>();
+
+    let builder = thread::Builder::new().name(format!("http_gateway_{}", address.port()));
+
+    builder.spawn(move || {
+        handle_requests(tcb, requests_receiver);
+    })?;
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let requests = requests.clone();
+
+                let builder = thread::Builder::new().name(String::from("http_gateway_connection"));
+
+                match builder.spawn(move || handle_connection(stream, &requests)) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!(
+                            "ERROR: Failed to spawn an HTTP connection thread\n\t- {}",
+                            e
+                        );
+                    }
+                }
+            }
+            Err(e) => {
+                println!("ERROR: Failed to accept an HTTP connection\n\t- {}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/**
+ * Handles the requests of the HTTP connections and streams the events of the
+ * middleware to the subscribers, until the gateway or the middleware terminates.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance.
+ *
+ * `requests` - Channel where the HTTP connections write the requests.
+ */
+fn handle_requests<T: TCB>(mut tcb: T, requests: Receiver<GatewayRequest>) {
+    let mut subscribers: Vec<Sender<String>> = Vec::new();
+    let mut counters = GatewayCounters::default();
+
+    loop {
+        //Handling every pending request before waiting for the next delivery
+        loop {
+            match requests.try_recv() {
+                Ok(GatewayRequest::Send {
+                    payload,
+                    headers,
+                    sent,
+                }) => {
+                    let _ = if headers.is_empty() {
+                        tcb.send(payload)
+                    } else {
+                        tcb.send_with_headers(payload, headers)
+                    };
+                    counters.sent += 1;
+
+                    let _ = sent.send(());
+                }
+                Ok(GatewayRequest::Subscribe { events }) => {
+                    subscribers.push(events);
+                }
+                Ok(GatewayRequest::Status { status }) => {
+                    let _ = status.send(status_json(&tcb, &counters, subscribers.len()));
+                }
+                Ok(GatewayRequest::Graph { graph }) => {
+                    let _ = graph.send(tcb.render_graph(GraphFormat::Json));
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    tcb.end();
+                    return;
+                }
+            }
+        }
+
+        match tcb.recv_timeout(POLL_INTERVAL) {
+            Ok(generic_return) => {
+                match &generic_return {
+                    GenericReturn::Delivery(..)
+                    | GenericReturn::TracedDelivery(..)
+                    | GenericReturn::HeaderedDelivery(..) => {
+                        counters.delivered += 1;
+                    }
+                    GenericReturn::Stable(id, counter) => {
+                        counters.stable += 1;
+                        if let Err(e) = tcb.tcbstable(*id, *counter) {
+                            println!(
+                                "ERROR: The HTTP gateway couldn't ack a stable message\n\t- {}",
+                                e
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+
+                let event = to_event(generic_return);
+
+                //Dropping the subscribers whose connections were closed
+                subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+            }
+            Err(TcbError::Timeout) => {}
+            Err(_) => {
+                println!("ERROR: The middleware of the HTTP gateway terminated");
+                return;
+            }
+        }
+    }
+}
+
+/**
+ * Handles an HTTP connection, which carries a single request.
+ *
+ * # Arguments
+ *
+ * `stream` - TCP stream of the connection.
+ *
+ * `requests` - Channel to the thread that owns the middleware instance.
+ */
+fn handle_connection(stream: TcpStream, requests: &Sender<GatewayRequest>) {
+    let mut reader = match stream.try_clone() {
+        Ok(stream_clone) => BufReader::new(stream_clone),
+        Err(e) => {
+            println!("ERROR: Failed to clone an HTTP connection\n\t- {}", e);
+            return;
+        }
+    };
+    let mut stream = stream;
+
+    //Reading the request line and the headers, of which only the
+    //body length and the message headers are used
+    let mut request_line = String::new();
+
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+
+    let mut content_length: usize = 0;
+    let mut headers = Headers::new();
+
+    loop {
+        let mut header = String::new();
+
+        match reader.read_line(&mut header) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(_) => return,
+        }
+
+        let header = header.trim_end();
+
+        if header.is_empty() {
+            break;
+        }
+
+        match header.split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+            Some((name, value)) if name.to_ascii_lowercase().starts_with(HEADER_PREFIX) => {
+                headers.insert(
+                    name[HEADER_PREFIX.len()..].to_ascii_lowercase(),
+                    value.trim().as_bytes().to_vec(),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    let result = match (method, path) {
+        ("POST", "/send") => {
+            if content_length > MAX_PAYLOAD_SIZE {
+                write_response(&mut stream, "413 Payload Too Large", "{\"sent\":false}")
+            } else {
+                let mut payload = vec![0; content_length];
+
+                match reader.read_exact(&mut payload) {
+                    Ok(_) => handle_send(&mut stream, payload, headers, requests),
+                    Err(_) => return,
+                }
+            }
+        }
+        ("GET", "/deliveries") => handle_deliveries(&mut stream, requests),
+        ("GET", "/status") => handle_status(&mut stream, requests),
+        ("GET", "/graph") => handle_graph(&mut stream, requests),
+        _ => write_response(&mut stream, "404 Not Found", "{\"error\":\"not found\"}"),
+    };
+
+    match result {
+        Ok(_) => {}
+        Err(e) => {
+            println!("WARN: HTTP connection was closed\n\t- {}", e);
+        }
+    }
+}
+
+/**
+ * Broadcasts the body of a POST /send request and replies once the
+ * middleware instance accepted the message.
+ */
+fn handle_send(
+    stream: &mut TcpStream,
+    payload: Vec<u8>,
+    headers: Headers,
+    requests: &Sender<GatewayRequest>,
+) -> std::io::Result<()> {
+    let (sent, sent_receiver) = unbounded();
+
+    match requests.send(GatewayRequest::Send {
+        payload,
+        headers,
+        sent,
+    }) {
+        Ok(_) => {}
+        Err(_) => return write_unavailable(stream),
+    }
+
+    match sent_receiver.recv() {
+        Ok(_) => write_response(stream, "200 OK", "{\"sent\":true}"),
+        Err(_) => write_unavailable(stream),
+    }
+}
+
+/**
+ * Streams the events of the middleware as server-sent events until the
+ * connection is closed.
+ */
+fn handle_deliveries(
+    stream: &mut TcpStream,
+    requests: &Sender<GatewayRequest>,
+) -> std::io::Result<()> {
+    let (events, events_receiver) = unbounded();
+
+    match requests.send(GatewayRequest::Subscribe { events }) {
+        Ok(_) => {}
+        Err(_) => return write_unavailable(stream),
+    }
+
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+    )?;
+    stream.flush()?;
+
+    for event in events_receiver.iter() {
+        stream.write_all(event.as_bytes())?;
+        stream.flush()?;
+    }
+
+    Ok(())
+}
+
+/**
+ * Replies with the gateway's status returned by the thread that owns the middleware instance.
+ */
+fn handle_status(stream: &mut TcpStream, requests: &Sender<GatewayRequest>) -> std::io::Result<()> {
+    let (status, status_receiver) = unbounded();
+
+    match requests.send(GatewayRequest::Status { status }) {
+        Ok(_) => {}
+        Err(_) => return write_unavailable(stream),
+    }
+
+    match status_receiver.recv() {
+        Ok(status) => write_response(stream, "200 OK", &status),
+        Err(_) => write_unavailable(stream),
+    }
+}
+
+/**
+ * Replies with the causal graph returned by the thread that owns the middleware
+ * instance, or an error if the middleware has no causal graph or terminated.
+ */
+fn handle_graph(stream: &mut TcpStream, requests: &Sender<GatewayRequest>) -> std::io::Result<()> {
+    let (graph, graph_receiver) = unbounded();
+
+    match requests.send(GatewayRequest::Graph { graph }) {
+        Ok(_) => {}
+        Err(_) => return write_unavailable(stream),
+    }
+
+    match graph_receiver.recv() {
+        Ok(Ok(Some(graph))) => write_response(stream, "200 OK", &graph),
+        Ok(Ok(None)) => write_response(
+            stream,
+            "404 Not Found",
+            "{\"error\":\"the middleware has no causal graph\"}",
+        ),
+        Ok(Err(_)) | Err(_) => write_unavailable(stream),
+    }
+}
+
+/**
+ * Writes an HTTP response with a JSON body and closes the connection.
+ *
+ * # Arguments
+ *
+ * `stream` - TCP stream of the connection.
+ *
+ * `status` - Status code and reason phrase.
+ *
+ * `body` - JSON body.
+ */
+fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
+
+/**
+ * Replies that the middleware instance has terminated.
+ */
+fn write_unavailable(stream: &mut TcpStream) -> std::io::Result<()> {
+    write_response(
+        stream,
+        "503 Service Unavailable",
+        "{\"error\":\"the middleware has terminated\"}",
+    )
+}
+
+/**
+ * Builds the JSON status of the gateway with the middleware metrics.
+ *
+ * # Arguments
+ *
+ * `tcb` - Middleware instance.
+ *
+ * `counters` - Counters of the messages that went through the gateway.
+ *
+ * `subscribers` - Number of connections streaming the deliveries.
+ */
+fn status_json<T: TCB>(tcb: &T, counters: &GatewayCounters, subscribers: usize) -> String {
+    let metrics = tcb.metrics();
+
+    let peers: Vec<String> = metrics
+        .peers
+        .iter()
+        .map(|peer| {
+            let smoothed_rtt = match peer.smoothed_rtt {
+                Some(rtt) => rtt.as_micros().to_string(),
+                None => String::from("null"),
+            };
+
+            format!(
+                "{{\"id\":{},\"smoothed_rtt_us\":{},\"delivery_lag\":{},\"bytes_sent\":{},\"bytes_received\":{}}}",
+                peer.peer_id,
+                smoothed_rtt,
+                peer.delivery_lag,
+                peer.bytes_sent,
+                peer.bytes_received
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"sent\":{},\"delivered\":{},\"stable\":{},\"subscribers\":{},\"retained_messages\":{},\"graph_nodes\":{},\"peers\":[{}]}}",
+        counters.sent,
+        counters.delivered,
+        counters.stable,
+        subscribers,
+        metrics.retention.messages,
+        metrics.graph.occupied,
+        peers.join(",")
+    )
+}
+
+/**
+ * Converts a delivery call return of the middleware into a server-sent event.
+ *
+ * # Arguments
+ *
+ * `generic_return` - Delivery, stable message, eviction, fault or send failure.
+ */
+fn to_event(generic_return: GenericReturn) -> String {
+    let (event, data) = match generic_return {
+        GenericReturn::Delivery(payload, id, counter) => (
+            "delivery",
+            format!(
+                "{{\"id\":{},\"counter\":{},\"payload\":\"{}\"}}",
+                id,
+                counter,
+                escape_json(&String::from_utf8_lossy(&payload))
+            ),
+        ),
+        GenericReturn::TracedDelivery(payload, id, counter, trace_id) => (
+            "delivery",
+            format!(
+                "{{\"id\":{},\"counter\":{},\"payload\":\"{}\",\"trace_id\":\"{:032x}\"}}",
+                id,
+                counter,
+                escape_json(&String::from_utf8_lossy(&payload)),
+                trace_id
+            ),
+        ),
+        GenericReturn::HeaderedDelivery(payload, id, counter, headers) => {
+            let headers: Vec<String> = headers
+                .iter()
+                .map(|(name, value)| {
+                    format!(
+                        "\"{}\":\"{}\"",
+                        escape_json(name),
+                        escape_json(&String::from_utf8_lossy(value))
+                    )
+                })
+                .collect();
+
+            (
+                "delivery",
+                format!(
+                    "{{\"id\":{},\"counter\":{},\"payload\":\"{}\",\"headers\":{{{}}}}}",
+                    id,
+                    counter,
+                    escape_json(&String::from_utf8_lossy(&payload)),
+                    headers.join(",")
+                ),
+            )
+        }
+        GenericReturn::Stable(id, counter) => (
+            "stable",
+            format!("{{\"id\":{},\"counter\":{}}}", id, counter),
+        ),
+        GenericReturn::StableFrontier(frontier) => {
+            let counters: Vec<String> =
+                frontier.iter().map(|counter| counter.to_string()).collect();
+
+            (
+                "stable_frontier",
+                format!("{{\"counters\":[{}]}}", counters.join(",")),
+            )
+        }
+        GenericReturn::Evicted(id) => ("evicted", format!("{{\"id\":{}}}", id)),
+        GenericReturn::Fault(fault) => {
+            let (kind, id, counter, last, blocked) = match fault {
+                Fault::Equivocation { id, counter } => {
+                    ("equivocation", id, counter, counter, Vec::new())
+                }
+                Fault::MissingHistory { id, counter } => {
+                    ("missing_history", id, counter, counter, Vec::new())
+                }
+                Fault::MissingDependency {
+                    id,
+                    counter,
+                    blocked,
+                } => ("missing_dependency", id, counter, counter, blocked),
+                Fault::MissingRange { id, first, last } => {
+                    ("missing_range", id, first, last, Vec::new())
+                }
+                Fault::CorruptedPayload { id, counter } => {
+                    ("corrupted_payload", id, counter, counter, Vec::new())
+                }
+            };
+
+            let blocked: Vec<String> = blocked
+                .iter()
+                .map(|(id, counter)| format!("{{\"id\":{},\"counter\":{}}}", id, counter))
+                .collect();
+
+            (
+                "fault",
+                format!(
+                    "{{\"kind\":\"{}\",\"id\":{},\"counter\":{},\"last\":{},\"blocked\":[{}]}}",
+                    kind,
+                    id,
+                    counter,
+                    last,
+                    blocked.join(",")
+                ),
+            )
+        }
+        GenericReturn::SendFailed(SendFailure {
+            id,
+            counter,
+            peer_id,
+            error,
+        }) => {
+            let peer_id = match peer_id {
+                Some(peer_id) => peer_id.to_string(),
+                None => String::from("null"),
+            };
+
+            (
+                "send_failed",
+                format!(
+                    "{{\"id\":{},\"counter\":{},\"peer_id\":{},\"error\":\"{}\"}}",
+                    id,
+                    counter,
+                    peer_id,
+                    escape_json(&error)
+                ),
+            )
+        }
+    };
+
+    format!("event: {}\ndata: {}\n\n", event, data)
+}
+
+/**
+ * Escapes a string to be written inside a JSON string.
+ *
+ * # Arguments
+ *
+ * `text` - String to be escaped.
+ */
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
@@ -0,0 +1,10 @@
+/**
+ * gRPC server that exposes a local middleware instance to other processes.
+ */
+#[cfg(feature = "grpc")]
+pub mod grpc;
+/**
+ * HTTP/JSON gateway to a local middleware instance for debugging and scripting.
+ */
+#[cfg(feature = "http")]
+pub mod http;
@@ -0,0 +1,4 @@
+/**
+ * OpenTelemetry spans and metrics for send, delivery and stable events.
+ */
+pub mod otel;
@@ -0,0 +1,151 @@
+use opentelemetry::metrics::Counter;
+use opentelemetry::trace::{
+    Span, SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+};
+use opentelemetry::{global, Context, KeyValue};
+
+/**
+ * Name of the tracer and meter used by the middleware.
+ */
+const INSTRUMENTATION_NAME: &str = "tcb";
+
+/**
+ * Emits spans and metrics for the middleware events through the global
+ * OpenTelemetry providers. The application is responsible for installing
+ * the providers, e.g. with an OTLP exporter pointing to Jaeger or Tempo.
+ *
+ * Spans of messages sent with a trace id are created in that trace, with a
+ * remote parent whose span id is derived from the message's dot, so the send
+ * and every delivery of a message are grouped under the same parent.
+ */
+pub struct Telemetry {
+    ///Local peer's globally unique id
+    local_id: usize,
+    ///Counter of sent messages
+    sent: Counter<u64>,
+    ///Counter of delivered messages
+    delivered: Counter<u64>,
+    ///Counter of stable messages
+    stable: Counter<u64>,
+    ///Counter of sent and delivered payload bytes
+    bytes: Counter<u64>,
+}
+
+impl Telemetry {
+    /**
+     * Creates the instruments for a middleware instance.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     */
+    pub fn new(local_id: usize) -> Self {
+        let meter = global::meter(INSTRUMENTATION_NAME);
+
+        Self {
+            local_id,
+            sent: meter.u64_counter("tcb.messages.sent").build(),
+            delivered: meter.u64_counter("tcb.messages.delivered").build(),
+            stable: meter.u64_counter("tcb.messages.stable").build(),
+            bytes: meter.u64_counter("tcb.payload.bytes").build(),
+        }
+    }
+
+    /**
+     * Records a message broadcast by the Client.
+     *
+     * # Arguments
+     *
+     * `id` - Dot id field
+     *
+     * `counter` - Dot counter field
+     *
+     * `size` - Payload size in bytes
+     *
+     * `trace_id` - Trace id set by the Client
+     */
+    pub fn on_send(&self, id: usize, counter: usize, size: usize, trace_id: Option<u128>) {
+        let attributes = self.attributes(id, "send");
+
+        self.sent.add(1, &attributes);
+        self.bytes.add(size as u64, &attributes);
+        self.record_span("tcb.send", id, counter, trace_id);
+    }
+
+    /**
+     * Records a message delivered to the Client.
+     *
+     * # Arguments
+     *
+     * `id` - Dot id field
+     *
+     * `counter` - Dot counter field
+     *
+     * `size` - Payload size in bytes
+     *
+     * `trace_id` - Trace id set by the sender
+     */
+    pub fn on_deliver(&self, id: usize, counter: usize, size: usize, trace_id: Option<u128>) {
+        let attributes = self.attributes(id, "deliver");
+
+        self.delivered.add(1, &attributes);
+        self.bytes.add(size as u64, &attributes);
+        self.record_span("tcb.deliver", id, counter, trace_id);
+    }
+
+    /**
+     * Records a stable message.
+     *
+     * # Arguments
+     *
+     * `id` - Dot id field
+     *
+     * `counter` - Dot counter field
+     */
+    pub fn on_stable(&self, id: usize, counter: usize) {
+        self.stable.add(1, &self.attributes(id, "stable"));
+        self.record_span("tcb.stable", id, counter, None);
+    }
+
+    fn attributes(&self, id: usize, event: &'static str) -> [KeyValue; 3] {
+        [
+            KeyValue::new("tcb.peer", self.local_id as i64),
+            KeyValue::new("tcb.sender", id as i64),
+            KeyValue::new("tcb.event", event),
+        ]
+    }
+
+    fn record_span(&self, name: &'static str, id: usize, counter: usize, trace_id: Option<u128>) {
+        let tracer = global::tracer(INSTRUMENTATION_NAME);
+        let builder = tracer.span_builder(name).with_attributes(vec![
+            KeyValue::new("tcb.peer", self.local_id as i64),
+            KeyValue::new("tcb.dot.id", id as i64),
+            KeyValue::new("tcb.dot.counter", counter as i64),
+        ]);
+
+        let mut span = match trace_id {
+            Some(trace_id) => {
+                let parent = SpanContext::new(
+                    TraceId::from_bytes(trace_id.to_be_bytes()),
+                    SpanId::from_bytes(dot_span_id(id, counter).to_be_bytes()),
+                    TraceFlags::SAMPLED,
+                    true,
+                    TraceState::default(),
+                );
+                let context = Context::new().with_remote_span_context(parent);
+
+                builder.start_with_context(&tracer, &context)
+            }
+            None => builder.start(&tracer),
+        };
+
+        span.end();
+    }
+}
+
+/**
+ * Derives a non zero span id from a message's dot.
+ */
+fn dot_span_id(id: usize, counter: usize) -> u64 {
+    (((id as u64) << 48) ^ counter as u64).max(1)
+}
@@ -0,0 +1,295 @@
+use crate::configuration::middleware_configuration::{Batching, WireEncoding};
+use crate::transport::encoding;
+use serde::Serialize;
+use std::io::{BufWriter, Write};
+use std::ops::Mul;
+use std::time::{Duration, Instant};
+
+/**
+ * Weight of the latest interval in the moving average of the arrival rate.
+ */
+const ARRIVAL_RATE_WEIGHT: f64 = 0.125;
+
+/**
+ * Exponentially weighted moving average of the interval between messages
+ * arriving at a Sender thread, used by the adaptive batching mode.
+ */
+pub struct ArrivalRate {
+    ///Average interval in microseconds between arriving messages
+    average_interval: Option<f64>,
+    ///Instant when the last message arrived
+    last_arrival: Option<Instant>,
+}
+
+impl ArrivalRate {
+    /**
+     * Creates an arrival rate without observations.
+     */
+    pub fn new() -> Self {
+        Self {
+            average_interval: None,
+            last_arrival: None,
+        }
+    }
+
+    /**
+     * Updates the average interval with a message arriving now.
+     */
+    pub fn update(&mut self) {
+        let now = Instant::now();
+
+        if let Some(last_arrival) = self.last_arrival {
+            let interval = now.duration_since(last_arrival).as_micros() as f64;
+
+            self.average_interval = match self.average_interval {
+                Some(average) => Some(average + ARRIVAL_RATE_WEIGHT * (interval - average)),
+                None => Some(interval),
+            };
+        }
+
+        self.last_arrival = Some(now);
+    }
+
+    /**
+     * Returns the average interval in microseconds between arriving messages.
+     */
+    pub fn get_average_interval(&self) -> Option<f64> {
+        self.average_interval
+    }
+}
+
+impl Default for ArrivalRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn calculate_timeout(timeout_flag: bool, timeout: Duration, batching: &Batching) -> Duration {
+    let ret_timeout: Duration;
+    //True  - NEW MESSAGES timeout
+    //False - NO MESSAGES timeout
+
+    if timeout_flag {
+        ret_timeout = batching.get_lower_timeout();
+    } else {
+        if timeout.as_micros() * 2 <= batching.get_upper_timeout().as_micros() {
+            ret_timeout = timeout.mul(2);
+        } else {
+            ret_timeout = batching.get_upper_timeout();
+        }
+    }
+
+    ret_timeout
+}
+
+/**
+ * Stream frame that carries a batch of messages.
+ */
+pub trait BatchFrame: Serialize {
+    /**
+     * Builds the frame of a batch of messages.
+     *
+     * # Arguments
+     *
+     * `sender_id` - Local peer's globally unique id.
+     *
+     * `epoch` - Epoch of the group.
+     *
+     * `messages` - Batched messages.
+     */
+    fn batch(sender_id: usize, epoch: u64, messages: Vec<BatchedMessage>) -> Self;
+}
+
+/**
+ * Message payload and its optional signature sent in a batch frame.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BatchedMessage {
+    ///Serialized message
+    pub msg: Vec<u8>,
+    ///Signature of the serialized message
+    pub signature: Option<Vec<u8>>,
+}
+
+/**
+ * Messages buffered by a Sender thread until they are written
+ * to the stream in a single batch frame.
+ */
+pub struct PendingBatch {
+    ///Local peer's globally unique id
+    sender_id: usize,
+    ///Epoch of the group
+    epoch: u64,
+    ///Encoding of the batch frames
+    encoding: WireEncoding,
+    ///Buffered messages
+    messages: Vec<BatchedMessage>,
+    ///Number of messages the buffer is allocated for
+    capacity: usize,
+    ///Number of buffered bytes
+    bytes: u64,
+}
+
+impl PendingBatch {
+    /**
+     * Creates an empty batch.
+     *
+     * # Arguments
+     *
+     * `sender_id` - Local peer's globally unique id.
+     *
+     * `epoch` - Epoch of the group.
+     *
+     * `encoding` - Encoding of the batch frames.
+     *
+     * `capacity` - Number of messages the buffer is allocated for, 0 growing it as messages are pushed.
+     */
+    pub fn new(sender_id: usize, epoch: u64, encoding: WireEncoding, capacity: usize) -> Self {
+        Self {
+            sender_id,
+            epoch,
+            encoding,
+            messages: Vec::with_capacity(capacity),
+            capacity,
+            bytes: 0,
+        }
+    }
+
+    /**
+     * Adds a message to the batch.
+     *
+     * # Arguments
+     *
+     * `message` - Message to buffer.
+     */
+    pub fn push(&mut self, message: BatchedMessage) {
+        self.bytes += message.msg.len() as u64;
+
+        if let Some(signature) = &message.signature {
+            self.bytes += signature.len() as u64;
+        }
+
+        self.messages.push(message);
+    }
+
+    /**
+     * Returns the number of buffered messages.
+     */
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /**
+     * Checks if there aren't buffered messages.
+     */
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /**
+     * Returns the number of buffered bytes.
+     */
+    pub fn get_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /**
+     * Returns the encoding of the batch frames.
+     */
+    pub fn get_encoding(&self) -> WireEncoding {
+        self.encoding
+    }
+}
+
+/**
+ * Writes the buffered messages to the stream's buffer as a single
+ * batch frame and empties the batch.
+ *
+ * # Arguments
+ *
+ * `stream` - TCP stream between the peers.
+ *
+ * `batch` - Buffered messages.
+ */
+pub fn write_batch<F: BatchFrame, W: Write>(
+    stream: &mut BufWriter<W>,
+    batch: &mut PendingBatch,
+) -> bincode::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let messages = std::mem::replace(&mut batch.messages, Vec::with_capacity(batch.capacity));
+    batch.bytes = 0;
+
+    let frame = F::batch(batch.sender_id, batch.epoch, messages);
+
+    encoding::serialize_into(batch.encoding, stream, &frame)
+}
+
+/**
+ * Writes the buffered messages to the TCP stream and empties the batch.
+ *
+ * # Arguments
+ *
+ * `stream` - TCP stream between the peers.
+ *
+ * `batch` - Buffered messages.
+ */
+pub fn flush_buffer<F: BatchFrame, W: Write>(stream: &mut BufWriter<W>, batch: &mut PendingBatch) {
+    write_batch::<F, W>(stream, batch).expect("ERROR: Could not write the batch to the stream!");
+    stream.flush().expect("ERROR: Could not flush stream!");
+}
+
+/**
+ * Checks if its necessary to write the bytes from the buffer to the TCP stream.
+ *
+ * # Arguments
+ *
+ * `sender_timeout_flag` - Flag for determining if the reading timeout has expired.
+ *
+ * `stream` - TCP stream between the peers.
+ *
+ * `batch` - Buffered messages.
+ *
+ * `timeout` - Timeout duration.
+ *
+ * `batching` - Batching of the messages sent to the peer.
+ *
+ * `arrival_rate` - Observed arrival rate of messages.
+ *
+ * `error` - Flag for determining if the reading from the channel threw an error.
+ */
+pub fn check_buffer_flush<F: BatchFrame, W: Write>(
+    sender_timeout_flag: &mut bool,
+    stream: &mut BufWriter<W>,
+    batch: &mut PendingBatch,
+    timeout: &mut Duration,
+    batching: &Batching,
+    arrival_rate: &ArrivalRate,
+    error: bool,
+) {
+    let message_threshold = batching.get_message_threshold(arrival_rate.get_average_interval());
+
+    if batch.len() >= message_threshold
+        || batch.get_bytes() > batching.size
+        || (error && !batch.is_empty())
+    {
+        //Check if the error happened because of the SEND or the NO MESSAGES timeout
+        if error && *sender_timeout_flag {
+            //Change to false if it was the SEND timeout
+            *sender_timeout_flag = false;
+        }
+
+        flush_buffer::<F, W>(stream, batch);
+    } else {
+        //Check if the error happened because of the SEND or the NO MESSAGES timeout
+        if error && *sender_timeout_flag {
+            //Change to false if it was the SEND timeout
+            *sender_timeout_flag = false;
+        }
+        if error {
+            *timeout = calculate_timeout(*sender_timeout_flag, *timeout, batching);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+/**
+ * Offset basis of the 64 bit FNV-1a hash.
+ */
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/**
+ * Prime of the 64 bit FNV-1a hash.
+ */
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/**
+ * Calculates the checksum of a message payload. The hash is implemented here
+ * instead of using the standard library's hasher, whose output isn't
+ * guaranteed to be the same in the builds of different peers.
+ *
+ * # Arguments
+ *
+ * `payload` - Message payload, in plaintext.
+ */
+pub fn checksum(payload: &[u8]) -> u64 {
+    payload.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
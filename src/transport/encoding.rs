@@ -0,0 +1,135 @@
+use crate::configuration::middleware_configuration::WireEncoding;
+use bincode::{DefaultOptions, Options};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/**
+ * Serializes a value with the passed wire encoding.
+ *
+ * # Arguments
+ *
+ * `encoding` - Encoding of the integers.
+ *
+ * `value` - Value to serialize.
+ */
+pub fn serialize<T: ?Sized + Serialize>(
+    encoding: WireEncoding,
+    value: &T,
+) -> bincode::Result<Vec<u8>> {
+    match encoding {
+        WireEncoding::Fixint => DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .serialize(value),
+        WireEncoding::Varint => DefaultOptions::new()
+            .with_varint_encoding()
+            .allow_trailing_bytes()
+            .serialize(value),
+    }
+}
+
+/**
+ * Serializes a value with the passed wire encoding into a writer.
+ *
+ * # Arguments
+ *
+ * `encoding` - Encoding of the integers.
+ *
+ * `writer` - Writer where the value is serialized into.
+ *
+ * `value` - Value to serialize.
+ */
+pub fn serialize_into<W: Write, T: ?Sized + Serialize>(
+    encoding: WireEncoding,
+    writer: W,
+    value: &T,
+) -> bincode::Result<()> {
+    match encoding {
+        WireEncoding::Fixint => DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .serialize_into(writer, value),
+        WireEncoding::Varint => DefaultOptions::new()
+            .with_varint_encoding()
+            .allow_trailing_bytes()
+            .serialize_into(writer, value),
+    }
+}
+
+/**
+ * Deserializes a value encoded with the passed wire encoding.
+ *
+ * # Arguments
+ *
+ * `encoding` - Encoding of the integers.
+ *
+ * `bytes` - Serialized value.
+ */
+pub fn deserialize<'a, T: Deserialize<'a>>(
+    encoding: WireEncoding,
+    bytes: &'a [u8],
+) -> bincode::Result<T> {
+    match encoding {
+        WireEncoding::Fixint => DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize(bytes),
+        WireEncoding::Varint => DefaultOptions::new()
+            .with_varint_encoding()
+            .allow_trailing_bytes()
+            .deserialize(bytes),
+    }
+}
+
+/**
+ * Deserializes a value encoded with the passed wire encoding, refusing the
+ * bytes left after it, so a frame with a wrong length isn't taken as valid.
+ *
+ * # Arguments
+ *
+ * `encoding` - Encoding of the integers.
+ *
+ * `bytes` - Serialized value.
+ */
+pub fn deserialize_exact<T: DeserializeOwned>(
+    encoding: WireEncoding,
+    bytes: &[u8],
+) -> bincode::Result<T> {
+    let mut remaining = bytes;
+    let value = deserialize_from(encoding, &mut remaining)?;
+
+    if remaining.is_empty() {
+        Ok(value)
+    } else {
+        Err(Box::new(bincode::ErrorKind::Custom(format!(
+            "{} bytes left after the value",
+            remaining.len()
+        ))))
+    }
+}
+
+/**
+ * Deserializes a value encoded with the passed wire encoding from a reader.
+ *
+ * # Arguments
+ *
+ * `encoding` - Encoding of the integers.
+ *
+ * `reader` - Reader where the value is deserialized from.
+ */
+pub fn deserialize_from<R: Read, T: DeserializeOwned>(
+    encoding: WireEncoding,
+    reader: R,
+) -> bincode::Result<T> {
+    match encoding {
+        WireEncoding::Fixint => DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_from(reader),
+        WireEncoding::Varint => DefaultOptions::new()
+            .with_varint_encoding()
+            .allow_trailing_bytes()
+            .deserialize_from(reader),
+    }
+}
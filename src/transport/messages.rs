@@ -0,0 +1,28 @@
+use std::sync::{Arc, Barrier};
+
+/**
+ * Enum for the messages sent by the Middleware to the Sender threads.
+ */
+pub enum MiddlewareSender {
+    ///Serialized message, its optional signature and the barrier shared by every Sender thread
+    Message {
+        barrier: Arc<Barrier>,
+        msg: Arc<Vec<u8>>,
+        signature: Option<Arc<Vec<u8>>>,
+    },
+    ///Request by the Client to write the buffered messages into the stream
+    Flush,
+    ///Response to a probe received from the peer
+    Pong { timestamp: u64 },
+    ///Receipt of the peer's messages delivered up to a counter
+    Receipt { counter: usize },
+    ///Request for the retained messages above the local delivered version vector
+    CatchUp { vector: Vec<usize> },
+    ///End of the retained messages relayed to the peer
+    CaughtUp { cut: Option<Vec<usize>> },
+    ///Local delivered version vector answering the one received from the peer
+    Vector {
+        vector: Vec<usize>,
+        signature: Option<Vec<u8>>,
+    },
+}
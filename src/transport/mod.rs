@@ -0,0 +1,54 @@
+/**
+ * Batching of the messages written by the Sender threads of both engines.
+ */
+pub mod batching;
+/**
+ * Checksums of the message payloads, verified before they're delivered.
+ */
+pub mod checksum;
+/**
+ * Wire encoding of the frames and messages sent to other peers.
+ */
+pub mod encoding;
+/**
+ * Messages sent by the Middleware thread to the Sender threads.
+ */
+pub mod messages;
+/**
+ * Negotiation of the wire protocol version spoken with each peer.
+ */
+pub mod protocol;
+/**
+ * Reordering window restoring the FIFO order of each sender's messages.
+ */
+pub mod reordering;
+/**
+ * Shared memory transport for peers on the same host.
+ */
+#[cfg(feature = "shm")]
+pub mod shm;
+/**
+ * Simulated latency, jitter and loss of the links to the peers.
+ */
+pub mod simulation;
+/**
+ * Datagram transport with acknowledgements and retransmissions.
+ */
+pub mod udp;
+
+/**
+ * Scheme of the addresses of peers listening on a shared memory segment.
+ */
+pub const SHM_SCHEME: &str = "shm://";
+
+/**
+ * Returns the name of the shared memory segment of an address,
+ * or None if the peer listens on a TCP port.
+ *
+ * # Arguments
+ *
+ * `address` - Peer's address in the topology.
+ */
+pub fn get_shm_segment(address: &str) -> Option<&str> {
+    address.strip_prefix(SHM_SCHEME)
+}
@@ -0,0 +1,147 @@
+use std::io::{Read, Write};
+
+/**
+ * Newest version of the wire protocol spoken by the local peer. It's increased
+ * whenever a frame or message sent to the peers changes incompatibly.
+ */
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/**
+ * Oldest version of the wire protocol the local peer still speaks, so a group
+ * can be upgraded one peer at a time.
+ */
+pub const MIN_PROTOCOL_VERSION: u16 = 1;
+
+/**
+ * Bytes at the start of the version preamble, which tell it apart from the
+ * handshake of the peers that don't negotiate a protocol version.
+ */
+const PREAMBLE_MAGIC: [u8; 4] = *b"TCBP";
+
+/**
+ * Version of the wire protocol with the crate version that introduced it.
+ */
+#[derive(Debug, Clone, Copy)]
+pub struct ProtocolRelease {
+    ///Protocol version
+    pub version: u16,
+    ///First crate version speaking the protocol version
+    pub crate_version: &'static str,
+    ///Oldest protocol version still spoken by the crate version
+    pub min_version: u16,
+    ///Changes to the frames and messages
+    pub changes: &'static str,
+}
+
+/**
+ * Negotiation table of the protocol versions, from the oldest. A peer running a
+ * crate version can connect to a peer running another one if the ranges of the
+ * protocol versions they speak overlap, in which case the newest common version is used.
+ * The crate versions before the first one listed don't negotiate a protocol version,
+ * and the newest version may be listed under the upcoming crate version.
+ */
+pub const PROTOCOL_RELEASES: &[ProtocolRelease] = &[
+    ProtocolRelease {
+        version: 1,
+        crate_version: "0.1.4",
+        min_version: 1,
+        changes: "Version preamble sent before the handshake and delivered version vectors exchanged after it (GRAPH)",
+    },
+];
+
+/**
+ * Range of the protocol versions spoken by a peer.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    ///Oldest spoken version
+    pub min: u16,
+    ///Newest spoken version
+    pub max: u16,
+}
+
+impl VersionRange {
+    /**
+     * Returns the range of the protocol versions spoken by the local peer.
+     */
+    pub fn local() -> Self {
+        Self {
+            min: MIN_PROTOCOL_VERSION,
+            max: PROTOCOL_VERSION,
+        }
+    }
+
+    /**
+     * Returns the newest version spoken by both peers, or `None` if the ranges don't overlap.
+     *
+     * # Arguments
+     *
+     * `remote` - Range of the protocol versions spoken by the other peer.
+     */
+    pub fn negotiate(&self, remote: &VersionRange) -> Option<u16> {
+        let version = self.max.min(remote.max);
+
+        if version >= self.min.max(remote.min) {
+            Some(version)
+        } else {
+            None
+        }
+    }
+}
+
+/**
+ * Writes the range of the protocol versions spoken by the local peer, which is
+ * sent before the handshake. The preamble has a fixed layout, independent of the
+ * protocol version and of the wire encoding, so it can be read by every version.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to write the preamble into.
+ */
+pub fn send_version<W: Write>(stream: &mut W) -> std::io::Result<()> {
+    let range = VersionRange::local();
+    let mut preamble = [0u8; 8];
+
+    preamble[..4].copy_from_slice(&PREAMBLE_MAGIC);
+    preamble[4..6].copy_from_slice(&range.min.to_le_bytes());
+    preamble[6..].copy_from_slice(&range.max.to_le_bytes());
+
+    stream.write_all(&preamble)
+}
+
+/**
+ * Reads the range of the protocol versions spoken by a peer and returns the
+ * version used with it, or the reason the peers can't talk to each other.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to read the preamble from.
+ */
+pub fn receive_version<R: Read>(stream: &mut R) -> Result<u16, String> {
+    let mut preamble = [0u8; 8];
+
+    match stream.read_exact(&mut preamble) {
+        Ok(_) => {}
+        Err(e) => return Err(format!("failed to read the protocol version - {}", e)),
+    }
+
+    if preamble[..4] != PREAMBLE_MAGIC {
+        return Err(format!(
+            "the peer doesn't negotiate a protocol version, so it runs a crate version older than {}",
+            PROTOCOL_RELEASES[0].crate_version
+        ));
+    }
+
+    let remote = VersionRange {
+        min: u16::from_le_bytes([preamble[4], preamble[5]]),
+        max: u16::from_le_bytes([preamble[6], preamble[7]]),
+    };
+    let local = VersionRange::local();
+
+    local.negotiate(&remote).ok_or_else(|| {
+        format!(
+            "no common protocol version, the peer speaks {} to {} and the local peer {} to {}",
+            remote.min, remote.max, local.min, local.max
+        )
+    })
+}
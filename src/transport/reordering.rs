@@ -0,0 +1,150 @@
+use crate::configuration::middleware_configuration::Configuration;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/**
+ * Reordering window of the messages read from the peers, when it's set. A
+ * message is held until the previous messages of its sender were read from
+ * the same peer, so the messages of each sender reach the Middleware in FIFO
+ * order. Once more messages of a sender are held than the peer's window, the
+ * held messages are released in order without waiting for the missing ones.
+ */
+#[derive(Debug)]
+pub struct ReorderBuffer<T> {
+    ///Window of each peer that overrides the global window
+    peer_windows: HashMap<usize, usize>,
+    ///Window of the remaining peers
+    window: usize,
+    ///Counter of the last message released of each sender, by peer and sender id
+    released: HashMap<(usize, usize), usize>,
+    ///Messages held by peer and sender id, ordered by their counters
+    held: HashMap<(usize, usize), BTreeMap<usize, T>>,
+    ///Messages released in order that weren't taken yet
+    ready: VecDeque<T>,
+}
+
+impl<T> ReorderBuffer<T> {
+    /**
+     * Creates the reordering window of a Reader or Deserializer thread,
+     * or `None` if no peer has a reordering window.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn from_configuration(configuration: &Configuration) -> Option<Self> {
+        let peer_windows: HashMap<usize, usize> = configuration
+            .topology
+            .peers
+            .iter()
+            .filter_map(|peer| peer.reorder_window.map(|window| (peer.id, window)))
+            .collect();
+
+        if configuration.reorder_window == 0 && peer_windows.values().all(|window| *window == 0) {
+            return None;
+        }
+
+        Some(Self {
+            peer_windows,
+            window: configuration.reorder_window,
+            released: HashMap::new(),
+            held: HashMap::new(),
+            ready: VecDeque::new(),
+        })
+    }
+
+    /**
+     * Adds a message read from a peer, releasing it and the held messages
+     * that follow it if every previous message of its sender was released.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Id of the peer the message was read from
+     *
+     * `sender_id` - Id of the message's sender
+     *
+     * `counter` - Message counter
+     *
+     * `message` - Message read from the peer
+     */
+    pub fn push(&mut self, peer_id: usize, sender_id: usize, counter: usize, message: T) {
+        let window = self
+            .peer_windows
+            .get(&peer_id)
+            .copied()
+            .unwrap_or(self.window);
+        let key = (peer_id, sender_id);
+
+        let last = match self.released.get(&key) {
+            Some(last) if window > 0 => *last,
+            //The first message of a sender has nothing to wait for
+            _ => {
+                self.released.insert(key, counter);
+                self.ready.push_back(message);
+                return;
+            }
+        };
+
+        //Duplicates and retransmissions are left to the Middleware
+        if counter <= last {
+            self.ready.push_back(message);
+            return;
+        }
+
+        let held = self.held.entry(key).or_default();
+        held.insert(counter, message);
+
+        let mut last = last;
+
+        loop {
+            let next = match held.first_key_value() {
+                Some((next, _)) if *next == last + 1 || held.len() > window => *next,
+                _ => break,
+            };
+
+            let message = held.remove(&next).unwrap();
+            self.ready.push_back(message);
+            last = next;
+        }
+
+        self.released.insert(key, last);
+
+        if held.is_empty() {
+            self.held.remove(&key);
+        }
+    }
+
+    /**
+     * Releases every message held for a peer, e.g. once its connection
+     * was closed, since the missing messages won't be read anymore.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Id of the peer the messages were read from
+     */
+    pub fn release_peer(&mut self, peer_id: usize) {
+        let mut keys: Vec<(usize, usize)> = self
+            .held
+            .keys()
+            .filter(|(id, _)| *id == peer_id)
+            .copied()
+            .collect();
+        keys.sort_unstable();
+
+        for key in keys {
+            if let Some(held) = self.held.remove(&key) {
+                if let Some((last, _)) = held.last_key_value() {
+                    self.released.insert(key, *last);
+                }
+
+                self.ready.extend(held.into_values());
+            }
+        }
+    }
+
+    /**
+     * Takes the next message released in order, if any.
+     */
+    pub fn pop_ready(&mut self) -> Option<T> {
+        self.ready.pop_front()
+    }
+}
@@ -0,0 +1,370 @@
+use memmap2::{MmapMut, MmapOptions};
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+ * Size in bytes of the data of each ring.
+ */
+pub const RING_CAPACITY: usize = 1 << 20;
+
+/**
+ * Size in bytes of the header of each ring, which keeps the counters written
+ * by each side in their own cache line.
+ */
+const RING_HEADER_SIZE: usize = 192;
+
+/**
+ * Offset of the total number of bytes written into a ring.
+ */
+const HEAD: usize = 0;
+
+/**
+ * Offset of the total number of bytes read from a ring.
+ */
+const TAIL: usize = 64;
+
+/**
+ * Offset of the flag set when either side of the connection is dropped.
+ */
+const CLOSED: usize = 128;
+
+/**
+ * Offset of the process id of the peer that created the connection file,
+ * in the header of the first ring.
+ */
+const OWNER: usize = 136;
+
+/**
+ * Size in bytes of each ring, including its header.
+ */
+const RING_SIZE: usize = RING_HEADER_SIZE + RING_CAPACITY;
+
+/**
+ * Size in bytes of the file of a connection, with a ring for each direction.
+ */
+const FILE_SIZE: usize = 2 * RING_SIZE;
+
+/**
+ * Interval between the checks for the file of a connection that wasn't created yet.
+ */
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(10);
+
+/**
+ * Connection between two peers on the same host over a memory mapped file,
+ * read and written by the Reader and Sender threads like a TCP stream. The
+ * file has a single producer, single consumer ring for each direction, so
+ * bytes are copied without system calls, and the threads spin while a ring
+ * is empty or full before backing off to short sleeps.
+ */
+pub struct ShmStream {
+    ///Mapping of the connection's file
+    mapping: MmapMut,
+    ///Offset of the ring read from
+    read_ring: usize,
+    ///Offset of the ring written into
+    write_ring: usize,
+}
+
+impl ShmStream {
+    /**
+     * Creates the connection of the local Sender thread to a peer listening on a
+     * segment. The file is created under a temporary name and renamed once it has
+     * its full size, so the peer never maps a partial file.
+     *
+     * # Arguments
+     *
+     * `segment` - Name of the segment the peer listens on.
+     *
+     * `local_id` - Local peer's globally unique id.
+     */
+    pub fn connect(segment: &str, local_id: usize) -> io::Result<Self> {
+        let path = connection_path(segment, local_id);
+        let pending_path = shm_directory().join(format!("tcb-{}-{}.pending", segment, local_id));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&pending_path)?;
+        file.set_len(FILE_SIZE as u64)?;
+
+        //The file is zeroed, so both rings start empty and open
+        let mapping = unsafe { MmapOptions::new().len(FILE_SIZE).map_mut(&file)? };
+
+        let stream = Self {
+            mapping,
+            read_ring: RING_SIZE,
+            write_ring: 0,
+        };
+
+        //Letting the peer tell this file apart from one left by a crashed run
+        stream
+            .counter(0, OWNER)
+            .store(std::process::id() as u64, Ordering::Release);
+
+        fs::rename(&pending_path, &path)?;
+
+        Ok(stream)
+    }
+
+    /**
+     * Waits for the connection of a peer's Sender thread to the local segment and
+     * removes its file, which stays mapped until both sides drop the connection.
+     * The files left by a crashed run, which were closed or whose creator is no
+     * longer running, are removed and the connection is waited for again.
+     *
+     * # Arguments
+     *
+     * `segment` - Name of the segment the local peer listens on.
+     *
+     * `peer_id` - Other peer's globally unique id.
+     *
+     * `timeout` - Maximum waiting time for the connection, or `None` to wait forever.
+     */
+    pub fn accept(segment: &str, peer_id: usize, timeout: Option<Duration>) -> io::Result<Self> {
+        let path = connection_path(segment, peer_id);
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            while !path.exists() {
+                match deadline {
+                    Some(deadline) if Instant::now() >= deadline => {
+                        return Err(io::Error::new(
+                            ErrorKind::TimedOut,
+                            format!(
+                                "{} wasn't created before the startup timeout",
+                                path.display()
+                            ),
+                        ));
+                    }
+                    _ => thread::sleep(ACCEPT_INTERVAL),
+                }
+            }
+
+            let file = OpenOptions::new().read(true).write(true).open(&path)?;
+
+            if file.metadata()?.len() != FILE_SIZE as u64 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("{} isn't a connection file", path.display()),
+                ));
+            }
+
+            let mapping = unsafe { MmapOptions::new().len(FILE_SIZE).map_mut(&file)? };
+
+            let stream = Self {
+                mapping,
+                read_ring: 0,
+                write_ring: RING_SIZE,
+            };
+
+            fs::remove_file(&path)?;
+
+            if stream.is_stale() {
+                println!(
+                    "WARN: Removed the stale shared memory connection file {}",
+                    path.display()
+                );
+
+                continue;
+            }
+
+            return Ok(stream);
+        }
+    }
+
+    /**
+     * Checks if the connection's file was left by a previous run, because either
+     * side closed it or the process that created it is no longer running.
+     */
+    fn is_stale(&self) -> bool {
+        if self.counter(self.read_ring, CLOSED).load(Ordering::Acquire) != 0
+            || self
+                .counter(self.write_ring, CLOSED)
+                .load(Ordering::Acquire)
+                != 0
+        {
+            return true;
+        }
+
+        let owner = self.counter(0, OWNER).load(Ordering::Acquire);
+
+        //The running processes are only listed on Linux, elsewhere only closed files are stale
+        let processes = Path::new("/proc");
+        processes.is_dir() && !processes.join(owner.to_string()).exists()
+    }
+
+    /**
+     * Returns a counter in the header of a ring.
+     */
+    fn counter(&self, ring: usize, offset: usize) -> &AtomicU64 {
+        unsafe { &*(self.mapping.as_ptr().add(ring + offset) as *const AtomicU64) }
+    }
+}
+
+impl Read for ShmStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let mut backoff = Backoff::new();
+
+        loop {
+            //Reading the flag first, so the bytes written before the peer left are still read
+            let closed = self.counter(self.read_ring, CLOSED).load(Ordering::Acquire) != 0;
+            let head = self.counter(self.read_ring, HEAD).load(Ordering::Acquire);
+            let tail = self.counter(self.read_ring, TAIL).load(Ordering::Relaxed);
+
+            if head != tail {
+                let position = (tail % RING_CAPACITY as u64) as usize;
+                let length = buffer
+                    .len()
+                    .min((head - tail) as usize)
+                    .min(RING_CAPACITY - position);
+
+                unsafe {
+                    let data = self
+                        .mapping
+                        .as_ptr()
+                        .add(self.read_ring + RING_HEADER_SIZE + position);
+                    ptr::copy_nonoverlapping(data, buffer.as_mut_ptr(), length);
+                }
+
+                self.counter(self.read_ring, TAIL)
+                    .store(tail + length as u64, Ordering::Release);
+
+                return Ok(length);
+            }
+
+            if closed {
+                return Ok(0);
+            }
+
+            backoff.wait();
+        }
+    }
+}
+
+impl Write for ShmStream {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let mut backoff = Backoff::new();
+
+        loop {
+            if self
+                .counter(self.write_ring, CLOSED)
+                .load(Ordering::Acquire)
+                != 0
+            {
+                return Err(io::Error::new(
+                    ErrorKind::BrokenPipe,
+                    "the peer closed the shared memory connection",
+                ));
+            }
+
+            let head = self.counter(self.write_ring, HEAD).load(Ordering::Relaxed);
+            let tail = self.counter(self.write_ring, TAIL).load(Ordering::Acquire);
+            let free = RING_CAPACITY - (head - tail) as usize;
+
+            if free > 0 {
+                let position = (head % RING_CAPACITY as u64) as usize;
+                let length = buffer.len().min(free).min(RING_CAPACITY - position);
+
+                unsafe {
+                    let data = self
+                        .mapping
+                        .as_mut_ptr()
+                        .add(self.write_ring + RING_HEADER_SIZE + position);
+                    ptr::copy_nonoverlapping(buffer.as_ptr(), data, length);
+                }
+
+                self.counter(self.write_ring, HEAD)
+                    .store(head + length as u64, Ordering::Release);
+
+                return Ok(length);
+            }
+
+            backoff.wait();
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        //Written bytes are visible to the peer right away
+        Ok(())
+    }
+}
+
+impl Drop for ShmStream {
+    fn drop(&mut self) {
+        //Ending the peer's reads and failing its writes
+        self.counter(self.read_ring, CLOSED)
+            .store(1, Ordering::Release);
+        self.counter(self.write_ring, CLOSED)
+            .store(1, Ordering::Release);
+    }
+}
+
+/**
+ * Waiting strategy of a thread whose ring is empty or full, which spins
+ * at first to keep the latency low and then yields and sleeps so idle
+ * connections don't take a core each.
+ */
+struct Backoff {
+    ///Number of times the thread waited
+    step: u32,
+}
+
+impl Backoff {
+    /**
+     * Creates the waiting strategy.
+     */
+    fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    /**
+     * Waits before the ring is checked again.
+     */
+    fn wait(&mut self) {
+        if self.step < 64 {
+            std::hint::spin_loop();
+        } else if self.step < 128 {
+            thread::yield_now();
+        } else {
+            thread::sleep(Duration::from_micros(50));
+        }
+
+        self.step = self.step.saturating_add(1);
+    }
+}
+
+/**
+ * Returns the directory of the connection files, which is the memory backed
+ * /dev/shm if it exists.
+ */
+fn shm_directory() -> PathBuf {
+    let directory = Path::new("/dev/shm");
+
+    if directory.is_dir() {
+        directory.to_path_buf()
+    } else {
+        std::env::temp_dir()
+    }
+}
+
+/**
+ * Returns the path of the file of the connection initiated by a peer to a segment.
+ */
+fn connection_path(segment: &str, initiator: usize) -> PathBuf {
+    shm_directory().join(format!("tcb-{}-{}", segment, initiator))
+}
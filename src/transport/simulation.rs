@@ -0,0 +1,235 @@
+use crate::channel::unbounded;
+use crate::channel::{Receiver, Sender};
+use crate::configuration::middleware_configuration::Configuration;
+use crate::transport::messages::MiddlewareSender;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "link-simulation")]
+use crate::channel::RecvTimeoutError;
+#[cfg(feature = "link-simulation")]
+use crate::configuration::middleware_configuration::LinkSimulation;
+#[cfg(feature = "link-simulation")]
+use std::collections::VecDeque;
+#[cfg(feature = "link-simulation")]
+use std::sync::{Arc, Barrier};
+#[cfg(feature = "link-simulation")]
+use std::thread;
+#[cfg(feature = "link-simulation")]
+use std::time::{Duration, Instant};
+
+/**
+ * Drops a share of the messages or datagrams with a xorshift generator,
+ * which is enough to simulate loss without a random number dependency.
+ */
+pub struct LossSimulator {
+    ///Probability of dropping a message
+    probability: f64,
+    ///Generator state
+    state: u64,
+}
+
+impl LossSimulator {
+    /**
+     * Creates the simulator, seeded with the current time.
+     *
+     * # Arguments
+     *
+     * `probability` - Probability of dropping a message, between 0 and 1.
+     */
+    pub fn new(probability: f64) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Self {
+            probability,
+            state: seed | 1,
+        }
+    }
+
+    /**
+     * Returns if the next message must be dropped.
+     */
+    pub fn should_drop(&mut self) -> bool {
+        if self.probability <= 0.0 {
+            return false;
+        }
+
+        self.sample() < self.probability
+    }
+
+    /**
+     * Returns the next number of the generator, between 0 and 1.
+     */
+    pub fn sample(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+
+        self.state as f64 / u64::MAX as f64
+    }
+}
+
+/**
+ * Creates the channel from the Middleware thread to the Sender thread of a peer.
+ * With the `link-simulation` feature and a simulated link to the peer, the
+ * messages go through a Link thread that holds them for the link's latency.
+ *
+ * # Arguments
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `peer_id` - Destination peer's globally unique id.
+ *
+ * `configuration` - Middleware's configuration file.
+ */
+#[cfg_attr(not(feature = "link-simulation"), allow(unused_variables))]
+pub fn sender_channel(
+    local_id: usize,
+    peer_id: usize,
+    configuration: &Configuration,
+) -> (Sender<MiddlewareSender>, Receiver<MiddlewareSender>) {
+    let (middleware_send, link_recv) = unbounded::<MiddlewareSender>();
+
+    #[cfg(feature = "link-simulation")]
+    if let Some(link) = configuration.get_link_simulation(peer_id) {
+        let (link_send, sender_recv) = unbounded::<MiddlewareSender>();
+        let link = link.clone();
+
+        let thread_name = format!("link_thread_{}_{}", local_id, peer_id);
+        let builder = thread::Builder::new()
+            .name(thread_name)
+            .stack_size(configuration.thread_stack_size);
+
+        builder
+            .spawn(move || {
+                simulate_link(link_recv, link_send, link);
+            })
+            .unwrap();
+
+        return (middleware_send, sender_recv);
+    }
+
+    (middleware_send, link_recv)
+}
+
+/**
+ * Main loop of the Link thread. Each message is held until its latency, plus or
+ * minus a random jitter, elapsed, but never sent before the previous message,
+ * so the Sender thread still writes the messages in order. A share of the
+ * broadcast messages is dropped with the link's loss probability.
+ *
+ * # Arguments
+ *
+ * `middleware_channel` - Channel where the Middleware thread sends the messages.
+ *
+ * `sender_channel` - Channel to the link's Sender thread.
+ *
+ * `link` - Simulated latency, jitter and loss of the link.
+ */
+#[cfg(feature = "link-simulation")]
+fn simulate_link(
+    middleware_channel: Receiver<MiddlewareSender>,
+    sender_channel: Sender<MiddlewareSender>,
+    link: LinkSimulation,
+) {
+    let latency = link.get_latency();
+    let jitter = link.get_jitter();
+    let mut random = LossSimulator::new(link.loss);
+    let mut held: VecDeque<(Instant, MiddlewareSender)> = VecDeque::new();
+    let mut last_release = Instant::now();
+    let mut disconnected = false;
+
+    loop {
+        let received = match held.front() {
+            Some((release, _)) if disconnected => {
+                thread::sleep(release.saturating_duration_since(Instant::now()));
+                None
+            }
+            Some((release, _)) => {
+                let timeout = release.saturating_duration_since(Instant::now());
+
+                match middleware_channel.recv_timeout(timeout) {
+                    Ok(msg) => Some(msg),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => {
+                        disconnected = true;
+                        None
+                    }
+                }
+            }
+            None if disconnected => break,
+            None => match middleware_channel.recv() {
+                Ok(msg) => Some(msg),
+                Err(_) => {
+                    disconnected = true;
+                    None
+                }
+            },
+        };
+
+        match received {
+            Some(MiddlewareSender::Message {
+                barrier,
+                msg,
+                signature,
+            }) => {
+                //Waiting with the other Sender threads, so the held message doesn't block them
+                barrier.wait();
+
+                if !random.should_drop() {
+                    let release = hold(&mut random, latency, jitter, &mut last_release);
+                    let msg = MiddlewareSender::Message {
+                        barrier: Arc::new(Barrier::new(1)),
+                        msg,
+                        signature,
+                    };
+
+                    held.push_back((release, msg));
+                }
+            }
+            Some(msg) => {
+                let release = hold(&mut random, latency, jitter, &mut last_release);
+                held.push_back((release, msg));
+            }
+            None => {}
+        }
+
+        //Handing the messages whose latency elapsed to the Sender thread
+        let now = Instant::now();
+
+        while held.front().map_or(false, |(release, _)| *release <= now) {
+            let (_, msg) = held.pop_front().unwrap();
+
+            match sender_channel.send(msg) {
+                Ok(_) => {}
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/**
+ * Returns when a message received now must be handed to the Sender thread,
+ * which is never before the previous message.
+ */
+#[cfg(feature = "link-simulation")]
+fn hold(
+    random: &mut LossSimulator,
+    latency: Duration,
+    jitter: Duration,
+    last_release: &mut Instant,
+) -> Instant {
+    let now = Instant::now();
+    let offset = jitter.mul_f64(random.sample());
+
+    let release = if random.sample() < 0.5 {
+        now + latency.saturating_sub(offset)
+    } else {
+        now + latency + offset
+    };
+
+    *last_release = release.max(*last_release);
+    *last_release
+}
@@ -0,0 +1,557 @@
+use crate::channel::unbounded;
+use crate::channel::{Receiver, Sender};
+use crate::configuration::middleware_configuration::{Configuration, Udp};
+use crate::transport::simulation::LossSimulator;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/**
+ * Maximum number of stream bytes carried by a datagram, which keeps the
+ * datagrams below the usual path MTU.
+ */
+pub const MAX_DATAGRAM_PAYLOAD: usize = 1200;
+
+/**
+ * Size in bytes of the header of every datagram: kind, sender id,
+ * connection initiator id and sequence number.
+ */
+const HEADER_SIZE: usize = 17;
+
+/**
+ * Kind of the datagrams carrying stream bytes.
+ */
+const DATA: u8 = 0;
+
+/**
+ * Kind of the datagrams acknowledging the stream bytes received in order.
+ */
+const ACK: u8 = 1;
+
+/**
+ * Connection between two peers over the UDP socket, which is identified by the
+ * other peer's id and the id of the peer whose Sender thread initiated it.
+ */
+type ConnectionKey = (usize, usize);
+
+/**
+ * Datagrams written into a connection that weren't acknowledged yet.
+ */
+#[derive(Debug, Default)]
+struct Outbound {
+    ///Sequence number of the next datagram
+    next_sequence: u64,
+    ///Unacknowledged datagrams with the time they were last sent, by sequence number
+    unacked: BTreeMap<u64, (Instant, Vec<u8>)>,
+}
+
+/**
+ * Datagrams received from a connection that can't be read yet.
+ */
+#[derive(Debug, Default)]
+struct Inbound {
+    ///Sequence number of the next datagram to be read
+    next_sequence: u64,
+    ///Datagrams received ahead of a missing one, by sequence number
+    pending: BTreeMap<u64, Vec<u8>>,
+}
+
+/**
+ * State of a connection shared by its stream and the transport thread.
+ */
+struct Connection {
+    ///Address of the other peer's socket
+    address: SocketAddr,
+    ///Datagrams waiting for their acknowledgement
+    outbound: Mutex<Outbound>,
+    ///Signalled when datagrams are acknowledged
+    acked: Condvar,
+    ///Datagrams received in order, written by the transport thread
+    inbound_send: Sender<Vec<u8>>,
+    ///Datagrams received in order, read by the stream
+    inbound_receive: Receiver<Vec<u8>>,
+}
+
+/**
+ * Experimental datagram transport used instead of TCP, for evaluating the causal
+ * delivery under loss against the TCP baseline. Every peer binds a single UDP
+ * socket and the connections of its Reader and Sender threads are multiplexed on
+ * it. Each connection numbers its datagrams, which are acknowledged cumulatively
+ * and retransmitted until acknowledged, and reorders them before they are read,
+ * so the Reader threads read the same frames as with TCP. A share of the received
+ * datagrams can be dropped on purpose to simulate a lossy network.
+ */
+pub struct UdpTransport {
+    ///Socket shared by every connection
+    socket: Arc<UdpSocket>,
+    ///Local peer's globally unique id
+    local_id: usize,
+    ///Connections with every other peer, initiated by either peer
+    connections: Arc<HashMap<ConnectionKey, Arc<Connection>>>,
+    ///Maximum number of unacknowledged datagrams of a connection
+    window: usize,
+}
+
+impl UdpTransport {
+    /**
+     * Binds the local socket and starts the transport thread, which receives the
+     * datagrams, acknowledges them and retransmits the unacknowledged ones.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `local_port` - Port where the socket is bound.
+     *
+     * `peer_addresses` - Addresses of the other peers, ordered by their id.
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    pub fn bind(
+        local_id: usize,
+        local_port: usize,
+        peer_addresses: &[String],
+        configuration: &Configuration,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind(format!("0.0.0.0:{}", local_port))?;
+        socket.set_read_timeout(Some(configuration.udp.get_retransmit_timeout() / 4))?;
+
+        let mut connections = HashMap::new();
+
+        for (index, peer_address) in peer_addresses.iter().enumerate() {
+            let peer_id = if index < local_id { index } else { index + 1 };
+
+            let address = match peer_address.to_socket_addrs()?.next() {
+                Some(address) => address,
+                None => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("{} has no address", peer_address),
+                    ))
+                }
+            };
+
+            //Connections of the local Sender thread and of the peer's Sender thread
+            for initiator in [local_id, peer_id].iter() {
+                let (inbound_send, inbound_receive) = unbounded::<Vec<u8>>();
+
+                let connection = Connection {
+                    address,
+                    outbound: Mutex::new(Outbound::default()),
+                    acked: Condvar::new(),
+                    inbound_send,
+                    inbound_receive,
+                };
+
+                connections.insert((peer_id, *initiator), Arc::new(connection));
+            }
+        }
+
+        let transport = Self {
+            socket: Arc::new(socket),
+            local_id,
+            connections: Arc::new(connections),
+            window: configuration.udp.get_window(),
+        };
+
+        let socket = Arc::clone(&transport.socket);
+        let connections = Arc::clone(&transport.connections);
+        let udp = configuration.udp.clone();
+
+        let thread_name = format!("udp_transport_{}", local_id);
+        let builder = thread::Builder::new()
+            .name(thread_name)
+            .stack_size(configuration.thread_stack_size);
+
+        builder.spawn(move || {
+            run(&socket, local_id, &connections, &udp);
+        })?;
+
+        Ok(transport)
+    }
+
+    /**
+     * Returns the stream of the connection initiated by the local Sender thread
+     * to a peer, where the Sender writes its frames and reads the peer's handshake.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Other peer's globally unique id.
+     */
+    pub fn connect(&self, peer_id: usize) -> Option<UdpStream> {
+        self.stream(peer_id, self.local_id)
+    }
+
+    /**
+     * Returns the stream of the connection initiated by a peer's Sender thread,
+     * which is read by the local Reader thread of that peer.
+     *
+     * # Arguments
+     *
+     * `peer_id` - Other peer's globally unique id.
+     */
+    pub fn accept(&self, peer_id: usize) -> Option<UdpStream> {
+        self.stream(peer_id, peer_id)
+    }
+
+    /**
+     * Returns the stream of a connection.
+     */
+    fn stream(&self, peer_id: usize, initiator: usize) -> Option<UdpStream> {
+        self.connections
+            .get(&(peer_id, initiator))
+            .map(|connection| UdpStream {
+                socket: Arc::clone(&self.socket),
+                local_id: self.local_id,
+                initiator,
+                connection: Arc::clone(connection),
+                window: self.window,
+                read_buffer: Vec::new(),
+                read_position: 0,
+                write_buffer: Vec::with_capacity(MAX_DATAGRAM_PAYLOAD),
+            })
+    }
+}
+
+/**
+ * Ordered and reliable byte stream of a connection over the UDP socket, read
+ * and written by the Reader and Sender threads like a TCP stream. Writes are
+ * split in datagrams and block while the connection's window is full.
+ */
+pub struct UdpStream {
+    ///Socket shared by every connection
+    socket: Arc<UdpSocket>,
+    ///Local peer's globally unique id
+    local_id: usize,
+    ///Id of the peer whose Sender thread initiated the connection
+    initiator: usize,
+    ///State of the connection shared with the transport thread
+    connection: Arc<Connection>,
+    ///Maximum number of unacknowledged datagrams
+    window: usize,
+    ///Datagram being read
+    read_buffer: Vec<u8>,
+    ///Position of the next byte to read in the datagram
+    read_position: usize,
+    ///Bytes written that don't fill a datagram yet
+    write_buffer: Vec<u8>,
+}
+
+impl UdpStream {
+    /**
+     * Sends the buffered bytes as a datagram, waiting for room in the window.
+     */
+    fn send_datagram(&mut self) -> io::Result<()> {
+        if self.write_buffer.is_empty() {
+            return Ok(());
+        }
+
+        let mut outbound = self
+            .connection
+            .outbound
+            .lock()
+            .expect("ERROR: UDP connection lock was poisoned");
+
+        while outbound.unacked.len() >= self.window {
+            outbound = self
+                .connection
+                .acked
+                .wait(outbound)
+                .expect("ERROR: UDP connection lock was poisoned");
+        }
+
+        let sequence = outbound.next_sequence;
+        outbound.next_sequence += 1;
+
+        let datagram = encode_datagram(
+            DATA,
+            self.local_id,
+            self.initiator,
+            sequence,
+            &self.write_buffer,
+        );
+
+        self.socket.send_to(&datagram, self.connection.address)?;
+        outbound
+            .unacked
+            .insert(sequence, (Instant::now(), datagram));
+
+        self.write_buffer.clear();
+        Ok(())
+    }
+}
+
+impl Read for UdpStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.read_position == self.read_buffer.len() {
+            match self.connection.inbound_receive.recv() {
+                Ok(datagram) => {
+                    self.read_buffer = datagram;
+                    self.read_position = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+
+        let length = buffer
+            .len()
+            .min(self.read_buffer.len() - self.read_position);
+
+        buffer[..length]
+            .copy_from_slice(&self.read_buffer[self.read_position..self.read_position + length]);
+        self.read_position += length;
+
+        Ok(length)
+    }
+}
+
+impl Write for UdpStream {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        let length = buffer
+            .len()
+            .min(MAX_DATAGRAM_PAYLOAD - self.write_buffer.len());
+
+        self.write_buffer.extend_from_slice(&buffer[..length]);
+
+        if self.write_buffer.len() == MAX_DATAGRAM_PAYLOAD {
+            self.send_datagram()?;
+        }
+
+        Ok(length)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_datagram()
+    }
+}
+
+/**
+ * Loop of the transport thread, which handles the received datagrams and
+ * retransmits the datagrams that weren't acknowledged in time.
+ *
+ * # Arguments
+ *
+ * `socket` - Socket shared by every connection.
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `connections` - Connections with every other peer.
+ *
+ * `udp` - Settings of the UDP transport.
+ */
+fn run(
+    socket: &UdpSocket,
+    local_id: usize,
+    connections: &HashMap<ConnectionKey, Arc<Connection>>,
+    udp: &Udp,
+) {
+    let retransmit_timeout = udp.get_retransmit_timeout();
+    let window = udp.get_window() as u64;
+    let mut inbound: HashMap<ConnectionKey, Inbound> = HashMap::new();
+    let mut loss = LossSimulator::new(udp.loss);
+    let mut last_retransmission = Instant::now();
+    let mut buffer = vec![0u8; HEADER_SIZE + MAX_DATAGRAM_PAYLOAD];
+
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((length, source)) => {
+                if !loss.should_drop() {
+                    handle_datagram(
+                        socket,
+                        local_id,
+                        connections,
+                        &mut inbound,
+                        &buffer[..length],
+                        source,
+                        window,
+                    );
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {}
+            Err(e) => {
+                println!("ERROR: UDP transport of {} stopped\n\t- {}", local_id, e);
+                break;
+            }
+        }
+
+        if last_retransmission.elapsed() >= retransmit_timeout / 4 {
+            last_retransmission = Instant::now();
+            retransmit(socket, connections, retransmit_timeout);
+        }
+    }
+}
+
+/**
+ * Handles a received datagram. Stream bytes are handed to the connection's stream
+ * in order and acknowledged, while acknowledgements release the window. Datagrams
+ * that weren't sent from the other peer's address, or whose sequence number is
+ * beyond the window, are discarded so they can't grow the pending datagrams.
+ */
+fn handle_datagram(
+    socket: &UdpSocket,
+    local_id: usize,
+    connections: &HashMap<ConnectionKey, Arc<Connection>>,
+    inbound: &mut HashMap<ConnectionKey, Inbound>,
+    datagram: &[u8],
+    source: SocketAddr,
+    window: u64,
+) {
+    let (kind, sender_id, initiator, sequence, payload) = match decode_datagram(datagram) {
+        Some(decoded) => decoded,
+        None => {
+            println!("ERROR: {} discarded a malformed datagram", local_id);
+            return;
+        }
+    };
+
+    let key = (sender_id, initiator);
+
+    let connection = match connections.get(&key) {
+        Some(connection) => connection,
+        None => {
+            println!(
+                "ERROR: {} discarded a datagram of an unknown connection {:?}",
+                local_id, key
+            );
+            return;
+        }
+    };
+
+    if source != connection.address {
+        println!(
+            "ERROR: {} discarded a datagram of connection {:?} sent from {}",
+            local_id, key, source
+        );
+        return;
+    }
+
+    match kind {
+        DATA => {
+            let state = inbound.entry(key).or_default();
+
+            //The sender never has more datagrams than the window unacknowledged
+            if sequence >= state.next_sequence.saturating_add(window) {
+                println!(
+                    "ERROR: {} discarded a datagram of connection {:?} beyond the window",
+                    local_id, key
+                );
+                return;
+            }
+
+            if sequence >= state.next_sequence {
+                state.pending.insert(sequence, payload.to_vec());
+            }
+
+            //Handing the datagrams received in order to the stream
+            while let Some(payload) = state.pending.remove(&state.next_sequence) {
+                state.next_sequence += 1;
+                let _ = connection.inbound_send.send(payload);
+            }
+
+            //Acknowledging every datagram before the next expected one, including duplicates
+            //whose acknowledgement was lost
+            let ack = encode_datagram(ACK, local_id, initiator, state.next_sequence, &[]);
+
+            match socket.send_to(&ack, connection.address) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!(
+                        "ERROR: {} failed to send an acknowledgement\n\t- {}",
+                        local_id, e
+                    );
+                }
+            }
+        }
+        ACK => {
+            let mut outbound = connection
+                .outbound
+                .lock()
+                .expect("ERROR: UDP connection lock was poisoned");
+
+            let unacked = outbound.unacked.split_off(&sequence);
+            outbound.unacked = unacked;
+
+            connection.acked.notify_all();
+        }
+        _ => {
+            println!("ERROR: {} discarded a datagram of kind {}", local_id, kind);
+        }
+    }
+}
+
+/**
+ * Resends the datagrams that weren't acknowledged within the retransmission timeout.
+ */
+fn retransmit(
+    socket: &UdpSocket,
+    connections: &HashMap<ConnectionKey, Arc<Connection>>,
+    retransmit_timeout: Duration,
+) {
+    for connection in connections.values() {
+        let mut outbound = connection
+            .outbound
+            .lock()
+            .expect("ERROR: UDP connection lock was poisoned");
+
+        for (sent, datagram) in outbound.unacked.values_mut() {
+            if sent.elapsed() >= retransmit_timeout {
+                *sent = Instant::now();
+
+                match socket.send_to(datagram, connection.address) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!("ERROR: Failed to retransmit a datagram\n\t- {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Builds a datagram with its header.
+ */
+fn encode_datagram(
+    kind: u8,
+    sender_id: usize,
+    initiator: usize,
+    sequence: u64,
+    payload: &[u8],
+) -> Vec<u8> {
+    let mut datagram = Vec::with_capacity(HEADER_SIZE + payload.len());
+    datagram.push(kind);
+    datagram.extend_from_slice(&(sender_id as u32).to_be_bytes());
+    datagram.extend_from_slice(&(initiator as u32).to_be_bytes());
+    datagram.extend_from_slice(&sequence.to_be_bytes());
+    datagram.extend_from_slice(payload);
+    datagram
+}
+
+/**
+ * Splits a datagram in its kind, sender id, connection initiator id, sequence
+ * number and payload, returning None if it's shorter than the header.
+ */
+fn decode_datagram(datagram: &[u8]) -> Option<(u8, usize, usize, u64, &[u8])> {
+    if datagram.len() < HEADER_SIZE {
+        return None;
+    }
+
+    let mut sender_id = [0u8; 4];
+    let mut initiator = [0u8; 4];
+    let mut sequence = [0u8; 8];
+
+    sender_id.copy_from_slice(&datagram[1..5]);
+    initiator.copy_from_slice(&datagram[5..9]);
+    sequence.copy_from_slice(&datagram[9..17]);
+
+    Some((
+        datagram[0],
+        u32::from_be_bytes(sender_id) as usize,
+        u32::from_be_bytes(initiator) as usize,
+        u64::from_be_bytes(sequence),
+        &datagram[HEADER_SIZE..],
+    ))
+}
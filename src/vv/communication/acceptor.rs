@@ -1,12 +1,83 @@
-use crate::configuration::middleware_configuration::Configuration;
-use crate::vv::communication::{handshake, reader};
+use crate::broadcast::setup::SetupBarrier;
+use crate::channel::{Receiver, Sender};
+use crate::configuration::middleware_configuration::{Configuration, WireEncoding};
+use crate::metrics::middleware_metrics::MiddlewareMetrics;
+use crate::security::peer_stream::PeerStream;
+use crate::transport::messages::MiddlewareSender;
+use crate::transport::udp::UdpTransport;
+use crate::vv::communication::connector::{SharedConnection, SharedConnections};
+use crate::vv::communication::deserializer::RawMessage;
+use crate::vv::communication::{deserializer, handshake, reader, sender};
 use crate::vv::structs::messages::{ClientPeerMiddleware, StreamMsg};
-use bincode::deserialize_from;
-use crossbeam::Sender;
-use std::net::{TcpListener, TcpStream};
-use std::sync::{Arc, Barrier};
+use std::collections::HashMap;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread;
 
+#[cfg(feature = "shm")]
+use crate::transport::shm::ShmStream;
+
+/**
+ * State shared by the Acceptor thread and the Reader threads it starts.
+ */
+#[derive(Clone)]
+pub struct AcceptorContext {
+    ///Local peer's globally unique id
+    pub local_id: usize,
+    ///Addresses the middleware will connect to
+    pub peer_addresses: Vec<String>,
+    ///Channel from the Reader threads to the middleware
+    pub middleware_channel: Sender<ClientPeerMiddleware>,
+    ///Middleware's configuration file
+    pub configuration: Arc<Configuration>,
+    ///Metrics shared with the Client
+    pub metrics: Arc<MiddlewareMetrics>,
+    ///Channels to the threads that deserialize the messages read by the Reader threads
+    pub deserializers: Vec<Sender<RawMessage>>,
+    ///Number of peers whose connection was made during the setup
+    pub connected_peers: Arc<AtomicUsize>,
+}
+
+impl AcceptorContext {
+    /**
+     * Creates the context of the Acceptor thread and starts the threads that
+     * deserialize the messages read by the Reader threads.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `peer_addresses` - Addresses the middleware will connect to.
+     *
+     * `middleware_channel` - Channel from the middleware to the peer.
+     *
+     * `configuration` - Middleware's configuration file.
+     *
+     * `metrics` - Metrics shared with the Client.
+     */
+    pub fn new(
+        local_id: usize,
+        peer_addresses: Vec<String>,
+        middleware_channel: Sender<ClientPeerMiddleware>,
+        configuration: Arc<Configuration>,
+        metrics: Arc<MiddlewareMetrics>,
+    ) -> Self {
+        let deserializers =
+            deserializer::start_workers(local_id, &middleware_channel, &configuration);
+
+        Self {
+            local_id,
+            peer_addresses,
+            middleware_channel,
+            configuration,
+            metrics,
+            deserializers,
+            connected_peers: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
 /**
  * Starts the Acceptor thread that waits for connections from other peers and
  * spawns a Reader for each. This function is called on a thread as to not
@@ -14,63 +85,299 @@ use std::thread;
  *
  * # Arguments
  *
- * `local_id` - Local peer's globally unique id.
- *
- * `local_port` - Port where the middleware will be listening for connections.
- *
- * `peer_addresses` - Addresses the middleware will connect to.
- *
- * `middleware_channel` - Channel from the middleware to the peer.
+ * `context` - State shared with the Reader threads.
  *
- * `configuration` - Middleware's configuration file.
+ * `server` - Listener bound to the local port, or `None` if the local peer listens
+ * on a shared memory segment.
  *
  * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ *
+ * `shared` - Channels from the Connector if each pair of peers shares a single connection.
  */
 pub fn start(
-    local_id: usize,
-    local_port: usize,
-    peer_addresses: Vec<String>,
-    middleware_channel: Sender<ClientPeerMiddleware>,
-    configuration: Arc<Configuration>,
-    setup_end_barrier: Arc<Barrier>,
+    context: AcceptorContext,
+    server: Option<TcpListener>,
+    setup_end_barrier: Arc<SetupBarrier>,
+    shared: Option<SharedConnections>,
 ) {
-    //Binding the TCP listener and setting blocking behaviour
-    let server = TcpListener::bind(format!("0.0.0.0:{}", local_port))
-        .expect("ERROR: Stream failed to connect");
+    //Accepting the connections over shared memory if the local peer listens on a segment
+    #[cfg(feature = "shm")]
+    if let Some(segment) = context.configuration.topology.get_local_segment() {
+        let segment = segment.to_string();
 
-    server
-        .set_nonblocking(false)
-        .expect("ERROR: Failed to set stream non-blocking mode");
+        return start_shm(context, &segment, setup_end_barrier);
+    }
+
+    let server = server.expect("ERROR: The acceptor has no TCP listener");
+
+    let local_id = context.local_id;
+    let configuration = &context.configuration;
 
-    let mut connected_peers = 0;
+    //Ids of the peers whose connection was accepted
+    let mut accepted = vec![false; context.peer_addresses.len() + 1];
+
+    //Starting the Reader threads of the shared connections made by the Connector
+    let mut shared_senders = match shared {
+        Some(shared) => {
+            start_shared_readers(
+                context.clone(),
+                shared.outbound,
+                Arc::clone(&setup_end_barrier),
+            );
+            Some((shared.senders, shared.connected))
+        }
+        None => None,
+    };
 
     loop {
-        match server.accept() {
-            Ok((stream, _)) => match deserialize_from::<_, StreamMsg>(&stream) {
+        let connection = server.accept();
+
+        //Stopping once the startup timed out, which closes the listener
+        if setup_end_barrier.is_aborted() {
+            return;
+        }
+
+        match connection {
+            Ok((stream, source)) => match handshake::read_handshake(&stream) {
                 Ok(decoded_msg_type) => match decoded_msg_type {
-                    StreamMsg::HND { index } => {
-                        let setup_end_barrier_clone = Arc::clone(&setup_end_barrier);
+                    StreamMsg::HND { index, encoding } => {
+                        //Refusing connections from ids outside the group or with the local id,
+                        //which would be misindexed by the middleware, from ids whose connection
+                        //wasn't closed or from addresses the peer isn't allowed to connect from
+                        let refusal = if index > context.peer_addresses.len() || index == local_id {
+                            Some("the peer id is invalid")
+                        } else if accepted[index] && context.metrics.is_inbound_connected(index) {
+                            Some("the peer id is already connected")
+                        } else if accepted[index] && shared_senders.is_some() {
+                            Some("the shared connection can't be replaced")
+                        } else if shared_senders.is_some() && index > local_id {
+                            Some("the local peer connects to the higher peer ids")
+                        } else if !configuration
+                            .topology
+                            .is_allowed_source(index, &source.ip())
+                        {
+                            Some("the address isn't allowed")
+                        } else {
+                            None
+                        };
+
+                        if let Some(reason) = refusal {
+                            println!(
+                                "ERROR: {} refused a connection from {} with the peer id {} - {}",
+                                local_id, source, index, reason
+                            );
+                            let _ = stream.shutdown(Shutdown::Both);
+                            continue;
+                        }
+
+                        //A peer that reconnects after its connection was closed replaces it,
+                        //and the new Reader thread doesn't wait for the setup that already ended
+                        let setup_end_barrier_clone = if accepted[index] {
+                            println!(
+                                "WARN: {} accepted a replacement connection from {}",
+                                local_id, index
+                            );
+                            None
+                        } else {
+                            Some(Arc::clone(&setup_end_barrier))
+                        };
+                        accepted[index] = true;
+
+                        send_connection(&stream, index, &context.middleware_channel);
+
+                        //Cloning the stream written by the Sender thread of a shared connection
+                        let sender_stream = match &shared_senders {
+                            Some(_) => match stream.try_clone() {
+                                Ok(sender_stream) => Some(sender_stream),
+                                Err(e) => {
+                                    println!(
+                                        "ERROR: Failed to clone the stream of peer {}\n\t- {}",
+                                        index, e
+                                    );
+                                    None
+                                }
+                            },
+                            None => None,
+                        };
 
                         handle_new_connection(
-                            local_id,
+                            &context,
                             index,
-                            &peer_addresses,
-                            stream,
-                            &middleware_channel,
-                            &mut connected_peers,
-                            &configuration,
+                            PeerStream::Plain(stream),
+                            encoding,
                             setup_end_barrier_clone,
                         );
+
+                        //Starting the Sender thread once the handshake was sent back
+                        if let (Some((senders, connected)), Some(sender_stream)) =
+                            (&mut shared_senders, sender_stream)
+                        {
+                            start_shared_sender(
+                                local_id,
+                                index,
+                                sender_stream,
+                                senders,
+                                connected,
+                                configuration,
+                                &context.metrics,
+                            );
+                        }
                     }
                     _ => {
-                        panic!("ERROR: Unexpected message type");
+                        //Dropping the socket and accepting the next connections
+                        println!("ERROR: Acceptor received an unexpected message type");
+                        let _ = stream.shutdown(Shutdown::Both);
                     }
                 },
                 Err(e) => {
-                    println!("ERROR: {}", e);
-                    break;
+                    println!(
+                        "ERROR: {} failed to read the handshake from {}\n\t- {}",
+                        local_id, source, e
+                    );
+                    let _ = stream.shutdown(Shutdown::Both);
                 }
             },
+            Err(e) => {
+                println!(
+                    "ERROR: {} failed to accept a connection\n\t- {}",
+                    local_id, e
+                );
+            }
+        }
+    }
+}
+
+/**
+ * Binds the TCP listener of the local port and sets its blocking behaviour, unless
+ * the local peer listens on a shared memory segment. The OS picks a free port if
+ * the local port is 0.
+ *
+ * # Arguments
+ *
+ * `local_port` - Port where the middleware will be listening for connections.
+ *
+ * `configuration` - Middleware's configuration file.
+ */
+#[cfg_attr(not(feature = "shm"), allow(unused_variables))]
+pub fn bind(local_port: usize, configuration: &Configuration) -> Option<TcpListener> {
+    #[cfg(feature = "shm")]
+    if configuration.topology.get_local_segment().is_some() {
+        return None;
+    }
+
+    let server = TcpListener::bind(format!("0.0.0.0:{}", local_port))
+        .expect("ERROR: Stream failed to connect");
+
+    server
+        .set_nonblocking(false)
+        .expect("ERROR: Failed to set stream non-blocking mode");
+
+    Some(server)
+}
+
+/**
+ * Starts the Reader threads of the connections initiated by the other peers over
+ * the UDP transport, in order of their ids, once each peer sent its handshake.
+ * The middleware isn't handed the streams, since they can't be shut down.
+ *
+ * # Arguments
+ *
+ * `context` - State shared with the Reader threads.
+ *
+ * `transport` - UDP transport shared by every connection.
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ */
+pub fn start_udp(
+    context: AcceptorContext,
+    transport: &UdpTransport,
+    setup_end_barrier: Arc<SetupBarrier>,
+) {
+    accept_each_peer(
+        context,
+        |peer_id| transport.accept(peer_id).map(PeerStream::Udp),
+        setup_end_barrier,
+    );
+}
+
+/**
+ * Starts the Reader threads of the connections initiated by the other peers on
+ * the same host to the shared memory segment the local peer listens on.
+ *
+ * # Arguments
+ *
+ * `context` - State shared with the Reader threads.
+ *
+ * `segment` - Name of the segment the local peer listens on.
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ */
+#[cfg(feature = "shm")]
+pub fn start_shm(context: AcceptorContext, segment: &str, setup_end_barrier: Arc<SetupBarrier>) {
+    //Waiting for the connections until the startup timeout, if set
+    let timeout = match context.configuration.startup_timeout {
+        0 => None,
+        _ => Some(context.configuration.get_startup_timeout()),
+    };
+    let local_id = context.local_id;
+
+    accept_each_peer(
+        context,
+        |peer_id| match ShmStream::accept(segment, peer_id, timeout) {
+            Ok(stream) => Some(PeerStream::Shm(stream)),
+            Err(e) => {
+                println!(
+                    "ERROR: {} failed to accept the shared memory connection of {}\n\t- {}",
+                    local_id, peer_id, e
+                );
+                None
+            }
+        },
+        setup_end_barrier,
+    );
+}
+
+/**
+ * Accepts the connection of each peer in order of their ids, once the peer sent
+ * its handshake, for the transports whose connections are identified by the peer
+ * before the handshake.
+ */
+fn accept_each_peer<F>(
+    context: AcceptorContext,
+    mut accept: F,
+    setup_end_barrier: Arc<SetupBarrier>,
+) where
+    F: FnMut(usize) -> Option<PeerStream>,
+{
+    let local_id = context.local_id;
+
+    for peer_id in (0..=context.peer_addresses.len()).filter(|peer_id| *peer_id != local_id) {
+        //Stopping once the startup timed out
+        if setup_end_barrier.is_aborted() {
+            break;
+        }
+
+        let mut stream = match accept(peer_id) {
+            Some(stream) => stream,
+            None => {
+                println!("ERROR: {} has no connection from {}", local_id, peer_id);
+                break;
+            }
+        };
+
+        match handshake::read_handshake(&mut stream) {
+            Ok(StreamMsg::HND { index, encoding }) if index == peer_id => {
+                handle_new_connection(
+                    &context,
+                    peer_id,
+                    stream,
+                    encoding,
+                    Some(Arc::clone(&setup_end_barrier)),
+                );
+            }
+            Ok(_) => {
+                panic!("ERROR: Unexpected message type");
+            }
             Err(e) => {
                 println!("ERROR: {}", e);
                 break;
@@ -79,24 +386,81 @@ pub fn start(
     }
 }
 
+/**
+ * Hands a clone of a TCP stream to the middleware so it can close the connection.
+ */
+fn send_connection(
+    stream: &TcpStream,
+    peer_id: usize,
+    middleware_channel: &Sender<ClientPeerMiddleware>,
+) {
+    match stream.try_clone() {
+        Ok(stream_clone) => {
+            let connection = ClientPeerMiddleware::CONNECTION {
+                peer_id,
+                stream: stream_clone,
+            };
+
+            match middleware_channel.send(connection) {
+                Ok(_) => {}
+                Err(e) => {
+                    println!(
+                        "ERROR: Failed to send the reader stream to the middleware\n\t- {}",
+                        e
+                    );
+                }
+            }
+        }
+        Err(e) => {
+            println!(
+                "ERROR: Failed to clone the stream of peer {}\n\t- {}",
+                peer_id, e
+            );
+        }
+    }
+}
+
 /**
  * Handles a new peer connection.
  */
 fn handle_new_connection(
-    local_id: usize,
+    context: &AcceptorContext,
     peer_id: usize,
-    peer_addresses: &Vec<String>,
-    stream: TcpStream,
-    middleware_channel: &Sender<ClientPeerMiddleware>,
-    connected_peers: &mut usize,
-    configuration: &Arc<Configuration>,
-    setup_end_barrier: Arc<Barrier>,
+    mut stream: PeerStream,
+    encoding: WireEncoding,
+    setup_end_barrier: Option<Arc<SetupBarrier>>,
 ) {
-    handshake::send_handshake(&stream, local_id);
+    handshake::send_handshake(
+        &mut stream,
+        context.local_id,
+        context.configuration.wire_encoding,
+    );
 
-    let middleware_channel_temp = middleware_channel.clone();
+    start_reader(context, peer_id, stream, encoding, setup_end_barrier);
+}
+
+/**
+ * Spawns the Reader thread of a connection whose handshake was exchanged.
+ */
+fn start_reader(
+    context: &AcceptorContext,
+    peer_id: usize,
+    stream: PeerStream,
+    encoding: WireEncoding,
+    setup_end_barrier: Option<Arc<SetupBarrier>>,
+) {
+    let local_id = context.local_id;
+    let middleware_channel = &context.middleware_channel;
+    let configuration = &context.configuration;
+    let metrics = &context.metrics;
+
+    let context_temp = context.clone();
+    metrics.update_connection(peer_id, false);
+
+    let metrics_disconnect = Arc::clone(metrics);
 
-    *connected_peers += 1;
+    //The connections replaced after the setup aren't counted
+    let replacement = setup_end_barrier.is_none();
 
     let thread_name = format!("stream_reader_{}_{}", local_id, peer_id);
     let builder = thread::Builder::new()
@@ -105,17 +469,17 @@ fn handle_new_connection(
 
     builder
         .spawn(move || {
-            reader::start(
-                stream,
-                middleware_channel_temp,
-                local_id,
-                peer_id,
-                setup_end_barrier,
-            );
+            reader::start(stream, context_temp, peer_id, encoding, setup_end_barrier);
+
+            //Letting the peer replace the connection once it's closed
+            metrics_disconnect.update_disconnection(peer_id);
         })
         .unwrap();
 
-    if *connected_peers == peer_addresses.len() {
+    if !replacement
+        && context.connected_peers.fetch_add(1, Ordering::SeqCst) + 1
+            == context.peer_addresses.len()
+    {
         let setup = ClientPeerMiddleware::SETUP;
         match middleware_channel.send(setup) {
             Ok(_) => {}
@@ -128,3 +492,88 @@ fn handle_new_connection(
         }
     }
 }
+
+/**
+ * Spawns the thread that starts the Reader threads of the shared connections
+ * made by the Connector to the peers with a higher id, as they're made.
+ */
+fn start_shared_readers(
+    context: AcceptorContext,
+    outbound: Receiver<SharedConnection>,
+    setup_end_barrier: Arc<SetupBarrier>,
+) {
+    let thread_name = format!("shared_connections_{}", context.local_id);
+    let builder = thread::Builder::new()
+        .name(thread_name)
+        .stack_size(context.configuration.thread_stack_size);
+
+    builder
+        .spawn(move || {
+            //The channel is disconnected once every outbound connection was made
+            for connection in outbound.iter() {
+                send_connection(
+                    &connection.stream,
+                    connection.peer_id,
+                    &context.middleware_channel,
+                );
+
+                start_reader(
+                    &context,
+                    connection.peer_id,
+                    PeerStream::Plain(connection.stream),
+                    connection.encoding,
+                    Some(Arc::clone(&setup_end_barrier)),
+                );
+            }
+        })
+        .unwrap();
+}
+
+/**
+ * Spawns the Sender thread of a shared connection accepted from a peer with
+ * a lower id, which writes to the same socket as the peer's Reader thread.
+ */
+fn start_shared_sender(
+    local_id: usize,
+    peer_id: usize,
+    stream: TcpStream,
+    senders: &mut HashMap<usize, Receiver<MiddlewareSender>>,
+    connected: &Sender<usize>,
+    configuration: &Arc<Configuration>,
+    metrics: &Arc<MiddlewareMetrics>,
+) {
+    let socket_thread_recv = match senders.remove(&peer_id) {
+        Some(socket_thread_recv) => socket_thread_recv,
+        None => {
+            println!(
+                "ERROR: {} has no Sender thread channel for peer {}",
+                local_id, peer_id
+            );
+            return;
+        }
+    };
+
+    let configuration_temp = Arc::clone(configuration);
+    let metrics_temp = Arc::clone(metrics);
+
+    let thread_name = format!("sender_thread_{}_{}", local_id, peer_id);
+    let builder = thread::Builder::new()
+        .name(thread_name)
+        .stack_size(configuration.thread_stack_size);
+
+    builder
+        .spawn(move || {
+            sender::start_shared(
+                PeerStream::Plain(stream),
+                socket_thread_recv,
+                local_id,
+                peer_id,
+                configuration_temp,
+                metrics_temp,
+            );
+        })
+        .unwrap();
+
+    //The receiver is dropped once the setup ended
+    let _ = connected.send(peer_id);
+}
@@ -1,14 +1,26 @@
-use super::sender;
-use crate::configuration::middleware_configuration::Configuration;
-use crossbeam::crossbeam_channel::unbounded;
-use crossbeam::Sender;
+use super::{handshake, sender};
+use crate::broadcast::setup::SetupBarrier;
+use crate::channel::unbounded;
+use crate::channel::{Receiver, Sender};
+use crate::configuration::middleware_configuration::{Configuration, WireEncoding};
+use crate::metrics::middleware_metrics::MiddlewareMetrics;
+use crate::security::peer_stream::PeerStream;
+use crate::transport::messages::MiddlewareSender;
+use crate::transport::simulation;
+use crate::transport::udp::UdpTransport;
+use std::collections::HashMap;
 use std::net::TcpStream;
-use std::sync::{Arc, Barrier};
+use std::sync::Arc;
 use std::thread;
 
+#[cfg(feature = "shm")]
+use crate::transport::{get_shm_segment, shm::ShmStream};
+
 /**
- * Starts the Connector thread that connects to every peer in the group and ends when
- * successfully connected to all of them.
+ * Starts the Sender threads of the connections to every peer in the group without
+ * waiting for the connections. The messages sent to a peer are buffered in the
+ * channel to its Sender thread until the connection is made. The id of each
+ * connected peer is sent to the returned channel.
  *
  * # Arguments
  *
@@ -17,88 +29,334 @@ use std::thread;
  * `peer_addresses` - Addresses the middleware will connect to.
  *
  * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics shared with the Client.
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer,
+ * whose abort stops the connection attempts.
  */
 pub fn start(
     local_id: usize,
-    peer_addresses: &Vec<String>,
+    peer_addresses: &[String],
     configuration: &Arc<Configuration>,
-) -> Vec<Sender<(Arc<Barrier>, Arc<Vec<u8>>)>> {
+    metrics: &Arc<MiddlewareMetrics>,
+    setup_end_barrier: &Arc<SetupBarrier>,
+) -> (Vec<Sender<MiddlewareSender>>, Receiver<usize>) {
     let mut peers_channels_to_sockets_threads = Vec::new();
-    let mut channels_thread_spawn = Vec::new();
+    let (connected_send, connected_receive) = unbounded::<usize>();
 
-    //The connections to the peers will be concurrent
-    for i in 0..peer_addresses.len() {
-        let peer_id: usize;
+    for (i, address) in peer_addresses.iter().enumerate() {
+        let peer_id = if i < local_id { i } else { i + 1 };
 
-        if i < local_id {
-            peer_id = i;
-        } else {
-            peer_id = i + 1;
-        }
-
-        let temp_peer_port = peer_addresses[i].clone();
+        let (socket_thread_send, socket_thread_recv) =
+            simulation::sender_channel(local_id, peer_id, configuration);
+        let peer_address = address.clone();
         let temp_configuration = Arc::clone(configuration);
+        let temp_metrics = Arc::clone(metrics);
+        let temp_setup_end_barrier = Arc::clone(setup_end_barrier);
+        let temp_connected = connected_send.clone();
 
-        channels_thread_spawn.push(thread::spawn(move || {
-            connect_to_single_peer(local_id, peer_id, temp_peer_port, temp_configuration)
-        }));
-    }
+        let thread_name = format!("sender_thread_{}_{}", local_id, peer_id);
+        let builder = thread::Builder::new()
+            .name(thread_name)
+            .stack_size(configuration.thread_stack_size);
 
-    for channel_spawn_result in channels_thread_spawn {
-        match channel_spawn_result.join() {
-            Ok(channel) => {
-                peers_channels_to_sockets_threads.push(channel);
-            }
-            Err(_) => {
-                println!("ERROR: There were problems when joining the peer channels");
-            }
-        }
+        builder
+            .spawn(move || {
+                //Stopping if the startup timed out before the connection was made
+                let stream = match connect(local_id, &peer_address, &temp_setup_end_barrier) {
+                    Some(stream) => stream,
+                    None => return,
+                };
+
+                //The receiver is dropped once the setup ended
+                let _ = temp_connected.send(peer_id);
+
+                sender::start(
+                    stream,
+                    socket_thread_recv,
+                    local_id,
+                    temp_configuration,
+                    temp_metrics,
+                );
+            })
+            .unwrap();
+
+        peers_channels_to_sockets_threads.push(socket_thread_send);
     }
 
-    peers_channels_to_sockets_threads
+    (peers_channels_to_sockets_threads, connected_receive)
 }
 
 /**
- * Function that connects to a single peer. The call to this will only end when the
- * connection to the peer is successfull.
+ * Opens the stream of a connection to a peer, retrying until it's successfull.
+ * Returns `None` if the setup was aborted first.
  */
-fn connect_to_single_peer(
+#[cfg_attr(not(feature = "shm"), allow(unused_variables))]
+fn connect(
     local_index: usize,
-    peer_index: usize,
-    peer_address: String,
-    configuration: Arc<Configuration>,
-) -> Sender<(Arc<Barrier>, Arc<Vec<u8>>)> {
-    let out: Sender<(Arc<Barrier>, Arc<Vec<u8>>)>;
+    peer_address: &String,
+    setup_end_barrier: &SetupBarrier,
+) -> Option<PeerStream> {
+    //Connecting over shared memory if the peer listens on a segment
+    #[cfg(feature = "shm")]
+    if let Some(segment) = get_shm_segment(peer_address) {
+        let stream = ShmStream::connect(segment, local_index)
+            .expect("ERROR: Failed to create the shared memory connection");
+
+        return Some(PeerStream::Shm(stream));
+    }
+
+    connect_tcp(peer_address, setup_end_barrier).map(PeerStream::Plain)
+}
 
+/**
+ * Opens a TCP connection to a peer, retrying until it's successfull. Returns
+ * `None` if the setup was aborted first.
+ */
+fn connect_tcp(peer_address: &String, setup_end_barrier: &SetupBarrier) -> Option<TcpStream> {
     loop {
-        let connect = TcpStream::connect(&peer_address);
+        if setup_end_barrier.is_aborted() {
+            return None;
+        }
+
+        let connect = TcpStream::connect(peer_address);
         match connect {
             Ok(stream) => {
                 stream
                     .set_nonblocking(false)
                     .expect("ERROR: Failed to set stream non-blocking mode");
 
-                let (socket_thread_send, socket_thread_recv) =
-                    unbounded::<(Arc<Barrier>, Arc<Vec<u8>>)>();
+                return Some(stream);
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/**
+ * Connection made to a peer with a higher id when the connections are shared,
+ * whose Reader thread is started by the Acceptor thread.
+ */
+pub struct SharedConnection {
+    ///Other peer's globally unique id
+    pub peer_id: usize,
+    ///Clone of the stream read by the Reader thread
+    pub stream: TcpStream,
+    ///Encoding of the frames and messages sent by the peer
+    pub encoding: WireEncoding,
+}
 
-                out = socket_thread_send;
+/**
+ * Ends of the channels handed to the Acceptor thread when the connections are
+ * shared, since it starts the threads of the connections made by either peer.
+ */
+pub struct SharedConnections {
+    ///Channel from the Middleware to the Sender thread of each peer with a lower id
+    pub senders: HashMap<usize, Receiver<MiddlewareSender>>,
+    ///Connections made to the peers with a higher id
+    pub outbound: Receiver<SharedConnection>,
+    ///Channel notified of each connection accepted from a peer with a lower id
+    pub connected: Sender<usize>,
+}
 
-                let temp_config_arc = Arc::clone(&configuration);
+/**
+ * Starts the Sender threads of the connections to the peers with a higher id when
+ * each pair of peers shares a single connection, without waiting for them. The
+ * peers with a lower id connect to the local peer, so their Sender threads are
+ * started by the Acceptor thread with the returned channels. The id of each
+ * connected peer is sent to the returned channel.
+ *
+ * # Arguments
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `peer_addresses` - Addresses the middleware will connect to.
+ *
+ * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics shared with the Client.
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer,
+ * whose abort stops the connection attempts.
+ */
+pub fn start_shared(
+    local_id: usize,
+    peer_addresses: &[String],
+    configuration: &Arc<Configuration>,
+    metrics: &Arc<MiddlewareMetrics>,
+    setup_end_barrier: &Arc<SetupBarrier>,
+) -> (
+    Vec<Sender<MiddlewareSender>>,
+    Receiver<usize>,
+    SharedConnections,
+) {
+    let mut peers_channels_to_sockets_threads = Vec::new();
+    let mut senders = HashMap::new();
+    let (connected_send, connected_receive) = unbounded::<usize>();
+    let (outbound_send, outbound_receive) = unbounded::<SharedConnection>();
 
-                let thread_name = format!("sender_thread_{}_{}", local_index, peer_index);
-                let builder = thread::Builder::new()
-                    .name(thread_name)
-                    .stack_size(configuration.thread_stack_size);
+    for (i, address) in peer_addresses.iter().enumerate() {
+        let peer_id = if i < local_id { i } else { i + 1 };
 
-                builder
-                    .spawn(move || {
-                        sender::start(stream, socket_thread_recv, local_index, temp_config_arc);
-                    })
-                    .unwrap();
+        let (socket_thread_send, socket_thread_recv) =
+            simulation::sender_channel(local_id, peer_id, configuration);
+        peers_channels_to_sockets_threads.push(socket_thread_send);
 
-                return out;
-            }
-            Err(_) => {}
+        if peer_id < local_id {
+            senders.insert(peer_id, socket_thread_recv);
+            continue;
         }
+
+        let peer_address = address.clone();
+        let temp_configuration = Arc::clone(configuration);
+        let temp_metrics = Arc::clone(metrics);
+        let temp_setup_end_barrier = Arc::clone(setup_end_barrier);
+        let temp_connected = connected_send.clone();
+        let temp_outbound = outbound_send.clone();
+
+        let thread_name = format!("sender_thread_{}_{}", local_id, peer_id);
+        let builder = thread::Builder::new()
+            .name(thread_name)
+            .stack_size(configuration.thread_stack_size);
+
+        builder
+            .spawn(move || {
+                //Stopping if the startup timed out before the connection was made
+                let mut stream = match connect_tcp(&peer_address, &temp_setup_end_barrier) {
+                    Some(stream) => stream,
+                    None => return,
+                };
+
+                //Exchanging the handshakes before the stream is shared with the Reader thread
+                handshake::send_handshake(&mut stream, local_id, temp_configuration.wire_encoding);
+                let (index, encoding) = handshake::receive_handshake(&mut stream);
+
+                if index != peer_id {
+                    println!(
+                        "ERROR: {} connected to {} but the peer has the id {}",
+                        local_id, peer_address, index
+                    );
+                    return;
+                }
+
+                let reader_stream = stream
+                    .try_clone()
+                    .expect("ERROR: Failed to clone the stream of the shared connection");
+
+                let connection = SharedConnection {
+                    peer_id,
+                    stream: reader_stream,
+                    encoding,
+                };
+
+                match temp_outbound.send(connection) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!(
+                            "ERROR: Failed to send the shared connection to the acceptor\n\t- {}",
+                            e
+                        );
+                    }
+                }
+
+                //The receiver is dropped once the setup ended
+                let _ = temp_connected.send(peer_id);
+
+                sender::start_shared(
+                    PeerStream::Plain(stream),
+                    socket_thread_recv,
+                    local_id,
+                    peer_id,
+                    temp_configuration,
+                    temp_metrics,
+                );
+            })
+            .unwrap();
+    }
+
+    let shared = SharedConnections {
+        senders,
+        outbound: outbound_receive,
+        connected: connected_send,
+    };
+
+    (peers_channels_to_sockets_threads, connected_receive, shared)
+}
+
+/**
+ * Starts the Sender threads of the connections to every peer over the UDP
+ * transport. The connections need no setup, so this doesn't block.
+ *
+ * # Arguments
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `transport` - UDP transport shared by every connection.
+ *
+ * `peer_addresses` - Addresses the middleware will connect to.
+ *
+ * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics shared with the Client.
+ */
+pub fn start_udp(
+    local_id: usize,
+    transport: &UdpTransport,
+    peer_addresses: &[String],
+    configuration: &Arc<Configuration>,
+    metrics: &Arc<MiddlewareMetrics>,
+) -> Vec<Sender<MiddlewareSender>> {
+    let mut peers_channels_to_sockets_threads = Vec::new();
+
+    for i in 0..peer_addresses.len() {
+        let peer_id = if i < local_id { i } else { i + 1 };
+
+        let stream = transport
+            .connect(peer_id)
+            .expect("ERROR: UDP transport has no connection to the peer");
+
+        peers_channels_to_sockets_threads.push(spawn_sender(
+            local_id,
+            peer_id,
+            PeerStream::Udp(stream),
+            Arc::clone(configuration),
+            Arc::clone(metrics),
+        ));
     }
+
+    peers_channels_to_sockets_threads
+}
+
+/**
+ * Spawns the Sender thread of a connection, returning the channel to it.
+ */
+fn spawn_sender(
+    local_index: usize,
+    peer_index: usize,
+    stream: PeerStream,
+    configuration: Arc<Configuration>,
+    metrics: Arc<MiddlewareMetrics>,
+) -> Sender<MiddlewareSender> {
+    let (socket_thread_send, socket_thread_recv) =
+        simulation::sender_channel(local_index, peer_index, &configuration);
+
+    let thread_name = format!("sender_thread_{}_{}", local_index, peer_index);
+    let builder = thread::Builder::new()
+        .name(thread_name)
+        .stack_size(configuration.thread_stack_size);
+
+    builder
+        .spawn(move || {
+            sender::start(
+                stream,
+                socket_thread_recv,
+                local_index,
+                configuration,
+                metrics,
+            );
+        })
+        .unwrap();
+
+    socket_thread_send
 }
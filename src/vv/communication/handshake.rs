@@ -1,38 +1,112 @@
+use crate::configuration::middleware_configuration::WireEncoding;
+use crate::transport::protocol;
 use crate::vv::structs::messages::StreamMsg;
 use bincode::{deserialize_from, serialize_into};
-use std::net::TcpStream;
+use std::io::{Read, Write};
 
 /**
- * Sends a handshake message to a peer.
+ * Sends a handshake message to a peer, after the range of the protocol versions
+ * spoken by the local peer. The handshake is always encoded with fixed size
+ * integers, since the peer doesn't know the local encoding yet.
  *
  * # Arguments
  *
- * `stream` - TCP stream to write the handshake message into.
+ * `stream` - Stream to write the handshake message into.
  *
  * `local_id` - Local peer's globally unique id.
+ *
+ * `encoding` - Encoding of the frames and messages sent by the local peer.
  */
-pub fn send_handshake(mut stream: &TcpStream, local_index: usize) {
-    serialize_into::<_, StreamMsg>(&mut stream, &StreamMsg::HND { index: local_index })
+pub fn send_handshake<W: Write>(mut stream: W, local_index: usize, encoding: WireEncoding) {
+    let handshake = StreamMsg::HND {
+        index: local_index,
+        encoding,
+    };
+
+    protocol::send_version(&mut stream)
+        .expect("ERROR: Couldn't write the protocol version to peer socket");
+
+    serialize_into::<_, StreamMsg>(&mut stream, &handshake)
+        .expect("ERROR: Couldn't write handshake message to peer socket");
+
+    stream
+        .flush()
         .expect("ERROR: Couldn't write handshake message to peer socket");
 }
 
 /**
- * Finishes the handshake process.
+ * Finishes the handshake process, returning the peer's id and the protocol
+ * version used with it.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to read the handshake message from.
+ */
+pub fn finish_protocol<R: Read>(stream: R) -> (usize, u16) {
+    match read_versioned_handshake(stream) {
+        Ok((version, StreamMsg::HND { index, .. })) => (index, version),
+        Ok(_) => {
+            panic!("ERROR: Unexpected message type");
+        }
+        Err(e) => {
+            panic!(
+                "ERROR: Occurred when handling the receiver handshake message - {}",
+                e
+            );
+        }
+    }
+}
+
+/**
+ * Reads a peer's handshake message, returning its id and the
+ * encoding of the frames and messages it sends.
  *
  * # Arguments
  *
- * `stream` - TCP stream to read the handshake message from.
+ * `stream` - Stream to read the handshake message from.
  */
-pub fn finish_protocol(stream: &TcpStream) -> usize {
-    match deserialize_from::<_, StreamMsg>(stream) {
+pub fn receive_handshake<R: Read>(stream: R) -> (usize, WireEncoding) {
+    match read_handshake(stream) {
         Ok(decoded_handshake) => match decoded_handshake {
-            StreamMsg::HND { index } => index,
+            StreamMsg::HND { index, encoding } => (index, encoding),
             _ => {
                 panic!("ERROR: Unexpected message type");
             }
         },
-        Err(_) => {
-            panic!("ERROR: Occurred when handling the receiver handshake message");
+        Err(e) => {
+            panic!(
+                "ERROR: Occurred when handling the receiver handshake message - {}",
+                e
+            );
         }
     }
 }
+
+/**
+ * Reads a peer's protocol version preamble and its handshake message, returning
+ * the reason the connection can't be used if the peers don't speak a common
+ * protocol version or the handshake couldn't be read.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to read the handshake message from.
+ */
+pub fn read_handshake<R: Read>(stream: R) -> Result<StreamMsg, String> {
+    read_versioned_handshake(stream).map(|(_, handshake)| handshake)
+}
+
+/**
+ * Reads a peer's protocol version preamble and its handshake message, returning
+ * the protocol version used with the peer and the handshake message.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream to read the handshake message from.
+ */
+pub fn read_versioned_handshake<R: Read>(mut stream: R) -> Result<(u16, StreamMsg), String> {
+    let version = protocol::receive_version(&mut stream)?;
+
+    deserialize_from::<_, StreamMsg>(stream)
+        .map(|handshake| (version, handshake))
+        .map_err(|e| e.to_string())
+}
@@ -6,6 +6,10 @@ pub mod acceptor;
  * Connects to another peer's acceptor thread.
  */
 pub mod connector;
+/**
+ * Deserializes messages read by the Reader threads.
+ */
+pub mod deserializer;
 /**
  * Initial handshake process between peers.
  */
@@ -1,43 +1,223 @@
+use crate::broadcast::setup::SetupBarrier;
+use crate::channel::Sender;
+use crate::configuration::middleware_configuration::WireEncoding;
+use crate::metrics::metered_stream::MeteredStream;
+use crate::metrics::middleware_metrics::probe_timestamp;
+use crate::platform::threads::apply_thread_settings;
+use crate::security::peer_stream::PeerStream;
+use crate::transport::encoding;
+use crate::transport::reordering::ReorderBuffer;
+use crate::vv::communication::acceptor::AcceptorContext;
+use crate::vv::communication::deserializer::{self, RawMessage};
 use crate::vv::structs::messages::{ClientPeerMiddleware, Message, StreamMsg};
-use bincode::{deserialize, deserialize_from};
-use crossbeam::Sender;
-use std::net::TcpStream;
-use std::sync::{Arc, Barrier};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
 use std::usize;
 
+#[cfg(feature = "tls")]
+use crate::security::tls;
+
+#[cfg(feature = "signing")]
+use crate::security::message_signing::MessageSigner;
+#[cfg(feature = "noise")]
+use crate::security::noise::NoiseSession;
+#[cfg(feature = "encryption")]
+use crate::security::payload_encryption::{decrypt_message, PayloadCipher};
+
 /**
  * Starts a Reader thread that receives messages from a stream
  * and sends them to the middleware.
  *
  * # Arguments
  *
- * `stream` - TCP stream between the peers.
+ * `stream` - Stream between the peers.
  *
- * `middleware_channel` - Channel from the the Reader to the Middleware.
- *
- * `local_id` - Local peer's globally unique id.
+ * `context` - State shared with the Acceptor thread.
  *
  * `peer_id` - Other peer's globally unique id.
  *
- * `setup_end_barrier` - Barrier signalling the middleware connected to every peer.
+ * `encoding` - Encoding of the frames and messages sent by the peer.
+ *
+ * `setup_end_barrier` - Barrier signalling the middleware connected to every peer,
+ * or `None` for a connection that replaced a closed one after the setup.
  */
 pub fn start(
-    stream: TcpStream,
-    middleware_channel: Sender<ClientPeerMiddleware>,
-    local_id: usize,
+    #[cfg_attr(not(any(feature = "noise", feature = "tls")), allow(unused_mut))]
+    mut stream: PeerStream,
+    context: AcceptorContext,
     peer_id: usize,
-    setup_end_barrier: Arc<Barrier>,
+    encoding: WireEncoding,
+    setup_end_barrier: Option<Arc<SetupBarrier>>,
 ) {
-    setup_end_barrier.wait();
+    let AcceptorContext {
+        local_id,
+        middleware_channel,
+        configuration,
+        metrics,
+        deserializers,
+        ..
+    } = context;
+
+    //Channel to the Deserializer thread of the peer, if the messages aren't deserialized by the Reader
+    let deserializer = deserializer::get_worker(&deserializers, peer_id);
+
+    apply_thread_settings(&configuration.threads.reader);
+
+    #[cfg(feature = "signing")]
+    let signer = MessageSigner::from_configuration(&configuration);
+
+    #[cfg(feature = "encryption")]
+    let cipher = PayloadCipher::from_configuration(&configuration);
+
+    //Establishing the session keys of the connection
+    #[cfg(feature = "noise")]
+    let mut noise_session = NoiseSession::respond(&mut stream, peer_id, &configuration)
+        .expect("ERROR: Failed the Noise handshake with the peer");
+
+    //Securing the connection with mutual TLS, if set
+    #[cfg(feature = "tls")]
+    {
+        stream = tls::accept(stream, peer_id, &configuration)
+            .expect("ERROR: Failed the TLS handshake with the peer");
+    }
+
+    //Stopping if the startup timed out before the setup ended
+    if let Some(setup_end_barrier) = &setup_end_barrier {
+        if !setup_end_barrier.wait() {
+            return;
+        }
+    }
+
+    //Counting the bytes read from the peer
+    let mut stream = MeteredStream::new(stream, peer_id, Arc::clone(&metrics));
+
+    //Messages held until the previous messages of their senders were read
+    let mut reorder = ReorderBuffer::from_configuration(&configuration);
+
+    let receive_context = ReceiveContext {
+        middleware_channel: &middleware_channel,
+        local_id,
+        #[cfg(feature = "signing")]
+        signer: &signer,
+        #[cfg(feature = "encryption")]
+        cipher: &cipher,
+    };
+
+    //Messages unpacked from the last batch frame that weren't handled yet
+    let mut batched: VecDeque<StreamMsg> = VecDeque::new();
 
     loop {
-        match deserialize_from::<_, StreamMsg>(&stream) {
+        let frame = match batched.pop_front() {
+            Some(frame) => Ok(frame),
+            None => encoding::deserialize_from::<_, StreamMsg>(encoding, &mut stream),
+        };
+
+        match frame {
             Ok(decoded_msg_type) => match decoded_msg_type {
-                StreamMsg::MSG { msg, .. } => {
-                    handle_received_peer_msg(msg, &middleware_channel, peer_id);
+                StreamMsg::MSG {
+                    msg,
+                    epoch,
+                    signature,
+                    ..
+                } => {
+                    //Decrypting every message in order, since the nonces are sequential
+                    #[cfg(feature = "noise")]
+                    let msg = match &mut noise_session {
+                        Some(session) => match session.decrypt(&msg) {
+                            Ok(msg) => msg,
+                            Err(e) => {
+                                println!(
+                                    "ERROR: {} failed to decrypt a message from {}\n\t- {}",
+                                    local_id, peer_id, e
+                                );
+                                break;
+                            }
+                        },
+                        None => msg,
+                    };
+
+                    if epoch < configuration.epoch {
+                        //Discarding frames from a stale epoch
+                        println!(
+                            "WARN: {} discarded a message from {} with stale epoch {}",
+                            local_id, peer_id, epoch
+                        );
+                    } else {
+                        match &deserializer {
+                            Some(deserializer) => {
+                                //Handing off the message without copying it
+                                let raw_message = RawMessage {
+                                    peer_id,
+                                    msg,
+                                    signature,
+                                    encoding,
+                                    closed: false,
+                                };
+
+                                match deserializer.send(raw_message) {
+                                    Ok(_) => {}
+                                    Err(e) => {
+                                        println!(
+                                            "ERROR: Failed to send message to the deserializer\n\t- {}",
+                                            e
+                                        );
+                                    }
+                                }
+                            }
+                            None => {
+                                handle_received_peer_msg(
+                                    msg,
+                                    signature,
+                                    peer_id,
+                                    encoding,
+                                    &receive_context,
+                                    &mut reorder,
+                                );
+                            }
+                        }
+                    }
+                }
+
+                StreamMsg::BATCH {
+                    peer_id: sender_id,
+                    epoch,
+                    messages,
+                } => {
+                    //Unpacking the batch so each message is handled as if it was sent in its own frame
+                    batched.extend(messages.into_iter().map(|message| StreamMsg::MSG {
+                        msg: message.msg,
+                        peer_id: sender_id,
+                        epoch,
+                        signature: message.signature,
+                    }));
                 }
 
+                StreamMsg::PING { timestamp } => {
+                    //Answering the probe through the Sender thread to this peer
+                    match middleware_channel.send(ClientPeerMiddleware::PING { peer_id, timestamp })
+                    {
+                        Ok(_) => {}
+                        Err(e) => {
+                            println!("ERROR: Failed to send probe to the middleware\n\t- {}", e);
+                        }
+                    }
+                }
+                StreamMsg::PONG { timestamp } => {
+                    let rtt = probe_timestamp().saturating_sub(timestamp);
+                    metrics.update_rtt(peer_id, Duration::from_micros(rtt));
+                }
+                StreamMsg::RECEIPT { counter } => {
+                    metrics.update_receipt(peer_id, counter);
+                }
                 StreamMsg::CLOSE => {
+                    //Releasing the held messages, since the missing ones won't be read anymore
+                    if let Some(reorder) = &mut reorder {
+                        reorder.release_peer(peer_id);
+                        send_released(reorder, &middleware_channel);
+                    }
+
+                    notify_closed(peer_id, &middleware_channel, &deserializer, encoding);
                     break;
                 }
 
@@ -54,23 +234,159 @@ pub fn start(
             }
         }
     }
+
+    //Releasing the messages still held once the connection ended
+    if let Some(reorder) = &mut reorder {
+        reorder.release_peer(peer_id);
+        send_released(reorder, &middleware_channel);
+    }
 }
 
-fn handle_received_peer_msg(
+/**
+ * Notifies the Middleware that a peer closed its connection. The notification
+ * goes through the peer's Deserializer thread, if it has one, so it's received
+ * after the peer's last messages.
+ *
+ * # Arguments
+ *
+ * `peer_id` - Other peer's globally unique id.
+ *
+ * `middleware_channel` - Channel from the the Reader to the Middleware.
+ *
+ * `deserializer` - Channel to the Deserializer thread of the peer, if the messages aren't deserialized by the Reader.
+ *
+ * `encoding` - Encoding of the frames and messages sent by the peer.
+ */
+fn notify_closed(
+    peer_id: usize,
+    middleware_channel: &Sender<ClientPeerMiddleware>,
+    deserializer: &Option<Sender<RawMessage>>,
+    encoding: WireEncoding,
+) {
+    let sent = match deserializer {
+        Some(deserializer) => deserializer
+            .send(RawMessage {
+                peer_id,
+                msg: Vec::new(),
+                signature: None,
+                encoding,
+                closed: true,
+            })
+            .is_ok(),
+        None => middleware_channel
+            .send(ClientPeerMiddleware::CLOSED { peer_id })
+            .is_ok(),
+    };
+
+    if !sent {
+        println!(
+            "ERROR: Failed to send the closed connection of {} to the middleware",
+            peer_id
+        );
+    }
+}
+
+/**
+ * State of the Reader or Deserializer thread used to handle the received messages.
+ */
+pub struct ReceiveContext<'a> {
+    ///Channel to the Middleware
+    pub middleware_channel: &'a Sender<ClientPeerMiddleware>,
+    ///Local peer's globally unique id
+    pub local_id: usize,
+    ///Verifier of the message signatures
+    #[cfg(feature = "signing")]
+    pub signer: &'a Option<MessageSigner>,
+    ///Cipher of the received payloads
+    #[cfg(feature = "encryption")]
+    pub cipher: &'a Option<PayloadCipher>,
+}
+
+/**
+ * Handles a message received from a peer. The work that only depends on the
+ * message, such as verifying and decrypting it, is done here so it runs in
+ * parallel in the Reader or Deserializer threads, and the Middleware thread is
+ * left with the causal delivery.
+ */
+pub fn handle_received_peer_msg(
     msg: Vec<u8>,
-    send_main_mid: &Sender<ClientPeerMiddleware>,
+    signature: Option<Vec<u8>>,
     peer_index: usize,
+    encoding: WireEncoding,
+    context: &ReceiveContext,
+    reorder: &mut Option<ReorderBuffer<ClientPeerMiddleware>>,
 ) {
-    let decoded_msg: Message = deserialize(&msg)
+    let send_main_mid = context.middleware_channel;
+    let local_id = context.local_id;
+
+    #[cfg_attr(not(feature = "encryption"), allow(unused_mut))]
+    let mut decoded_msg: Message = encoding::deserialize_exact(encoding, &msg)
         .expect("ERROR: Couldn't deserialize the Message type after reading from the stream");
 
+    //Discarding messages that weren't signed by the peer of this connection,
+    //which the middleware takes as their sender
+    #[cfg(feature = "signing")]
+    match context.signer {
+        Some(signer) if !signer.verify(peer_index, &msg, &signature) => {
+            println!(
+                "ERROR: {} discarded a message from {} with an invalid signature",
+                local_id, peer_index
+            );
+            return;
+        }
+        _ => {}
+    }
+
+    //Signatures are ignored without the signing feature
+    #[cfg(not(feature = "signing"))]
+    let _ = (signature, local_id);
+
+    #[cfg(feature = "encryption")]
+    {
+        decoded_msg.payload = match decrypt_message(decoded_msg.payload, context.cipher) {
+            Some(payload) => payload,
+            None => return,
+        };
+    }
+
+    let counter = decoded_msg.id;
+
     let peer_msg: ClientPeerMiddleware = ClientPeerMiddleware::PEER {
         message: decoded_msg,
         peer_id: peer_index,
     };
 
-    //Sending the payload to the middleware thread
-    send_main_mid
-        .send(peer_msg)
-        .expect("ERROR: Failed to send message to main middleware thread");
+    match reorder {
+        Some(reorder) => {
+            //Holding the message until the previous messages of its sender were read
+            reorder.push(peer_index, peer_index, counter, peer_msg);
+            send_released(reorder, send_main_mid);
+        }
+        None => {
+            //Sending the payload to the middleware thread
+            send_main_mid
+                .send(peer_msg)
+                .expect("ERROR: Failed to send message to main middleware thread");
+        }
+    }
+}
+
+/**
+ * Sends the messages released in order by the reordering window to the Middleware.
+ *
+ * # Arguments
+ *
+ * `reorder` - Reordering window of the Reader or Deserializer thread.
+ *
+ * `send_main_mid` - Channel to the Middleware.
+ */
+pub fn send_released(
+    reorder: &mut ReorderBuffer<ClientPeerMiddleware>,
+    send_main_mid: &Sender<ClientPeerMiddleware>,
+) {
+    while let Some(peer_msg) = reorder.pop_ready() {
+        send_main_mid
+            .send(peer_msg)
+            .expect("ERROR: Failed to send message to main middleware thread");
+    }
 }
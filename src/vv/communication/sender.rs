@@ -1,42 +1,157 @@
+use crate::channel::Receiver;
+use crate::channel::RecvTimeoutError;
 use crate::configuration::middleware_configuration::Configuration;
-use crate::graph::communication::sender::*;
+use crate::metrics::metered_stream::MeteredStream;
+use crate::metrics::middleware_metrics::{probe_timestamp, MiddlewareMetrics};
+use crate::platform::threads::apply_thread_settings;
+use crate::security::peer_stream::PeerStream;
+use crate::transport::batching::BatchedMessage;
+use crate::transport::batching::*;
+use crate::transport::encoding;
+use crate::transport::messages::MiddlewareSender;
+use crate::transport::protocol::PROTOCOL_VERSION;
 use crate::vv::communication::handshake;
 use crate::vv::structs::messages::StreamMsg;
-use bincode::{serialize_into, serialized_size};
-use crossbeam::crossbeam_channel::RecvTimeoutError;
-use crossbeam::Receiver;
 use std::io::BufWriter;
-use std::net::TcpStream;
-use std::sync::{Arc, Barrier};
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "tls")]
+use crate::security::tls;
+
+#[cfg(feature = "noise")]
+use crate::security::noise::NoiseSession;
 
 /**
  * Starts a Sender thread that sends messages to a peer.
  *
  * # Arguments
  *
- * `stream` - TCP stream between the peers.
+ * `stream` - Stream between the peers.
  *
  * `middleware_channel` - Channel from the the Middleware to the Sender.
  *
  * `local_id` - Local peer's globally unique id.
  *
  * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics shared with the Client.
  */
 pub fn start(
-    stream: TcpStream,
-    middleware_channel: Receiver<(Arc<Barrier>, Arc<Vec<u8>>)>,
+    mut stream: PeerStream,
+    middleware_channel: Receiver<MiddlewareSender>,
     local_id: usize,
     configuration: Arc<Configuration>,
+    metrics: Arc<MiddlewareMetrics>,
 ) {
+    apply_thread_settings(&configuration.threads.sender);
+
     //Starting handshake protocol
-    handshake::send_handshake(&stream, local_id);
+    handshake::send_handshake(&mut stream, local_id, configuration.wire_encoding);
 
     //Receiving the id from the peer
-    let peer_id = handshake::finish_protocol(&stream);
+    let (peer_id, version) = handshake::finish_protocol(&mut stream);
+
+    //The peer runs an older crate version until the group is upgraded
+    if version < PROTOCOL_VERSION {
+        println!(
+            "WARN: Peer {} speaks protocol version {}, older than {}",
+            peer_id, version, PROTOCOL_VERSION
+        );
+    }
+
+    //Establishing the session keys of the connection
+    #[cfg(feature = "noise")]
+    let noise_session = NoiseSession::initiate(&mut stream, peer_id, &configuration)
+        .expect("ERROR: Failed the Noise handshake with the peer");
+
+    //Securing the connection with mutual TLS, if set
+    #[cfg(feature = "tls")]
+    let stream = tls::connect(stream, peer_id, &configuration)
+        .expect("ERROR: Failed the TLS handshake with the peer");
+
+    run(
+        stream,
+        middleware_channel,
+        local_id,
+        peer_id,
+        configuration,
+        metrics,
+        #[cfg(feature = "noise")]
+        noise_session,
+    );
+}
+
+/**
+ * Starts a Sender thread that sends messages to a peer over a connection
+ * shared with the Reader thread, whose handshake was already exchanged.
+ *
+ * # Arguments
+ *
+ * `stream` - Stream between the peers.
+ *
+ * `middleware_channel` - Channel from the the Middleware to the Sender.
+ *
+ * `local_id` - Local peer's globally unique id.
+ *
+ * `peer_id` - Other peer's globally unique id.
+ *
+ * `configuration` - Middleware's configuration file.
+ *
+ * `metrics` - Metrics shared with the Client.
+ */
+pub fn start_shared(
+    stream: PeerStream,
+    middleware_channel: Receiver<MiddlewareSender>,
+    local_id: usize,
+    peer_id: usize,
+    configuration: Arc<Configuration>,
+    metrics: Arc<MiddlewareMetrics>,
+) {
+    apply_thread_settings(&configuration.threads.sender);
+
+    run(
+        stream,
+        middleware_channel,
+        local_id,
+        peer_id,
+        configuration,
+        metrics,
+        #[cfg(feature = "noise")]
+        None,
+    );
+}
+
+/**
+ * Sends the messages from the Middleware to a peer once the connection is set up.
+ */
+fn run(
+    stream: PeerStream,
+    middleware_channel: Receiver<MiddlewareSender>,
+    local_id: usize,
+    peer_id: usize,
+    configuration: Arc<Configuration>,
+    metrics: Arc<MiddlewareMetrics>,
+    #[cfg(feature = "noise")] mut noise_session: Option<NoiseSession>,
+) {
+    //Batching of the messages sent to this peer
+    let batching = configuration.get_batching(peer_id);
 
-    let mut buffered_messages: usize = 0;
-    let mut buffered_bytes: u64 = 0;
+    //Pre-allocating the batch for the messages batched at once, if the load is known
+    let batch_capacity = if configuration.warm_up.message_rate > 0 {
+        batching.message_number
+    } else {
+        0
+    };
+
+    let mut batch = PendingBatch::new(
+        local_id,
+        configuration.epoch,
+        configuration.wire_encoding,
+        batch_capacity,
+    );
+    let mut arrival_rate = ArrivalRate::new();
+    let mut last_probe = Instant::now();
 
     //Flag that determines if the thread is in the new messages period
     //True  - NEW MESSAGES timeout
@@ -44,28 +159,77 @@ pub fn start(
     let mut sender_timeout_flag: bool = true;
     let mut timeout: Duration = configuration.get_stream_sender_timeout();
 
-    let mut stream = BufWriter::new(stream);
+    //Counting the bytes written to the peer
+    let mut stream = BufWriter::new(MeteredStream::new(stream, peer_id, metrics));
 
     loop {
         match middleware_channel.recv_timeout(timeout) {
-            Ok((message_barrier, msg)) => {
+            Ok(MiddlewareSender::Message {
+                barrier: message_barrier,
+                msg,
+                signature,
+            }) => {
                 if !sender_timeout_flag {
                     sender_timeout_flag = true;
                     timeout = configuration.get_stream_sender_timeout();
                 }
 
+                arrival_rate.update();
                 message_barrier.wait();
 
-                let stream_msg = StreamMsg::MSG {
-                    msg: (*msg).clone(),
-                    peer_id: local_id,
+                let msg = (*msg).clone();
+
+                //Encrypting the message with the connection's session keys
+                #[cfg(feature = "noise")]
+                let msg = match &mut noise_session {
+                    Some(session) => session
+                        .encrypt(&msg)
+                        .expect("ERROR: Failed to encrypt the message"),
+                    None => msg,
                 };
 
-                //Sending the message type and message payload as a single array of bytes
-                match serialize_into::<_, StreamMsg>(&mut stream, &stream_msg) {
+                //Buffering the message until the batch is written to the stream as a single frame
+                batch.push(BatchedMessage {
+                    msg,
+                    signature: signature.map(|signature| (*signature).clone()),
+                });
+            }
+            Ok(MiddlewareSender::Flush) => {
+                flush_buffer::<StreamMsg, _>(&mut stream, &mut batch);
+            }
+            Ok(MiddlewareSender::Pong { timestamp }) => {
+                let stream_msg = StreamMsg::PONG { timestamp };
+
+                //Answering the probe without waiting for the batching thresholds
+                match encoding::serialize_into(
+                    configuration.wire_encoding,
+                    &mut stream,
+                    &stream_msg,
+                ) {
+                    Ok(_) => {
+                        flush_buffer::<StreamMsg, _>(&mut stream, &mut batch);
+                    }
+                    Err(_) => {
+                        println!(
+                            "WARN: Stream was closed between {} and {}",
+                            local_id, peer_id
+                        );
+                        break;
+                    }
+                }
+            }
+            //Catching up is only supported by GRAPH, whose messages keep their sender's dot
+            Ok(MiddlewareSender::Receipt { counter }) => {
+                let stream_msg = StreamMsg::RECEIPT { counter };
+
+                //Receipts are already coalesced by the middleware, so they're written right away
+                match encoding::serialize_into(
+                    configuration.wire_encoding,
+                    &mut stream,
+                    &stream_msg,
+                ) {
                     Ok(_) => {
-                        buffered_messages += 1;
-                        buffered_bytes += serialized_size::<StreamMsg>(&stream_msg).unwrap();
+                        flush_buffer::<StreamMsg, _>(&mut stream, &mut batch);
                     }
                     Err(_) => {
                         println!(
@@ -76,41 +240,72 @@ pub fn start(
                     }
                 }
             }
+            Ok(MiddlewareSender::CatchUp { .. })
+            | Ok(MiddlewareSender::CaughtUp { .. })
+            | Ok(MiddlewareSender::Vector { .. }) => {}
             Err(e) => {
                 match e {
                     RecvTimeoutError::Disconnected => {
+                        //Writing the buffered messages before closing the connection
+                        let _ = write_batch::<StreamMsg, _>(&mut stream, &mut batch);
+
                         //Creating and serializing CLOSE message
                         let stream_msg = StreamMsg::CLOSE;
 
-                        match serialize_into::<_, StreamMsg>(&mut stream, &stream_msg) {
-                            Ok(_) => {}
-                            Err(_) => {}
-                        }
+                        let _ = encoding::serialize_into(
+                            configuration.wire_encoding,
+                            &mut stream,
+                            &stream_msg,
+                        );
 
                         break;
                     }
                     _ => {}
                 }
 
-                check_buffer_flush(
+                check_buffer_flush::<StreamMsg, _>(
                     &mut sender_timeout_flag,
                     &mut stream,
-                    &mut buffered_messages,
-                    &mut buffered_bytes,
+                    &mut batch,
                     &mut timeout,
-                    &configuration,
+                    batching,
+                    &arrival_rate,
                     true,
                 );
             }
         }
-        check_buffer_flush(
+        check_buffer_flush::<StreamMsg, _>(
             &mut sender_timeout_flag,
             &mut stream,
-            &mut buffered_messages,
-            &mut buffered_bytes,
+            &mut batch,
             &mut timeout,
-            &configuration,
+            batching,
+            &arrival_rate,
             false,
         );
+
+        //Probing the peer to measure the round trip time
+        if configuration.probe_interval > 0
+            && last_probe.elapsed() >= configuration.get_probe_interval()
+        {
+            let stream_msg = StreamMsg::PING {
+                timestamp: probe_timestamp(),
+            };
+
+            match encoding::serialize_into(configuration.wire_encoding, &mut stream, &stream_msg) {
+                Ok(_) => {
+                    flush_buffer::<StreamMsg, _>(&mut stream, &mut batch);
+                }
+                Err(_) => {
+                    println!(
+                        "WARN: Stream was closed between {} and {}",
+                        local_id, peer_id
+                    );
+                    break;
+                }
+            }
+
+            last_probe = Instant::now();
+        }
     }
 }
@@ -1,10 +1,42 @@
 use super::version_vector::VV;
-use crate::configuration::middleware_configuration::Configuration;
+use crate::broadcast::hooks::Hooks;
+use crate::broadcast::inbound::{self, InboundQueues};
+use crate::broadcast::polling::BusyPoll;
+use crate::broadcast::receipts::DeliveryReceipts;
+use crate::channel::{Receiver, Sender};
+use crate::configuration::middleware_configuration::{
+    Configuration, DroppedClientPolicy, WireEncoding,
+};
+use crate::metrics::middleware_metrics::MiddlewareMetrics;
+use crate::platform::threads::apply_thread_settings;
+use crate::transport::messages::MiddlewareSender;
+use crate::transport::{checksum, encoding};
+use crate::vv::structs::frontier::SharedFrontier;
 use crate::vv::structs::messages::{ClientPeerMiddleware, Message, MiddlewareClient};
-use crate::vv::structs::version_vector::VersionVector;
-use bincode::serialize;
-use crossbeam::{Receiver, Sender};
+use std::collections::{HashMap, VecDeque};
+use std::mem;
+use std::net::{Shutdown, TcpStream};
 use std::sync::{Arc, Barrier};
+use std::time::Instant;
+
+#[cfg(feature = "signing")]
+use crate::security::message_signing::MessageSigner;
+#[cfg(feature = "encryption")]
+use crate::security::payload_encryption::PayloadCipher;
+
+/**
+ * Configuration and handles shared between the Client and the Middleware thread.
+ */
+pub struct MiddlewareContext {
+    ///Middleware's configuration file
+    pub configuration: Arc<Configuration>,
+    ///Metrics shared with the Client
+    pub metrics: Arc<MiddlewareMetrics>,
+    ///Callbacks fired when messages are sent, received, delivered and stable
+    pub hooks: Arc<dyn Hooks>,
+    ///Delivered and stable frontier shared with the Client
+    pub frontier: Arc<SharedFrontier>,
+}
 
 /**
  * Starts the Middleware thread that receives messages from the Client to
@@ -21,52 +53,384 @@ use std::sync::{Arc, Barrier};
  *
  * `client` - Channel where the middleware will send delivered/stable messages to the Client.
  *
+ * `stable_client` - Channel where the middleware will send stable messages to the Client.
+ *
  * `peer_channels` - Channels to the Sender threads to send broadcast messages.
  *
- * `configuration` - Middleware's configuration file.
+ * `context` - Configuration and handles shared with the Client.
  */
 pub fn start(
     local_id: usize,
     peer_addresses: Vec<String>,
     receive_channel: Receiver<ClientPeerMiddleware>,
     client: Sender<MiddlewareClient>,
-    peer_channels: Vec<Sender<(Arc<Barrier>, Arc<Vec<u8>>)>>,
-    configuration: Arc<Configuration>,
+    stable_client: Sender<MiddlewareClient>,
+    mut peer_channels: Vec<Sender<MiddlewareSender>>,
+    context: MiddlewareContext,
 ) {
+    let MiddlewareContext {
+        configuration,
+        metrics,
+        hooks,
+        frontier,
+    } = context;
+
+    apply_thread_settings(&configuration.threads.middleware);
+
+    //Ids of the peers connected to each channel in peer_channels
+    let mut channel_peer_ids: Vec<usize> = (0..peer_channels.len())
+        .map(|i| if i < local_id { i } else { i + 1 })
+        .collect();
+
+    //Streams read by the Reader threads, used to close the connections
+    let mut reader_streams: HashMap<usize, TcpStream> = HashMap::new();
+
+    #[cfg(feature = "encryption")]
+    let cipher = PayloadCipher::from_configuration(&configuration);
+
+    #[cfg(feature = "signing")]
+    let signer = MessageSigner::from_configuration(&configuration);
+
     let mut vv = VV::new(
         peer_addresses.len() + 1,
         local_id,
         client.clone(),
+        stable_client,
         Arc::clone(&configuration),
+        hooks,
+        frontier,
     );
 
+    //Messages from the Client waiting for the delivery of their dependencies
+    let mut pending: VecDeque<Message> = VecDeque::new();
+
+    //Messages received from each peer, handled in a round-robin order
+    let mut inbound = if configuration.fair_inbound {
+        Some(InboundQueues::new(peer_addresses.len() + 1))
+    } else {
+        None
+    };
+
+    //Spins on the channel before parking, if busy polling is enabled
+    let mut poller = BusyPoll::from_configuration(&configuration);
+
+    //Receipts of the delivered messages, if they are sent back to their senders
+    let mut receipts =
+        DeliveryReceipts::from_configuration(&configuration, peer_addresses.len() + 1);
+
+    //Peers that closed their connections
+    let mut closed = vec![false; peer_addresses.len() + 1];
+
+    //Instant when the shutdown ends, once the Client ended the middleware
+    let mut draining: Option<Instant> = None;
+
     loop {
-        match receive_channel.recv() {
-            Ok(ClientPeerMiddleware::CLIENT {
-                msg_id,
-                payload,
-                version_vector,
-            }) => {
-                handle_message_from_client(
+        //Delivering the next chunk of a chain of deliveries unlocked by a received
+        //message, between the handling of the other messages
+        if vv.has_ready() {
+            vv.deliver_ready();
+
+            if !pending.is_empty() {
+                send_ready_messages(
                     &mut vv,
+                    &mut pending,
+                    &peer_channels,
+                    &channel_peer_ids,
+                    &configuration,
+                    #[cfg(feature = "encryption")]
+                    &cipher,
+                    #[cfg(feature = "signing")]
+                    &signer,
+                );
+            }
+        }
+
+        //Only waiting for a message once the chain was delivered
+        let has_messages = !receive_channel.is_empty()
+            || match &inbound {
+                Some(queues) => !queues.is_empty(),
+                None => false,
+            };
+
+        if !vv.has_ready() || has_messages {
+            let next_message =
+                inbound::next_message(&receive_channel, &mut inbound, &mut poller, |message| {
+                    match message {
+                        ClientPeerMiddleware::PEER { peer_id, .. } => Some(*peer_id),
+                        _ => None,
+                    }
+                });
+
+            match next_message {
+                Ok(ClientPeerMiddleware::CLIENT {
                     msg_id,
                     payload,
                     version_vector,
-                    &peer_channels,
-                );
+                    trace_id,
+                    headers,
+                    recipients,
+                }) => {
+                    let mut message = Message::new(msg_id, payload, version_vector, trace_id);
+                    message.headers = headers;
+                    message.recipients = recipients;
+                    pending.push_back(message);
+
+                    send_ready_messages(
+                        &mut vv,
+                        &mut pending,
+                        &peer_channels,
+                        &channel_peer_ids,
+                        &configuration,
+                        #[cfg(feature = "encryption")]
+                        &cipher,
+                        #[cfg(feature = "signing")]
+                        &signer,
+                    );
+                }
+                Ok(ClientPeerMiddleware::PEER { message, peer_id }) => {
+                    vv.receive(peer_id, message);
+
+                    if !pending.is_empty() {
+                        send_ready_messages(
+                            &mut vv,
+                            &mut pending,
+                            &peer_channels,
+                            &channel_peer_ids,
+                            &configuration,
+                            #[cfg(feature = "encryption")]
+                            &cipher,
+                            #[cfg(feature = "signing")]
+                            &signer,
+                        );
+                    }
+                }
+                Ok(ClientPeerMiddleware::SETUP) => {}
+                Ok(ClientPeerMiddleware::FLUSH) => {
+                    handle_flush_from_client(&peer_channels);
+                }
+                Ok(ClientPeerMiddleware::PAUSE) => {
+                    vv.set_paused(true);
+                }
+                Ok(ClientPeerMiddleware::RESUME) => {
+                    vv.set_paused(false);
+                }
+                Ok(ClientPeerMiddleware::ACK {
+                    sender_id,
+                    message_id,
+                }) => {
+                    vv.ack(sender_id, message_id);
+                }
+                Ok(ClientPeerMiddleware::REPLAY) => {
+                    vv.replay_unacked();
+                }
+                Ok(ClientPeerMiddleware::FILTER { filter }) => {
+                    vv.set_filter(filter);
+                }
+                Ok(ClientPeerMiddleware::SNAPSHOT) => {
+                    handle_flush_from_client(&peer_channels);
+
+                    let cut = MiddlewareClient::SNAPSHOT { cut: vv.V.clone() };
+                    vv.send_to_client(cut);
+                }
+                Ok(ClientPeerMiddleware::DUMP { reply }) => match reply.send(vv.event_timestamps())
+                {
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!(
+                            "ERROR: Couldn't send the timestamps to the Client\n\t- {}",
+                            e
+                        );
+                    }
+                },
+                Ok(ClientPeerMiddleware::EXPLAIN {
+                    sender_id,
+                    message_id,
+                    reply,
+                }) => match reply.send(vv.explain(sender_id, message_id)) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!(
+                            "ERROR: Couldn't send the explanation to the Client\n\t- {}",
+                            e
+                        );
+                    }
+                },
+                Ok(ClientPeerMiddleware::PING { peer_id, timestamp }) => {
+                    handle_probe_from_peer(peer_id, timestamp, &peer_channels, &channel_peer_ids);
+                }
+                Ok(ClientPeerMiddleware::CONNECTION { peer_id, stream }) => {
+                    reader_streams.insert(peer_id, stream);
+                }
+                Ok(ClientPeerMiddleware::EVICT { peer_id }) => {
+                    handle_peer_eviction(
+                        &mut vv,
+                        peer_id,
+                        local_id,
+                        peer_addresses.len() + 1,
+                        &mut peer_channels,
+                        &mut channel_peer_ids,
+                        &mut reader_streams,
+                    );
+
+                    if peer_id < closed.len() {
+                        closed[peer_id] = true;
+                    }
+
+                    //Dependencies on the evicted peer may no longer be delivered
+                    if !pending.is_empty() {
+                        send_ready_messages(
+                            &mut vv,
+                            &mut pending,
+                            &peer_channels,
+                            &channel_peer_ids,
+                            &configuration,
+                            #[cfg(feature = "encryption")]
+                            &cipher,
+                            #[cfg(feature = "signing")]
+                            &signer,
+                        );
+                    }
+                }
+                Ok(ClientPeerMiddleware::CLOSED { peer_id }) => {
+                    if peer_id < closed.len() {
+                        closed[peer_id] = true;
+                    }
+                }
+                Ok(ClientPeerMiddleware::END) => {
+                    if configuration.shutdown_grace_period == 0 {
+                        publish_metrics(&metrics, &vv);
+                        handle_finished_setup(&client);
+                        break;
+                    }
+
+                    //Closing the connections to the peers, which are still read until
+                    //every peer closes its connections or the grace period ends
+                    peer_channels.clear();
+                    channel_peer_ids.clear();
+
+                    draining = Some(Instant::now() + configuration.get_shutdown_grace_period());
+                    poller.set_deadline(draining);
+                }
+                Err(_) => {
+                    //The grace period ended while waiting for a message
+                    if draining.is_some() {
+                        publish_metrics(&metrics, &vv);
+                        handle_finished_setup(&client);
+                    }
+
+                    break;
+                }
             }
-            Ok(ClientPeerMiddleware::PEER { message, peer_id }) => {
-                vv.receive(peer_id, message);
+        }
+
+        match &mut receipts {
+            Some(receipts) if receipts.is_due(receive_channel.is_empty()) => {
+                receipts.send(&vv.V.0, &peer_channels, &channel_peer_ids);
             }
-            Ok(ClientPeerMiddleware::SETUP) => {}
-            Ok(ClientPeerMiddleware::END) => {
-                handle_finished_setup(&client);
+            _ => {}
+        }
+
+        vv.notify_stable_frontier(receive_channel.is_empty());
+
+        //Ending the middleware once the Client dropped its receiver, if set by the policy
+        if vv.is_client_dropped()
+            && configuration.dropped_client == DroppedClientPolicy::Shutdown
+            && draining.is_none()
+        {
+            if configuration.shutdown_grace_period == 0 {
+                publish_metrics(&metrics, &vv);
                 break;
             }
-            Err(_) => {
+
+            peer_channels.clear();
+            channel_peer_ids.clear();
+
+            draining = Some(Instant::now() + configuration.get_shutdown_grace_period());
+            poller.set_deadline(draining);
+        }
+
+        publish_metrics(&metrics, &vv);
+
+        //Closed connections are handled before the peers' messages queued for fairness
+        match draining {
+            Some(deadline)
+                if Instant::now() >= deadline
+                    || (is_drained(&closed, local_id)
+                        && inbound.as_ref().map_or(true, |queues| queues.is_empty())) =>
+            {
+                handle_finished_setup(&client);
                 break;
             }
+            _ => {}
+        }
+    }
+}
+
+/**
+ * Records the current state of the middleware in the metrics shared with the
+ * Client, which are final once the middleware ended.
+ *
+ * # Arguments
+ *
+ * `metrics` - Metrics shared with the Client.
+ *
+ * `vv` - Causal delivery state.
+ */
+fn publish_metrics(metrics: &MiddlewareMetrics, vv: &VV) {
+    metrics.update_watermarks(&vv.R.0, &vv.V.0);
+    metrics.update_counts(vv.message_counts());
+    metrics.update_retention(vv.retention_stats());
+}
+
+/**
+ * Checks if every other peer closed its connections, so no more messages
+ * are received while shutting down.
+ *
+ * # Arguments
+ *
+ * `closed` - Peers that closed their connections.
+ *
+ * `local_id` - Local peer's globally unique id.
+ */
+fn is_drained(closed: &[bool], local_id: usize) -> bool {
+    closed
+        .iter()
+        .enumerate()
+        .all(|(peer_id, &closed)| closed || peer_id == local_id)
+}
+
+/**
+ * Broadcasts the messages from the Client whose dependencies were delivered,
+ * in the order they were sent. A message waiting for its dependencies holds
+ * the messages sent after it, so the local messages keep their order.
+ */
+fn send_ready_messages(
+    vv: &mut VV,
+    pending: &mut VecDeque<Message>,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &Vec<usize>,
+    configuration: &Configuration,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+    #[cfg(feature = "signing")] signer: &Option<MessageSigner>,
+) {
+    while let Some(message) = pending.front() {
+        if !vv.is_delivered(&message.version_vector) {
+            break;
         }
+
+        let message = pending.pop_front().unwrap();
+
+        handle_message_from_client(
+            vv,
+            message,
+            channels,
+            channel_peer_ids,
+            configuration,
+            #[cfg(feature = "encryption")]
+            cipher,
+            #[cfg(feature = "signing")]
+            signer,
+        );
     }
 }
 
@@ -76,35 +440,279 @@ pub fn start(
  */
 fn handle_message_from_client(
     vv: &mut VV,
-    msg_id: usize,
-    payload: Vec<u8>,
-    version_vector: VersionVector,
-    channels: &Vec<Sender<(Arc<Barrier>, Arc<Vec<u8>>)>>,
+    message: Message,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &Vec<usize>,
+    configuration: &Configuration,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+    #[cfg(feature = "signing")] signer: &Option<MessageSigner>,
 ) {
-    let message = Message::new(msg_id, payload, version_vector);
-    vv.dequeue(message.clone());
+    //Setting the history digests of the message's version vector
+    #[cfg(feature = "bcb")]
+    let message = vv.attach_history(message);
+
+    //Serializing the message for the peers before it's moved into the engine,
+    //so the local delivery neither serializes nor copies it
+    let mut message = message;
+    let message_id = message.version_vector[message.id];
 
-    //Creating a new struct Message
-    //let message = Message::new(msg_id, payload, version_vector);
-    //Serializing the struct with the new message
-    let encoded_message: Vec<u8> =
-        serialize(&message).expect("ERROR: Couldn't serialize the CLIENT message");
+    //Encrypted payloads are already authenticated by the cipher, and the checksum
+    //of their plaintext would be sent in the clear
+    #[cfg(feature = "encryption")]
+    let payload_checksums = configuration.payload_checksums && cipher.is_none();
+    #[cfg(not(feature = "encryption"))]
+    let payload_checksums = configuration.payload_checksums;
+
+    if payload_checksums {
+        message.checksum = Some(checksum::checksum(&message.payload));
+    }
+
+    let encoded_message = serialize_for_peers(
+        &mut message,
+        configuration.wire_encoding,
+        #[cfg(feature = "encryption")]
+        cipher,
+    );
+
+    //Serializing the message without its payload for the peers outside its recipients,
+    //unless the payload is needed to verify the history digests
+    let skipped_message = match &message.recipients {
+        Some(recipients) if !configuration.byzantine_tolerance => Some((
+            recipients.clone(),
+            serialize_without_payload(
+                &mut message,
+                configuration.wire_encoding,
+                #[cfg(feature = "encryption")]
+                cipher,
+            ),
+        )),
+        _ => None,
+    };
+
+    //Reporting the messages that couldn't be serialized to the Client. Their dot is
+    //already in the context of the following local messages, so an empty message
+    //that no peer delivers is sent in their place and the peers don't wait for it
+    let (encoded_message, skipped_message) = match (encoded_message, skipped_message) {
+        (Ok(encoded_message), None) => (encoded_message, None),
+        (Ok(encoded_message), Some((recipients, Ok(skipped_message)))) => {
+            (encoded_message, Some((recipients, skipped_message)))
+        }
+        (Err(e), _) | (_, Some((_, Err(e)))) => {
+            println!("ERROR: Couldn't serialize the CLIENT message\n\t- {}", e);
+            vv.send_failed(
+                message_id,
+                None,
+                format!("Couldn't serialize the message - {}", e),
+            );
+
+            message.payload = Vec::new();
+            message.headers = None;
+            message.recipients = Some(Vec::new());
+            message.checksum = None;
+
+            match serialize_for_peers(
+                &mut message,
+                configuration.wire_encoding,
+                #[cfg(feature = "encryption")]
+                cipher,
+            ) {
+                Ok(encoded_message) => (encoded_message, None),
+                Err(e) => {
+                    println!(
+                        "ERROR: Couldn't serialize the empty CLIENT message\n\t- {}",
+                        e
+                    );
+                    vv.dequeue(message);
+                    return;
+                }
+            }
+        }
+    };
+
+    //Calling the dequeue function
+    vv.dequeue(message);
+
+    //Signing the serialized message so the peers can verify its sender
+    #[cfg(feature = "signing")]
+    let sign = |encoded_message: &[u8]| {
+        signer
+            .as_ref()
+            .map(|signer| Arc::new(signer.sign(encoded_message)))
+    };
+    #[cfg(not(feature = "signing"))]
+    let sign = |_: &[u8]| None;
+
+    let signature = sign(&encoded_message);
+    let skipped_message = skipped_message.map(|(recipients, encoded_message)| {
+        let signature = sign(&encoded_message);
+        (recipients, Arc::new(encoded_message), signature)
+    });
 
     //Creating a new arc with the serialized message
     let arc_msg = Arc::new(encoded_message);
     let stream_sender_barrier = Arc::new(Barrier::new(channels.len()));
 
     //Writing the message arc into the channels connected to each peer stream sender thread
-    for channel in channels {
-        match &channel.send((Arc::clone(&stream_sender_barrier), Arc::clone(&arc_msg))) {
+    for (channel, peer_id) in channels.iter().zip(channel_peer_ids) {
+        let (msg, signature) = match &skipped_message {
+            Some((recipients, skipped_msg, skipped_signature)) if !recipients.contains(peer_id) => {
+                (Arc::clone(skipped_msg), skipped_signature.clone())
+            }
+            _ => (Arc::clone(&arc_msg), signature.clone()),
+        };
+
+        let sender_message = MiddlewareSender::Message {
+            barrier: Arc::clone(&stream_sender_barrier),
+            msg,
+            signature,
+        };
+
+        match &channel.send(sender_message) {
             Ok(_) => {}
             Err(e) => {
                 println!("ERROR: Could not send message to sender threads\n\t- {}", e);
+                vv.send_failed(
+                    message_id,
+                    Some(*peer_id),
+                    String::from("The Sender thread of the peer terminated"),
+                );
             }
         }
     }
 }
 
+/**
+ * Serializes a message from the client once for every peer. If the group key is set, the payload is swapped
+ * for its ciphertext while serializing, so the message isn't copied and the
+ * local delivery still uses the plaintext.
+ */
+fn serialize_for_peers(
+    message: &mut Message,
+    encoding: WireEncoding,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+) -> bincode::Result<Vec<u8>> {
+    #[cfg(feature = "encryption")]
+    let plaintext = match cipher {
+        Some(cipher) => {
+            let ciphertext = cipher.encrypt(&message.payload);
+            Some(mem::replace(&mut message.payload, ciphertext))
+        }
+        None => None,
+    };
+
+    let encoded_message = encoding::serialize(encoding, &*message);
+
+    #[cfg(feature = "encryption")]
+    if let Some(plaintext) = plaintext {
+        message.payload = plaintext;
+    }
+
+    encoded_message
+}
+
+/**
+ * Serializes a message from the client without its payload, for the peers that
+ * aren't recipients of a message sent to a subset of the group. The empty payload
+ * is still encrypted if the group key is set, so the peers can decrypt it.
+ */
+fn serialize_without_payload(
+    message: &mut Message,
+    encoding: WireEncoding,
+    #[cfg(feature = "encryption")] cipher: &Option<PayloadCipher>,
+) -> bincode::Result<Vec<u8>> {
+    #[cfg(feature = "encryption")]
+    let empty_payload = match cipher {
+        Some(cipher) => cipher.encrypt(&[]),
+        None => Vec::new(),
+    };
+    #[cfg(not(feature = "encryption"))]
+    let empty_payload = Vec::new();
+
+    let payload = mem::replace(&mut message.payload, empty_payload);
+
+    let encoded_message = encoding::serialize(encoding, &*message);
+
+    message.payload = payload;
+    encoded_message
+}
+
+/**
+ * Handles a flush request from the client by signalling every
+ * Sender thread to write its buffered messages into the stream.
+ */
+fn handle_flush_from_client(channels: &Vec<Sender<MiddlewareSender>>) {
+    for channel in channels {
+        match channel.send(MiddlewareSender::Flush) {
+            Ok(_) => {}
+            Err(e) => {
+                println!("ERROR: Could not send flush to sender threads\n\t- {}", e);
+            }
+        }
+    }
+}
+
+/**
+ * Handles a probe from a peer by writing the response in the
+ * channel connected to the peer's Sender thread.
+ */
+fn handle_probe_from_peer(
+    peer_id: usize,
+    timestamp: u64,
+    channels: &[Sender<MiddlewareSender>],
+    channel_peer_ids: &[usize],
+) {
+    if let Some(index) = channel_peer_ids.iter().position(|&id| id == peer_id) {
+        match channels[index].send(MiddlewareSender::Pong { timestamp }) {
+            Ok(_) => {}
+            Err(e) => {
+                println!(
+                    "ERROR: Could not send probe response to sender thread\n\t- {}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+/**
+ * Handles the eviction of a peer requested by the client. The channel to the peer's
+ * Sender thread is dropped, which makes it close the connection, and the stream read
+ * by the peer's Reader thread is shut down.
+ */
+fn handle_peer_eviction(
+    vv: &mut VV,
+    peer_id: usize,
+    local_id: usize,
+    peer_number: usize,
+    channels: &mut Vec<Sender<MiddlewareSender>>,
+    channel_peer_ids: &mut Vec<usize>,
+    reader_streams: &mut HashMap<usize, TcpStream>,
+) {
+    if peer_id == local_id || peer_id >= peer_number {
+        println!("ERROR: Peer {} can't be evicted by {}", peer_id, local_id);
+        return;
+    }
+
+    if let Some(index) = channel_peer_ids.iter().position(|&id| id == peer_id) {
+        channel_peer_ids.remove(index);
+        channels.remove(index);
+    }
+
+    if let Some(stream) = reader_streams.remove(&peer_id) {
+        match stream.shutdown(Shutdown::Both) {
+            Ok(_) => {}
+            Err(e) => {
+                println!(
+                    "WARN: Failed to close the connection with {}\n\t- {}",
+                    peer_id, e
+                );
+            }
+        }
+    }
+
+    vv.evict(peer_id);
+}
+
 /**
  * Handles the setup end from the transport layer. The Middleware informs
  * the Client about this by sending a message.
@@ -1,10 +1,21 @@
+use crate::broadcast::acks::PendingAcks;
+use crate::broadcast::broadcast_trait::{
+    DeliveryFilter, Explanation, Fault, MessageTimestamps, PendingDependency, SendFailure,
+};
+use crate::broadcast::hooks::Hooks;
+use crate::broadcast::retention::RetentionLog;
+use crate::channel::Sender;
+use crate::clock::dot::{Dot, DotMap};
+use crate::clock::version_vector::VersionVector;
 use crate::configuration::middleware_configuration::Configuration;
-use crate::graph::middleware::dot::Dot;
+use crate::metrics::middleware_metrics::{MessageCounts, RetentionMetrics};
+#[cfg(feature = "bcb")]
+use crate::security::history::{HistoryDigest, HistoryDigests};
+use crate::transport::checksum;
+use crate::vv::structs::frontier::SharedFrontier;
 use crate::vv::structs::messages::{Message, MiddlewareClient};
-use crate::vv::structs::version_vector::VersionVector;
-use crossbeam::Sender;
-use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 
 /**
  * Struct for wrapping received messages waiting to be delivered.
@@ -15,6 +26,8 @@ pub struct QueueNode {
     pub j: usize,
     ///Payload
     pub message: Message,
+    ///Instant when the message was received
+    pub received_at: Instant,
 }
 
 /**
@@ -58,12 +71,38 @@ pub struct VV {
     pub M: Vec<VersionVector>,
     pub M_entry_row_num: VersionVector,
     pub SV: VersionVector,
-    pub SMap: HashMap<Dot, StableDot>,
+    pub SMap: DotMap<StableDot>,
+    pub evicted: Vec<bool>,
+    ///Ranges of counters skipped by the messages of each peer
+    pub gaps: Vec<Vec<(usize, usize)>>,
+    #[cfg(feature = "bcb")]
+    pub history: Option<HistoryDigests>,
     pub ctr: usize,
     pub peer_index: usize,
     pub client: Sender<MiddlewareClient>,
+    pub stable_client: Sender<MiddlewareClient>,
     pub configuration: Arc<Configuration>,
     pub peer_number: usize,
+    pub hooks: Arc<dyn Hooks>,
+    pub filter: Option<DeliveryFilter>,
+    pub frontier: Arc<SharedFrontier>,
+    pub retained: Option<RetentionLog<Message>>,
+    ///Set when a chunk of deliveries stopped before the end of the delivery queue
+    pub ready: bool,
+    ///Set while the Client paused the deliveries
+    pub paused: bool,
+    ///Set when the stable frontier advanced since it was last notified to the Client
+    pub frontier_advanced: bool,
+    ///Numbers of the sent, delivered and stable messages
+    pub counts: MessageCounts,
+    ///Instant when the stable frontier was last notified to the Client
+    pub frontier_notified_at: Instant,
+    ///Deliveries the Client didn't ack yet, with their sender ids, if delivery acks are enabled
+    pub acks: Option<PendingAcks<(usize, Message)>>,
+    ///Set once the Client dropped the receiver of the deliveries
+    pub client_dropped: bool,
+    ///Set once the Client dropped the receiver of the stable messages
+    pub stable_dropped: bool,
 }
 
 #[allow(non_snake_case)]
@@ -79,21 +118,40 @@ impl VV {
      *
      * `client` - Channel between the Middleware and the Peer that will be used to send delivered/stable messages to Peer.
      *
+     * `stable_client` - Channel used to send stable messages to Peer, which is `client` unless they are routed to their own channel.
+     *
      * `configuration` - Middleware's configuration file.
+     *
+     * `hooks` - Callbacks fired when messages are sent, received, delivered and stable.
+     *
+     * `frontier` - Delivered and stable frontier shared with the Client.
      */
     pub fn new(
         peer_number: usize,
         peer_index: usize,
         client: Sender<MiddlewareClient>,
+        stable_client: Sender<MiddlewareClient>,
         configuration: Arc<Configuration>,
+        hooks: Arc<dyn Hooks>,
+        frontier: Arc<SharedFrontier>,
     ) -> Self {
-        let DQ: Vec<QueueNode> = Vec::with_capacity(peer_number * 2);
+        //Pre-allocating the delivery queue for the messages expected until stability
+        let expected_messages = configuration.warm_up.expected_messages();
+        let DQ: Vec<QueueNode> = Vec::with_capacity(expected_messages.max(peer_number * 2));
         let mut M: Vec<VersionVector> = Vec::new();
 
         for _ in 0..peer_number {
             M.push(VersionVector::new(peer_number));
         }
 
+        let retained = if configuration.retention.enabled {
+            Some(RetentionLog::new(peer_number, &configuration.retention))
+        } else {
+            None
+        };
+
+        let acks = PendingAcks::from_configuration(&configuration);
+
         Self {
             V: VersionVector::new(peer_number),
             R: VersionVector::new(peer_number),
@@ -101,12 +159,33 @@ impl VV {
             M,
             M_entry_row_num: VersionVector::new(peer_number),
             SV: VersionVector::new(peer_number),
-            SMap: HashMap::new(),
+            SMap: DotMap::default(),
+            evicted: vec![false; peer_number],
+            gaps: vec![Vec::new(); peer_number],
+            #[cfg(feature = "bcb")]
+            history: if configuration.byzantine_tolerance {
+                Some(HistoryDigests::new())
+            } else {
+                None
+            },
             ctr: 0,
             peer_index,
             client,
+            stable_client,
             configuration,
             peer_number,
+            hooks,
+            filter: None,
+            frontier,
+            retained,
+            ready: false,
+            paused: false,
+            frontier_advanced: false,
+            counts: MessageCounts::default(),
+            frontier_notified_at: Instant::now(),
+            acks,
+            client_dropped: false,
+            stable_dropped: false,
         }
     }
 
@@ -119,13 +198,146 @@ impl VV {
      * `message` - Message received from the Client.
      */
     pub fn dequeue(&mut self, message: Message) {
+        self.hooks
+            .on_send(self.peer_index, message.id, message.payload.len());
+
         self.V[self.peer_index] += 1;
+        self.counts.sent += 1;
+        self.frontier
+            .update_delivered(self.peer_index, self.V[self.peer_index]);
+
+        if self.retained.is_some() {
+            self.retain(self.peer_index, message.clone(), None);
+        }
 
         if self.configuration.track_causal_stability {
             self.updatestability(self.peer_index, message);
         }
     }
 
+    /**
+     * Sets the history digests of a message sent by the Client and records
+     * its digest, in the Byzantine-tolerant mode. Must be called before the
+     * message is dequeued.
+     *
+     * # Arguments
+     *
+     * `message` - Message received from the Client.
+     */
+    #[cfg(feature = "bcb")]
+    pub fn attach_history(&mut self, mut message: Message) -> Message {
+        let j = self.peer_index;
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return message,
+        };
+
+        message.history_digests = Self::history_counters(j, &message.version_vector)
+            .map(|(id, counter)| match counter {
+                0 => [0; 32],
+                _ => history.get(id, counter).unwrap_or_default(),
+            })
+            .collect();
+
+        let counter = message.version_vector[j];
+        let dependencies = Self::history_dependencies(j, &message);
+        let digest = HistoryDigests::digest(j, counter, &dependencies, &message.payload);
+
+        match history.record(j, counter, digest) {
+            Ok(_) => {}
+            Err(fault) => {
+                println!(
+                    "ERROR: Local message conflicts with its history - {:?}",
+                    fault
+                );
+            }
+        }
+
+        message
+    }
+
+    /**
+     * Validates the history digests of a message received from a peer and records
+     * its digest, in the Byzantine-tolerant mode. If an equivocation is detected,
+     * the Client is notified with a fault and false is returned.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `message` - Message received from a peer in the group.
+     */
+    #[cfg(feature = "bcb")]
+    fn validate_history(&mut self, j: usize, message: &Message) -> bool {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return true,
+        };
+
+        let counter = message.version_vector[j];
+
+        let result = if message.history_digests.len() != message.version_vector.len() {
+            Err(Fault::MissingHistory { id: j, counter })
+        } else {
+            let dependencies = Self::history_dependencies(j, message);
+            let digest = HistoryDigests::digest(j, counter, &dependencies, &message.payload);
+
+            dependencies
+                .iter()
+                .try_for_each(|&(id, counter, digest)| history.expect(id, counter, digest))
+                .and_then(|_| history.record(j, counter, digest))
+        };
+
+        match result {
+            Ok(_) => true,
+            Err(fault) => {
+                self.send_to_client(MiddlewareClient::FAULT { fault });
+                false
+            }
+        }
+    }
+
+    /**
+     * Returns the counter of the last message of each peer that a message depends on.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `version_vector` - Message version vector
+     */
+    #[cfg(feature = "bcb")]
+    fn history_counters(
+        j: usize,
+        version_vector: &VersionVector,
+    ) -> impl Iterator<Item = (usize, usize)> + '_ {
+        version_vector
+            .iter()
+            .enumerate()
+            .map(move |(id, &counter)| match id == j {
+                true => (id, counter.saturating_sub(1)),
+                false => (id, counter),
+            })
+    }
+
+    /**
+     * Returns the dependencies of a message with their history digests.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `message` - Message with the history digests set
+     */
+    #[cfg(feature = "bcb")]
+    fn history_dependencies(j: usize, message: &Message) -> Vec<(usize, usize, HistoryDigest)> {
+        Self::history_counters(j, &message.version_vector)
+            .zip(message.history_digests.iter())
+            .filter(|((_, counter), _)| *counter > 0)
+            .map(|((id, counter), digest)| (id, counter, *digest))
+            .collect()
+    }
+
     /**
      * Handles a message received from a peer via broadcast.
      *
@@ -136,26 +348,287 @@ impl VV {
      * `message` - Message received from a peer in the group.
      */
     pub fn receive(&mut self, j: usize, message: Message) {
-        if self.R[j] < message.version_vector[j] {
-            self.R[j] += 1;
+        self.hooks.on_receive(j, message.id, message.payload.len());
 
-            if VersionVector::compare_version_vectors(j, &self.V, &message.version_vector) {
-                self.deliver_and_log_message(None, Some(message), Some(j));
+        //Discarding messages from ids outside the group or with
+        //version vectors of a group with a different size
+        if j >= self.peer_number || message.version_vector.len() != self.peer_number {
+            println!(
+                "ERROR: Discarded a message from {} with a version vector of length {}",
+                j,
+                message.version_vector.len()
+            );
+            return;
+        }
 
-                if self.DQ.len() > 0 {
-                    self.deliver();
-                }
-            } else {
-                let queue_node = QueueNode { j, message };
-                self.DQ.push(queue_node);
+        //Ignoring messages from evicted peers
+        if self.evicted[j] {
+            return;
+        }
+
+        //Discarding messages whose history doesn't match the received messages
+        #[cfg(feature = "bcb")]
+        {
+            if !self.validate_history(j, &message) {
+                return;
+            }
+        }
+
+        let counter = message.version_vector[j];
+
+        if self.R[j] < counter {
+            //The messages between the last received one and this one were skipped
+            if counter > self.R[j] + 1 {
+                self.report_gap(j, self.R[j] + 1, counter - 1);
+            }
+
+            self.R[j] = counter;
+            self.enqueue(j, message);
+        } else if self.fill_gap(j, counter) {
+            self.enqueue(j, message);
+        }
+    }
+
+    /**
+     * Delivers a received message if its dependencies were delivered,
+     * or adds it to the delivery queue otherwise.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `message` - Message received from a peer in the group.
+     */
+    fn enqueue(&mut self, j: usize, message: Message) {
+        if !self.paused
+            && VersionVector::compare_version_vectors(j, &self.V, &message.version_vector)
+        {
+            self.deliver_and_log_message(None, Some(message), Some(j));
+
+            if !self.DQ.is_empty() {
+                self.deliver();
+            }
+        } else {
+            let queue_node = QueueNode {
+                j,
+                message,
+                received_at: Instant::now(),
+            };
+            self.DQ.push(queue_node);
+
+            //Checking the delivery queue once the deliveries are resumed
+            if self.paused {
+                self.ready = true;
+            }
+        }
+    }
+
+    /**
+     * Records the range of counters skipped by the messages of a peer and reports
+     * it to the Client. The messages after the gap wait in the delivery queue
+     * until the missing ones are received.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `first` - Counter of the first skipped message
+     *
+     * `last` - Counter of the last skipped message
+     */
+    fn report_gap(&mut self, j: usize, first: usize, last: usize) {
+        self.gaps[j].push((first, last));
+
+        self.send_to_client(MiddlewareClient::FAULT {
+            fault: Fault::MissingRange { id: j, first, last },
+        });
+    }
+
+    /**
+     * Removes a counter from the gaps of a peer, returning if it was missing.
+     * A message received later with a missing counter, e.g. retransmitted by
+     * a resync, fills its gap.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `counter` - Counter of the received message
+     */
+    fn fill_gap(&mut self, j: usize, counter: usize) -> bool {
+        let gaps = &mut self.gaps[j];
+
+        let position = match gaps
+            .iter()
+            .position(|&(first, last)| first <= counter && counter <= last)
+        {
+            Some(position) => position,
+            None => return false,
+        };
+
+        let (first, last) = gaps.remove(position);
+
+        if first < counter {
+            gaps.push((first, counter - 1));
+        }
+
+        if counter < last {
+            gaps.push((counter + 1, last));
+        }
+
+        true
+    }
+
+    /**
+     * Returns true if the last chunk of deliveries stopped before the end of the
+     * delivery queue, so its messages may still be delivered.
+     */
+    pub fn has_ready(&self) -> bool {
+        self.ready && !self.paused
+    }
+
+    /**
+     * Pauses or resumes the deliveries to the Client. While paused, the received
+     * messages wait in the delivery queue.
+     *
+     * # Arguments
+     *
+     * `paused` - Whether the deliveries are paused
+     */
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /**
+     * Sends a message to the Client. Once the Client dropped its receiver, the
+     * message is dropped and the middleware follows the dropped client policy.
+     *
+     * # Arguments
+     *
+     * `message` - Message sent to the Client.
+     */
+    pub fn send_to_client(&mut self, message: MiddlewareClient) {
+        if self.client_dropped {
+            return;
+        }
+
+        if self.client.send(message).is_err() {
+            println!(
+                "WARN: The Client of {} dropped its receiver, applying the {:?} policy",
+                self.peer_index, self.configuration.dropped_client
+            );
+
+            self.client_dropped = true;
+        }
+    }
+
+    /**
+     * Sends a stable message to the Client in the channel of the stable messages.
+     *
+     * # Arguments
+     *
+     * `message` - Stable message sent to the Client.
+     */
+    fn send_stable(&mut self, message: MiddlewareClient) {
+        if self.stable_dropped {
+            return;
+        }
+
+        if self.stable_client.send(message).is_err() {
+            println!(
+                "WARN: The Client of {} dropped its stable receiver, applying the {:?} policy",
+                self.peer_index, self.configuration.dropped_client
+            );
+
+            self.stable_dropped = true;
+        }
+    }
+
+    /**
+     * Checks if the Client dropped the receiver of the deliveries or of the stable messages.
+     */
+    pub fn is_client_dropped(&self) -> bool {
+        self.client_dropped || self.stable_dropped
+    }
+
+    /**
+     * Marks a delivery as processed by the Client, so it's no longer sent again.
+     *
+     * # Arguments
+     *
+     * `sender_id` - Sender id of the delivered message.
+     *
+     * `message_id` - Message id of the delivered message.
+     */
+    pub fn ack(&mut self, sender_id: usize, message_id: usize) {
+        if let Some(acks) = &mut self.acks {
+            if !acks.ack(sender_id, message_id) {
+                println!(
+                    "ERROR: Acked a delivery that isn't pending - ({}, {})",
+                    sender_id, message_id
+                );
             }
         }
     }
 
+    /**
+     * Sends the deliveries the Client didn't ack again, in the order they were
+     * delivered, which are still pending until they are acked.
+     */
+    pub fn replay_unacked(&mut self) {
+        let unacked: Vec<(usize, Message)> = match &self.acks {
+            Some(acks) => acks.unacked().cloned().collect(),
+            None => return,
+        };
+
+        for (sender_id, message) in unacked {
+            let version_vector = message.version_vector.clone();
+
+            self.send_to_client(MiddlewareClient::DELIVER {
+                sender_id,
+                message,
+                version_vector,
+            });
+        }
+    }
+
+    /**
+     * Returns the numbers of the sent, delivered and stable messages, and of the
+     * received messages waiting in the delivery queue.
+     */
+    pub fn message_counts(&self) -> MessageCounts {
+        MessageCounts {
+            pending: self.DQ.len(),
+            ..self.counts
+        }
+    }
+
+    /**
+     * Delivers the next chunk of the messages in the delivery queue whose
+     * dependencies were delivered.
+     */
+    pub fn deliver_ready(&mut self) {
+        if self.ready && !self.paused {
+            self.deliver();
+        }
+    }
+
+    /**
+     * Delivers the messages in the delivery queue whose dependencies were delivered,
+     * up to `delivery_chunk_size` messages, so a long chain of deliveries doesn't
+     * hold back the Middleware thread.
+     */
     fn deliver(&mut self) {
         let mut delivered_index = 0;
         let mut received_index = 0;
 
+        let mut remaining = match self.configuration.delivery_chunk_size {
+            0 => usize::MAX,
+            chunk_size => chunk_size,
+        };
+
+        self.ready = false;
+
         loop {
             if delivered_index >= self.DQ.len() {
                 //Reached the end of the queue
@@ -174,6 +647,11 @@ impl VV {
                 } else {
                     break;
                 }
+            } else if remaining == 0 {
+                //Removing the delivered messages and keeping the rest for the next chunk
+                self.DQ.drain(received_index..delivered_index);
+                self.ready = true;
+                break;
             } else {
                 let queue_node = self.DQ[delivered_index].clone();
 
@@ -186,6 +664,7 @@ impl VV {
                     self.deliver_and_log_message(Some(delivered_index), None, None);
 
                     delivered_index += 1;
+                    remaining -= 1;
                 } else {
                     //Current message can't be delivered
                     //Copy value to "new" position and advance indexes
@@ -205,30 +684,134 @@ impl VV {
     ) {
         let message: Message;
         let sender_id: usize;
+        let received_at: Instant;
 
         if let Some(index) = message_index {
             message = self.DQ[index].message.clone();
             sender_id = self.DQ[index].j;
+            received_at = self.DQ[index].received_at;
         } else {
+            //Delivered as soon as it was received
             message = received_message.unwrap();
             sender_id = j.unwrap();
+            received_at = Instant::now();
+        }
+
+        //Payloads that don't match their sender's checksum aren't delivered
+        if self.is_corrupted(sender_id, &message) {
+            self.keep_corrupted(sender_id, &message);
+            return;
         }
 
         self.V[sender_id] += 1;
+        self.counts.delivered += 1;
+        self.frontier.update_delivered(sender_id, self.V[sender_id]);
+
+        self.hooks
+            .on_deliver(sender_id, message.id, message.payload.len());
 
-        let delivered_message = MiddlewareClient::DELIVER {
-            sender_id,
-            message: message.clone(),
-            version_vector: message.version_vector.clone(),
+        //Messages sent to a subset of the group are skipped by the other peers
+        let is_recipient = match &message.recipients {
+            Some(recipients) => recipients.contains(&self.peer_index),
+            None => true,
         };
 
-        self.client.send(delivered_message).unwrap();
+        let matches = is_recipient
+            && match &self.filter {
+                Some(filter) => filter(sender_id, message.id, &message.payload),
+                None => true,
+            };
+
+        //Filtered messages only carry the message id the Client needs
+        //to keep the version vector of its next sent message
+        let delivered_message = if matches {
+            MiddlewareClient::DELIVER {
+                sender_id,
+                message: message.clone(),
+                version_vector: message.version_vector.clone(),
+            }
+        } else {
+            MiddlewareClient::SKIPPED {
+                sender_id,
+                message_id: message.version_vector[sender_id],
+            }
+        };
+
+        self.send_to_client(delivered_message);
+
+        //Keeping a copy of the delivery until the Client acks it
+        match &mut self.acks {
+            Some(acks) if matches && !self.client_dropped => {
+                acks.push(
+                    sender_id,
+                    message.version_vector[sender_id],
+                    (sender_id, message.clone()),
+                );
+            }
+            _ => {}
+        }
+
+        if self.retained.is_some() {
+            self.retain(sender_id, message.clone(), Some(received_at));
+        }
 
         if self.configuration.track_causal_stability {
             self.updatestability(sender_id, message);
         }
     }
 
+    /**
+     * Checks if the payload of a message sent to the local peer doesn't match
+     * its sender's checksum.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `message` - Message whose dependencies were delivered.
+     */
+    fn is_corrupted(&self, j: usize, message: &Message) -> bool {
+        let is_recipient = match &message.recipients {
+            Some(recipients) => recipients.contains(&self.peer_index),
+            None => true,
+        };
+
+        match message.checksum {
+            Some(expected) => {
+                j != self.peer_index
+                    && is_recipient
+                    && checksum::checksum(&message.payload) != expected
+            }
+            None => false,
+        }
+    }
+
+    /**
+     * Drops a message whose payload doesn't match its checksum and keeps its
+     * counter missing, so the following messages of its sender wait in the
+     * delivery queue and a copy received later fills the gap.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `message` - Corrupted message.
+     */
+    fn keep_corrupted(&mut self, j: usize, message: &Message) {
+        let counter = message.version_vector[j];
+
+        println!(
+            "ERROR: {} dropped the corrupted payload of ({}, {}) until it's received again",
+            self.peer_index, j, counter
+        );
+
+        self.gaps[j].push((counter, counter));
+
+        self.send_to_client(MiddlewareClient::FAULT {
+            fault: Fault::CorruptedPayload { id: j, counter },
+        });
+    }
+
     fn updatestability(&mut self, j: usize, message: Message) {
         self.M[self.peer_index] = self.V.clone();
 
@@ -247,6 +830,188 @@ impl VV {
 
         self.SMap.insert(temp_dot, stable_dot);
 
+        self.updateSV(j);
+    }
+
+    /**
+     * Checks if every message in a version vector from the Client, other than the
+     * local ones, was delivered or was sent by an evicted peer, so the message
+     * with that version vector can be broadcast.
+     *
+     * # Arguments
+     *
+     * `version_vector` - Version vector of a message from the Client.
+     */
+    pub fn is_delivered(&self, version_vector: &VersionVector) -> bool {
+        version_vector
+            .iter()
+            .enumerate()
+            .all(|(i, &counter)| i == self.peer_index || counter <= self.V[i] || self.evicted[i])
+    }
+
+    /**
+     * Adds a delivered message to the retention log and removes the oldest
+     * messages that no longer need to be kept.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id
+     *
+     * `message` - Delivered or sent message.
+     *
+     * `received_at` - Instant when the message was received, `None` if it was sent by the local peer
+     */
+    fn retain(&mut self, j: usize, message: Message, received_at: Option<Instant>) {
+        let SV = &self.SV;
+        let track_causal_stability = self.configuration.track_causal_stability;
+
+        if let Some(retained) = &mut self.retained {
+            retained.push(j, self.V[j], message.payload.len(), received_at, message);
+            retained.prune(|id, counter| !track_causal_stability || counter <= SV[id]);
+        }
+    }
+
+    /**
+     * Explains why a message wasn't delivered yet from the delivery queue. A
+     * received message waits for the previous messages of its sender and for the
+     * messages of the other peers in its version vector that weren't delivered.
+     *
+     * # Arguments
+     *
+     * `sender_id` - Sender id of the explained message.
+     *
+     * `message_id` - Message id of the explained message.
+     */
+    pub fn explain(&self, sender_id: usize, message_id: usize) -> Explanation {
+        if sender_id >= self.peer_number {
+            return Explanation::NotReceived;
+        }
+
+        if message_id <= self.V[sender_id] {
+            return Explanation::Delivered;
+        }
+
+        if self.evicted[sender_id] {
+            return Explanation::Evicted;
+        }
+
+        let version_vector = match self.DQ.iter().find(|node| {
+            node.j == sender_id && node.message.version_vector[sender_id] == message_id
+        }) {
+            Some(node) => &node.message.version_vector,
+            None => return Explanation::NotReceived,
+        };
+
+        let dependencies: Vec<PendingDependency> = (0..self.peer_number)
+            .filter_map(|id| {
+                let first = self.V[id] + 1;
+                let last = if id == sender_id {
+                    message_id - 1
+                } else {
+                    version_vector[id]
+                };
+
+                if last < first {
+                    return None;
+                }
+
+                //Every message in the range was received if none of them is in a gap
+                let received = last <= self.R[id]
+                    && !self.gaps[id]
+                        .iter()
+                        .any(|&(gap_first, gap_last)| gap_first <= last && first <= gap_last);
+
+                Some(PendingDependency {
+                    id,
+                    first,
+                    last,
+                    received,
+                })
+            })
+            .collect();
+
+        if dependencies.is_empty() {
+            Explanation::Ready
+        } else {
+            Explanation::Waiting { dependencies }
+        }
+    }
+
+    /**
+     * Returns the timestamps of the retained messages, which are empty if messages aren't retained.
+     */
+    pub fn event_timestamps(&self) -> Vec<MessageTimestamps> {
+        match &self.retained {
+            Some(retained) => retained.timestamps(),
+            None => Vec::new(),
+        }
+    }
+
+    /**
+     * Returns the statistics of the retention log, which are empty if messages aren't retained.
+     */
+    pub fn retention_stats(&self) -> RetentionMetrics {
+        match &self.retained {
+            Some(retained) => retained.stats(),
+            None => RetentionMetrics::default(),
+        }
+    }
+
+    /**
+     * Reports a local message that wasn't sent to a peer, or to any peer, to the Client.
+     *
+     * # Arguments
+     *
+     * `message_id` - Message id of the local message.
+     *
+     * `peer_id` - Peer the message wasn't sent to, or `None` if it wasn't sent to any peer.
+     *
+     * `error` - Description of the error.
+     */
+    pub fn send_failed(&mut self, message_id: usize, peer_id: Option<usize>, error: String) {
+        let failure = SendFailure {
+            id: self.peer_index,
+            counter: message_id,
+            peer_id,
+            error,
+        };
+
+        self.send_to_client(MiddlewareClient::FAILED { failure });
+    }
+
+    /**
+     * Sets or removes the predicate that selects the messages delivered to the Client.
+     *
+     * # Arguments
+     *
+     * `filter` - Predicate over the sender id, message id and payload.
+     */
+    pub fn set_filter(&mut self, filter: Option<DeliveryFilter>) {
+        self.filter = filter;
+    }
+
+    /**
+     * Evicts a peer from the group. Further messages from the peer are ignored
+     * and its row in the matrix M is no longer used when calculating the stable
+     * vector, which can make some delivered messages stable. The Client is
+     * notified after the eviction.
+     *
+     * # Arguments
+     *
+     * `j` - Evicted peer's id
+     */
+    pub fn evict(&mut self, j: usize) {
+        self.evicted[j] = true;
+        self.gaps[j].clear();
+
+        if self.configuration.track_causal_stability {
+            self.updateSV(j);
+        }
+
+        self.send_to_client(MiddlewareClient::EVICTED { peer_id: j });
+    }
+
+    fn updateSV(&mut self, j: usize) {
         //Making it a smarter Stable Vector
         //Only calculate the new SV if the new stable message from j
         //Was a previous row for the minimum of the matrix M
@@ -265,6 +1030,7 @@ impl VV {
 
                 //My code
                 self.SV = newSV;
+                self.frontier.update_stable(&self.SV);
 
                 self.stabilize(SD);
             }
@@ -286,6 +1052,19 @@ impl VV {
             }
 
             let stable_dot = self.SMap.remove(&s).unwrap();
+            self.counts.stable += 1;
+
+            self.hooks.on_stable(stable_dot.j, stable_dot.message.id);
+
+            if let Some(retained) = &mut self.retained {
+                retained.mark_stable(s.id, s.counter);
+            }
+
+            //The advances of the stable frontier are notified instead
+            if self.configuration.stable_frontier_interval > 0 {
+                self.frontier_advanced = true;
+                continue;
+            }
 
             let stable_message = MiddlewareClient::STABLE {
                 sender_id: stable_dot.j,
@@ -293,8 +1072,34 @@ impl VV {
                 version_vector: stable_dot.message.version_vector,
             };
 
-            self.client.send(stable_message).unwrap();
+            self.send_stable(stable_message);
+        }
+    }
+
+    /**
+     * Notifies the Client of the stable frontier if it advanced since it was
+     * last notified and the frontier was coalesced for `stable_frontier_interval`
+     * or the Middleware thread is idle.
+     *
+     * # Arguments
+     *
+     * `idle` - Whether the Middleware thread has no messages left to handle.
+     */
+    pub fn notify_stable_frontier(&mut self, idle: bool) {
+        if !self.frontier_advanced
+            || !(idle
+                || self.frontier_notified_at.elapsed()
+                    >= self.configuration.get_stable_frontier_interval())
+        {
+            return;
         }
+
+        self.send_to_client(MiddlewareClient::FRONTIER {
+            frontier: self.SV.clone(),
+        });
+
+        self.frontier_advanced = false;
+        self.frontier_notified_at = Instant::now();
     }
 
     fn calculateSV(&mut self, sender_id: usize) -> VersionVector {
@@ -304,11 +1109,12 @@ impl VV {
 
         for column in 0..self.peer_number {
             if self.M_entry_row_num[column] == sender_id {
-                min = self.M[0][column];
-                min_row_num = 0;
+                min = usize::MAX;
+                min_row_num = self.peer_index;
 
-                for row in 1..self.peer_number {
-                    if self.M[row][column] < min {
+                //Rows of evicted peers aren't considered
+                for row in 0..self.peer_number {
+                    if !self.evicted[row] && self.M[row][column] < min {
                         min = self.M[row][column];
                         min_row_num = row;
                     }
@@ -6,10 +6,18 @@ pub mod communication;
  * Middleware that ensures causal delivery.
  */
 pub mod middleware;
+/**
+ * Receiver of the stability notifications routed to their own channel.
+ */
+pub mod stable_receiver;
 /**
  * Common structs of the middleware service.
  */
 pub mod structs;
+/**
+ * Cloneable handle that broadcasts messages from any thread.
+ */
+pub mod tcb_sender;
 /**
  * API and necessary state for the client to communicate with the middleware.
  */
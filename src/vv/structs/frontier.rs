@@ -0,0 +1,91 @@
+use crate::clock::version_vector::VersionVector;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/**
+ * Snapshot of the causal frontier of the middleware returned to the Client.
+ */
+#[derive(Debug, Clone)]
+pub struct Frontier {
+    ///Version vector of the messages delivered by the middleware, including the local ones
+    pub delivered: VersionVector,
+    ///Stable vector, where each entry is the counter of the last stable message of a peer
+    pub stable: VersionVector,
+}
+
+/**
+ * Causal frontier shared between the Middleware thread and the Client,
+ * updated as messages are delivered and become stable.
+ */
+#[derive(Debug)]
+pub struct SharedFrontier {
+    ///Delivered counter of each peer, indexed by the peer's id
+    delivered: Vec<AtomicUsize>,
+    ///Stable counter of each peer, indexed by the peer's id
+    stable: Vec<AtomicUsize>,
+}
+
+impl SharedFrontier {
+    /**
+     * Creates the frontier of a group where no message was delivered.
+     *
+     * # Arguments
+     *
+     * `peer_number` - Number of peers in the group.
+     */
+    pub fn new(peer_number: usize) -> Self {
+        Self {
+            delivered: (0..peer_number).map(|_| AtomicUsize::new(0)).collect(),
+            stable: (0..peer_number).map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    /**
+     * Records the counter of the last message delivered from a peer.
+     *
+     * # Arguments
+     *
+     * `j` - Sender id.
+     *
+     * `counter` - Counter of the delivered message.
+     */
+    pub fn update_delivered(&self, j: usize, counter: usize) {
+        self.delivered[j].store(counter, Ordering::Release);
+    }
+
+    /**
+     * Records a new stable vector.
+     *
+     * # Arguments
+     *
+     * `stable` - Stable vector calculated by the middleware.
+     */
+    pub fn update_stable(&self, stable: &VersionVector) {
+        for (entry, &counter) in self.stable.iter().zip(stable.iter()) {
+            entry.store(counter, Ordering::Release);
+        }
+    }
+
+    /**
+     * Returns a snapshot of the current frontier. Each entry is read on its own,
+     * so an entry may be more recent than the others, but never older than a
+     * previous snapshot.
+     */
+    pub fn snapshot(&self) -> Frontier {
+        let delivered = self
+            .delivered
+            .iter()
+            .map(|entry| entry.load(Ordering::Acquire))
+            .collect();
+
+        let stable = self
+            .stable
+            .iter()
+            .map(|entry| entry.load(Ordering::Acquire))
+            .collect();
+
+        Frontier {
+            delivered: VersionVector(delivered),
+            stable: VersionVector(stable),
+        }
+    }
+}
@@ -1,4 +1,12 @@
-use super::version_vector::VersionVector;
+use crate::broadcast::broadcast_trait::{
+    DeliveryFilter, Explanation, Fault, Headers, MessageTimestamps, SendFailure,
+};
+use crate::channel::Sender;
+use crate::clock::version_vector::VersionVector;
+use crate::configuration::middleware_configuration::WireEncoding;
+use crate::transport::batching::BatchFrame;
+use crate::transport::batching::BatchedMessage;
+use std::net::TcpStream;
 
 /**
  * Struct for the message sent over the network.
@@ -11,6 +19,16 @@ pub struct Message {
     pub payload: Vec<u8>,
     ///Message version vector
     pub version_vector: VersionVector,
+    ///Trace id set by the sender
+    pub trace_id: Option<u128>,
+    ///Metadata headers set by the sender
+    pub headers: Option<Headers>,
+    ///History digests of the version vector entries, in the Byzantine-tolerant mode
+    pub history_digests: Vec<[u8; 32]>,
+    ///Peers the message is delivered to, besides its sender, or every peer if not set
+    pub recipients: Option<Vec<usize>>,
+    ///Checksum of the payload, if enabled and the payload isn't encrypted
+    pub checksum: Option<u64>,
 }
 
 impl Message {
@@ -24,12 +42,24 @@ impl Message {
      * `payload` - Serialized message payload
      *
      * `version_vector` - Message version vector
+     *
+     * `trace_id` - Trace id set by the sender
      */
-    pub fn new(id: usize, payload: Vec<u8>, version_vector: VersionVector) -> Self {
+    pub fn new(
+        id: usize,
+        payload: Vec<u8>,
+        version_vector: VersionVector,
+        trace_id: Option<u128>,
+    ) -> Self {
         Self {
             id,
             payload,
             version_vector,
+            trace_id,
+            headers: None,
+            history_digests: Vec::new(),
+            recipients: None,
+            checksum: None,
         }
     }
 }
@@ -39,14 +69,44 @@ impl Message {
  * */
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum StreamMsg {
-    ///Handshake
-    HND { index: usize },
-    ///Peer message
-    MSG { msg: Vec<u8>, peer_id: usize },
+    ///Handshake with the encoding of the sender's frames and messages
+    HND {
+        index: usize,
+        encoding: WireEncoding,
+    },
+    ///Peer message stamped with the sender's epoch and optionally signed
+    MSG {
+        msg: Vec<u8>,
+        peer_id: usize,
+        epoch: u64,
+        signature: Option<Vec<u8>>,
+    },
+    ///Batch of peer messages stamped with the sender's epoch
+    BATCH {
+        peer_id: usize,
+        epoch: u64,
+        messages: Vec<BatchedMessage>,
+    },
+    ///Probe for measuring the round trip time
+    PING { timestamp: u64 },
+    ///Response to a probe with the probe's timestamp
+    PONG { timestamp: u64 },
+    ///Receipt of the messages of the receiving peer delivered up to a counter
+    RECEIPT { counter: usize },
     ///Terminate connection
     CLOSE,
 }
 
+impl BatchFrame for StreamMsg {
+    fn batch(sender_id: usize, epoch: u64, messages: Vec<BatchedMessage>) -> Self {
+        StreamMsg::BATCH {
+            peer_id: sender_id,
+            epoch,
+            messages,
+        }
+    }
+}
+
 /**
  * Enum for the messages that will be sent/received in the channels between
  * the main middleware, stream reader and client
@@ -57,11 +117,63 @@ pub enum ClientPeerMiddleware {
         msg_id: usize,
         payload: Vec<u8>,
         version_vector: VersionVector,
+        trace_id: Option<u128>,
+        headers: Option<Headers>,
+        recipients: Option<Vec<usize>>,
     },
     ///Message received from a peer
-    PEER { peer_id: usize, message: Message },
+    PEER {
+        peer_id: usize,
+        message: Message,
+    },
     ///Indicates that the Middleware has finished the starting up
     SETUP,
+    ///Stream of a Reader thread connected to a peer
+    CONNECTION {
+        peer_id: usize,
+        stream: TcpStream,
+    },
+    ///Request by the Client to evict a peer from the group
+    EVICT {
+        peer_id: usize,
+    },
+    ///Request by the Client to flush the Sender threads
+    FLUSH,
+    ///Request by the Client to stop or restart the deliveries
+    PAUSE,
+    RESUME,
+    ///Delivery processed by the Client, with its sender id and message id
+    ACK {
+        sender_id: usize,
+        message_id: usize,
+    },
+    ///Request by the Client to send the unacked deliveries again
+    REPLAY,
+    ///Predicate set by the Client to select the delivered messages
+    FILTER {
+        filter: Option<DeliveryFilter>,
+    },
+    ///Request by the Client for a consistent cut of the delivered messages
+    SNAPSHOT,
+    ///Request by the Client for the timestamps of the retained messages
+    DUMP {
+        reply: Sender<Vec<MessageTimestamps>>,
+    },
+    ///Request by the Client for the reason a message wasn't delivered
+    EXPLAIN {
+        sender_id: usize,
+        message_id: usize,
+        reply: Sender<Explanation>,
+    },
+    ///Probe received from a peer that must be answered
+    PING {
+        peer_id: usize,
+        timestamp: u64,
+    },
+    ///Peer that closed its connection, so it won't send more messages
+    CLOSED {
+        peer_id: usize,
+    },
     ///Connection end
     END,
 }
@@ -76,12 +188,24 @@ pub enum MiddlewareClient {
         message: Message,
         version_vector: VersionVector,
     },
+    ///Delivered message that didn't match the Client's filter, with its sender id and message id
+    SKIPPED { sender_id: usize, message_id: usize },
     ///Stable message with its sender id, message id and version vector
     STABLE {
         sender_id: usize,
         message_id: usize,
         version_vector: VersionVector,
     },
+    ///Stable version vector, notified instead of each stable message
+    FRONTIER { frontier: VersionVector },
+    ///Peer that was evicted from the group
+    EVICTED { peer_id: usize },
+    ///Protocol fault detected in the messages of a peer
+    FAULT { fault: Fault },
+    ///Local message that wasn't sent to some or every peer
+    FAILED { failure: SendFailure },
+    ///Delivered version vector of the middleware, following every delivery up to it
+    SNAPSHOT { cut: VersionVector },
     ///Setup variation
     SETUP,
 }
@@ -1,3 +1,7 @@
+/**
+ * Causal frontier shared with the Client.
+ */
+pub mod frontier;
 /**
  * Wrappers for messages sent through channels and TCP streams.
  */
@@ -5,4 +9,4 @@ pub mod messages;
 /**
  * Version vector that is tagged to each message before sending.
  */
-pub mod version_vector;
+pub use crate::clock::version_vector;
@@ -0,0 +1,263 @@
+use crate::broadcast::broadcast_trait::{Headers, TcbError};
+use crate::channel::Sender;
+use crate::clock::dot::Dot;
+use crate::clock::version_vector::VersionVector;
+#[cfg(feature = "otel")]
+use crate::telemetry::otel::Telemetry;
+use crate::vv::structs::messages::ClientPeerMiddleware;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/**
+ * Id of the last sent message and version vector of the next sent message,
+ * shared by every handle of a middleware instance.
+ */
+#[allow(non_snake_case)]
+struct SendState {
+    message_id: usize,
+    V: VersionVector,
+}
+
+/**
+ * Handle that broadcasts messages from any thread, without a lock around the whole
+ * VV struct. The message ids are assigned under a lock shared by every clone and
+ * the middleware instance, which holds it while the message is written to the
+ * Middleware thread's channel, so the messages arrive in the order of their ids.
+ */
+pub struct TcbSender {
+    ///Sender end of the channel between the client and the middleware thread
+    middleware_channel: Sender<ClientPeerMiddleware>,
+    ///Message id and version vector shared with the other handles
+    state: Arc<Mutex<SendState>>,
+    ///Local peer's globally unique id
+    local_id: usize,
+    ///Flag set once the middleware was ended, shared by every handle
+    terminated: Arc<AtomicBool>,
+    ///OpenTelemetry instruments of the send events
+    #[cfg(feature = "otel")]
+    telemetry: Telemetry,
+}
+
+impl TcbSender {
+    /**
+     * Builds the handle of a middleware instance that didn't send or deliver any message.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `client_number` - Number of peers in the group.
+     *
+     * `middleware_channel` - Channel to the middleware thread.
+     */
+    pub fn new(
+        local_id: usize,
+        client_number: usize,
+        middleware_channel: Sender<ClientPeerMiddleware>,
+    ) -> Self {
+        let state = SendState {
+            message_id: 0,
+            V: VersionVector::new(client_number),
+        };
+
+        Self {
+            middleware_channel,
+            state: Arc::new(Mutex::new(state)),
+            local_id,
+            terminated: Arc::new(AtomicBool::new(false)),
+            #[cfg(feature = "otel")]
+            telemetry: Telemetry::new(local_id),
+        }
+    }
+
+    /**
+     * Updates the next sent message's version vector upon a delivery.
+     *
+     * # Arguments
+     *
+     * `sender_id` - Delivered message's sender id.
+     *
+     * `message_id` - Delivered message's id.
+     */
+    pub fn delivered(&self, sender_id: usize, message_id: usize) {
+        self.state.lock().unwrap().V[sender_id] = message_id;
+    }
+
+    /**
+     * Marks the middleware as ended, so the sends of every handle return
+     * `TcbError::Terminated`. Returns false if it was already ended.
+     */
+    pub fn mark_terminated(&self) -> bool {
+        !self.terminated.swap(true, Ordering::SeqCst)
+    }
+
+    /**
+     * Checks if the middleware was ended.
+     */
+    pub fn is_terminated(&self) -> bool {
+        self.terminated.load(Ordering::SeqCst)
+    }
+
+    /**
+     * Broadcasts a message to every peer in the group.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     */
+    pub fn send(&self, message: Vec<u8>) -> Result<(), TcbError> {
+        self.broadcast(message, None, None, None, &[])
+    }
+
+    /**
+     * Broadcasts a message tagged with a trace id to every peer in the group.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     *
+     * `trace_id` - Trace or correlation id of the message
+     */
+    pub fn send_traced(&self, message: Vec<u8>, trace_id: u128) -> Result<(), TcbError> {
+        self.broadcast(message, Some(trace_id), None, None, &[])
+    }
+
+    /**
+     * Broadcasts a message with metadata headers to every peer in the group.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     *
+     * `headers` - Metadata headers of the message
+     */
+    pub fn send_with_headers(&self, message: Vec<u8>, headers: Headers) -> Result<(), TcbError> {
+        self.broadcast(message, None, Some(headers), None, &[])
+    }
+
+    /**
+     * Broadcasts a message that is ordered after the explicit dependencies, besides
+     * the messages already delivered by the Client.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     *
+     * `deps` - Dots of the messages this message must be delivered after
+     */
+    pub fn send_after(&self, message: Vec<u8>, deps: &[Dot]) -> Result<(), TcbError> {
+        self.broadcast(message, None, None, None, deps)
+    }
+
+    /**
+     * Sends a message that is only delivered by a subset of the peers.
+     *
+     * # Arguments
+     *
+     * `recipients` - Ids of the peers that deliver the message
+     *
+     * `message` - Serialized message to be sent
+     */
+    pub fn send_to(&self, recipients: &[usize], message: Vec<u8>) -> Result<(), TcbError> {
+        self.broadcast(message, None, None, Some(recipients.to_vec()), &[])
+    }
+
+    /**
+     * Broadcasts a message with an optional trace id, headers, recipients and
+     * explicit dependencies to every peer in the group. Returns
+     * `TcbError::Terminated` once the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     *
+     * `trace_id` - Trace id of the message
+     *
+     * `headers` - Metadata headers of the message
+     *
+     * `recipients` - Ids of the peers that deliver the message
+     *
+     * `deps` - Explicit dependencies of the message
+     */
+    pub fn broadcast(
+        &self,
+        message: Vec<u8>,
+        trace_id: Option<u128>,
+        headers: Option<Headers>,
+        recipients: Option<Vec<usize>>,
+        deps: &[Dot],
+    ) -> Result<(), TcbError> {
+        if self.is_terminated() {
+            return Err(TcbError::Terminated);
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        //Refusing to send once the counter is exhausted,
+        //instead of reusing the ids of previous messages
+        state.message_id = match state.message_id.checked_add(1) {
+            Some(message_id) => message_id,
+            None => {
+                println!(
+                    "ERROR: The message counter of {} is exhausted",
+                    self.local_id
+                );
+
+                return Err(TcbError::CounterExhausted);
+            }
+        };
+        let message_id = state.message_id;
+        state.V[self.local_id] = message_id;
+
+        //Raising the version vector to the explicit dependencies
+        for dep in deps {
+            if dep.id >= state.V.len() {
+                println!(
+                    "ERROR: Discarded a dependency with an id outside the group - {:?}",
+                    dep
+                );
+            } else if dep.id != self.local_id && state.V[dep.id] < dep.counter {
+                state.V[dep.id] = dep.counter;
+            }
+        }
+
+        #[cfg(feature = "otel")]
+        let message_size = message.len();
+
+        let msg = ClientPeerMiddleware::CLIENT {
+            msg_id: message_id,
+            payload: message,
+            version_vector: state.V.clone(),
+            trace_id,
+            headers,
+            recipients,
+        };
+
+        //Writing the message while holding the lock, so the
+        //messages of every handle arrive in the order of their ids
+        self.middleware_channel.send(msg)?;
+        drop(state);
+
+        #[cfg(feature = "otel")]
+        self.telemetry
+            .on_send(self.local_id, message_id, message_size, trace_id);
+
+        Ok(())
+    }
+}
+
+impl Clone for TcbSender {
+    /**
+     * Creates another handle to the same middleware, sharing its message ids.
+     */
+    fn clone(&self) -> Self {
+        Self {
+            middleware_channel: self.middleware_channel.clone(),
+            state: Arc::clone(&self.state),
+            local_id: self.local_id,
+            terminated: Arc::clone(&self.terminated),
+            #[cfg(feature = "otel")]
+            telemetry: Telemetry::new(self.local_id),
+        }
+    }
+}
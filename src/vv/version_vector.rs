@@ -1,15 +1,41 @@
-use crate::broadcast::broadcast_trait::{GenericReturn, TCB};
-use crate::configuration::middleware_configuration::Configuration;
-use crate::vv::communication::{acceptor, connector};
-use crate::vv::middleware::middleware_thread;
+use crate::broadcast::broadcast_trait::{
+    DeliveryFilter, Explanation, GenericReturn, GraphFormat, Headers, MessageTimestamps, Snapshot,
+    TcbError, TCB,
+};
+use crate::broadcast::hooks::{Hooks, NoHooks};
+use crate::broadcast::setup::{self, SetupBarrier};
+use crate::channel::{bounded, unbounded};
+use crate::channel::{Receiver, Sender};
+use crate::clock::dot::Dot;
+use crate::configuration::middleware_configuration::{Configuration, Transport};
+use crate::metrics::middleware_metrics::{Metrics, MiddlewareMetrics, ShutdownReport};
+#[cfg(feature = "otel")]
+use crate::telemetry::otel::Telemetry;
+use crate::transport::udp::UdpTransport;
+use crate::vv::communication::acceptor::{self, AcceptorContext};
+use crate::vv::communication::connector;
+use crate::vv::middleware::middleware_thread::{self, MiddlewareContext};
+use crate::vv::stable_receiver::StableReceiver;
+use crate::vv::structs::frontier::{Frontier, SharedFrontier};
 use crate::vv::structs::messages::{ClientPeerMiddleware, MiddlewareClient};
-use crate::vv::structs::version_vector::VersionVector;
-use crossbeam::crossbeam_channel::unbounded;
-use crossbeam::{Receiver, RecvError, RecvTimeoutError, SendError, Sender, TryRecvError};
-use std::sync::{Arc, Barrier};
-use std::time::Duration;
+use crate::vv::tcb_sender::TcbSender;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{thread, usize};
 
+/**
+ * Channels and handles of a started middleware service, as returned to the client.
+ */
+type ServiceChannels = (
+    Sender<ClientPeerMiddleware>,
+    Receiver<MiddlewareClient>,
+    Option<Receiver<MiddlewareClient>>,
+    Arc<SetupBarrier>,
+    Arc<MiddlewareMetrics>,
+    Option<SocketAddr>,
+);
+
 /**
  * Client side of the version vector based middleware service.
  * Maintains the API and necessary state to send and deliver messages.
@@ -20,43 +46,321 @@ pub struct VV {
     receive_channel: Receiver<MiddlewareClient>,
     //Sender end of the channel between the client and the middleware thread
     middleware_channel: Sender<ClientPeerMiddleware>,
-    message_id: usize,
-    //Dot of the next sent message
-    V: VersionVector,
+    //Metrics shared with the middleware threads
+    metrics: Arc<MiddlewareMetrics>,
+    //Receiver of the stable messages, if they are routed to their own channel
+    stable_receiver: Option<StableReceiver>,
+    //Barrier signalling the middleware connected to every peer
+    ready: Arc<SetupBarrier>,
+    //Delivered and stable frontier shared with the middleware thread
+    frontier: Arc<SharedFrontier>,
+    //OpenTelemetry instruments of the send, delivery and stable events
+    #[cfg(feature = "otel")]
+    telemetry: Telemetry,
+    //Handle that broadcasts the messages sent by the client
+    sender: TcbSender,
     //Peer's id
     local_id: usize,
+    //Address the middleware listens on, if it's bound to a TCP port
+    local_addr: Option<SocketAddr>,
 }
 
 impl VV {
     /**
-     * Updates the next sent message's version vector upon a delivery.
+     * Creates a new middleware instance whose Middleware thread fires the passed
+     * hooks when messages are sent, received, delivered and stable. Like `new`,
+     * this function only returns after the middleware has a connection to every
+     * other peer in both directions, unless the configuration sets a lazy setup.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     *
+     * `hooks` - Callbacks for custom metrics, audit logging or test assertions.
+     */
+    #[allow(non_snake_case)]
+    pub fn with_hooks(configuration: Configuration, hooks: Arc<dyn Hooks>) -> Self {
+        Self::build(configuration, hooks, None).unwrap_or_else(|e| panic!("ERROR: {}", e))
+    }
+
+    /**
+     * Creates a new middleware instance that accepts the connections of the other
+     * peers on a listener bound by the application, e.g. to port 0 so the OS picks
+     * a free port. The bound addresses of every peer must be exchanged before, since
+     * they're set in the configuration's topology. Like `new`, this function only
+     * returns after the middleware has a connection to every other peer in both
+     * directions, unless the configuration sets a lazy setup.
+     *
+     * # Arguments
+     *
+     * `configuration` - Middleware's configuration file.
+     *
+     * `listener` - TCP listener bound to the local peer's address.
+     */
+    pub fn with_listener(configuration: Configuration, listener: TcpListener) -> Self {
+        Self::build(configuration, Arc::new(NoHooks), Some(listener))
+            .unwrap_or_else(|e| panic!("ERROR: {}", e))
+    }
+
+    /**
+     * Creates a new middleware instance with the passed hooks, accepting the
+     * connections on the passed listener or on the configured local port. Returns
+     * `TcbError::StartupTimeout` if the startup timed out.
+     */
+    #[allow(non_snake_case)]
+    fn build(
+        configuration: Configuration,
+        hooks: Arc<dyn Hooks>,
+        listener: Option<TcpListener>,
+    ) -> Result<Self, TcbError> {
+        configuration
+            .topology
+            .validate()
+            .expect("ERROR: Invalid peer topology in the configuration");
+
+        let local_id = configuration.topology.local_id;
+        let local_port = configuration.topology.get_local_port();
+        let peer_addresses = configuration.topology.get_peer_addresses();
+        let configuration = Arc::new(configuration);
+        let client_number = peer_addresses.len() + 1;
+        let frontier = Arc::new(SharedFrontier::new(client_number));
+
+        let (middleware_channel, receive_channel, stable_channel, ready, metrics, local_addr) =
+            Self::start_service(
+                local_id,
+                local_port,
+                listener,
+                peer_addresses,
+                Arc::clone(&configuration),
+                hooks,
+                Arc::clone(&frontier),
+            )?;
+
+        let stable_receiver = match stable_channel {
+            Some(stable_channel) => {
+                let stable_receiver = StableReceiver::new(local_id, stable_channel);
+
+                if configuration.auto_stable_ack {
+                    Self::start_auto_ack(local_id, stable_receiver, &configuration);
+                    None
+                } else {
+                    Some(stable_receiver)
+                }
+            }
+            None => None,
+        };
+
+        let sender = TcbSender::new(local_id, client_number, middleware_channel.clone());
+
+        Ok(VV {
+            receive_channel,
+            middleware_channel,
+            metrics,
+            stable_receiver,
+            ready,
+            frontier,
+            #[cfg(feature = "otel")]
+            telemetry: Telemetry::new(local_id),
+            sender,
+            local_id,
+            local_addr,
+        })
+    }
+
+    /**
+     * Writes a request of the Client to the middleware thread, or returns
+     * `TcbError::Terminated` if the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `message` - Request to the middleware thread.
+     */
+    fn request(&self, message: ClientPeerMiddleware) -> Result<(), TcbError> {
+        if self.sender.is_terminated() {
+            return Err(TcbError::Terminated);
+        }
+
+        self.middleware_channel.send(message)?;
+        Ok(())
+    }
+
+    /**
+     * Reads the next message from the middleware's channel, or returns
+     * `TcbError::Terminated` if the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `receive` - Function that reads a message from the channel.
+     */
+    fn next_message<E>(
+        &self,
+        receive: impl FnOnce(&Receiver<MiddlewareClient>) -> Result<MiddlewareClient, E>,
+    ) -> Result<MiddlewareClient, TcbError>
+    where
+        TcbError: From<E>,
+    {
+        if self.sender.is_terminated() {
+            return Err(TcbError::Terminated);
+        }
+
+        Ok(receive(&self.receive_channel)?)
+    }
+
+    /**
+     * Updates the next sent message's version vector upon a delivery. Returns
+     * `None` for the messages that didn't match the delivery filter.
      *
      * # Arguments
      *
      * `message` - Delivered or stable message.
      */
-    fn handle_delivery(&mut self, message: MiddlewareClient) -> GenericReturn {
-        match message {
+    fn handle_delivery(&mut self, message: MiddlewareClient) -> Option<GenericReturn> {
+        let generic_return = match message {
             MiddlewareClient::DELIVER {
                 sender_id,
                 version_vector,
                 message,
             } => {
-                self.V[sender_id] = version_vector[sender_id];
+                self.sender.delivered(sender_id, version_vector[sender_id]);
 
-                GenericReturn::Delivery(message.payload, sender_id, version_vector[sender_id])
+                #[cfg(feature = "otel")]
+                self.telemetry.on_deliver(
+                    sender_id,
+                    version_vector[sender_id],
+                    message.payload.len(),
+                    message.trace_id,
+                );
+
+                match (message.headers, message.trace_id) {
+                    (Some(headers), _) => GenericReturn::HeaderedDelivery(
+                        message.payload,
+                        sender_id,
+                        version_vector[sender_id],
+                        headers,
+                    ),
+                    (None, Some(trace_id)) => GenericReturn::TracedDelivery(
+                        message.payload,
+                        sender_id,
+                        version_vector[sender_id],
+                        trace_id,
+                    ),
+                    (None, None) => GenericReturn::Delivery(
+                        message.payload,
+                        sender_id,
+                        version_vector[sender_id],
+                    ),
+                }
+            }
+            MiddlewareClient::SKIPPED {
+                sender_id,
+                message_id,
+            } => {
+                self.sender.delivered(sender_id, message_id);
+                return None;
             }
             MiddlewareClient::STABLE {
                 sender_id,
                 message_id,
                 ..
-            } => GenericReturn::Stable(sender_id, message_id),
+            } => {
+                #[cfg(feature = "otel")]
+                self.telemetry.on_stable(sender_id, message_id);
+
+                GenericReturn::Stable(sender_id, message_id)
+            }
+            MiddlewareClient::FRONTIER { frontier } => GenericReturn::StableFrontier(frontier.0),
+            MiddlewareClient::EVICTED { peer_id } => GenericReturn::Evicted(peer_id),
+            MiddlewareClient::FAULT { fault } => GenericReturn::Fault(fault),
+            MiddlewareClient::FAILED { failure } => GenericReturn::SendFailed(failure),
+            //Cuts are only expected by the snapshot calls
+            MiddlewareClient::SNAPSHOT { .. } => return None,
             _ => {
                 panic!("ERROR: Received a SETUP when it shouldn't!");
             }
+        };
+
+        Some(generic_return)
+    }
+
+    /**
+     * Sends a message that is only delivered by a subset of the peers, for workloads
+     * with partial replication. The other peers receive the message without its
+     * payload and skip it, so the messages each peer delivers stay causally ordered
+     * and the message still becomes stable. In the Byzantine-tolerant mode the payload
+     * is sent to every peer, since it's needed to verify the history digests.
+     *
+     * # Arguments
+     *
+     * `recipients` - Ids of the peers that deliver the message
+     *
+     * `message` - Serialized message to be sent
+     */
+    pub fn send_to(&mut self, recipients: &[usize], message: Vec<u8>) -> Result<(), TcbError> {
+        self.sender
+            .broadcast(message, None, None, Some(recipients.to_vec()), &[])
+    }
+
+    /**
+     * Returns the causal frontier of the middleware: the version vector of the
+     * delivered messages and the stable vector. Every message whose counter is
+     * at or below its sender's entry in the stable vector was delivered by every
+     * peer, so the application can prune its own logs and caches up to it
+     * without tracking the individual stable notifications. The stable vector
+     * isn't updated if causal stability isn't tracked.
+     */
+    pub fn frontier(&self) -> Frontier {
+        self.frontier.snapshot()
+    }
+
+    /**
+     * Returns the ids of the peers that acknowledged the delivery of a message
+     * sent by the local peer, when delivery receipts are enabled, as soon as
+     * each receipt arrives. The list is empty for the messages of other peers.
+     *
+     * # Arguments
+     *
+     * `dot` - Sender id and message id of a message sent by the local peer
+     */
+    pub fn delivery_status(&self, dot: &Dot) -> Vec<usize> {
+        if dot.id == self.local_id {
+            self.metrics.delivered_by(dot.counter)
+        } else {
+            Vec::new()
         }
     }
 
+    /**
+     * Spawns the thread that acks the stable messages once they were stable
+     * for the configured hold time, when stable messages are acked automatically.
+     *
+     * # Arguments
+     *
+     * `local_id` - Local peer's globally unique id.
+     *
+     * `stable_receiver` - Receiver of the stable messages.
+     *
+     * `configuration` - Middleware's configuration file.
+     */
+    fn start_auto_ack(
+        local_id: usize,
+        stable_receiver: StableReceiver,
+        configuration: &Configuration,
+    ) {
+        let hold_time = Duration::from_micros(configuration.stable_hold_time);
+
+        //Formatting the peer's stable ack thread name
+        let thread_name = format!("stable_ack_thread_{}", local_id);
+        let builder = thread::Builder::new()
+            .name(thread_name)
+            .stack_size(configuration.thread_stack_size);
+
+        builder
+            .spawn(move || {
+                stable_receiver.ack_after(hold_time);
+            })
+            .unwrap();
+    }
+
     /**
      * Starting method of the Middleware service. It creates and initializes
      * the necessary variables, communication channels and threads.
@@ -67,16 +371,30 @@ impl VV {
      *
      * `local_port` - Port where the middleware will be listening for connections.
      *
+     * `listener` - TCP listener bound by the application, used instead of the local port.
+     *
      * `peer_addresses` - Addresses the middleware will connect to.
      *
      * `configuration` - Middleware's configuration file.
+     *
+     * `hooks` - Callbacks fired by the Middleware thread.
+     *
+     * `frontier` - Delivered and stable frontier shared with the Middleware thread.
      */
     fn start_service(
         local_id: usize,
         local_port: usize,
+        listener: Option<TcpListener>,
         peer_addresses: Vec<String>,
         configuration: Arc<Configuration>,
-    ) -> (Sender<ClientPeerMiddleware>, Receiver<MiddlewareClient>) {
+        hooks: Arc<dyn Hooks>,
+        frontier: Arc<SharedFrontier>,
+    ) -> Result<ServiceChannels, TcbError> {
+        //Creating the metrics shared between the middleware threads and the client
+        let metrics = Arc::new(MiddlewareMetrics::new(local_id, peer_addresses.len() + 1));
+        let metrics_clone = Arc::clone(&metrics);
+        let middleware_metrics = Arc::clone(&metrics);
+
         //Creating the clone of the middleware configuration arc
         let configuration_clone = Arc::clone(&configuration);
 
@@ -84,6 +402,16 @@ impl VV {
         //and the client reads from
         let (middleware_send_channel, peer_receive_channel) = unbounded::<MiddlewareClient>();
 
+        //Creating the channel where the middleware writes the stable messages to,
+        //which is the channel of the deliveries unless they are routed to their own
+        let (stable_send_channel, stable_receive_channel) =
+            if configuration.separate_stable_channel || configuration.auto_stable_ack {
+                let (stable_send_channel, stable_receive_channel) = unbounded::<MiddlewareClient>();
+                (stable_send_channel, Some(stable_receive_channel))
+            } else {
+                (middleware_send_channel.clone(), None)
+            };
+
         //Creating the channel where the main middleware thread reads from
         //and the peer threads and client write to
         let (peer_reader_send_channel, middleware_receive_channel) =
@@ -101,27 +429,91 @@ impl VV {
             .stack_size(configuration.thread_stack_size);
 
         //Cloning the channel to the logging service
-        let setup_end_barrier = Arc::new(Barrier::new(peer_addresses.len() + 1));
+        let setup_end_barrier = Arc::new(SetupBarrier::new(peer_addresses.len() + 1));
         let setup_end_barrier_clone = Arc::clone(&setup_end_barrier);
 
+        //Binding the socket shared by every connection, if the UDP transport is set
+        let udp_transport = match configuration.transport {
+            Transport::Tcp => None,
+            Transport::Udp => Some(Arc::new(
+                UdpTransport::bind(local_id, local_port, &peer_addresses, &configuration)
+                    .expect("ERROR: Failed to bind the UDP transport"),
+            )),
+        };
+        let acceptor_udp_transport = udp_transport.clone();
+
+        //Binding the TCP listener before the acceptor thread, so its address is known
+        let tcp_listener = match &udp_transport {
+            Some(_) => None,
+            None => match listener {
+                Some(listener) => Some(listener),
+                None => acceptor::bind(local_port, &configuration),
+            },
+        };
+        let local_addr = match &tcp_listener {
+            Some(listener) => listener.local_addr().ok(),
+            None => None,
+        };
+
+        //Connecting to the peers' ports and getting the channels sender ends
+        //between the middleware and the sender thread, which are made in the background
+        let (channels_to_socket_threads, connected, shared) = match &udp_transport {
+            Some(transport) => (
+                connector::start_udp(
+                    local_id,
+                    transport,
+                    &peer_addresses,
+                    &configuration_clone,
+                    &metrics,
+                ),
+                None,
+                None,
+            ),
+            //Handing the acceptor thread the connections it starts the threads of
+            None if configuration.shares_connections() => {
+                let (channels, connected, shared) = connector::start_shared(
+                    local_id,
+                    &peer_addresses,
+                    &configuration_clone,
+                    &metrics,
+                    &setup_end_barrier,
+                );
+                (channels, Some(connected), Some(shared))
+            }
+            None => {
+                let (channels, connected) = connector::start(
+                    local_id,
+                    &peer_addresses,
+                    &configuration_clone,
+                    &metrics,
+                    &setup_end_barrier,
+                );
+                (channels, Some(connected), None)
+            }
+        };
+
         //Spawning the acceptor thread
         builder
             .spawn(move || {
-                acceptor::start(
+                let context = AcceptorContext::new(
                     local_id,
-                    local_port,
                     acceptor_thread_peer_addresses,
                     peer_reader_send_channel_clone,
                     configuration,
-                    setup_end_barrier_clone,
+                    metrics_clone,
                 );
+
+                match acceptor_udp_transport {
+                    Some(transport) => {
+                        acceptor::start_udp(context, &transport, setup_end_barrier_clone)
+                    }
+                    None => acceptor::start(context, tcp_listener, setup_end_barrier_clone, shared),
+                }
             })
             .unwrap();
 
-        //Connecting to the peers' ports and getting the channels sender ends
-        //between the middleware and the sender thread
-        let channels_to_socket_threads: Vec<Sender<(Arc<Barrier>, Arc<Vec<u8>>)>> =
-            connector::start(local_id, &peer_addresses, &configuration_clone);
+        let setup_configuration = Arc::clone(&configuration_clone);
+        let setup_peer_addresses = peer_addresses.clone();
 
         //Formatting the peer's middlware thread name
         let thread_name = format!("middleware_thread_{}", local_id);
@@ -137,15 +529,54 @@ impl VV {
                     peer_addresses,
                     middleware_receive_channel,
                     middleware_send_channel,
+                    stable_send_channel,
                     channels_to_socket_threads,
-                    configuration_clone,
+                    MiddlewareContext {
+                        configuration: configuration_clone,
+                        metrics: middleware_metrics,
+                        hooks,
+                        frontier,
+                    },
                 )
             })
             .unwrap();
 
-        setup_end_barrier.wait();
+        setup::wait_for_setup(
+            local_id,
+            connected,
+            setup_peer_addresses.len(),
+            Arc::clone(&setup_end_barrier),
+            Arc::clone(&metrics),
+        );
+
+        //Blocking until the middleware is connected to every peer, unless the setup is lazy
+        if !setup_configuration.lazy_setup {
+            let startup = setup::wait_for_startup(
+                &setup_end_barrier,
+                local_id,
+                &setup_peer_addresses,
+                &setup_configuration,
+                &metrics,
+            );
+
+            //Stopping the threads waiting for the aborted setup and closing the listener
+            if let Err(e) = startup {
+                setup::wake_acceptor(local_addr);
+                let _ = peer_reader_send_channel.send(ClientPeerMiddleware::END);
+
+                return Err(e);
+            }
+        }
+
         //Return the channels the peer writes and reads from to the middleware
-        (peer_reader_send_channel, peer_receive_channel)
+        Ok((
+            peer_reader_send_channel,
+            peer_receive_channel,
+            stable_receive_channel,
+            setup_end_barrier,
+            metrics,
+            local_addr,
+        ))
     }
 }
 
@@ -154,44 +585,44 @@ impl TCB for VV {
     /**
      * Type of the return from a send call, which is an empty value or an error.
      */
-    type SendCallReturn = Result<(), SendError<ClientPeerMiddleware>>;
+    type SendCallReturn = Result<(), TcbError>;
+
+    /**
+     * Type of the receiver of the stability notifications.
+     */
+    type StableReceiver = StableReceiver;
+
+    /**
+     * Type of the cloneable handle that broadcasts messages from other threads.
+     */
+    type Sender = TcbSender;
 
     /**
      * Creates a new middleware instance. This function only returns after the middleware
-     * has a connection to every other peer in both directions.
+     * has a connection to every other peer in both directions, unless the configuration
+     * sets a lazy setup. The local id, the port where the middleware will be listening
+     * for connections and the addresses of the other peers are set by the configuration's
+     * topology, which must be valid.
      *
      * # Arguments
      *
-     * `local_id` - Peer's globally unique id in the group.
-     *
-     * `local_port` - Port where the middleware will be listening for connections.
+     * `configuration` - Middleware's configuration file.
+     */
+    fn new(configuration: Configuration) -> Self {
+        Self::with_hooks(configuration, Arc::new(NoHooks))
+    }
+
+    /**
+     * Creates a new middleware instance like `new`, but returns
+     * `TcbError::StartupTimeout` with the connections that weren't made instead of
+     * panicking if the configured startup timeout ends first.
      *
-     * `peer_addresses` - Addresses the middleware will connect to.
+     * # Arguments
      *
      * `configuration` - Middleware's configuration file.
      */
-    fn new(
-        local_id: usize,
-        local_port: usize,
-        peer_addresses: Vec<String>,
-        configuration: Configuration,
-    ) -> Self {
-        let configuration = Arc::new(configuration);
-        let client_number = peer_addresses.len() + 1;
-
-        let (middleware_channel, receive_channel) =
-            Self::start_service(local_id, local_port, peer_addresses, configuration);
-
-        //Initializing the version vector
-        let V = VersionVector::new(client_number);
-
-        VV {
-            receive_channel,
-            middleware_channel,
-            message_id: 0,
-            V,
-            local_id,
-        }
+    fn try_new(configuration: Configuration) -> Result<Self, TcbError> {
+        Self::build(configuration, Arc::new(NoHooks), None)
     }
 
     /**
@@ -203,79 +634,148 @@ impl TCB for VV {
      * `message` - Serialized message to be broadcast
      */
     fn send(&mut self, message: Vec<u8>) -> Self::SendCallReturn {
-        self.message_id += 1;
-        self.V[self.local_id] = self.message_id;
+        self.sender.broadcast(message, None, None, None, &[])
+    }
 
-        let msg = ClientPeerMiddleware::CLIENT {
-            msg_id: self.message_id,
-            payload: message,
-            version_vector: self.V.clone(),
-        };
+    /**
+     * Broadcasts a message tagged with a trace id to every peer in the group.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     *
+     * `trace_id` - Trace or correlation id of the message
+     */
+    fn send_traced(&mut self, message: Vec<u8>, trace_id: u128) -> Self::SendCallReturn {
+        self.sender
+            .broadcast(message, Some(trace_id), None, None, &[])
+    }
 
-        self.middleware_channel.send(msg)?;
+    /**
+     * Broadcasts a message with metadata headers to every peer in the group.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     *
+     * `headers` - Metadata headers of the message
+     */
+    fn send_with_headers(&mut self, message: Vec<u8>, headers: Headers) -> Self::SendCallReturn {
+        self.sender
+            .broadcast(message, None, Some(headers), None, &[])
+    }
 
-        Ok(())
+    /**
+     * Broadcasts a message that is ordered after the explicit dependencies, besides
+     * the messages already delivered by the Client. The middleware only broadcasts
+     * the message once every dependency was delivered locally, and the messages
+     * sent after it wait for it to keep their order. Returns the same as `send`.
+     *
+     * # Arguments
+     *
+     * `message` - Serialized message to be broadcast
+     *
+     * `deps` - Dots of the messages this message must be delivered after
+     */
+    fn send_after(&mut self, message: Vec<u8>, deps: &[Dot]) -> Self::SendCallReturn {
+        self.sender.broadcast(message, None, None, None, deps)
     }
 
     /**
-     * Signals and waits for the middleware to terminate.
+     * Signals and waits for the middleware to terminate. Every later call that
+     * needs the middleware returns `TcbError::Terminated`.
      */
     fn end(&self) {
+        //Ending the middleware only once, since its thread is gone afterwards
+        if !self.sender.mark_terminated() {
+            return;
+        }
+
         let end_message = ClientPeerMiddleware::END;
-        self.middleware_channel.send(end_message).unwrap();
+
+        //The middleware thread may have terminated on its own
+        if self.middleware_channel.send(end_message).is_err() {
+            return;
+        }
 
         loop {
             match self.receive_channel.recv() {
-                Ok(msg) => match msg {
-                    MiddlewareClient::SETUP => {
-                        break;
-                    }
-                    _ => {}
-                },
-                Err(_) => {}
+                Ok(MiddlewareClient::SETUP) | Err(_) => break,
+                Ok(_) => {}
             }
         }
     }
 
+    /**
+     * Ends the middleware like `end`, then returns the summary of its activity,
+     * recorded by the Middleware thread before it terminated.
+     */
+    fn shutdown(&self) -> ShutdownReport {
+        self.end();
+        self.metrics.report()
+    }
+
     /**
      * Delivers a message from the middleware. Blocks the calling thread
-     * until a message is delivered or the channel to the middleware is
-     * empty or disconnected.
+     * until a message is delivered, or returns `TcbError::Terminated` once
+     * the middleware terminated.
      */
-    fn recv(&mut self) -> Result<GenericReturn, RecvError> {
-        match self.receive_channel.recv() {
-            Ok(msg) => Ok(self.handle_delivery(msg)),
-            Err(e) => Err(e),
+    fn recv(&mut self) -> Result<GenericReturn, TcbError> {
+        loop {
+            match self.next_message(|channel| channel.recv()) {
+                Ok(msg) => {
+                    if let Some(generic_return) = self.handle_delivery(msg) {
+                        return Ok(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
     /**
      * Attempts to deliver a message from the middleware without blocking
-     * the caller thread. Either a message is immeadiately delivered
-     * from the channel or an error is returned if the channel is empty.
+     * the caller thread. Either a message is immeadiately delivered from
+     * the channel or `TcbError::Empty` is returned if the channel is empty.
      */
-    fn try_recv(&mut self) -> Result<GenericReturn, TryRecvError> {
-        match self.receive_channel.try_recv() {
-            Ok(msg) => Ok(self.handle_delivery(msg)),
-            Err(e) => Err(e),
+    fn try_recv(&mut self) -> Result<GenericReturn, TcbError> {
+        loop {
+            match self.next_message(|channel| channel.try_recv()) {
+                Ok(msg) => {
+                    if let Some(generic_return) = self.handle_delivery(msg) {
+                        return Ok(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
     /**
      * Waits for a message to be delivered from the middleware for a
-     * limited time. If the channel is empty and not disconnected, the
-     * caller thread is blocked until a message is received in the channel
-     * or the timeout ends. If there are no messages until the timeout ends or
-     * the channel becomes disconnected, an error is returned.
+     * limited time. If the channel is empty, the caller thread is blocked
+     * until a message is received in the channel or the timeout ends, in
+     * which case `TcbError::Timeout` is returned.
      *
      * # Arguments
      *
      * `duration` - Timeout duration
      */
-    fn recv_timeout(&mut self, duration: Duration) -> Result<GenericReturn, RecvTimeoutError> {
-        match self.receive_channel.recv_timeout(duration) {
-            Ok(msg) => Ok(self.handle_delivery(msg)),
-            Err(e) => Err(e),
+    fn recv_timeout(&mut self, duration: Duration) -> Result<GenericReturn, TcbError> {
+        //The filtered messages don't extend the timeout
+        let deadline = Instant::now() + duration;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            match self.next_message(|channel| channel.recv_timeout(remaining)) {
+                Ok(msg) => {
+                    if let Some(generic_return) = self.handle_delivery(msg) {
+                        return Ok(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
         }
     }
 
@@ -288,7 +788,237 @@ impl TCB for VV {
      *
      * `counter` - Stable dot counter field
      */
-    fn tcbstable(&mut self, _: usize, _: usize) {
+    fn tcbstable(&mut self, _: usize, _: usize) -> Result<(), TcbError> {
         //Not implemented for VV
+        if self.sender.is_terminated() {
+            return Err(TcbError::Terminated);
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Evicts a peer that is known to be permanently gone from the group. The
+     * middleware closes the connections to the peer, ignores any further messages
+     * from it and stops waiting for it when calculating causal stability, so the
+     * remaining peers can resume making stability progress. The client is notified
+     * with a `GenericReturn::Evicted` once the eviction was handled by the middleware.
+     *
+     * # Arguments
+     *
+     * `id` - Evicted peer's globally unique id
+     */
+    fn evict_peer(&mut self, id: usize) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::EVICT { peer_id: id })
+    }
+
+    /**
+     * Forces every Sender thread to immediately write its buffered messages
+     * into the stream, instead of waiting for the batching thresholds or
+     * timeouts. Useful at latency-critical moments, such as the end of a
+     * client transaction.
+     */
+    fn flush(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::FLUSH)
+    }
+
+    /**
+     * Returns the receiver of the stability notifications if the configuration
+     * routes them to their own channel. The receiver can be moved to another
+     * thread, e.g. a garbage collection thread that acks the stable messages,
+     * so the deliveries aren't interleaved with the stability bookkeeping.
+     * Returns `None` if the notifications are returned by `recv`, if they are
+     * acked automatically or if the receiver was already taken.
+     */
+    fn stable_receiver(&mut self) -> Option<StableReceiver> {
+        self.stable_receiver.take()
+    }
+
+    /**
+     * Returns a handle that broadcasts messages from other threads, which can be
+     * cloned and shared without a lock around the middleware instance. The message
+     * ids of every handle are assigned under a lock shared with this instance.
+     */
+    fn sender(&self) -> TcbSender {
+        self.sender.clone()
+    }
+
+    /**
+     * Takes a consistent cut of the messages delivered by the middleware, for
+     * applications implementing consistent backups. The Sender threads are flushed
+     * and the middleware returns its delivered version vector, including the local
+     * messages. The messages delivered up to the cut that the Client didn't receive
+     * yet are returned with it, so once the Client applies them its state is the
+     * state at the cut, and every later delivery is above the cut.
+     */
+    fn snapshot(&mut self) -> Result<Snapshot, TcbError> {
+        self.request(ClientPeerMiddleware::SNAPSHOT)?;
+
+        let mut deliveries: Vec<GenericReturn> = Vec::new();
+
+        //Receiving the deliveries written by the middleware before the cut
+        loop {
+            match self.next_message(|channel| channel.recv()) {
+                Ok(MiddlewareClient::SNAPSHOT { cut }) => {
+                    return Ok(Snapshot {
+                        cut: cut.0,
+                        deliveries,
+                    });
+                }
+                Ok(message) => {
+                    if let Some(generic_return) = self.handle_delivery(message) {
+                        deliveries.push(generic_return);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /**
+     * Registers a predicate that selects which deliveries are returned to the
+     * Client, or removes the current one if `None` is passed. The messages that
+     * don't match are still delivered inside the middleware, so the causal order
+     * of the matching messages and of the Client's own messages is kept, and they
+     * are still notified when stable.
+     *
+     * # Arguments
+     *
+     * `filter` - Predicate over the sender id, message id and payload
+     */
+    fn set_delivery_filter(&mut self, filter: Option<DeliveryFilter>) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::FILTER { filter })
+    }
+
+    /**
+     * Stops the middleware from delivering messages to the Client, e.g. while
+     * it does a slow operation. The messages received from the peers keep being
+     * handled and wait in the middleware until the deliveries are resumed, and the
+     * deliveries already sent to the Client can still be received.
+     */
+    fn pause_delivery(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::PAUSE)
+    }
+
+    /**
+     * Resumes the deliveries to the Client after `pause_delivery`, starting with
+     * the messages that were held back while paused.
+     */
+    fn resume_delivery(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::RESUME)
+    }
+
+    /**
+     * Acknowledges that the Client processed a delivered message, when delivery
+     * acks are enabled in the configuration. The middleware keeps every delivery
+     * until it's acked, so the Client can receive the unacked ones again with
+     * `replay_unacked`. Does nothing if delivery acks aren't enabled.
+     *
+     * # Arguments
+     *
+     * `id` - Delivered message's sender id
+     *
+     * `counter` - Delivered message's counter
+     */
+    fn ack(&mut self, id: usize, counter: usize) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::ACK {
+            sender_id: id,
+            message_id: counter,
+        })
+    }
+
+    /**
+     * Sends the deliveries that weren't acked to the Client again, in the order
+     * they were delivered, e.g. after the thread processing them crashed and
+     * was restarted. They are returned by `recv` and its variants after the
+     * deliveries already in the channel, and stay pending until they are acked.
+     */
+    fn replay_unacked(&mut self) -> Result<(), TcbError> {
+        self.request(ClientPeerMiddleware::REPLAY)
+    }
+
+    /**
+     * Returns a snapshot of the middleware metrics, such as the round
+     * trip time measured to each peer.
+     */
+    fn metrics(&self) -> Metrics {
+        self.metrics.snapshot()
+    }
+
+    /**
+     * Returns the timestamps of the internal events of the retained messages, in
+     * the order they were delivered, to break down their latency offline. Empty
+     * unless messages are retained in the configuration. Returns
+     * `TcbError::Terminated` if the middleware terminated.
+     */
+    fn debug_dump(&self) -> Result<Vec<MessageTimestamps>, TcbError> {
+        let (reply, timestamps) = bounded::<Vec<MessageTimestamps>>(1);
+
+        self.request(ClientPeerMiddleware::DUMP { reply })?;
+
+        Ok(timestamps.recv()?)
+    }
+
+    /**
+     * Explains why a message wasn't delivered yet, for debugging: whether it
+     * wasn't received, or which messages of which peers it waits for, from the
+     * middleware's current state. Returns `TcbError::Terminated` if the
+     * middleware terminated.
+     *
+     * # Arguments
+     *
+     * `id` - Message's sender id
+     *
+     * `counter` - Message's counter
+     */
+    fn explain(&self, id: usize, counter: usize) -> Result<Explanation, TcbError> {
+        let (reply, explanation) = bounded::<Explanation>(1);
+
+        self.request(ClientPeerMiddleware::EXPLAIN {
+            sender_id: id,
+            message_id: counter,
+            reply,
+        })?;
+
+        Ok(explanation.recv()?)
+    }
+
+    /**
+     * Returns the causal graph of the middleware serialized in a format, with
+     * the stage and edges of each node, to inspect the causal state of a wedged
+     * peer. Returns `None` for the VV implementation, which has no causal graph,
+     * and `TcbError::Terminated` if the middleware terminated.
+     *
+     * # Arguments
+     *
+     * `format` - Format of the serialized graph
+     */
+    fn render_graph(&self, _format: GraphFormat) -> Result<Option<String>, TcbError> {
+        if self.sender.is_terminated() {
+            return Err(TcbError::Terminated);
+        }
+
+        Ok(None)
+    }
+
+    /**
+     * Waits for the middleware to be connected to every peer in both directions,
+     * for a limited time. Returns true once connected, which is always the case
+     * unless the setup is lazy.
+     *
+     * # Arguments
+     *
+     * `timeout` - Maximum waiting time
+     */
+    fn wait_ready(&self, timeout: Duration) -> bool {
+        setup::wait_ready(&self.ready, timeout)
+    }
+
+    /**
+     * Returns the address the middleware listens on for connections, which has
+     * the port chosen by the OS if the configuration set the local port to 0.
+     */
+    fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
     }
 }
@@ -0,0 +1,253 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use tcb::clock::dot::Dot;
+use tcb::clock::version_vector::VersionVector;
+use tcb::configuration::middleware_configuration::{KeyRange, WireEncoding};
+use tcb::graph::communication::msg_types::StreamMessages;
+use tcb::graph::structs::message::Message as GraphMessage;
+use tcb::transport::encoding;
+use tcb::transport::protocol::{self, VersionRange, PROTOCOL_RELEASES, PROTOCOL_VERSION};
+use tcb::vv::structs::messages::{Message as VVMessage, StreamMsg};
+
+//Checks that the frames and messages sent to the peers are still encoded as in
+//the current protocol version, so a peer running this crate version can talk to
+//a peer running the previous one. When a frame changes on purpose, the protocol
+//version must be increased and the negotiation table updated before the golden
+//frames below are replaced.
+
+#[test]
+fn negotiation_table_ends_at_the_current_version() {
+    let release = PROTOCOL_RELEASES
+        .last()
+        .expect("ERROR: The negotiation table is empty");
+
+    assert_eq!(release.version, PROTOCOL_VERSION);
+
+    //The current version is spoken by this crate version or by the upcoming one
+    assert!(crate_version(release.crate_version) >= crate_version(env!("CARGO_PKG_VERSION")));
+    assert_eq!(release.min_version, protocol::MIN_PROTOCOL_VERSION);
+}
+
+#[test]
+fn negotiation_table_allows_rolling_upgrades() {
+    //The first negotiating crate version is the one refused peers are told to
+    //upgrade to, the release after 0.1.3, which sends no preamble
+    assert_eq!(PROTOCOL_RELEASES[0].version, 1);
+    assert_eq!(PROTOCOL_RELEASES[0].crate_version, "0.1.4");
+
+    for pair in PROTOCOL_RELEASES.windows(2) {
+        let (previous, release) = (&pair[0], &pair[1]);
+
+        assert_eq!(release.version, previous.version + 1);
+
+        //Rolling upgrades need every version to speak the previous one
+        let range = VersionRange {
+            min: release.min_version,
+            max: release.version,
+        };
+        let previous_range = VersionRange {
+            min: previous.min_version,
+            max: previous.version,
+        };
+
+        assert_eq!(
+            range.negotiate(&previous_range),
+            Some(previous.version),
+            "{} can't talk to {}",
+            release.crate_version,
+            previous.crate_version
+        );
+    }
+}
+
+#[test]
+fn version_preamble() {
+    let mut preamble = Vec::new();
+    protocol::send_version(&mut preamble).expect("ERROR: Failed to write the preamble");
+
+    assert_eq!(to_hex(&preamble), to_hex(&hex("54434250 0100 0100")));
+    assert_eq!(
+        protocol::receive_version(&mut preamble.as_slice()),
+        Ok(PROTOCOL_VERSION)
+    );
+}
+
+#[test]
+fn peers_without_a_preamble_are_refused() {
+    let handshake = hex("00000000 0100000000000000");
+
+    assert!(protocol::receive_version(&mut handshake.as_slice()).is_err());
+}
+
+#[test]
+fn graph_handshake() {
+    check(
+        &StreamMessages::Handshake {
+            index: 1,
+            encoding: WireEncoding::Varint,
+            interests: vec![KeyRange { start: 0, end: 9 }],
+        },
+        "00000000 0100000000000000 01000000 0100000000000000 0000000000000000 0900000000000000",
+    );
+}
+
+#[test]
+fn graph_stream_messages() {
+    check(
+        &StreamMessages::Message {
+            epoch: 3,
+            msg: vec![0xab, 0xcd],
+            signature: None,
+        },
+        "01000000 0300000000000000 0200000000000000 abcd 00",
+    );
+    check(
+        &StreamMessages::Ping { timestamp: 42 },
+        "03000000 2a00000000000000",
+    );
+    check(
+        &StreamMessages::Receipt { counter: 7 },
+        "05000000 0700000000000000",
+    );
+    check(
+        &StreamMessages::CatchUp { vector: vec![1, 2] },
+        "06000000 0200000000000000 0100000000000000 0200000000000000",
+    );
+    check(&StreamMessages::CaughtUp { cut: None }, "07000000 00");
+    check(&StreamMessages::Close, "08000000");
+    check(
+        &StreamMessages::Vector {
+            vector: vec![1, 2],
+            reply: true,
+            signature: None,
+        },
+        "09000000 0200000000000000 0100000000000000 0200000000000000 01 00",
+    );
+}
+
+#[test]
+fn graph_message() {
+    check(
+        &GraphMessage {
+            dot: Dot::new(1, 2),
+            payload: b"hi".to_vec(),
+            context: vec![Dot::new(0, 1)],
+            trace_id: None,
+            headers: None,
+            key: Some(5),
+            context_digests: Vec::new(),
+            recipients: None,
+            checksum: Some(7),
+        },
+        "0100000000000000 0200000000000000 0200000000000000 6869 0100000000000000 \
+         0000000000000000 0100000000000000 00 00 01 0500000000000000 0000000000000000 00 \
+         01 0700000000000000",
+    );
+}
+
+#[test]
+fn vv_handshake() {
+    check(
+        &StreamMsg::HND {
+            index: 2,
+            encoding: WireEncoding::Fixint,
+        },
+        "00000000 0200000000000000 00000000",
+    );
+}
+
+#[test]
+fn vv_stream_messages() {
+    check(
+        &StreamMsg::MSG {
+            msg: vec![1, 2, 3],
+            peer_id: 1,
+            epoch: 4,
+            signature: Some(vec![9]),
+        },
+        "01000000 0300000000000000 010203 0100000000000000 0400000000000000 01 0100000000000000 09",
+    );
+    check(
+        &StreamMsg::PONG { timestamp: 42 },
+        "04000000 2a00000000000000",
+    );
+    check(&StreamMsg::CLOSE, "06000000");
+}
+
+#[test]
+fn vv_message() {
+    check(
+        &VVMessage {
+            id: 1,
+            payload: b"hi".to_vec(),
+            version_vector: VersionVector(vec![0, 1]),
+            trace_id: None,
+            headers: None,
+            history_digests: Vec::new(),
+            recipients: None,
+            checksum: None,
+        },
+        "0100000000000000 0200000000000000 6869 0200000000000000 0000000000000000 \
+         0100000000000000 00 00 0000000000000000 00 00",
+    );
+}
+
+#[test]
+fn messages_with_trailing_bytes_are_refused() {
+    let mut frame = encoding::serialize(WireEncoding::Fixint, &GraphMessage::empty())
+        .expect("ERROR: Failed to serialize the message");
+    frame.extend_from_slice(&[0; 8]);
+
+    assert!(encoding::deserialize_exact::<GraphMessage>(WireEncoding::Fixint, &frame).is_err());
+}
+
+//Compares the encoding of a value with its golden frame and checks
+//that the golden frame is still decoded into the same value
+fn check<T: Serialize + DeserializeOwned + Debug>(value: &T, golden: &str) {
+    let golden = hex(golden);
+    let encoded =
+        encoding::serialize(WireEncoding::Fixint, value).expect("ERROR: Failed to encode a frame");
+
+    assert_eq!(
+        to_hex(&encoded),
+        to_hex(&golden),
+        "Incompatible change to {:?}",
+        value
+    );
+
+    let decoded = encoding::deserialize::<T>(WireEncoding::Fixint, &golden)
+        .expect("ERROR: Golden frame not decoded");
+    let reencoded = encoding::serialize(WireEncoding::Fixint, &decoded)
+        .expect("ERROR: Failed to encode a frame");
+
+    assert_eq!(
+        to_hex(&reencoded),
+        to_hex(&golden),
+        "Golden frame of {:?} decoded differently",
+        value
+    );
+}
+
+fn hex(frame: &str) -> Vec<u8> {
+    let digits: Vec<char> = frame.chars().filter(|c| !c.is_whitespace()).collect();
+
+    digits
+        .chunks(2)
+        .map(|byte| {
+            u8::from_str_radix(&byte.iter().collect::<String>(), 16)
+                .expect("ERROR: Invalid golden frame")
+        })
+        .collect()
+}
+
+fn to_hex(frame: &[u8]) -> String {
+    frame.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn crate_version(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| part.parse().expect("ERROR: Invalid crate version"))
+        .collect()
+}